@@ -19,6 +19,7 @@ pub fn generate_completions(mut cli: Command) -> Result<(), std::io::Error> {
 }
 
 fn main() -> Result<(), std::io::Error> {
+    generate_completions(WallpapersArgs::command())?;
     generate_completions(WallpaperUIArgs::command())?;
     generate_completions(WallpapersAddArgs::command())?;
 
@@ -29,5 +30,13 @@ fn main() -> Result<(), std::io::Error> {
     }
     println!("cargo:rerun-if-env-changed=NIX_RELEASE_VERSION");
 
+    // embed the face model for the onnx feature
+    println!("cargo::rustc-check-cfg=cfg(bundled_face_model)");
+    if let Ok(model) = std::env::var("WALLPAPER_UI_FACE_MODEL") {
+        println!("cargo:rustc-cfg=bundled_face_model");
+        println!("cargo:rerun-if-changed={model}");
+    }
+    println!("cargo:rerun-if-env-changed=WALLPAPER_UI_FACE_MODEL");
+
     Ok(())
 }
@@ -86,21 +86,24 @@ rustPlatform.buildRustPackage {
   '';
 
   postFixup = ''
-    # add own path so wallpaper-pipeline can run wallpaper-ui
-    wrapProgram $out/bin/add-wallpaper \
-      --prefix PATH : "$out/bin" \
-      --prefix PATH : "${
-        lib.makeBinPath [
-          realcugan-ncnn-vulkan
-          anime-face-detector
-          libwebp
-          oxipng
-          jpegoptim
-        ]
-      }"
+    # add own path so the pipeline can run the wallpaper ui
+    for prog in wallpapers add-wallpapers; do
+      wrapProgram $out/bin/$prog \
+        --prefix PATH : "$out/bin" \
+        --prefix PATH : "${
+          lib.makeBinPath [
+            realcugan-ncnn-vulkan
+            anime-face-detector
+            libwebp
+            oxipng
+            jpegoptim
+          ]
+        }"
+    done
 
     # FIXME: GDK_BACKEND=x11 is required for keyboard shortcuts to work?
-    wrapProgram $out/bin/wallpaper-ui \
+    # the wallpaper-ui shim runs the wallpapers binary
+    wrapProgram $out/bin/wallpapers \
       --set WEBKIT_DISABLE_COMPOSITING_MODE 1
   '';
 
@@ -109,6 +112,6 @@ rustPlatform.buildRustPackage {
     homepage = "https://github.com/iynaix/wallpaper-ui";
     license = licenses.mit;
     maintainers = with maintainers; [ iynaix ];
-    mainProgram = "wallpaper-ui";
+    mainProgram = "wallpapers";
   };
 }
@@ -1,18 +1,39 @@
-use clap::Parser;
-use itertools::Itertools;
-use std::path::PathBuf;
+use rayon::prelude::*;
+use std::{
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::BuildHasher,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+use thiserror::Error;
+use tracing::warn;
 
 use wallpaper_ui::{
-    aspect_ratio::AspectRatio,
+    aspect_ratio::{resolutions_from_arg, AspectRatio},
+    cache::filter_brightness,
     cli::WallpaperUIArgs,
+    commands::add_resolution::backfill_resolution,
     config::WallpaperConfig,
-    cropper::Direction,
-    filename, filter_images,
+    cropper::{CropOptions, Cropper, Direction},
+    filename,
     geometry::Geometry,
-    is_image,
-    wallpapers::{WallInfo, WallpapersCsv},
+    is_heif, is_image, scan_images,
+    store::{self, Store},
+    wallpapers::{Face, FaceCount, WallInfo, WallpapersCsv, WallpapersError},
 };
 
+/// minimum dimming and crop border of the high contrast overlays
+const HIGH_CONTRAST_OPACITY: u32 = 85;
+const HIGH_CONTRAST_BORDER_WIDTH: u32 = 3;
+
+/// each step of the mouse wheel zooms the preview by this factor
+const ZOOM_STEP: f64 = 1.25;
+/// maximum zoom of the preview in percent of the image size
+const MAX_ZOOM: u32 = 800;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UiMode {
     Editor,
@@ -30,12 +51,105 @@ impl Default for UiMode {
 pub struct UiState {
     pub mode: UiMode,
     pub preview_mode: PreviewMode,
+    /// preview mode used when switching wallpapers
+    pub default_preview_mode: PreviewMode,
     pub show_faces: bool,
+    /// faces are drawn, resized and deleted on the preview instead of moving the crop
+    pub face_edit: bool,
+    /// zoom of the preview in percent of the image size, None fits the preview in the window
+    pub zoom: Option<u32>,
+    pub theme: String,
+    pub auto_advance: bool,
+    pub arrow_step: u32,
+    pub pad_color: String,
+    /// colors of the overlays, either hex colors or auto
+    pub face_color: String,
+    pub crop_border_color: String,
+    pub crop_border_width: u32,
+    pub overlay_opacity: u32,
+    /// thicker face boxes, the crop border and dimming are already raised in from_config
+    pub high_contrast: bool,
+    /// size of the ui in percent
+    pub ui_scale: u32,
     pub is_saving: bool,
     pub arrow_key_start: Option<std::time::Instant>,
+    /// warnings from validating the last save, saving again with the same warnings saves anyway
+    pub warnings: Vec<String>,
+    /// menu of the custom commands in the header
+    pub show_commands: bool,
+    /// the default crops are applied to all the wallpapers when confirmed again
+    pub confirm_default_crops: bool,
+    /// the dialog asking to delete the current wallpaper is open
+    pub confirm_delete: bool,
+    /// error from the last save or navigation, cleared by the next one that succeeds
+    pub error: Option<String>,
 }
 
 impl UiState {
+    /// initial ui state from the defaults in the config
+    pub fn from_config(config: &WallpaperConfig) -> Self {
+        let preview_mode = if config.preview_mode == "pan" {
+            PreviewMode::Pan
+        } else {
+            PreviewMode::default()
+        };
+
+        let mut state = Self {
+            preview_mode: preview_mode.clone(),
+            default_preview_mode: preview_mode,
+            show_faces: config.show_faces,
+            theme: config.theme.clone(),
+            auto_advance: config.auto_advance,
+            arrow_step: config.arrow_step,
+            pad_color: config.pad_color.clone(),
+            face_color: config.face_color.clone(),
+            crop_border_color: config.crop_border_color.clone(),
+            crop_border_width: config.crop_border_width,
+            overlay_opacity: config.overlay_opacity,
+            high_contrast: config.high_contrast,
+            ui_scale: config.ui_scale,
+            ..Self::default()
+        };
+
+        if config.high_contrast {
+            state.overlay_opacity = state.overlay_opacity.max(HIGH_CONTRAST_OPACITY);
+            state.crop_border_width = state.crop_border_width.max(HIGH_CONTRAST_BORDER_WIDTH);
+        }
+        state
+    }
+
+    /// factor the pixel sizes of the ui are scaled by
+    pub fn scale(&self) -> f64 {
+        f64::from(self.ui_scale) / 100.0
+    }
+
+    pub fn reset_preview_mode(&mut self) {
+        self.preview_mode = self.default_preview_mode.clone();
+    }
+
+    /// factor the preview is scaled by from the size fitting the window, fit_zoom is the zoom
+    /// in percent at which it fits, the preview is never smaller than that
+    pub fn zoom_factor(&self, fit_zoom: f64) -> f64 {
+        self.zoom
+            .map_or(1.0, |zoom| (f64::from(zoom) / fit_zoom).max(1.0))
+    }
+
+    /// zooms the preview in or out by a step, zooming out past the size fitting the window fits
+    /// it again
+    pub fn zoom_by(&mut self, zoom_in: bool, fit_zoom: f64) {
+        let current = self.zoom.map_or(fit_zoom, f64::from).max(fit_zoom);
+        let zoom = if zoom_in {
+            current * ZOOM_STEP
+        } else {
+            current / ZOOM_STEP
+        };
+        self.zoom = (zoom > fit_zoom).then(|| (zoom.round() as u32).min(MAX_ZOOM));
+    }
+
+    pub fn toggle_face_edit(&mut self) {
+        self.face_edit = !self.face_edit;
+    }
+
     pub fn toggle_filelist(&mut self) {
         self.mode = match self.mode {
             UiMode::FileList => UiMode::Editor,
@@ -64,42 +178,408 @@ impl Default for PreviewMode {
     }
 }
 
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|meta| meta.modified()).ok()
+}
+
+/// size and modification time of a wallpaper, files that can't be read are empty and sorted last
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+}
+
+impl FileStat {
+    fn read(path: &Path) -> Self {
+        path.metadata().map_or_else(
+            |_| Self::default(),
+            |meta| Self {
+                size: meta.len(),
+                mtime: meta.modified().ok(),
+            },
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("wallpaper directory does not exist: {0:?}")]
+    MissingDirectory(PathBuf),
+    #[error("could not read {path:?}: {source}")]
+    ReadDirectory {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("no wallpapers found")]
+    NoWallpapers,
+    #[error("invalid sort: {0}")]
+    InvalidSort(String),
+    #[error("invalid ratio: {0}, expected WxH, W:H or a decimal ratio")]
+    InvalidRatio(String),
+    #[error(transparent)]
+    Csv(#[from] WallpapersError),
+}
+
+/// wallpapers.csv shared by the ui, so navigating doesn't reparse it, it is only read again
+/// when it is modified by something else, e.g. wallpapers-add or the api
+///
+/// with store = sqlite single wallpapers are looked up and saved in the database instead, all of
+/// them are only read for the filters and sorting
+///
+/// saves are kept in memory and written after save_delay without any other saves, the
+/// unsaved changes win over changes made by something else in the meantime
+#[derive(Clone)]
+pub struct CsvCache {
+    /// None until the wallpapers are first read with store = sqlite
+    wallpapers_csv: Option<WallpapersCsv>,
+    /// the database with store = sqlite, saves are written to it right away
+    db: Option<Rc<RefCell<Box<dyn Store>>>>,
+    csv_path: PathBuf,
+    mtime: Option<SystemTime>,
+    save_delay: Duration,
+    /// ratios of the unsaved changes and when they were last saved
+    pending: Option<(Vec<AspectRatio>, Instant)>,
+    /// ratios of the snapshot being written in the background
+    writing: Option<Vec<AspectRatio>>,
+    /// incremented on each save, so older snapshots are never written over newer ones
+    generation: u64,
+    written: Arc<Mutex<u64>>,
+    /// metadata of the wallpapers, cleared when wallpapers.csv is read again as the images
+    /// might have been replaced
+    stats: HashMap<PathBuf, FileStat>,
+}
+
+/// snapshot of wallpapers.csv to be written in the background
+pub struct CsvWrite {
+    wallpapers_csv: WallpapersCsv,
+    ratios: Vec<AspectRatio>,
+    generation: u64,
+    written: Arc<Mutex<u64>>,
+}
+
+impl CsvWrite {
+    pub fn run(mut self) -> Result<(), WallpapersError> {
+        write_csv(
+            &mut self.wallpapers_csv,
+            &self.ratios,
+            self.generation,
+            &self.written,
+        )
+    }
+}
+
+/// writes the csv unless a newer generation has been written already
+fn write_csv(
+    wallpapers_csv: &mut WallpapersCsv,
+    ratios: &[AspectRatio],
+    generation: u64,
+    written: &Mutex<u64>,
+) -> Result<(), WallpapersError> {
+    let mut written = written.lock().unwrap_or_else(PoisonError::into_inner);
+    if *written >= generation {
+        return Ok(());
+    }
+    wallpapers_csv.save(ratios)?;
+    *written = generation;
+    Ok(())
+}
+
+impl CsvCache {
+    pub fn load(config: &WallpaperConfig) -> Result<Self, WallpapersError> {
+        let (wallpapers_csv, db) = if config.store == "sqlite" {
+            (None, Some(Rc::new(RefCell::new(store::open(config)?))))
+        } else {
+            (Some(WallpapersCsv::open()?), None)
+        };
+
+        Ok(Self {
+            wallpapers_csv,
+            db,
+            csv_path: config.store_path().to_path_buf(),
+            mtime: modified(config.store_path()),
+            save_delay: Duration::from_millis(config.save_delay),
+            pending: None,
+            writing: None,
+            generation: 0,
+            written: Arc::default(),
+            stats: HashMap::new(),
+        })
+    }
+
+    /// the wallpapers.csv, read again if it was modified since it was last read or saved
+    pub fn csv(&mut self) -> Result<&WallpapersCsv, WallpapersError> {
+        let mtime = modified(&self.csv_path);
+        let stale = mtime != self.mtime && self.pending.is_none() && self.writing.is_none();
+        let wallpapers_csv = match self.wallpapers_csv.take() {
+            Some(wallpapers_csv) if !stale => wallpapers_csv,
+            _ => {
+                let wallpapers_csv = WallpapersCsv::open()?;
+                self.mtime = mtime;
+                self.stats.clear();
+                wallpapers_csv
+            }
+        };
+        Ok(self.wallpapers_csv.insert(wallpapers_csv))
+    }
+
+    /// reads the metadata of the files that aren't cached yet, in parallel as reading them one
+    /// at a time is slow on network filesystems
+    pub fn cache_stats(&mut self, files: &[PathBuf]) {
+        let missing: Vec<_> = files
+            .iter()
+            .filter(|f| !self.stats.contains_key(*f))
+            .collect();
+        let stats: Vec<_> = missing
+            .into_par_iter()
+            .map(|f| (f.clone(), FileStat::read(f)))
+            .collect();
+        self.stats.extend(stats);
+    }
+
+    pub fn stat(&mut self, path: &Path) -> FileStat {
+        *self
+            .stats
+            .entry(path.to_path_buf())
+            .or_insert_with(|| FileStat::read(path))
+    }
+
+    pub fn get(&mut self, fname: &str) -> Result<WallInfo, WallpapersError> {
+        if let Some(db) = &self.db {
+            return db
+                .borrow()
+                .get(fname)?
+                .ok_or_else(|| WallpapersError::MissingInfo(fname.to_string()));
+        }
+        self.csv()?.info(fname).cloned()
+    }
+
+    /// saves the wallpaper info, it is written to wallpapers.csv after the save_delay or by flush,
+    /// the database of store = sqlite is written right away
+    pub fn save(&mut self, info: &WallInfo, ratios: &[AspectRatio]) -> Result<(), WallpapersError> {
+        self.save_all(std::slice::from_ref(info), ratios)
+    }
+
+    /// saves the wallpaper infos at once, like save they are written after the save_delay or by
+    /// flush
+    pub fn save_all(
+        &mut self,
+        infos: &[WallInfo],
+        ratios: &[AspectRatio],
+    ) -> Result<(), WallpapersError> {
+        if let Some(db) = self.db.clone() {
+            let fresh = modified(&self.csv_path) == self.mtime;
+            for info in infos {
+                db.borrow_mut().upsert(info, ratios)?;
+            }
+            if fresh {
+                self.mtime = modified(&self.csv_path);
+                if let Some(wallpapers_csv) = &mut self.wallpapers_csv {
+                    for info in infos {
+                        wallpapers_csv.insert(info.filename.clone(), info.clone());
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        self.csv()?;
+        if let Some(wallpapers_csv) = &mut self.wallpapers_csv {
+            for info in infos {
+                wallpapers_csv.insert(info.filename.clone(), info.clone());
+            }
+        }
+        self.generation += 1;
+        self.pending = Some((ratios.to_vec(), Instant::now()));
+
+        if self.save_delay.is_zero() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// deletes the row of the wallpaper, like save it is written after the save_delay or by flush
+    pub fn remove(&mut self, fname: &str, ratios: &[AspectRatio]) -> Result<(), WallpapersError> {
+        if let Some(db) = self.db.clone() {
+            let fresh = modified(&self.csv_path) == self.mtime;
+            db.borrow_mut().remove(fname)?;
+            if fresh {
+                self.mtime = modified(&self.csv_path);
+                if let Some(wallpapers_csv) = &mut self.wallpapers_csv {
+                    wallpapers_csv.remove(fname);
+                }
+            }
+            return Ok(());
+        }
+
+        self.csv()?;
+        if let Some(wallpapers_csv) = &mut self.wallpapers_csv {
+            wallpapers_csv.remove(fname);
+        }
+        self.generation += 1;
+        self.pending = Some((ratios.to_vec(), Instant::now()));
+
+        if self.save_delay.is_zero() {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// the unsaved changes are older than the save_delay
+    pub fn is_due(&self) -> bool {
+        self.writing.is_none()
+            && self
+                .pending
+                .as_ref()
+                .is_some_and(|(_, saved)| saved.elapsed() >= self.save_delay)
+    }
+
+    /// snapshot of the unsaved changes to be written in the background, finish_write has to be
+    /// called with the result
+    pub fn take_write(&mut self) -> Option<CsvWrite> {
+        let wallpapers_csv = self.wallpapers_csv.clone()?;
+        let (ratios, _) = self.pending.take()?;
+        self.writing = Some(ratios.clone());
+        Some(CsvWrite {
+            wallpapers_csv,
+            ratios,
+            generation: self.generation,
+            written: Arc::clone(&self.written),
+        })
+    }
+
+    /// failed writes are retried after the save_delay
+    pub fn finish_write(&mut self, written: bool) {
+        let ratios = self.writing.take().unwrap_or_default();
+        if written {
+            self.mtime = modified(&self.csv_path);
+        } else {
+            self.pending.get_or_insert_with(|| (ratios, Instant::now()));
+        }
+    }
+
+    /// writes the unsaved changes to wallpapers.csv now
+    pub fn flush(&mut self) -> Result<(), WallpapersError> {
+        let Some(wallpapers_csv) = &mut self.wallpapers_csv else {
+            return Ok(());
+        };
+        let Some((ratios, saved)) = self.pending.take() else {
+            return Ok(());
+        };
+
+        match write_csv(wallpapers_csv, &ratios, self.generation, &self.written) {
+            Ok(()) => {
+                self.mtime = modified(&self.csv_path);
+                Ok(())
+            }
+            Err(e) => {
+                self.pending = Some((ratios, saved));
+                Err(e)
+            }
+        }
+    }
+}
+
+/// the queue of wallpapers being edited, the signal is cloned on every read so the files and
+/// wallpaper infos are shared and only copied when they are modified
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Wallpapers {
-    pub files: Vec<PathBuf>,
+    pub files: Rc<Vec<PathBuf>>,
     // the original wallinfo before any modifications
-    pub source: WallInfo,
-    pub current: WallInfo,
+    pub source: Rc<WallInfo>,
+    pub current: Rc<WallInfo>,
     pub index: usize,
     pub ratio: AspectRatio,
-    pub resolutions: Vec<(String, AspectRatio)>,
+    /// ratios shown since the wallpaper was loaded, so every ratio can be reviewed
+    pub visited_ratios: HashSet<AspectRatio>,
+    pub resolutions: Rc<[(String, AspectRatio)]>,
+    /// ratios of the --unmodified, --modified or --orientation filters, all the ratios without
+    /// them, the default crops are applied to these ratios
+    pub filter_ratios: Rc<[AspectRatio]>,
+    /// pixel distance within which candidates are considered duplicates
+    pub candidate_tolerance: u32,
+    pub crop_options: CropOptions,
+    /// earlier versions of the current wallpaper with the ratio that was changed, newest last
+    pub undo: Vec<(AspectRatio, Rc<WallInfo>)>,
+    /// versions of the current wallpaper that were undone, newest last
+    pub redo: Vec<(AspectRatio, Rc<WallInfo>)>,
 }
 
 impl Wallpapers {
     /// parse an optional comma separated list of resolutions
     fn resolution_arg(
         resolution_arg: Option<&str>,
-        resolutions: &[AspectRatio],
-    ) -> Vec<AspectRatio> {
-        match resolution_arg {
-            None => Vec::new(),
-            Some("all") => resolutions.to_vec(),
-            Some(res_arg) => res_arg
-                .split(',')
-                .map(|s| {
-                    std::convert::TryInto::<AspectRatio>::try_into(s.trim())
-                        .unwrap_or_else(|()| panic!("Invalid resolution {s} provided."))
-                })
-                .collect(),
+        resolutions: &[(String, AspectRatio)],
+    ) -> Result<Vec<AspectRatio>, WallpapersError> {
+        resolution_arg.map_or_else(
+            || Ok(Vec::new()),
+            |res_arg| resolutions_from_arg(res_arg, resolutions),
+        )
+    }
+
+    /// orders the files for the queue, all orders except name are largest / newest first
+    fn sort_files(
+        files: &mut [PathBuf],
+        sort: &str,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), QueueError> {
+        match sort {
+            "name" => files.sort_by_cached_key(|f| filename(f).to_lowercase()),
+            "mtime" => {
+                wallpapers_csv.cache_stats(files);
+                files.sort_by_cached_key(|f| wallpapers_csv.stat(f).mtime);
+                files.reverse();
+            }
+            "size" => {
+                wallpapers_csv.cache_stats(files);
+                files.sort_by_cached_key(|f| wallpapers_csv.stat(f).size);
+                files.reverse();
+            }
+            "resolution" => {
+                let wallpapers_csv = wallpapers_csv.csv()?;
+                files.sort_by_cached_key(|f| {
+                    wallpapers_csv
+                        .get(&filename(f))
+                        .map_or(0, |info| u64::from(info.width) * u64::from(info.height))
+                });
+                files.reverse();
+            }
+            "faces" => {
+                let wallpapers_csv = wallpapers_csv.csv()?;
+                files.sort_by_cached_key(|f| {
+                    wallpapers_csv
+                        .get(&filename(f))
+                        .map_or(0, |info| info.faces.len())
+                });
+                files.reverse();
+            }
+            "random" => {
+                // RandomState is seeded randomly, so hashing gives a cheap shuffle
+                let state = RandomState::new();
+                files.sort_by_cached_key(|f| state.hash_one(f));
+            }
+            _ => return Err(QueueError::InvalidSort(sort.to_string())),
         }
+        Ok(())
     }
 
-    pub fn from_args(wall_dir: &PathBuf) -> Self {
-        let args = WallpaperUIArgs::parse();
-        let resolution_pairs = WallpaperConfig::new().resolutions;
+    pub fn from_args(
+        args: &WallpaperUIArgs,
+        wall_dir: &PathBuf,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<Self, QueueError> {
+        let config = WallpaperConfig::load().map_err(WallpapersError::from)?;
+        let resolution_pairs = config.resolutions.clone();
         let resolutions: Vec<_> = resolution_pairs.iter().map(|(_, r)| r.clone()).collect();
 
-        let mut modified_filters = Self::resolution_arg(args.modified.as_deref(), &resolutions);
+        let custom_ratio = args
+            .ratio
+            .as_deref()
+            .map(|r| AspectRatio::try_from(r).map_err(|()| QueueError::InvalidRatio(r.to_string())))
+            .transpose()?;
+
+        let mut modified_filters =
+            Self::resolution_arg(args.modified.as_deref(), &resolution_pairs)?;
+        let modified_arg = modified_filters.clone();
         if !modified_filters.is_empty() {
             modified_filters = resolutions
                 .iter()
@@ -108,17 +588,35 @@ impl Wallpapers {
                 .collect();
         }
 
-        let unmodified_filters = Self::resolution_arg(args.unmodified.as_deref(), &resolutions);
+        let unmodified_filters =
+            Self::resolution_arg(args.unmodified.as_deref(), &resolution_pairs)?;
+
+        // the queue of an orientation is the wallpapers that weren't edited for it yet
+        let oriented: Vec<_> = resolutions
+            .iter()
+            .filter(|ratio| match args.orientation.as_str() {
+                "portrait" => ratio.is_portrait(),
+                "landscape" => !ratio.is_portrait(),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        if oriented.is_empty() && args.orientation != "all" {
+            warn!("no {} resolutions in the config", args.orientation);
+        }
 
         let mut all_files = Vec::new();
-        if let Some(paths) = args.paths {
+        if let Some(paths) = &args.paths {
             paths.iter().flat_map(std::fs::canonicalize).for_each(|p| {
                 if p.is_file() {
                     if let Some(p) = is_image(&p) {
                         all_files.push(p);
                     }
                 } else {
-                    all_files.extend(filter_images(&p));
+                    match scan_images(&p, &config.ignore) {
+                        Ok(images) => all_files.extend(images),
+                        Err(e) => warn!("could not read {p:?}: {e}"),
+                    }
                 }
             });
         }
@@ -126,137 +624,201 @@ impl Wallpapers {
         if all_files.is_empty() {
             // defaults to wallpaper directory
             if !wall_dir.exists() {
-                eprintln!("Wallpaper directory does not exist: {:?}", wall_dir);
-                std::process::exit(1);
+                return Err(QueueError::MissingDirectory(wall_dir.clone()));
             }
 
-            all_files.extend(filter_images(&wall_dir));
+            all_files = scan_images(wall_dir, &config.ignore).map_err(|source| {
+                QueueError::ReadDirectory {
+                    path: wall_dir.clone(),
+                    source,
+                }
+            })?;
         }
 
-        let wallpapers_csv = WallpapersCsv::load();
+        // the webview can't display heic images, they have to be added first
+        all_files.retain(|f| !is_heif(f));
+
+        let csv = wallpapers_csv.csv()?;
+
+        // the faces and crops are filtered with the indexes of the database with store = sqlite
+        let db = (config.store == "sqlite")
+            .then(|| store::open(&config))
+            .transpose()?;
+        let store: &dyn Store = db.as_deref().unwrap_or(csv);
+        let modified_in = |ratios: &[AspectRatio]| -> Result<HashSet<String>, WallpapersError> {
+            let mut modified = HashSet::new();
+            for ratio in ratios {
+                modified.extend(store.modified_filenames(ratio)?);
+            }
+            Ok(modified)
+        };
+        let oriented_modified = modified_in(&oriented)?;
+        let modified_filters_modified = modified_in(&modified_filters)?;
+        let unmodified_filters_modified = modified_in(&unmodified_filters)?;
+        let with_faces: HashSet<_> = store
+            .filenames_with_faces(FaceCount::try_from(args.faces.as_str())?)?
+            .into_iter()
+            .collect();
 
         // filter only wallpapers that still use the default crops if needed
         all_files.retain(|f| {
             let fname = filename(f);
-            if let Some(info) = wallpapers_csv.get(&fname) {
-                if args.filter.is_some()
-                    && !fname.to_lowercase().contains(
-                        &args
-                            .filter
-                            .as_ref()
-                            .expect("no --filter provided")
-                            .to_lowercase(),
-                    )
+            if let Some(info) = csv.get(&fname) {
+                if let Some(filter) = &args.filter {
+                    if !fname.to_lowercase().contains(&filter.to_lowercase()) {
+                        return false;
+                    }
+                }
+
+                if !info.matches_tags(&args.tag, &args.not_tag) || !info.matches_state(&args.state)
                 {
                     return false;
                 }
 
+                if oriented_modified.contains(&fname) {
+                    return false;
+                }
+
                 // check if wallpaper uses default crop for a resolution / all resolutions
                 if !modified_filters.is_empty() {
-                    return info.is_default_crops(&modified_filters);
+                    return !modified_filters_modified.contains(&fname);
                 }
 
                 if !unmodified_filters.is_empty() {
-                    return info.is_default_crops(&unmodified_filters);
+                    return !unmodified_filters_modified.contains(&fname);
                 }
 
-                return match args.faces.as_str() {
-                    "all" => true,
-                    "zero" | "none" => info.faces.is_empty(),
-                    "one" | "single" => info.faces.len() == 1,
-                    "many" | "multiple" => info.faces.len() > 1,
-                    _ => panic!("Invalid faces : {}", args.faces),
-                };
+                return with_faces.contains(&fname);
             }
             true
         });
+        filter_brightness(&mut all_files, &args.brightness);
 
-        // order by reverse chronological order
-        all_files.sort_by_key(|f| {
-            f.metadata()
-                .unwrap_or_else(|_| panic!("could not get file metadata: {:?}", f))
-                .modified()
-                .unwrap_or_else(|_| panic!("could not get file mtime: {:?}", f))
-        });
-        all_files.reverse();
+        Self::sort_files(
+            &mut all_files,
+            args.sort.as_deref().unwrap_or(&config.sort),
+            wallpapers_csv,
+        )?;
+        if args.reverse {
+            all_files.reverse();
+        }
 
-        let fname = filename(
-            all_files
-                .first()
-                .unwrap_or_else(|| panic!("no wallpapers found")),
-        );
-        let loaded = wallpapers_csv
-            .get(&fname)
-            .unwrap_or_else(|| panic!("could not get wallpaper info for {fname}"));
+        let fname = filename(all_files.first().ok_or(QueueError::NoWallpapers)?);
+        let loaded = Rc::new(wallpapers_csv.get(&fname)?);
+        let ratio = oriented.first().unwrap_or(&resolutions[0]).clone();
 
-        Self {
+        let filter_ratios = [unmodified_filters, modified_arg, oriented]
+            .into_iter()
+            .find(|ratios| !ratios.is_empty())
+            .unwrap_or(resolutions);
+
+        let mut wallpapers = Self {
             index: Default::default(),
-            files: all_files,
-            source: loaded.clone(),
-            current: loaded.clone(),
-            ratio: resolutions[0].clone(),
-            resolutions: resolution_pairs,
+            files: Rc::new(all_files),
+            source: Rc::clone(&loaded),
+            current: loaded,
+            visited_ratios: HashSet::from([ratio.clone()]),
+            ratio,
+            resolutions: resolution_pairs.into(),
+            filter_ratios: filter_ratios.into(),
+            candidate_tolerance: config.candidate_tolerance,
+            crop_options: config.crop_options(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+        };
+
+        // --ratio crops for a ratio that isn't in the config, it is added like a new monitor
+        if let Some(ratio) = custom_ratio {
+            wallpapers.add_config_resolution(&ratio.to_string(), &ratio, wallpapers_csv)?;
+            wallpapers.ratio = ratio.clone();
+            wallpapers.visited_ratios = HashSet::from([ratio]);
+        }
+        Ok(wallpapers)
+    }
+
+    /// loads the wallpaper at the index, the index is unchanged if it can't be loaded
+    fn load_index(
+        &mut self,
+        index: usize,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), WallpapersError> {
+        let loaded = Rc::new(wallpapers_csv.get(&filename(&self.files[index]))?);
+        self.index = index;
+        self.source = Rc::clone(&loaded);
+        self.current = loaded;
+        self.visited_ratios = HashSet::from([self.ratio.clone()]);
+        self.undo.clear();
+        self.redo.clear();
+        Ok(())
+    }
+
+    pub fn set_ratio(&mut self, ratio: &AspectRatio) {
+        self.ratio = ratio.clone();
+        self.visited_ratios.insert(ratio.clone());
+    }
+
+    /// moves to the next or previous ratio of the image, wrapping around
+    pub fn cycle_ratio(&mut self, forward: bool) {
+        let ratios: Vec<_> = self.image_ratios().into_iter().map(|(_, r)| r).collect();
+        if ratios.is_empty() {
+            return;
         }
+
+        let next = match ratios.iter().position(|r| *r == self.ratio) {
+            Some(pos) if forward => (pos + 1) % ratios.len(),
+            Some(pos) => (pos + ratios.len() - 1) % ratios.len(),
+            None => 0,
+        };
+        self.set_ratio(&ratios[next]);
     }
 
-    pub fn prev_wall(&mut self) {
+    pub fn prev_wall(&mut self, wallpapers_csv: &mut CsvCache) -> Result<(), WallpapersError> {
         // loop back to the last wallpaper
-        self.index = if self.index == 0 {
+        let index = if self.index == 0 {
             self.files.len() - 1
         } else {
             self.index - 1
         };
-
-        let wallpapers_csv = WallpapersCsv::load();
-        let fname = filename(&self.files[self.index]);
-        let loaded = wallpapers_csv
-            // bounds check is not necessary since the index is always valid
-            .get(&fname)
-            .unwrap_or_else(|| panic!("could not get wallpaper info for {fname}"));
-        self.source = loaded.clone();
-        self.current = loaded.clone();
+        self.load_index(index, wallpapers_csv)
     }
 
-    pub fn next_wall(&mut self) {
+    pub fn next_wall(&mut self, wallpapers_csv: &mut CsvCache) -> Result<(), WallpapersError> {
         // loop back to the first wallpaper
-        self.index = if self.index == self.files.len() - 1 {
+        let index = if self.index == self.files.len() - 1 {
             0
         } else {
             self.index + 1
         };
+        self.load_index(index, wallpapers_csv)
+    }
 
-        let wallpapers_csv = WallpapersCsv::load();
-        let fname = filename(&self.files[self.index]);
-        let loaded = wallpapers_csv
-            // bounds check is not necessary since the index is always valid
-            .get(&filename(&self.files[self.index]))
-            .unwrap_or_else(|| panic!("could not get wallpaper info for {fname}"));
-        self.source = loaded.clone();
-        self.current = loaded.clone();
-    }
-
-    /// removes the current wallpaper from the list
-    pub fn remove(&mut self) {
-        let current_index = self.index;
-        self.next_wall();
-        self.files.remove(current_index);
-        // current_index is unchanged after removal
-        self.index = current_index;
-    }
-
-    pub fn set_from_filename(&mut self, fname: &str) {
-        let wallpapers_csv = WallpapersCsv::load();
-        let loaded = wallpapers_csv
-            .get(fname)
-            .unwrap_or_else(|| panic!("could not get wallpaper info for {fname}"))
-            .clone();
-        self.source = loaded.clone();
-        self.current = loaded;
-        self.index = self
-            .files
-            .iter()
-            .position(|f| filename(f) == fname)
-            .unwrap_or_else(|| panic!("could not find wallpaper: {}", fname));
+    /// removes the current wallpaper from the list and loads the next one
+    pub fn remove(&mut self, wallpapers_csv: &mut CsvCache) -> Result<(), WallpapersError> {
+        Rc::make_mut(&mut self.files).remove(self.index);
+        if self.files.is_empty() {
+            return Ok(());
+        }
+
+        // the next wallpaper is now at the current index, loop back after the last one
+        let index = if self.index == self.files.len() {
+            0
+        } else {
+            self.index
+        };
+        // the removed wallpaper can't stay current even if the next one can't be loaded
+        self.index = index;
+        self.load_index(index, wallpapers_csv)
+    }
+
+    pub fn set_from_filename(
+        &mut self,
+        fname: &str,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), WallpapersError> {
+        match self.files.iter().position(|f| filename(f) == fname) {
+            Some(index) => self.load_index(index, wallpapers_csv),
+            None => Err(WallpapersError::MissingInfo(fname.to_string())),
+        }
     }
 
     /// gets geometry for current aspect ratio
@@ -264,21 +826,221 @@ impl Wallpapers {
         self.current.get_geometry(&self.ratio)
     }
 
-    /// sets the geometry for current aspect ratio
+    /// sets the geometry for current aspect ratio as a single undo step
     pub fn set_geometry(&mut self, geom: &Geometry) {
-        self.current.set_geometry(&self.ratio, geom);
+        if *geom != self.get_geometry() {
+            self.checkpoint();
+            self.drag_geometry(geom);
+        }
+    }
+
+    /// sets the geometry for current aspect ratio without an undo step, for continuous changes
+    /// like dragging the crop, which start with a checkpoint
+    pub fn drag_geometry(&mut self, geom: &Geometry) {
+        // only copies the wallpaper info on the first change, or while a clone of it is alive
+        Rc::make_mut(&mut self.current).set_geometry(&self.ratio, geom);
     }
 
-    /// returns crop candidates for current ratio and image
-    pub fn crop_candidates(&self) -> Vec<Geometry> {
-        self.current.cropper().crop_candidates(&self.ratio)
+    /// saves the current wallpaper as an undo step, the undone steps can't be redone afterwards
+    pub fn checkpoint(&mut self) {
+        if self
+            .undo
+            .last()
+            .map_or(true, |(_, info)| *info != self.current)
+        {
+            self.undo
+                .push((self.ratio.clone(), Rc::clone(&self.current)));
+        }
+        self.redo.clear();
+    }
+
+    /// reverts the last change, showing the ratio that was changed
+    pub fn undo(&mut self) {
+        // a checkpoint without any changes after it, e.g. a click on the crop, is skipped
+        while let Some((ratio, info)) = self.undo.pop() {
+            if info != self.current {
+                let undone = std::mem::replace(&mut self.current, info);
+                self.redo.push((ratio.clone(), undone));
+                self.set_ratio(&ratio);
+                return;
+            }
+        }
+    }
+
+    /// applies the last undone change again
+    pub fn redo(&mut self) {
+        if let Some((ratio, info)) = self.redo.pop() {
+            let redone = std::mem::replace(&mut self.current, info);
+            self.undo.push((ratio.clone(), redone));
+            self.set_ratio(&ratio);
+        }
+    }
+
+    /// adds the resolution of a monitor connected while the ui is open, the geometries of all the
+    /// wallpapers are backfilled like add-resolution, the resolution has to be in the config
+    pub fn add_resolution(
+        &mut self,
+        config: &WallpaperConfig,
+        name: &str,
+        ratio: &AspectRatio,
+        closest: Option<&AspectRatio>,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), WallpapersError> {
+        if self.resolutions.iter().any(|(_, r)| r == ratio) {
+            return Ok(());
+        }
+
+        // the unsaved crops are written first so the backfill doesn't overwrite them
+        wallpapers_csv.flush()?;
+        backfill_resolution(config, ratio, closest)?;
+
+        // the backfilled crop is the baseline of the current wallpaper, its other crops may
+        // still have unsaved changes
+        let geom = wallpapers_csv.get(&self.current.filename).map_or_else(
+            |_| self.cropper().crop(ratio),
+            |info| info.get_geometry(ratio),
+        );
+        Rc::make_mut(&mut self.current).set_geometry(ratio, &geom);
+        Rc::make_mut(&mut self.source).set_geometry(ratio, &geom);
+
+        let mut resolutions = self.resolutions.to_vec();
+        resolutions.push((name.to_string(), ratio.clone()));
+        resolutions.sort_by_key(|(_, r)| r.clone());
+        self.resolutions = resolutions.into();
+        Ok(())
+    }
+
+    /// adds a resolution that may not be in the config yet, e.g. of a new monitor or from --ratio,
+    /// it is saved to the config with the name unless the config already has the ratio and the
+    /// geometries are backfilled
+    pub fn add_config_resolution(
+        &mut self,
+        name: &str,
+        ratio: &AspectRatio,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), WallpapersError> {
+        let mut config = WallpaperConfig::load()?;
+        let closest = config.closest_resolution(ratio);
+        let name = match config.resolutions.iter().find(|(_, r)| r == ratio) {
+            Some((name, _)) => name.clone(),
+            None => {
+                config.add_resolution(name, ratio.clone());
+                config.save().map_err(|source| WallpapersError::Write {
+                    path: WallpaperConfig::config_file(),
+                    source,
+                })?;
+                name.to_string()
+            }
+        };
+
+        self.add_resolution(&config, &name, ratio, closest.as_ref(), wallpapers_csv)
+    }
+
+    /// replaces the faces of the current wallpaper and the detector they came from, the crops
+    /// are reset to the defaults for the new faces
+    pub fn set_faces(&mut self, faces: Vec<Face>, detector: &str) {
+        self.checkpoint();
+        let current = Rc::make_mut(&mut self.current);
+        current.faces = faces;
+        current.detector = detector.to_string();
+        let cropper = self.cropper();
+        let geometries = self
+            .resolutions
+            .iter()
+            .map(|(_, ratio)| (ratio.clone(), cropper.crop(ratio)))
+            .collect();
+        Rc::make_mut(&mut self.current).geometries = geometries;
+    }
+
+    /// replaces the faces of the current wallpaper with faces edited by hand, the detector is
+    /// kept so detecting again uses the same model
+    pub fn edit_faces(&mut self, faces: Vec<Face>) {
+        let detector = self.current.detector.clone();
+        self.set_faces(faces, &detector);
+    }
+
+    /// writes the default crops of the filter ratios for all the wallpapers in the queue and marks
+    /// them as reviewed like saving them in the editor, returns the number of wallpapers changed
+    pub fn apply_default_crops(
+        &mut self,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<usize, WallpapersError> {
+        let csv = wallpapers_csv.csv()?;
+        let updated: Vec<WallInfo> = self
+            .files
+            .iter()
+            .filter_map(|f| csv.get(&filename(f)))
+            .filter_map(|info| {
+                let cropper = info.cropper().with_options(self.crop_options);
+                let mut new_info = info.clone();
+                for ratio in self.filter_ratios.iter() {
+                    new_info.set_geometry(ratio, &cropper.crop(ratio));
+                }
+                new_info.state = "reviewed".to_string();
+                (new_info != *info).then_some(new_info)
+            })
+            .collect();
+
+        let resolutions: Vec<_> = self.resolutions.iter().map(|(_, r)| r.clone()).collect();
+        wallpapers_csv.save_all(&updated, &resolutions)?;
+        wallpapers_csv.flush()?;
+
+        // the current wallpaper is loaded again to show the applied crops
+        self.load_index(self.index, wallpapers_csv)?;
+        Ok(updated.len())
+    }
+
+    /// saves the wallust arguments of the current wallpaper, its other unsaved edits are kept in
+    /// the editor but not saved
+    pub fn save_wallust(
+        &mut self,
+        wallust: &str,
+        wallpapers_csv: &mut CsvCache,
+    ) -> Result<(), WallpapersError> {
+        let mut saved = wallpapers_csv.get(&self.current.filename)?;
+        saved.wallust = wallust.to_string();
+        let resolutions: Vec<_> = self.resolutions.iter().map(|(_, r)| r.clone()).collect();
+        wallpapers_csv.save(&saved, &resolutions)?;
+
+        Rc::make_mut(&mut self.source).wallust = wallust.to_string();
+        Rc::make_mut(&mut self.current).wallust = wallust.to_string();
+        Ok(())
+    }
+
+    /// sets the review state of the current wallpaper
+    pub fn set_state(&mut self, state: &str) {
+        Rc::make_mut(&mut self.current).state = state.to_string();
+    }
+
+    /// adds the comma separated tags to the current wallpaper, tags it already has are skipped
+    pub fn add_tags(&mut self, tags: &str) {
+        Rc::make_mut(&mut self.current).add_tags(tags);
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        Rc::make_mut(&mut self.current).tags.retain(|t| t != tag);
+    }
+
+    /// adds or removes the time of day, weekday, month or season from the schedule of the current
+    /// wallpaper
+    pub fn toggle_schedule(&mut self, entry: &str) {
+        let schedule = &mut Rc::make_mut(&mut self.current).schedule;
+        if let Some(pos) = schedule.iter().position(|e| e == entry) {
+            schedule.remove(pos);
+        } else {
+            schedule.push(entry.to_string());
+        }
+    }
+
+    /// cropper for the current wallpaper
+    pub fn cropper(&self) -> Cropper {
+        self.current.cropper().with_options(self.crop_options)
     }
 
     /// returns cropping ratios for resolution buttons
     pub fn image_ratios(&self) -> Vec<(String, AspectRatio)> {
         self.resolutions
-            .clone()
-            .into_iter()
+            .iter()
             .filter(|(_, ratio)| {
                 // do not show resolution if aspect ratio of image is the same,
                 // as there is only a single possible crop
@@ -286,36 +1048,46 @@ impl Wallpapers {
                     .abs()
                     > f64::EPSILON
             })
+            .cloned()
             .collect()
     }
 
-    /// returns the candidate geometries for candidate buttons
+    /// returns the candidate geometries for candidate buttons, without near duplicates
     pub fn candidate_geometries(&self) -> Vec<Geometry> {
-        self.crop_candidates().into_iter().unique().collect()
+        self.cropper()
+            .crop_candidates_within(&self.ratio, self.candidate_tolerance)
     }
 
     /// moves the crop area of the current wallpaper based on its direction
-    pub fn move_geometry_by(&self, delta: i32) -> Geometry {
+    ///
+    /// crops constrained to a single axis move along it by either delta, crops that are
+    /// free in both axes move by each delta separately
+    pub fn move_geometry_by(&self, dx: i32, dy: i32) -> Geometry {
         let current_geom = self.get_geometry();
 
-        let negative_delta = delta < 0;
-        let delta = delta.unsigned_abs();
+        let shift = |pos: u32, delta: i32, max: u32| {
+            let abs_delta = delta.unsigned_abs();
+            if delta < 0 {
+                pos.max(abs_delta) - abs_delta
+            } else {
+                (pos + abs_delta).min(max)
+            }
+        };
+        let max_x = self.current.width - current_geom.w;
+        let max_y = self.current.height - current_geom.h;
 
         match self.current.direction(&current_geom) {
             Direction::X => Geometry {
-                x: if negative_delta {
-                    current_geom.x.max(delta) - delta
-                } else {
-                    (current_geom.x + delta).min(self.current.width - current_geom.w)
-                },
+                x: shift(current_geom.x, dx + dy, max_x),
                 ..current_geom
             },
             Direction::Y => Geometry {
-                y: if negative_delta {
-                    current_geom.y.max(delta) - delta
-                } else {
-                    (current_geom.y + delta).min(self.current.height - current_geom.h)
-                },
+                y: shift(current_geom.y, dx + dy, max_y),
+                ..current_geom
+            },
+            Direction::Both => Geometry {
+                x: shift(current_geom.x, dx, max_x),
+                y: shift(current_geom.y, dy, max_y),
                 ..current_geom
             },
         }
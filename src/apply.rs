@@ -0,0 +1,164 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use thiserror::Error;
+
+use crate::{
+    cache::render_cached, config::WallpaperConfig, geometry::Geometry, monitors::Monitor,
+    tools::ExternalTools, wallpapers::WallInfo,
+};
+
+#[derive(Error, Debug)]
+pub enum ApplyError {
+    #[error("could not crop wallpaper: {0}")]
+    Crop(#[from] image::ImageError),
+    #[error("could not set wallpaper: {0}")]
+    Set(#[from] std::io::Error),
+}
+
+/// transition options for swww, hyprpaper does not support transitions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub kind: String,
+    /// in seconds
+    pub duration: f64,
+}
+
+impl Transition {
+    pub fn from_config(config: &WallpaperConfig) -> Self {
+        Self {
+            kind: config.transition_type.clone(),
+            duration: config.transition_duration,
+        }
+    }
+}
+
+fn check_status(cmd: &str, status: ExitStatus) -> std::io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{cmd} exited with {status}")))
+    }
+}
+
+/// runs the command with its output silenced
+fn run(cmd: &mut Command, name: &str) -> std::io::Result<()> {
+    let status = cmd.stdout(Stdio::null()).status()?;
+    check_status(name, status)
+}
+
+/// sets the wallpapers of the monitors, as (monitor name, cropped wallpaper)
+///
+/// swww, hyprpaper and xwallpaper set each monitor separately, feh sets all monitors in one
+/// call, while gnome and kde only support a single wallpaper for all monitors
+pub fn set_wallpapers(
+    backend: &str,
+    tools: &ExternalTools,
+    wallpapers: &[(String, PathBuf)],
+    transition: &Transition,
+) -> std::io::Result<()> {
+    let Some((_, first)) = wallpapers.first() else {
+        return Ok(());
+    };
+
+    match backend {
+        "hyprpaper" => {
+            for (monitor, wallpaper) in wallpapers {
+                // hyprpaper needs the wallpaper to be preloaded before it can be set
+                run(
+                    Command::new("hyprctl")
+                        .args(["hyprpaper", "preload"])
+                        .arg(wallpaper),
+                    "hyprctl",
+                )?;
+                run(
+                    Command::new("hyprctl")
+                        .args(["hyprpaper", "wallpaper"])
+                        .arg(format!("{monitor},{}", wallpaper.display())),
+                    "hyprctl",
+                )?;
+            }
+            Ok(())
+        }
+        "gnome" => {
+            let uri = format!("file://{}", first.display());
+            for key in ["picture-uri", "picture-uri-dark"] {
+                run(
+                    Command::new("gsettings")
+                        .args(["set", "org.gnome.desktop.background", key])
+                        .arg(&uri),
+                    "gsettings",
+                )?;
+            }
+            run(
+                Command::new("gsettings").args([
+                    "set",
+                    "org.gnome.desktop.background",
+                    "picture-options",
+                    "zoom",
+                ]),
+                "gsettings",
+            )
+        }
+        "kde" => run(
+            Command::new("plasma-apply-wallpaperimage").arg(first),
+            "plasma-apply-wallpaperimage",
+        ),
+        // feh assigns the wallpapers to the monitors in xinerama order
+        "feh" => run(
+            tools
+                .get("feh")
+                .command()
+                .args(["--no-fehbg", "--bg-fill"])
+                .args(wallpapers.iter().map(|(_, wallpaper)| wallpaper)),
+            "feh",
+        ),
+        "xwallpaper" => {
+            let mut cmd = tools.get("xwallpaper").command();
+            for (monitor, wallpaper) in wallpapers {
+                cmd.args(["--output", monitor, "--zoom"]).arg(wallpaper);
+            }
+            run(&mut cmd, "xwallpaper")
+        }
+        _ => {
+            for (monitor, wallpaper) in wallpapers {
+                run(
+                    tools
+                        .get("swww")
+                        .command()
+                        .args(["img", "--outputs", monitor])
+                        .args(["--transition-type", &transition.kind])
+                        .args(["--transition-duration", &transition.duration.to_string()])
+                        .arg(wallpaper),
+                    "swww",
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// crops each wallpaper with its stored geometry for its monitor and sets them all at once,
+/// returns the geometries
+pub fn apply_to_monitors(
+    config: &WallpaperConfig,
+    wallpapers: &[(&WallInfo, &Path, &Monitor)],
+    backend: &str,
+    transition: &Transition,
+) -> Result<Vec<Geometry>, ApplyError> {
+    let mut geometries = Vec::new();
+    let mut cropped = Vec::new();
+
+    for (info, image, monitor) in wallpapers {
+        let ratio = monitor.aspect_ratio();
+        let geom = info.get_geometry(&ratio);
+
+        cropped.push((monitor.name.clone(), render_cached(image, &ratio, &geom)?));
+        geometries.push(geom);
+    }
+
+    set_wallpapers(backend, &config.tools, &cropped, transition)?;
+    Ok(geometries)
+}
@@ -1,26 +1,7 @@
-use wallpaper_ui::{config::WallpaperConfig, wallpapers::WallpapersCsv};
+use clap::Parser;
+use wallpaper_ui::{cli::BatchArgs, commands};
 
+/// compatibility shim for `wallpapers batch`
 fn main() {
-    let config = WallpaperConfig::new();
-    let wallpapers_csv = WallpapersCsv::load();
-
-    wallpapers_csv.save(&config.sorted_resolutions());
-
-    // let argstr = [
-    //     "wallust",
-    //     "--backend",
-    //     "wal",
-    //     "--palette",
-    //     "dark",
-    //     "--fallback-generator",
-    //     "complementary",
-    // ];
+    commands::batch::run(&BatchArgs::parse());
 }
-
-// #[cfg(test)]
-// mod tests {
-//     use clap::Parser;
-
-//     #[test]
-//     fn test_wallust_to() {}
-// }
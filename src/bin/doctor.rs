@@ -0,0 +1,7 @@
+use clap::Parser;
+use wallpaper_ui::{cli::DoctorArgs, commands};
+
+/// compatibility shim for `wallpapers doctor`
+fn main() {
+    commands::doctor::run(&DoctorArgs::parse());
+}
@@ -0,0 +1,4 @@
+/// compatibility shim for `wallpapers ui`
+fn main() {
+    wallpaper_ui::run_wallpaper_ui(std::env::args().skip(1).collect::<Vec<_>>());
+}
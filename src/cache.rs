@@ -0,0 +1,253 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    aspect_ratio::AspectRatio, colorscheme::luminance, geometry::Geometry,
+    image_ops::render_geometry, paths, write_atomic,
+};
+
+/// largest dimension of the thumbnails of the exported gallery
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// largest dimension of the thumbnails in the file list, twice its size for hidpi screens
+pub const FILELIST_THUMBNAIL_SIZE: u32 = 160;
+
+/// sizes of the thumbnails that are rendered by `wallpapers cache build`
+pub const THUMBNAIL_SIZES: &[u32] = &[THUMBNAIL_SIZE, FILELIST_THUMBNAIL_SIZE];
+
+/// wallpapers with an average luminance below this are dark, the others are light
+pub const DARK_LUMINANCE: u8 = 128;
+
+/// values of the --brightness filter
+pub const BRIGHTNESS_FILTERS: &[&str] = &["all", "dark", "light"];
+
+/// directory of the rendered crops
+pub fn crops_dir() -> PathBuf {
+    paths::cache_dir().join("crops")
+}
+
+/// directory of the thumbnails of the wallpapers
+pub fn thumbnails_dir() -> PathBuf {
+    paths::cache_dir().join("thumbnails")
+}
+
+/// hash of the contents of the image, so replaced or edited wallpapers are rendered again
+pub fn content_hash(image: &Path) -> std::io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    std::fs::read(image)?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// path of the cached crop, keyed by the content hash of the image and the geometry
+pub fn cached_crop_path(
+    image: &Path,
+    content: u64,
+    ratio: &AspectRatio,
+    geom: &Geometry,
+) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    geom.hash(&mut hasher);
+
+    let stem = image
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().to_string());
+
+    crops_dir().join(format!("{stem}-{ratio}-{:x}.png", hasher.finish()))
+}
+
+/// renders the crops of the image that are not cached yet, returns the paths of all the crops
+///
+/// the image is only decoded if any of the crops need to be rendered
+pub fn render_all(
+    image: &Path,
+    geometries: &[(AspectRatio, Geometry)],
+) -> image::ImageResult<Vec<(PathBuf, bool)>> {
+    let content = content_hash(image)?;
+    std::fs::create_dir_all(crops_dir())?;
+
+    let mut img = None;
+    let mut crops = Vec::new();
+    for (ratio, geom) in geometries {
+        let dest = cached_crop_path(image, content, ratio, geom);
+        let rendered = !dest.exists();
+
+        if rendered {
+            if img.is_none() {
+                img = Some(image::open(image)?);
+            }
+            if let Some(img) = &img {
+                render_geometry(img, geom, ratio).save(&dest)?;
+            }
+        }
+
+        crops.push((dest, rendered));
+    }
+
+    Ok(crops)
+}
+
+/// renders the crop of the image for the aspect ratio, reusing the cached crop if it exists
+pub fn render_cached(
+    image: &Path,
+    ratio: &AspectRatio,
+    geom: &Geometry,
+) -> image::ImageResult<PathBuf> {
+    let mut crops = render_all(image, &[(ratio.clone(), geom.clone())])?;
+    Ok(crops.remove(0).0)
+}
+
+/// path of the cached thumbnail, keyed by the content hash of the image and the size
+pub fn thumbnail_path(content: u64, size: u32) -> PathBuf {
+    thumbnails_dir().join(format!("{content:x}-{size}.jpg"))
+}
+
+/// renders the thumbnails of the image for the sizes that are not cached yet, returns the paths
+/// of all the thumbnails and if they had to be rendered
+///
+/// the image is only decoded if any of the thumbnails need to be rendered
+pub fn thumbnails(image: &Path, sizes: &[u32]) -> image::ImageResult<Vec<(PathBuf, bool)>> {
+    let content = content_hash(image)?;
+
+    let mut img = None;
+    let mut thumbs = Vec::new();
+    for &size in sizes {
+        let dest = thumbnail_path(content, size);
+        let rendered = !dest.exists();
+
+        if rendered {
+            if img.is_none() {
+                img = Some(image::open(image)?);
+            }
+            if let Some(img) = &img {
+                let mut bytes = Vec::new();
+                img.thumbnail(size, size)
+                    .to_rgb8()
+                    .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Jpeg)?;
+                // written atomically since the ui generates thumbnails in the background
+                write_atomic(&dest, &bytes)?;
+            }
+        }
+
+        thumbs.push((dest, rendered));
+    }
+
+    Ok(thumbs)
+}
+
+/// thumbnail of the image with the largest dimension of size, reusing the cached thumbnail if it
+/// exists, returns the path of the thumbnail and if it had to be rendered
+pub fn thumbnail(image: &Path, size: u32) -> image::ImageResult<(PathBuf, bool)> {
+    let mut thumbs = thumbnails(image, &[size])?;
+    Ok(thumbs.remove(0))
+}
+
+/// file of the cached average luminance of the wallpapers
+pub fn brightness_file() -> PathBuf {
+    paths::cache_dir().join("brightness.json")
+}
+
+/// seconds since the unix epoch the image was modified
+fn mtime(image: &Path) -> Option<u64> {
+    let modified = image.metadata().and_then(|meta| meta.modified()).ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// average perceived brightness of the image from 0 to 255
+pub fn average_luminance(image: &Path) -> image::ImageResult<u8> {
+    let thumb = image::open(image)?.thumbnail(64, 64).to_rgb8();
+    let pixels = u64::from(thumb.width()) * u64::from(thumb.height());
+    let total: u64 = thumb
+        .pixels()
+        .map(|image::Rgb(rgb)| u64::from(luminance(*rgb)))
+        .sum();
+    Ok((total / pixels.max(1)) as u8)
+}
+
+/// average luminance of the wallpapers, keyed by their path and modification time so replaced
+/// wallpapers are computed again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BrightnessCache {
+    entries: HashMap<PathBuf, (u64, u8)>,
+}
+
+impl BrightnessCache {
+    /// reads the cache, a missing or invalid cache is empty
+    pub fn load() -> Self {
+        std::fs::read_to_string(brightness_file())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let contents = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        write_atomic(&brightness_file(), contents.as_bytes())
+    }
+
+    /// computes the luminance of the images that are new or have been modified, returns true if
+    /// any were computed
+    pub fn update(&mut self, images: &[PathBuf]) -> bool {
+        let computed: Vec<_> = images
+            .par_iter()
+            .filter_map(|image| {
+                let mtime = mtime(image)?;
+                if self
+                    .entries
+                    .get(image)
+                    .is_some_and(|(cached, _)| *cached == mtime)
+                {
+                    return None;
+                }
+                let luminance = average_luminance(image).ok()?;
+                Some((image.clone(), (mtime, luminance)))
+            })
+            .collect();
+
+        let updated = !computed.is_empty();
+        self.entries.extend(computed);
+        updated
+    }
+
+    pub fn luminance(&self, image: &Path) -> Option<u8> {
+        self.entries.get(image).map(|(_, luminance)| *luminance)
+    }
+
+    /// checks the image against the --brightness filter, images that couldn't be read only
+    /// match all
+    pub fn matches(&self, image: &Path, brightness: &str) -> bool {
+        match brightness {
+            "dark" => self.luminance(image).is_some_and(|l| l < DARK_LUMINANCE),
+            "light" => self.luminance(image).is_some_and(|l| l >= DARK_LUMINANCE),
+            _ => true,
+        }
+    }
+}
+
+/// keeps the images that match the --brightness filter, the luminance of new images is computed
+/// and cached
+pub fn filter_brightness(images: &mut Vec<PathBuf>, brightness: &str) {
+    if brightness == "all" {
+        return;
+    }
+
+    let mut cache = BrightnessCache::load();
+    if cache.update(images) {
+        if let Err(e) = cache.save() {
+            warn!("Could not write {:?}: {e}", brightness_file());
+        }
+    }
+    images.retain(|image| cache.matches(image, brightness));
+}
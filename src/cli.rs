@@ -1,6 +1,114 @@
 use std::path::PathBuf;
 
-use clap::{builder::PossibleValuesParser, Parser};
+use clap::{builder::PossibleValuesParser, ArgAction, Args, Parser, Subcommand};
+
+// ------------------------- WALLPAPERS -------------------------
+#[derive(Parser, Debug)]
+#[command(
+    name = "wallpapers",
+    about = "Manages wallpapers and their crops for multiple monitor resolutions"
+)]
+pub struct WallpapersArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(subcommand)]
+    pub command: Option<WallpapersCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WallpapersCommand {
+    #[command(about = "Allows the selection of a cropping area for multiple monitor resolutions")]
+    Ui(WallpaperUIArgs),
+
+    #[command(about = "Adds wallpapers, and performs the face detection")]
+    Add(WallpapersAddArgs),
+
+    #[command(about = "Adds a new resolution for cropping")]
+    AddResolution(AddResolutionArgs),
+
+    #[command(about = "Applies a cropping rule to many wallpapers without the UI")]
+    Batch(BatchArgs),
+
+    #[command(about = "Checks the config, external tools and wallpapers.csv for problems")]
+    Doctor(DoctorArgs),
+
+    #[command(about = "Crops a wallpaper for the monitors and sets it")]
+    Apply(ApplyArgs),
+
+    #[command(about = "Rotates wallpapers on the monitors on an interval")]
+    Daemon(DaemonArgs),
+
+    #[command(about = "Prints the state of the daemon as json for waybar")]
+    Waybar(WaybarArgs),
+
+    #[command(about = "Syncs the wallpapers and wallpapers.csv with another machine via rsync")]
+    Sync(SyncArgs),
+
+    #[command(about = "Writes a json manifest of the wallpapers, their crops, palettes and tags")]
+    ExportManifest(ExportManifestArgs),
+
+    #[command(about = "Exports variants of the crops for other programs")]
+    Export(ExportArgs),
+
+    #[command(about = "Manages the config")]
+    Config(ConfigArgs),
+
+    #[command(about = "Manages the cache of cropped wallpapers")]
+    Cache(CacheArgs),
+
+    #[command(about = "Deletes wallpapers to the trash and restores them with their crops")]
+    Trash(TrashArgs),
+
+    #[command(about = "Finds copies of the wallpapers and keeps the highest resolution of each")]
+    Dedupe(DedupeArgs),
+
+    #[command(about = "Serves a json api for the wallpapers, their crops, tags and palettes")]
+    Serve(ServeArgs),
+
+    #[command(
+        about = "Prints the changes to the crops, faces and tags between two wallpapers.csv"
+    )]
+    Diff(DiffArgs),
+
+    #[command(about = "Sets the review state of wallpapers, e.g. to publish the reviewed ones")]
+    State(StateArgs),
+
+    #[command(
+        about = "Copies the wallpapers between wallpapers.csv and the sqlite database and switches the store in the config"
+    )]
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        help = "increase logging verbosity, can be repeated"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        action,
+        global = true,
+        conflicts_with = "verbose",
+        help = "only log errors"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "also write logs to a file"
+    )]
+    pub log_file: Option<PathBuf>,
+}
 
 // ------------------------- WALLPAPER UI -------------------------
 #[allow(clippy::struct_excessive_bools)]
@@ -13,6 +121,9 @@ pub struct WallpaperUIArgs {
     #[arg(long, action, help = "print version information and exit")]
     pub version: bool,
 
+    #[command(flatten)]
+    pub log: LogArgs,
+
     #[arg(
         long,
         default_value = None,
@@ -50,9 +161,116 @@ pub struct WallpaperUIArgs {
     )]
     pub faces: String,
 
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "new", "needs-crop", "reviewed", "published"]),
+        help = "only show wallpapers in the review state"
+    )]
+    pub state: String,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "dark", "light"]),
+        help = "only show dark or light wallpapers, by their average luminance"
+    )]
+    pub brightness: String,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "portrait", "landscape"]),
+        help = "only show wallpapers that still use the default crops of the portrait or landscape resolutions, starting on the first of them"
+    )]
+    pub orientation: String,
+
+    #[arg(
+        long,
+        value_name = "WxH",
+        help = "crops for the aspect ratio, e.g. 21x9 or 2.39, it is added to the config if it isn't in it yet"
+    )]
+    pub ratio: Option<String>,
+
     #[arg(long, help = "filters wallpapers by filename (case-insensitive)")]
     pub filter: Option<String>,
 
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "only show wallpapers with any of the comma separated tags, can be repeated to require all"
+    )]
+    pub tag: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "hide wallpapers with any of the comma separated tags"
+    )]
+    pub not_tag: Vec<String>,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new([
+            "name",
+            "mtime",
+            "size",
+            "resolution",
+            "faces",
+            "random",
+        ]),
+        help = "order of the wallpapers, newest / largest first except for name; defaults to the sort in config.ini or mtime"
+    )]
+    pub sort: Option<String>,
+
+    #[arg(long, action, help = "reverses the sort order")]
+    pub reverse: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "reviews the upscaled images in the json file written by the add command instead of editing wallpapers"
+    )]
+    pub review_upscales: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "reviews the default crops of the wallpapers in the json file written by the add command instead of editing wallpapers"
+    )]
+    pub review_crops: Option<PathBuf>,
+
+    #[arg(
+        long,
+        action,
+        help = "edits the crops in the terminal instead of opening a window, e.g. over ssh"
+    )]
+    pub tui: bool,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = "0.0.0.0:8080",
+        help = "serves the editor over http for a browser on another machine, defaults to 0.0.0.0:8080"
+    )]
+    pub serve: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        requires = "serve",
+        help = "token needed to open the served editor, a random token is printed if not provided"
+    )]
+    pub token: Option<String>,
+
+    #[arg(
+        long,
+        action,
+        help = "writes the default crops of the filtered wallpapers for the filtered resolutions and marks them as reviewed, without opening the editor"
+    )]
+    pub accept_defaults: bool,
+
     // positional arguments for file paths
     pub paths: Option<Vec<PathBuf>>,
 }
@@ -66,11 +284,14 @@ pub struct WallpapersAddArgs {
     #[arg(long, action, help = "print version information and exit")]
     pub version: bool,
 
+    #[command(flatten)]
+    pub log: LogArgs,
+
     #[arg(
         long,
         action,
         value_name = "MIN_WIDTH",
-        help = "minimum width for wallpapers to be resized, defaults to 1920 if not provided in config.ini"
+        help = "minimum width for wallpapers to be resized, defaults to 1920 if not provided in config.ini; the min_sizes of the resolutions still apply"
     )]
     pub min_width: Option<u32>,
 
@@ -78,7 +299,7 @@ pub struct WallpapersAddArgs {
         long,
         action,
         value_name = "MIN_HEIGHT",
-        help = "minimum height for wallpapers to be resized, defaults to 1080 if not provided in config.ini"
+        help = "minimum height for wallpapers to be resized, defaults to 1080 if not provided in config.ini; the min_sizes of the resolutions still apply"
     )]
     pub min_height: Option<u32>,
 
@@ -91,8 +312,74 @@ pub struct WallpapersAddArgs {
     )]
     pub format: Option<String>,
 
-    // required positional argument for input directory
-    // positional arguments for file paths
+    #[arg(
+        short,
+        long,
+        action,
+        help = "recursively add images from subdirectories of the input directories"
+    )]
+    pub recursive: bool,
+
+    #[arg(
+        long,
+        action,
+        value_name = "GPUS",
+        help = "gpus to upscale with, either auto, cpu or comma separated gpu indexes to spread the upscales across, e.g. 0,1; overrides upscale_gpus in config.ini"
+    )]
+    pub gpu: Option<String>,
+
+    #[arg(
+        long,
+        action,
+        value_name = "JOBS",
+        help = "number of upscales, optimizations and other tools run at the same time, the upscales of each gpu still run one after another; overrides max_jobs in config.ini"
+    )]
+    pub jobs: Option<usize>,
+
+    #[arg(
+        long,
+        action,
+        help = "reprocess the images even if they have already been added, overwriting the existing entries"
+    )]
+    pub replace: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "reviews the default crops of the new wallpapers before opening the editor, only the rejected wallpapers are edited; defaults to review_crops in config.ini"
+    )]
+    pub review: bool,
+
+    #[arg(
+        long,
+        default_value = "anime",
+        value_parser = PossibleValuesParser::new(["anime", "photo", "none", "command"]),
+        help = "face detection backend: anime uses face_detector, photo uses photo_face_model, none skips detection for abstract wallpapers and command runs face_command in config.ini"
+    )]
+    pub detector: String,
+
+    #[arg(
+        long,
+        action,
+        help = "add the images at their original size, even if they are smaller than the minimum size"
+    )]
+    pub no_upscale: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "copy the images to the wallpapers directory without recompressing them"
+    )]
+    pub no_optimize: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "only detect the faces of wallpapers that were already added, their edited crops are kept"
+    )]
+    pub detect_only: bool,
+
+    // positional arguments for file paths, directories or glob patterns
     pub paths: Option<Vec<PathBuf>>,
 }
 
@@ -102,9 +389,761 @@ pub struct AddResolutionArgs {
     #[arg(long, action, help = "print version information and exit")]
     pub version: bool,
 
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(long, action, help = "print output as json")]
+    pub json: bool,
+
     // required positional argument for input directory
     pub name: String,
 
     // required positional argument for input directory
     pub resolution: String,
 }
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Parser, Debug)]
+#[command(
+    name = "batch",
+    about = "Applies a cropping rule to many wallpapers without the UI"
+)]
+pub struct BatchArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(["start", "center", "end", "default"]),
+        help = "aligns the crops, \"default\" re-runs the default cropper; the csv is only cleaned up if not provided"
+    )]
+    pub align: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_name = "RESOLUTIONS",
+        help = "resolutions to apply the rule to; either \"all\" or resolution(s) in the format \"1920x1080,1920x1200\""
+    )]
+    pub resolutions: String,
+
+    #[arg(
+        long,
+        action,
+        help = "only apply the rule to crops that still use the default crop"
+    )]
+    pub unmodified: bool,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new([
+            "zero",
+            "none",
+            "one",
+            "single",
+            "many",
+            "multiple",
+            "all",
+        ]),
+        help = "only apply the rule to wallpapers with the given number of faces"
+    )]
+    pub faces: String,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "new", "needs-crop", "reviewed", "published"]),
+        help = "only apply the rule to wallpapers in the review state"
+    )]
+    pub state: String,
+
+    #[arg(long, help = "filters wallpapers by filename (case-insensitive)")]
+    pub filter: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "only apply the rule to wallpapers with any of the comma separated tags, can be repeated to require all"
+    )]
+    pub tag: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "skip wallpapers with any of the comma separated tags"
+    )]
+    pub not_tag: Vec<String>,
+
+    #[arg(long, action, help = "print the changes without saving them")]
+    pub dry_run: bool,
+
+    #[arg(long, action, help = "print output as json")]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "doctor",
+    about = "Checks the config, external tools and wallpapers.csv for problems"
+)]
+pub struct DoctorArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(long, action, help = "print output as json")]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "apply",
+    about = "Crops a wallpaper for the monitors and sets it"
+)]
+pub struct ApplyArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(help = "path or filename of the wallpaper")]
+    pub wallpaper: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "MONITORS",
+        help = "comma separated monitor names, e.g. DP-1, defaults to all monitors"
+    )]
+    pub monitors: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new([
+            "swww",
+            "hyprpaper",
+            "gnome",
+            "kde",
+            "feh",
+            "xwallpaper",
+        ]),
+        help = "program used to set the wallpaper, defaults to apply_backend in the config"
+    )]
+    pub backend: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TYPE",
+        help = "swww transition type, defaults to transition_type in the config"
+    )]
+    pub transition_type: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "swww transition duration, defaults to transition_duration in the config"
+    )]
+    pub transition_duration: Option<f64>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "daemon",
+    about = "Rotates wallpapers on the monitors on an interval"
+)]
+pub struct DaemonArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "sends a command to the running daemon instead of starting one; one of pause, resume, next, prev, current, status, \"crop FILENAME RESOLUTION\" or \"set FILENAME\""
+    )]
+    pub send: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "600",
+        value_name = "SECONDS",
+        help = "seconds between wallpaper changes"
+    )]
+    pub interval: u64,
+
+    #[arg(
+        long,
+        value_name = "MONITORS",
+        help = "comma separated monitor names to rotate, defaults to all monitors"
+    )]
+    pub monitors: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "any",
+        value_parser = PossibleValuesParser::new(["any", "landscape", "portrait", "monitor"]),
+        help = "only rotate wallpapers with the given orientation, \"monitor\" matches the orientation of each monitor"
+    )]
+    pub orientation: String,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new([
+            "zero",
+            "none",
+            "one",
+            "single",
+            "many",
+            "multiple",
+            "all",
+        ]),
+        help = "only rotate wallpapers with the given number of faces"
+    )]
+    pub faces: String,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "new", "needs-crop", "reviewed", "published"]),
+        help = "only rotate wallpapers in the review state"
+    )]
+    pub state: String,
+
+    #[arg(
+        long,
+        action,
+        help = "rotate through all wallpapers, including the ones outside of their schedule"
+    )]
+    pub ignore_schedule: bool,
+
+    #[arg(
+        long,
+        default_value = "all",
+        value_parser = PossibleValuesParser::new(["all", "dark", "light"]),
+        help = "only rotate dark or light wallpapers, by their average luminance"
+    )]
+    pub brightness: String,
+
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "only rotate wallpapers with any of the comma separated tags, can be repeated to require all"
+    )]
+    pub tag: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TAGS",
+        help = "skip wallpapers with any of the comma separated tags"
+    )]
+    pub not_tag: Vec<String>,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new([
+            "swww",
+            "hyprpaper",
+            "gnome",
+            "kde",
+            "feh",
+            "xwallpaper",
+        ]),
+        help = "program used to set the wallpaper, defaults to apply_backend in the config"
+    )]
+    pub backend: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "waybar",
+    about = "Prints the state of the daemon as json for waybar"
+)]
+pub struct WaybarArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        value_name = "MONITOR",
+        help = "monitor to show the wallpaper of, defaults to the first monitor"
+    )]
+    pub monitor: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "<span color='{accent}'>●</span> {filename}",
+        help = "format of the text, with {filename}, {accent}, {countdown} and {monitor}"
+    )]
+    pub format: String,
+
+    #[arg(
+        long,
+        default_value = "{monitor}: {filename}\nnext wallpaper in {countdown}",
+        help = "format of the tooltip, with the same placeholders as --format"
+    )]
+    pub tooltip_format: String,
+
+    #[arg(
+        long,
+        action,
+        help = "print the state once and exit instead of updating every second"
+    )]
+    pub once: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "sync",
+    about = "Syncs the wallpapers and wallpapers.csv with another machine via rsync"
+)]
+pub struct SyncArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(help = "rsync destination of the wallpapers directory, e.g. host:Pictures/Wallpapers")]
+    pub remote: String,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "rsync destination of wallpapers.csv, defaults to wallpapers.csv in the remote directory"
+    )]
+    pub remote_csv: Option<String>,
+
+    #[arg(
+        long,
+        action,
+        help = "print the files that would be transferred without syncing"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "export-manifest",
+    about = "Writes a json manifest of the wallpapers, their crops, palettes and tags"
+)]
+pub struct ExportManifestArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        help = "path of the manifest, defaults to manifest.json next to wallpapers.csv"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+// ------------------------- EXPORT -------------------------
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    pub command: ExportCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    #[command(about = "Exports blurred and dimmed crops for swaylock or hyprlock")]
+    Lockscreen(LockscreenArgs),
+
+    #[command(about = "Renders the colorscheme templates with the palette of each wallpaper")]
+    Colorschemes(ColorschemesArgs),
+
+    #[command(about = "Exports downscaled and compressed crops for laptops and phones")]
+    LowPower(LowPowerArgs),
+
+    #[command(
+        about = "Renders a static html gallery of the wallpapers that can be browsed anywhere"
+    )]
+    Gallery(GalleryArgs),
+
+    #[command(about = "Writes a shell script of magick or ffmpeg commands that render the crops")]
+    Script(ScriptArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct LockscreenArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        help = "output directory, defaults to lockscreen_dir in the config"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "gaussian blur sigma, defaults to lockscreen_blur in the config"
+    )]
+    pub blur: Option<f32>,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "brightness as a percentage of the original, defaults to lockscreen_brightness in the config"
+    )]
+    pub brightness: Option<u32>,
+
+    #[arg(long, action, help = "export all crops, even if they are unchanged")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ColorschemesArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "directory of the templates, defaults to colorscheme_templates in the config"
+    )]
+    pub templates: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        help = "output directory, defaults to colorscheme_dir in the config"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        action,
+        help = "render all colorschemes, even if they are up to date"
+    )]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LowPowerArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        help = "output directory, defaults to low_power_dir in the config"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        help = "maximum height of the crops, defaults to low_power_height in the config"
+    )]
+    pub height: Option<u32>,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(["webp", "jpg"]),
+        help = "format of the crops, defaults to low_power_format in the config"
+    )]
+    pub format: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(0..=100),
+        help = "encoder quality, defaults to low_power_quality in the config"
+    )]
+    pub quality: Option<u32>,
+
+    #[arg(long, action, help = "export all crops, even if they are unchanged")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct GalleryArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        help = "output directory, defaults to gallery_dir in the config"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(long, action, help = "copy all crops, even if they are unchanged")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ScriptArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "path of the script, defaults to stdout"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "magick",
+        value_parser = PossibleValuesParser::new(["magick", "ffmpeg"]),
+        help = "tool used to render the crops"
+    )]
+    pub tool: String,
+}
+
+// ------------------------- CONFIG -------------------------
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Detects the connected monitors and adds their resolutions to the config")]
+    DetectMonitors(DetectMonitorsArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DetectMonitorsArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(short, long, action, help = "add all new resolutions without asking")]
+    pub yes: bool,
+
+    #[arg(long, action, help = "print output as json")]
+    pub json: bool,
+}
+
+// ------------------------- CACHE -------------------------
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    #[command(
+        about = "Renders the crops and thumbnails of all wallpapers that are not cached yet"
+    )]
+    Build(CacheBuildArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CacheBuildArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        action,
+        help = "remove cached crops and thumbnails of edited or deleted wallpapers"
+    )]
+    pub prune: bool,
+}
+
+// ------------------------- TRASH -------------------------
+#[derive(Args, Debug)]
+pub struct TrashArgs {
+    #[command(subcommand)]
+    pub command: TrashCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommand {
+    #[command(about = "Lists the deleted wallpapers, newest last")]
+    List(TrashListArgs),
+
+    #[command(about = "Moves wallpapers to the trash, keeping their rows of wallpapers.csv")]
+    Delete(TrashDeleteArgs),
+
+    #[command(about = "Restores deleted wallpapers and their rows of wallpapers.csv")]
+    Restore(TrashRestoreArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TrashListArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(long, action, help = "print output as json")]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TrashDeleteArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        required = true,
+        help = "filenames of the wallpapers in the wallpapers directory"
+    )]
+    pub filenames: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TrashRestoreArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        required = true,
+        help = "filenames of the deleted wallpapers, the most recent deletion of each is restored"
+    )]
+    pub filenames: Vec<String>,
+}
+
+// ------------------------- MIGRATE -------------------------
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        default_value = "sqlite",
+        value_parser = PossibleValuesParser::new(["csv", "sqlite"]),
+        help = "store to copy the wallpapers to"
+    )]
+    pub to: String,
+
+    #[arg(
+        long,
+        action,
+        help = "replace the wallpapers that are already in the store"
+    )]
+    pub force: bool,
+}
+
+// ------------------------- DIFF -------------------------
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(help = "older wallpapers.csv, e.g. from git show HEAD:wallpapers.csv")]
+    pub old: PathBuf,
+
+    #[arg(help = "newer wallpapers.csv, defaults to the csv in the config")]
+    pub new: Option<PathBuf>,
+}
+
+// ------------------------- STATE -------------------------
+#[derive(Args, Debug)]
+pub struct StateArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        value_parser = PossibleValuesParser::new(["new", "needs-crop", "reviewed", "published"]),
+        help = "new review state of the wallpapers"
+    )]
+    pub state: String,
+
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(["new", "needs-crop", "reviewed", "published"]),
+        help = "only change wallpapers in this review state, e.g. --from reviewed to publish them"
+    )]
+    pub from: Option<String>,
+
+    #[arg(help = "filenames of the wallpapers, defaults to all wallpapers")]
+    pub filenames: Vec<String>,
+}
+
+// ------------------------- DEDUPE -------------------------
+#[derive(Args, Debug)]
+pub struct DedupeArgs {
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        short,
+        long,
+        action,
+        help = "keep the highest resolution copy of each group without asking"
+    )]
+    pub yes: bool,
+
+    #[arg(long, action, help = "print the duplicates without removing them")]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "only find byte for byte copies, skipping the perceptual hashes"
+    )]
+    pub exact: bool,
+
+    #[arg(
+        long,
+        value_name = "BITS",
+        value_parser = clap::value_parser!(u32).range(0..=64),
+        default_value_t = 4,
+        help = "maximum number of bits the perceptual hash of an image can differ from the copy that is kept"
+    )]
+    pub threshold: u32,
+
+    #[arg(
+        value_name = "DIRS",
+        help = "directories to check along with the wallpapers directory and dedupe_dirs in the config"
+    )]
+    pub dirs: Vec<PathBuf>,
+}
+
+// ------------------------- SERVE -------------------------
+#[derive(Parser, Debug)]
+#[command(
+    name = "serve",
+    about = "Serves a json api for the wallpapers, their crops, tags and palettes"
+)]
+pub struct ServeArgs {
+    #[arg(long, action, help = "print version information and exit")]
+    pub version: bool,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1:8000",
+        help = "address to listen on"
+    )]
+    pub addr: String,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "require the token as a bearer token in the authorization header"
+    )]
+    pub token: Option<String>,
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use crate::{image_ops::dominant_colors, write_atomic};
+
+/// 16 color terminal palette generated from a wallpaper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+    /// color0 to color15, the bright colors are lighter versions of the normal ones
+    pub colors: [[u8; 3]; 16],
+}
+
+/// perceived brightness from 0 to 255
+pub fn luminance([r, g, b]: [u8; 3]) -> u32 {
+    (299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b)) / 1000
+}
+
+/// blends the color towards the target by the factor from 0 to 1
+fn mix(color: [u8; 3], target: [u8; 3], factor: f64) -> [u8; 3] {
+    let mut mixed = color;
+    for (c, t) in mixed.iter_mut().zip(target) {
+        *c = f64::from(*c)
+            .mul_add(1.0 - factor, f64::from(t) * factor)
+            .round() as u8;
+    }
+    mixed
+}
+
+/// moves the color away from its gray by the percentage
+fn saturate(color: [u8; 3], percent: u8) -> [u8; 3] {
+    let gray = f64::from(luminance(color));
+    let factor = 1.0 + f64::from(percent) / 100.0;
+    color.map(|c| {
+        (f64::from(c) - gray)
+            .mul_add(factor, gray)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    })
+}
+
+pub fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// options of the palette from the wallust arguments of a wallpaper, the arguments that aren't
+/// used here only change the palette of wallust itself
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteOptions {
+    /// how far the background is darkened and the foreground lightened, from 0 to 1
+    pub contrast: f64,
+    /// the bright colors are the complements of the normal ones
+    pub complementary: bool,
+    /// percentage the accents are saturated by
+    pub saturation: u8,
+}
+
+impl Default for PaletteOptions {
+    fn default() -> Self {
+        Self {
+            contrast: 0.6,
+            complementary: false,
+            saturation: 0,
+        }
+    }
+}
+
+impl PaletteOptions {
+    /// reads --palette and --saturation, e.g. "--palette harddarkcomp16 --saturation 20"
+    pub fn from_wallust_args(args: &str) -> Self {
+        let mut options = Self::default();
+        let mut args = args.split_whitespace();
+        while let Some(arg) = args.next() {
+            match arg {
+                "--palette" => {
+                    let palette = args.next().unwrap_or_default().to_lowercase();
+                    options.contrast = if palette.starts_with("harddark") {
+                        0.8
+                    } else if palette.starts_with("softdark") {
+                        0.4
+                    } else {
+                        0.6
+                    };
+                    options.complementary = palette.contains("comp");
+                }
+                "--saturation" => {
+                    options.saturation = args.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+impl Palette {
+    pub fn from_image(img: &image::DynamicImage, options: PaletteOptions) -> Option<Self> {
+        let dominant = dominant_colors(img);
+        let top: Vec<_> = dominant.iter().take(16).copied().collect();
+
+        let background = mix(
+            *top.iter().min_by_key(|c| luminance(**c))?,
+            [0; 3],
+            options.contrast,
+        );
+        let foreground = mix(
+            *top.iter().max_by_key(|c| luminance(**c))?,
+            [255; 3],
+            options.contrast,
+        );
+
+        // colors that are readable on the background
+        let mut accents: Vec<_> = dominant
+            .iter()
+            .filter(|c| (60..=220).contains(&luminance(**c)))
+            .take(6)
+            .copied()
+            .collect();
+        if accents.is_empty() {
+            accents = top;
+        }
+
+        let mut colors = [[0; 3]; 16];
+        colors[0] = background;
+        colors[7] = foreground;
+        colors[8] = mix(background, [255; 3], 0.25);
+        colors[15] = foreground;
+        for (i, accent) in accents.iter().cycle().take(6).enumerate() {
+            let accent = saturate(*accent, options.saturation);
+            let bright = if options.complementary {
+                accent.map(|c| 255 - c)
+            } else {
+                accent
+            };
+            colors[i + 1] = accent;
+            colors[i + 9] = mix(bright, [255; 3], 0.25);
+        }
+
+        Some(Self {
+            background,
+            foreground,
+            colors,
+        })
+    }
+
+    /// template variables and their colors
+    pub fn variables(&self) -> Vec<(String, [u8; 3])> {
+        let mut vars = vec![
+            ("background".to_string(), self.background),
+            ("foreground".to_string(), self.foreground),
+            ("cursor".to_string(), self.foreground),
+        ];
+        vars.extend(
+            self.colors
+                .iter()
+                .enumerate()
+                .map(|(i, color)| (format!("color{i}"), *color)),
+        );
+        vars
+    }
+}
+
+/// replaces {{name}} with the color as #rrggbb, {{name.strip}} without the # and {{wallpaper}}
+/// with the path of the wallpaper
+pub fn render(template: &str, palette: &Palette, wallpaper: &Path) -> String {
+    let mut rendered = template.replace("{{wallpaper}}", &wallpaper.to_string_lossy());
+    for (name, color) in palette.variables() {
+        let hex = hex(color);
+        rendered = rendered
+            .replace(&format!("{{{{{name}}}}}"), &hex)
+            .replace(&format!("{{{{{name}.strip}}}}"), &hex[1..]);
+    }
+    rendered
+}
+
+/// template files in the directory, sorted by name
+pub fn templates(dir: &Path) -> Vec<PathBuf> {
+    let mut templates: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    templates.sort();
+    templates
+}
+
+/// renders each template for the wallpaper into the output directory, keeping the template names
+pub fn export(
+    palette: &Palette,
+    wallpaper: &Path,
+    templates: &[PathBuf],
+    out_dir: &Path,
+) -> std::io::Result<()> {
+    for template in templates {
+        let Some(name) = template.file_name() else {
+            continue;
+        };
+
+        let contents = std::fs::read_to_string(template)?;
+        write_atomic(
+            &out_dir.join(name),
+            render(&contents, palette, wallpaper).as_bytes(),
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use rayon::prelude::*;
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    cli::AddResolutionArgs,
+    config::WallpaperConfig,
+    cropper::{CropOptions, Direction},
+    geometry::Geometry,
+    logging, print_json, run_wallpaper_ui,
+    wallpapers::{WallInfo, WallpapersCsv, WallpapersError},
+    WallpaperConfigExt,
+};
+
+pub fn add_geometry(info: &WallInfo, ratio: &AspectRatio, geom: Geometry) -> WallInfo {
+    let mut new_geometries = info.geometries.clone();
+    new_geometries.insert(ratio.clone(), geom);
+
+    WallInfo {
+        geometries: new_geometries,
+        ..info.clone()
+    }
+}
+
+/// centers the new crop based on the old crop
+fn center_new_crop(old_crop: &Geometry, new_crop: &Geometry, info: &WallInfo) -> Geometry {
+    let (crop_start, crop_length, direction) = match info.direction(old_crop) {
+        Direction::X => (old_crop.x, old_crop.w, Direction::X),
+        Direction::Y => (old_crop.y, old_crop.h, Direction::Y),
+        // zoomed or padded crops cannot be carried over, use the default crop instead
+        Direction::Both => return new_crop.clone(),
+    };
+
+    let closest_mid = f64::from(crop_start + crop_length) / 2.0;
+    let default_start = closest_mid - f64::from(new_crop.w) / 2.0;
+    info.cropper()
+        .clamp(default_start, direction, new_crop.w, new_crop.h)
+}
+
+/// geometry of the new resolution for the wallpaper, and if it should be checked in the ui
+fn new_geometry(
+    info: &WallInfo,
+    new_res: &AspectRatio,
+    closest_res: Option<&AspectRatio>,
+    options: CropOptions,
+) -> (Geometry, bool) {
+    let cropper = info.cropper().with_options(options);
+    let default_crop = cropper.crop(new_res);
+
+    let Some(closest) = closest_res else {
+        return (default_crop, false);
+    };
+    let closest_default_crop = cropper.crop(closest);
+
+    if info.direction(&default_crop) != info.direction(&closest_default_crop)
+        || info.get_geometry(closest) == closest_default_crop
+    {
+        return (default_crop, false);
+    }
+
+    // center new crop based on previous default crop
+    (
+        center_new_crop(&closest_default_crop, &default_crop, info),
+        true,
+    )
+}
+
+/// adds the resolution to the config and backfills its geometries in the csv
+///
+/// returns if the resolution is new, and the wallpapers that should be checked in the ui
+pub fn add_resolution(
+    config: &mut WallpaperConfig,
+    name: &str,
+    new_res: &AspectRatio,
+) -> (bool, Vec<String>) {
+    let closest_res = config.closest_resolution(new_res);
+
+    // save the updated config
+    let is_new = !config.resolutions.iter().any(|(_, res)| res == new_res);
+    if is_new {
+        config.add_resolution(name, new_res.clone());
+        config.save().unwrap_or_else(|_| {
+            error!(
+                "Could not save config to {:?}!",
+                WallpaperConfig::config_file()
+            );
+            std::process::exit(1);
+        });
+    }
+
+    let to_process =
+        backfill_resolution(config, new_res, closest_res.as_ref()).unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(1);
+        });
+    (is_new, to_process)
+}
+
+/// adds the geometries of the resolution to the wallpapers that don't have it yet, the
+/// resolution has to be in the config already
+///
+/// returns the wallpapers that should be checked in the ui
+pub fn backfill_resolution(
+    config: &WallpaperConfig,
+    new_res: &AspectRatio,
+    closest_res: Option<&AspectRatio>,
+) -> Result<Vec<String>, WallpapersError> {
+    let crop_options = config.crop_options();
+    let mut to_process: Vec<String> = Vec::new();
+
+    // the csv is rewritten a chunk at a time, the wallpapers are independent so only the new
+    // geometries are computed in parallel and then added in place
+    WallpapersCsv::rewrite(config, &config.sorted_resolutions(), |mut chunk| {
+        let needs_review: Vec<String> = chunk
+            .par_iter_mut()
+            .filter(|info| !info.geometries.contains_key(new_res))
+            .filter_map(|info| {
+                let (geom, needs_review) = new_geometry(info, new_res, closest_res, crop_options);
+                info.geometries.insert(new_res.clone(), geom);
+                needs_review.then(|| info.filename.clone())
+            })
+            .collect();
+        to_process.extend(needs_review);
+        chunk
+    })?;
+
+    to_process.sort();
+    Ok(to_process)
+}
+
+/// full paths of the wallpapers to be processed in the ui
+pub fn wallpaper_paths(config: &WallpaperConfig, fnames: &[String]) -> Vec<String> {
+    fnames
+        .iter()
+        .map(|fname| {
+            config
+                .wallpapers_path
+                .join(fname)
+                .to_str()
+                .expect("could not convert path to str")
+                .to_string()
+        })
+        .collect()
+}
+
+pub fn run(args: &AddResolutionArgs) {
+    if args.version {
+        println!("add-resolution {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let new_res = std::convert::TryInto::<AspectRatio>::try_into(args.resolution.as_str())
+        .unwrap_or_else(|()| {
+            panic!(
+                "could not convert aspect ratio {} into string",
+                args.resolution
+            )
+        });
+
+    let mut config = WallpaperConfig::new();
+    let (is_new, to_process) = add_resolution(&mut config, &args.name, &new_res);
+
+    if args.json {
+        print_json(&json!({
+            "name": args.name,
+            "resolution": new_res.to_string(),
+            "added": is_new,
+            "to_process": to_process,
+        }));
+    } else {
+        for fname in &to_process {
+            println!("{fname}");
+        }
+    }
+
+    // process the images in wallpaper ui
+    run_wallpaper_ui(wallpaper_paths(&config, &to_process));
+}
@@ -0,0 +1,112 @@
+use indexmap::IndexSet;
+use tracing::error;
+
+use crate::{
+    cli::WallpapersAddArgs,
+    config::{parse_gpus, WallpaperConfig},
+    expand_glob, filter_images, filter_images_recursive,
+    image_ops::WallpaperPipeline,
+    is_image, logging, WallpaperConfigExt,
+};
+
+pub async fn run(args: &WallpapersAddArgs) {
+    let cfg = WallpaperConfig::new();
+
+    if args.version {
+        println!("wallpapers-add {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let upscale_gpus = args.gpu.as_ref().map_or_else(
+        || cfg.upscale_gpus.clone(),
+        |gpu| {
+            parse_gpus(gpu).unwrap_or_else(|| {
+                error!("Invalid --gpu {gpu}, expected auto, cpu or gpu indexes, e.g. 0,1");
+                std::process::exit(1);
+            })
+        },
+    );
+
+    let wall_dir =
+        std::fs::canonicalize(&cfg.wallpapers_path).unwrap_or_else(|_| cfg.wallpapers_path.clone());
+    // inputs are deduped by their canonical paths
+    let mut all_files = IndexSet::new();
+    if let Some(paths) = &args.paths {
+        paths
+            .iter()
+            .flat_map(expand_glob)
+            .flat_map(std::fs::canonicalize)
+            .for_each(|p| {
+                if p.is_file() {
+                    if let Some(p) = is_image(&p) {
+                        all_files.insert(p);
+                    }
+                } else {
+                    // the faces of the wallpapers directory can be detected again
+                    if p == wall_dir && !args.detect_only {
+                        error!("Input directory cannot be the same as the wallpapers directory.");
+                        std::process::exit(1);
+                    }
+
+                    let images = if args.recursive {
+                        filter_images_recursive(&p, &cfg.ignore)
+                    } else {
+                        filter_images(&p, &cfg.ignore).map(Iterator::collect)
+                    };
+                    let images = images.unwrap_or_else(|e| {
+                        error!("Could not read {p:?}: {e}");
+                        Vec::new()
+                    });
+
+                    all_files.extend(
+                        images
+                            .iter()
+                            .flat_map(std::fs::canonicalize)
+                            // don't pick up the wallpapers directory when recursing
+                            .filter(|img| args.detect_only || !img.starts_with(&wall_dir)),
+                    );
+                }
+            });
+    }
+
+    // allow loading and cleaning of wallpapers.csv
+    let mut pipeline = WallpaperPipeline::new(&cfg, args, upscale_gpus).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    if all_files.is_empty() {
+        if let Err(e) = pipeline.save_csv() {
+            error!("{e}");
+        }
+
+        error!("No files found in input paths.");
+        std::process::exit(1);
+    }
+
+    // ctrl+c kills the running tools and keeps the wallpapers that were already added
+    pipeline.scheduler().cancel_on_ctrl_c();
+    pipeline.scheduler().log_progress();
+
+    for img in all_files {
+        pipeline.add_image(&img).await;
+    }
+
+    if let Err(e) = pipeline.process().await {
+        error!("Could not process the images: {e}");
+        std::process::exit(1);
+    }
+    if pipeline.scheduler().is_cancelled() {
+        error!("Cancelled");
+        std::process::exit(130);
+    }
+    // the palettes and depth maps don't depend on the faces
+    if !args.detect_only {
+        pipeline.generate_palettes().await;
+        pipeline.generate_depth_maps().await;
+        pipeline.run_post_add_hook(&cfg);
+    }
+    pipeline.preview();
+}
@@ -0,0 +1,113 @@
+use tracing::{error, info, warn};
+
+use crate::{
+    apply::{apply_to_monitors, Transition},
+    cli::ApplyArgs,
+    colorscheme::{self, Palette, PaletteOptions},
+    config::WallpaperConfig,
+    filename, logging, monitors,
+    wallpapers::WallpapersCsv,
+    WallpaperConfigExt, WallpapersCsvExt,
+};
+
+pub fn run(args: &ApplyArgs) {
+    if args.version {
+        println!("apply {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+
+    // the monitors are enumerated when applying, so each gets the crop for its resolution
+    let detected = monitors::detect();
+    let monitors: Vec<_> = match &args.monitors {
+        None => detected,
+        Some(names) => names
+            .split(',')
+            .map(str::trim)
+            .filter_map(|name| {
+                let monitor = detected.iter().find(|mon| mon.name == name).cloned();
+                if monitor.is_none() {
+                    error!("Could not find monitor {name}");
+                }
+                monitor
+            })
+            .collect(),
+    };
+
+    if monitors.is_empty() {
+        error!("No monitors to apply the wallpaper to");
+        std::process::exit(1);
+    }
+
+    // wallpapers can be given by path or by their filename in the wallpapers directory
+    let fname = filename(&args.wallpaper);
+    let image = if args.wallpaper.is_file() {
+        args.wallpaper.clone()
+    } else {
+        config.wallpapers_path.join(&fname)
+    };
+
+    let wallpapers_csv = WallpapersCsv::load();
+    let Some(info) = wallpapers_csv.get(&fname) else {
+        error!("{fname} is not in wallpapers.csv, add it with wallpapers add first");
+        std::process::exit(1);
+    };
+
+    let backend = args.backend.as_deref().unwrap_or(&config.apply_backend);
+    let mut transition = Transition::from_config(&config);
+    if let Some(kind) = &args.transition_type {
+        transition.kind.clone_from(kind);
+    }
+    if let Some(duration) = args.transition_duration {
+        transition.duration = duration;
+    }
+
+    for monitor in &monitors {
+        let ratio = monitor.aspect_ratio();
+        if !config.resolutions.iter().any(|(_, res)| *res == ratio) {
+            warn!(
+                "{ratio} of {} is not in the config, using the default crop",
+                monitor.name
+            );
+        }
+    }
+
+    let wallpapers: Vec<_> = monitors
+        .iter()
+        .map(|monitor| (info, image.as_path(), monitor))
+        .collect();
+    let failed = match apply_to_monitors(&config, &wallpapers, backend, &transition) {
+        Ok(geometries) => {
+            for (monitor, geom) in monitors.iter().zip(geometries) {
+                info!("Set {fname} ({geom}) on {}", monitor.name);
+            }
+            false
+        }
+        Err(e) => {
+            error!("Could not apply {fname} with {backend}: {e}");
+            true
+        }
+    };
+
+    // swap the terminal theme along with the wallpaper
+    let templates = colorscheme::templates(&config.colorscheme_templates);
+    if !templates.is_empty() {
+        let current = config.colorscheme_dir.join("current");
+        let palette = image::open(&image).ok().and_then(|img| {
+            Palette::from_image(&img, PaletteOptions::from_wallust_args(&info.wallust))
+        });
+
+        match palette.map(|palette| colorscheme::export(&palette, &image, &templates, &current)) {
+            Some(Ok(())) => info!("Rendered colorschemes to {current:?}"),
+            Some(Err(e)) => error!("Could not render colorschemes: {e}"),
+            None => error!("Could not generate a palette for {fname}"),
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
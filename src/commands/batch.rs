@@ -0,0 +1,116 @@
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    aspect_ratio::resolutions_from_arg,
+    cli::BatchArgs,
+    config::WallpaperConfig,
+    logging, print_json,
+    wallpapers::{FaceCount, WallInfo, WallpapersCsv},
+    WallpaperConfigExt, WallpapersCsvExt,
+};
+
+pub fn run(args: &BatchArgs) {
+    if args.version {
+        println!("batch {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let mut wallpapers_csv = WallpapersCsv::load();
+    let faces = FaceCount::try_from(args.faces.as_str()).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    if let Some(align) = &args.align {
+        let ratios =
+            resolutions_from_arg(&args.resolutions, &config.resolutions).unwrap_or_else(|e| {
+                error!("{e}");
+                std::process::exit(1);
+            });
+        let filter = args.filter.as_ref().map(|f| f.to_lowercase());
+        let mut changes = Vec::new();
+
+        let updated_infos: Vec<WallInfo> = wallpapers_csv
+            .iter()
+            .filter(|(fname, info)| {
+                if let Some(filter) = &filter {
+                    if !fname.to_lowercase().contains(filter) {
+                        return false;
+                    }
+                }
+
+                info.matches_faces(faces)
+                    && info.matches_state(&args.state)
+                    && info.matches_tags(&args.tag, &args.not_tag)
+            })
+            .filter_map(|(fname, info)| {
+                let cropper = info.cropper().with_options(config.crop_options());
+                let mut new_info = info.clone();
+
+                for ratio in &ratios {
+                    let default_crop = cropper.crop(ratio);
+                    let geom = info.get_geometry(ratio);
+
+                    if args.unmodified && geom != default_crop {
+                        continue;
+                    }
+
+                    let new_geom = match align.as_str() {
+                        "start" => geom.align_start(info.width, info.height),
+                        "center" => geom.align_center(info.width, info.height),
+                        "end" => geom.align_end(info.width, info.height),
+                        "default" => default_crop,
+                        _ => panic!("Invalid align: {align}"),
+                    };
+
+                    if new_geom != geom {
+                        if !args.json {
+                            println!(
+                                "{fname} {}: {geom} -> {new_geom}",
+                                config.resolution_name(ratio)
+                            );
+                        }
+                        changes.push(json!({
+                            "filename": fname,
+                            "resolution": ratio.to_string(),
+                            "old": geom.to_string(),
+                            "new": new_geom.to_string(),
+                        }));
+                        new_info.set_geometry(ratio, &new_geom);
+                    }
+                }
+
+                (new_info != *info).then_some(new_info)
+            })
+            .collect();
+
+        if args.json {
+            print_json(&json!({
+                "dry_run": args.dry_run,
+                "updated": updated_infos.len(),
+                "changes": changes,
+            }));
+        } else if args.dry_run {
+            println!("{} wallpapers would be updated.", updated_infos.len());
+        } else {
+            println!("{} wallpapers updated.", updated_infos.len());
+        }
+
+        if args.dry_run {
+            return;
+        }
+
+        for updated_info in updated_infos {
+            wallpapers_csv.insert(updated_info.filename.clone(), updated_info);
+        }
+    }
+
+    if let Err(e) = wallpapers_csv.save(&config.sorted_resolutions()) {
+        error!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+use tracing::{error, info};
+
+use crate::{
+    cache::{crops_dir, render_all, thumbnails, thumbnails_dir, THUMBNAIL_SIZES},
+    cli::CacheBuildArgs,
+    config::WallpaperConfig,
+    logging, monitors,
+    wallpapers::WallpapersCsv,
+    WallpaperConfigExt, WallpapersCsvExt,
+};
+
+pub fn build(args: &CacheBuildArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers_csv = WallpapersCsv::load();
+
+    // monitors can use resolutions that are not in the config, which use the default crop
+    let mut ratios = config.sorted_resolutions();
+    for mon in monitors::detect() {
+        let ratio = mon.aspect_ratio();
+        if !ratios.contains(&ratio) {
+            ratios.push(ratio);
+        }
+    }
+
+    let mut cached = HashSet::new();
+    let mut rendered = 0;
+    let mut thumbnail_count = 0;
+    let mut failed = false;
+    for (fname, info) in &wallpapers_csv {
+        let image = config.wallpapers_path.join(fname);
+        if !image.exists() {
+            continue;
+        }
+
+        let geometries: Vec<_> = ratios
+            .iter()
+            .map(|ratio| (ratio.clone(), info.get_geometry(ratio)))
+            .collect();
+
+        match render_all(&image, &geometries) {
+            Ok(crops) => {
+                for (path, is_new) in crops {
+                    if is_new {
+                        info!("Rendered {path:?}");
+                        rendered += 1;
+                    }
+                    cached.insert(path);
+                }
+            }
+            Err(e) => {
+                error!("Could not render crops of {fname}: {e}");
+                failed = true;
+            }
+        }
+
+        match thumbnails(&image, THUMBNAIL_SIZES) {
+            Ok(thumbs) => {
+                for (path, is_new) in thumbs {
+                    if is_new {
+                        info!("Rendered {path:?}");
+                        thumbnail_count += 1;
+                    }
+                    cached.insert(path);
+                }
+            }
+            Err(e) => {
+                error!("Could not render thumbnails of {fname}: {e}");
+                failed = true;
+            }
+        }
+    }
+
+    let mut removed = 0;
+    if args.prune {
+        let entries = [crops_dir(), thumbnails_dir()]
+            .iter()
+            .flat_map(std::fs::read_dir)
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !cached.contains(path));
+
+        for path in entries {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                Err(e) => error!("Could not remove {path:?}: {e}"),
+            }
+        }
+    }
+
+    println!(
+        "{rendered} crops and {thumbnail_count} thumbnails rendered, {} cached, {removed} removed",
+        cached.len()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
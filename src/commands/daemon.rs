@@ -0,0 +1,317 @@
+use std::{
+    collections::BTreeMap,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use tracing::{error, info};
+
+use crate::{
+    apply::{apply_to_monitors, Transition},
+    aspect_ratio::AspectRatio,
+    cache::filter_brightness,
+    cli::DaemonArgs,
+    config::WallpaperConfig,
+    daemon::{self, DaemonCommand, DaemonEvent, DaemonStatus, Rotation},
+    filename, logging,
+    monitors::{self, Monitor, MonitorEvent},
+    paths,
+    schedule::Moment,
+    wallpapers::{FaceCount, WallInfo, WallpapersCsv},
+    WallpaperConfigExt, WallpapersCsvExt,
+};
+
+/// checks the orientation of the wallpaper against the --orientation argument
+fn matches_orientation(info: &WallInfo, orientation: &str, monitor: &Monitor) -> bool {
+    let is_portrait = |width: u32, height: u32| height > width;
+
+    match orientation {
+        "landscape" => !is_portrait(info.width, info.height),
+        "portrait" => is_portrait(info.width, info.height),
+        "monitor" => {
+            is_portrait(info.width, info.height) == is_portrait(monitor.width, monitor.height)
+        }
+        _ => true,
+    }
+}
+
+/// checks the schedule of the wallpaper unless --ignore-schedule is given
+fn is_scheduled(
+    args: &DaemonArgs,
+    wallpapers_csv: &WallpapersCsv,
+    now: &Moment,
+    fname: &str,
+) -> bool {
+    args.ignore_schedule
+        || wallpapers_csv
+            .get(fname)
+            .map_or(true, |info| info.matches_schedule(now))
+}
+
+/// rotation of the wallpapers matching the filters for the monitor
+fn rotation_for(
+    args: &DaemonArgs,
+    config: &WallpaperConfig,
+    wallpapers_csv: &WallpapersCsv,
+    monitor: Monitor,
+) -> Option<Rotation> {
+    let faces = match FaceCount::try_from(args.faces.as_str()) {
+        Ok(faces) => faces,
+        Err(e) => {
+            error!("{e}");
+            return None;
+        }
+    };
+    let mut paths: Vec<_> = wallpapers_csv
+        .iter()
+        .filter(|(_, info)| {
+            info.matches_faces(faces)
+                && info.matches_state(&args.state)
+                && info.matches_tags(&args.tag, &args.not_tag)
+                && matches_orientation(info, &args.orientation, &monitor)
+        })
+        .map(|(fname, _)| config.wallpapers_path.join(fname))
+        .filter(|path| path.exists())
+        .collect();
+    filter_brightness(&mut paths, &args.brightness);
+    let files: Vec<_> = paths.iter().map(filename).collect();
+
+    if files.is_empty() {
+        error!("No wallpapers match the filters for {}", monitor.name);
+        return None;
+    }
+
+    // wallpapers outside of their schedule are skipped instead of removed, as the time changes
+    let now = Moment::now();
+    let mut rotation = Rotation::new(monitor, &files);
+    rotation.skip_unavailable(|fname| is_scheduled(args, wallpapers_csv, &now, fname));
+    Some(rotation)
+}
+
+/// sets the current wallpaper of each rotation
+fn apply_all(
+    config: &WallpaperConfig,
+    wallpapers_csv: &WallpapersCsv,
+    rotations: &[Rotation],
+    backend: &str,
+    transition: &Transition,
+) {
+    let current: Vec<_> = rotations
+        .iter()
+        .filter_map(|rotation| {
+            let info = wallpapers_csv.get(rotation.current()?)?;
+            Some((
+                info,
+                config.wallpapers_path.join(&info.filename),
+                &rotation.monitor,
+            ))
+        })
+        .collect();
+    let wallpapers: Vec<_> = current
+        .iter()
+        .map(|(info, image, monitor)| (*info, image.as_path(), *monitor))
+        .collect();
+
+    match apply_to_monitors(config, &wallpapers, backend, transition) {
+        Ok(geometries) => {
+            for ((info, _, monitor), geom) in wallpapers.iter().zip(geometries) {
+                info!("Set {} ({geom}) on {}", info.filename, monitor.name);
+            }
+        }
+        Err(e) => error!("Could not apply wallpapers with {backend}: {e}"),
+    }
+}
+
+/// current wallpaper of each monitor
+fn current_wallpapers(rotations: &[Rotation]) -> BTreeMap<&str, Option<&str>> {
+    rotations
+        .iter()
+        .map(|rotation| (rotation.monitor.name.as_str(), rotation.current()))
+        .collect()
+}
+
+pub fn run(args: &DaemonArgs) {
+    if args.version {
+        println!("daemon {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let socket = paths::socket_file();
+
+    if let Some(cmd) = &args.send {
+        let cmd: DaemonCommand = cmd.parse().unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(1);
+        });
+        match daemon::send(&socket, &cmd) {
+            Ok(reply) => println!("{reply}"),
+            Err(e) => {
+                error!("Could not connect to the daemon at {socket:?}: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let config = WallpaperConfig::new();
+    let wallpapers_csv = WallpapersCsv::load();
+
+    let monitor_names: Option<Vec<_>> = args
+        .monitors
+        .as_ref()
+        .map(|names| names.split(',').map(str::trim).collect());
+    let is_selected = |name: &str| {
+        monitor_names
+            .as_ref()
+            .map_or(true, |names| names.contains(&name))
+    };
+    let monitors: Vec<_> = monitors::detect()
+        .into_iter()
+        .filter(|mon| is_selected(&mon.name))
+        .collect();
+
+    if monitors.is_empty() {
+        error!("No monitors to rotate wallpapers on");
+        std::process::exit(1);
+    }
+
+    let mut rotations: Vec<_> = monitors
+        .into_iter()
+        .filter_map(|mon| rotation_for(args, &config, &wallpapers_csv, mon))
+        .collect();
+
+    if rotations.is_empty() {
+        std::process::exit(1);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    if let Err(e) = daemon::listen(&socket, tx.clone()) {
+        error!("Could not listen on {socket:?}: {e}");
+        std::process::exit(1);
+    }
+    info!("Listening for commands on {socket:?}");
+
+    if monitors::watch_hyprland(tx, DaemonEvent::Monitor) {
+        info!("Watching for hyprland monitor changes");
+    }
+
+    let backend = args.backend.as_deref().unwrap_or(&config.apply_backend);
+    let transition = Transition::from_config(&config);
+    let interval = Duration::from_secs(args.interval.max(1));
+    let mut paused = false;
+    let mut next_change = Instant::now() + interval;
+
+    apply_all(&config, &wallpapers_csv, &rotations, backend, &transition);
+    loop {
+        // commands don't delay the next change
+        let timeout = next_change.saturating_duration_since(Instant::now());
+        let forward = match rx.recv_timeout(timeout) {
+            Ok(DaemonEvent::Command(cmd, reply)) => {
+                let forward = match &cmd {
+                    DaemonCommand::Next => Some(true),
+                    DaemonCommand::Prev => Some(false),
+                    _ => None,
+                };
+                let response = match cmd {
+                    DaemonCommand::Pause => {
+                        paused = true;
+                        "ok".to_string()
+                    }
+                    DaemonCommand::Resume => {
+                        paused = false;
+                        "ok".to_string()
+                    }
+                    DaemonCommand::Current => {
+                        serde_json::to_string(&current_wallpapers(&rotations)).unwrap_or_default()
+                    }
+                    DaemonCommand::Status => {
+                        let remaining = next_change.saturating_duration_since(Instant::now());
+                        let status = DaemonStatus {
+                            current: current_wallpapers(&rotations)
+                                .into_iter()
+                                .map(|(mon, fname)| (mon.to_string(), fname.map(String::from)))
+                                .collect(),
+                            paused,
+                            remaining: (!paused).then_some(remaining.as_secs()),
+                        };
+                        serde_json::to_string(&status).unwrap_or_default()
+                    }
+                    DaemonCommand::Crop {
+                        filename,
+                        resolution,
+                    } => {
+                        // resolutions_from_arg panics on invalid resolutions
+                        let ratio = config
+                            .resolutions
+                            .iter()
+                            .find(|(name, _)| name.eq_ignore_ascii_case(&resolution))
+                            .map(|(_, ratio)| ratio.clone())
+                            .or_else(|| AspectRatio::try_from(resolution.as_str()).ok());
+                        match (wallpapers_csv.get(&filename), ratio) {
+                            (None, _) => format!("error: {filename} is not in wallpapers.csv"),
+                            (_, None) => format!("error: invalid resolution {resolution}"),
+                            (Some(info), Some(ratio)) => info.get_geometry(&ratio).to_string(),
+                        }
+                    }
+                    DaemonCommand::Set(filename) => {
+                        if wallpapers_csv.get(&filename).is_some() {
+                            for rotation in &mut rotations {
+                                rotation.set(&filename);
+                            }
+                            apply_all(&config, &wallpapers_csv, &rotations, backend, &transition);
+                            "ok".to_string()
+                        } else {
+                            format!("error: {filename} is not in wallpapers.csv")
+                        }
+                    }
+                    DaemonCommand::Next | DaemonCommand::Prev => "ok".to_string(),
+                };
+                // the client may have disconnected already
+                reply.send(response).ok();
+
+                let Some(forward) = forward else {
+                    continue;
+                };
+                forward
+            }
+            Ok(DaemonEvent::Monitor(MonitorEvent::Added(name))) => {
+                // the resolution of the new monitor is only known after it is added
+                let added = monitors::detect()
+                    .into_iter()
+                    .find(|mon| mon.name == name && is_selected(&mon.name))
+                    .and_then(|mon| rotation_for(args, &config, &wallpapers_csv, mon));
+
+                if let Some(rotation) = added {
+                    info!("Monitor {name} added");
+                    rotations.retain(|rotation| rotation.monitor.name != name);
+                    rotations.push(rotation);
+                    // feh sets all monitors at once, so the other monitors are set again
+                    apply_all(&config, &wallpapers_csv, &rotations, backend, &transition);
+                }
+                continue;
+            }
+            Ok(DaemonEvent::Monitor(MonitorEvent::Removed(name))) => {
+                info!("Monitor {name} removed");
+                rotations.retain(|rotation| rotation.monitor.name != name);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) if paused => {
+                next_change = Instant::now() + interval;
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let now = Moment::now();
+        for rotation in &mut rotations {
+            rotation.step(forward, |fname| {
+                is_scheduled(args, &wallpapers_csv, &now, fname)
+            });
+        }
+        apply_all(&config, &wallpapers_csv, &rotations, backend, &transition);
+        next_change = Instant::now() + interval;
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hasher},
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use tracing::{error, info, warn};
+
+use crate::{
+    ask,
+    cli::DedupeArgs,
+    config::WallpaperConfig,
+    filename, logging, scan_images,
+    trash::{self, TrashError},
+    wallpapers::{Face, WallInfo, WallpapersCsv, WallpapersError},
+    WallpaperConfigExt,
+};
+
+/// an image in one of the directories being deduplicated
+#[derive(Debug, Clone)]
+struct ImageFile {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    size: u64,
+    /// the image is in the wallpapers directory and can have a row in wallpapers.csv
+    in_wallpapers: bool,
+}
+
+impl ImageFile {
+    fn pixels(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+}
+
+/// hash of the contents of the file, only used to group the files of a single run
+fn exact_hash(path: &Path) -> std::io::Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+/// difference hash of the image, each bit is whether a pixel of a 9x8 grayscale thumbnail is
+/// brighter than the next one, so resized and re-encoded copies have similar hashes
+fn perceptual_hash(path: &Path) -> Option<u64> {
+    let thumb = image::open(path).ok()?.thumbnail_exact(9, 8).to_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if thumb.get_pixel(x, y)[0] > thumb.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    parents[a.max(b)] = a.min(b);
+}
+
+/// groups of copies of the same image, each sorted with the suggested copy to keep first
+///
+/// byte for byte copies are always grouped, similar images are only grouped if they are within
+/// the threshold of the copy that is kept, so a chain of small differences isn't a single group
+fn group_copies(copies: &[ImageFile], exact: bool, threshold: u32) -> Vec<Vec<usize>> {
+    let mut parents: Vec<_> = (0..copies.len()).collect();
+
+    let hashes: Vec<_> = copies
+        .par_iter()
+        .map(|copy| {
+            exact_hash(&copy.path)
+                .inspect_err(|e| warn!("Could not read {:?}: {e}", copy.path))
+                .ok()
+        })
+        .collect();
+    let mut by_hash: HashMap<_, usize> = HashMap::new();
+    for (i, hash) in hashes.iter().enumerate() {
+        if let Some(hash) = hash {
+            let key = (copies[i].size, *hash);
+            match by_hash.get(&key) {
+                Some(&first) => union(&mut parents, first, i),
+                None => {
+                    by_hash.insert(key, i);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..copies.len() {
+        groups.entry(find(&mut parents, i)).or_default().push(i);
+    }
+    let mut groups: Vec<_> = groups.into_values().collect();
+    for group in &mut groups {
+        group.sort_by_key(|&i| suggested_order(&copies[i]));
+    }
+    // the best copies come first, so they are the ones the others are compared to
+    groups.sort_by_cached_key(|group| suggested_order(&copies[group[0]]));
+
+    if !exact {
+        info!("Hashing {} images...", copies.len());
+        let hashes: Vec<_> = copies
+            .par_iter()
+            .map(|copy| perceptual_hash(&copy.path))
+            .collect();
+
+        let mut similar: Vec<(Option<u64>, Vec<usize>)> = Vec::new();
+        for group in groups {
+            // byte for byte copies have the same perceptual hash
+            let hash = group.iter().find_map(|&i| hashes[i]);
+            let kept = hash.and_then(|a| {
+                similar
+                    .iter_mut()
+                    .find(|(b, _)| b.is_some_and(|b| (a ^ b).count_ones() <= threshold))
+            });
+            match kept {
+                Some((_, kept)) => kept.extend(group),
+                None => similar.push((hash, group)),
+            }
+        }
+        groups = similar.into_iter().map(|(_, group)| group).collect();
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().filter(|g| g.len() > 1).collect();
+    for group in &mut groups {
+        group.sort_by_key(|&i| suggested_order(&copies[i]));
+    }
+    groups.sort_by(|a, b| copies[a[0]].path.cmp(&copies[b[0]].path));
+    groups
+}
+
+/// the highest resolution copy comes first, then copies in the wallpapers directory and larger
+/// files
+fn suggested_order(copy: &ImageFile) -> (std::cmp::Reverse<(u64, bool, u64)>, PathBuf) {
+    (
+        std::cmp::Reverse((copy.pixels(), copy.in_wallpapers, copy.size)),
+        copy.path.clone(),
+    )
+}
+
+/// the row of a copy at another resolution, the faces are scaled and the crops are reset to
+/// the defaults for the new resolution
+fn rescale(info: &WallInfo, width: u32, height: u32) -> WallInfo {
+    if info.width == width && info.height == height {
+        return info.clone();
+    }
+
+    let scale_x = f64::from(width) / f64::from(info.width);
+    let scale_y = f64::from(height) / f64::from(info.height);
+    let scale = |v: u32, scale: f64| (f64::from(v) * scale).round() as u32;
+
+    WallInfo {
+        width,
+        height,
+        faces: info
+            .faces
+            .iter()
+            .map(|face| Face {
+                xmin: scale(face.xmin, scale_x),
+                xmax: scale(face.xmax, scale_x),
+                ymin: scale(face.ymin, scale_y),
+                ymax: scale(face.ymax, scale_y),
+                eye_y: face.eye_y.map(|y| scale(y, scale_y)),
+            })
+            .collect(),
+        geometries: HashMap::new(),
+        // the depth map is for the old resolution
+        depth: String::new(),
+        ..info.clone()
+    }
+}
+
+/// keeps the copy and trashes the others, the rows of the copies in the wallpapers directory are
+/// merged into the row of the copy that is kept, returns the number of copies trashed
+///
+/// a kept copy outside of the wallpapers directory replaces the copy in the wallpapers directory,
+/// so the wallpaper isn't removed from the collection
+fn dedupe_group(
+    wallpapers_csv: &mut WallpapersCsv,
+    copies: &[&ImageFile],
+    keep: usize,
+) -> Result<usize, TrashError> {
+    let keeper = copies[keep];
+    let in_wallpapers: Vec<_> = copies.iter().filter(|copy| copy.in_wallpapers).collect();
+
+    let dest = match in_wallpapers.first() {
+        Some(copy) if !keeper.in_wallpapers => {
+            // keep the name of the wallpaper with the extension of the kept copy
+            let mut dest = copy.path.clone();
+            if let Some(ext) = keeper.path.extension() {
+                dest.set_extension(ext);
+            }
+            dest
+        }
+        _ => keeper.path.clone(),
+    };
+    if dest != keeper.path && dest.exists() && !copies.iter().any(|copy| copy.path == dest) {
+        warn!("Skipping {:?}, {dest:?} already exists", keeper.path);
+        return Ok(0);
+    }
+
+    let rows: Vec<_> = in_wallpapers
+        .iter()
+        .filter_map(|copy| wallpapers_csv.get(&filename(&copy.path)).cloned())
+        .collect();
+    let dest_name = filename(&dest);
+    let merged = rows
+        .iter()
+        .find(|row| row.filename == dest_name)
+        .or_else(|| rows.first())
+        .map(|row| {
+            let mut merged = rescale(row, keeper.width, keeper.height);
+            merged.filename.clone_from(&dest_name);
+            for tag in rows.iter().flat_map(|row| &row.tags) {
+                if !merged.tags.contains(tag) {
+                    merged.tags.push(tag.clone());
+                }
+            }
+            merged
+        });
+
+    let mut trashed = 0;
+    for copy in copies.iter().filter(|copy| copy.path != keeper.path) {
+        let row = rows
+            .iter()
+            .find(|row| copy.in_wallpapers && row.filename == filename(&copy.path));
+        let entry = trash::trash_path(&copy.path, row)?;
+        info!("Moved {:?} to {:?}", copy.path, entry.trashed);
+        if copy.in_wallpapers {
+            wallpapers_csv.remove(&filename(&copy.path));
+        }
+        trashed += 1;
+    }
+
+    if dest != keeper.path {
+        trash::move_file(&keeper.path, &dest).map_err(|source| TrashError::Restore {
+            path: dest.clone(),
+            source,
+        })?;
+        info!("Moved {:?} to {dest:?}", keeper.path);
+    }
+
+    if let Some(merged) = merged {
+        wallpapers_csv.insert(dest_name, merged);
+    }
+    Ok(trashed)
+}
+
+fn print_group(n: usize, copies: &[&ImageFile]) {
+    println!("Duplicates {n}:");
+    for (i, copy) in copies.iter().enumerate() {
+        println!(
+            "  {}) {}x{}  {:.2} MB  {}",
+            i + 1,
+            copy.width,
+            copy.height,
+            copy.size as f64 / 1024.0 / 1024.0,
+            copy.path.display()
+        );
+    }
+}
+
+/// asks which of the copies to keep, None skips the group
+fn ask_keep(copies: usize) -> Option<usize> {
+    let answer = ask(&format!(
+        "Keep which copy? [1-{copies}, s to skip, enter keeps 1]"
+    ));
+    match answer.as_str() {
+        "" => Some(0),
+        "s" => None,
+        answer => match answer.parse::<usize>() {
+            Ok(i) if (1..=copies).contains(&i) => Some(i - 1),
+            _ => {
+                println!("Skipping, {answer} is not one of the copies");
+                None
+            }
+        },
+    }
+}
+
+/// asks which row to keep for each group of rows in wallpapers.csv with the same dimensions and
+/// faces, the tags are merged into the kept row and the other wallpapers are moved to the trash
+///
+/// the csv is saved if any duplicates were removed
+pub fn resolve_duplicates(
+    config: &WallpaperConfig,
+    wallpapers_csv: &mut WallpapersCsv,
+) -> Result<(), WallpapersError> {
+    let mut removed = 0;
+    for (n, group) in wallpapers_csv.duplicate_groups().iter().enumerate() {
+        let mut copies: Vec<_> = group
+            .iter()
+            .filter_map(|fname| {
+                let path = config.wallpapers_path.join(fname);
+                let (width, height) = image::image_dimensions(&path)
+                    .inspect_err(|e| warn!("Could not read {path:?}: {e}"))
+                    .ok()?;
+                let size = path.metadata().map(|m| m.len()).unwrap_or_default();
+                Some(ImageFile {
+                    path,
+                    width,
+                    height,
+                    size,
+                    in_wallpapers: true,
+                })
+            })
+            .collect();
+        if copies.len() < 2 {
+            continue;
+        }
+        copies.sort_by_key(suggested_order);
+
+        println!("Duplicates {}:", n + 1);
+        for (i, copy) in copies.iter().enumerate() {
+            let crops = wallpapers_csv
+                .get(&filename(&copy.path))
+                .map_or("", |info| {
+                    if info.is_default_crops(&config.sorted_resolutions(), config.crop_options()) {
+                        "default crops"
+                    } else {
+                        "edited crops"
+                    }
+                });
+            println!(
+                "  {}) {}x{}  {:.2} MB  {crops}  {}",
+                i + 1,
+                copy.width,
+                copy.height,
+                copy.size as f64 / 1024.0 / 1024.0,
+                filename(&copy.path)
+            );
+        }
+
+        let Some(keep) = ask_keep(copies.len()) else {
+            continue;
+        };
+        let copies: Vec<_> = copies.iter().collect();
+        match dedupe_group(wallpapers_csv, &copies, keep) {
+            Ok(trashed) => removed += trashed,
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    if removed > 0 {
+        // the rows of the removed wallpapers are dropped when the csv is saved
+        wallpapers_csv.save(&config.sorted_resolutions())?;
+        println!("{removed} duplicates moved to the trash, see `wallpapers trash list`");
+    }
+    Ok(())
+}
+
+/// finds the copies of the wallpapers in the wallpapers directory, the dedupe_dirs and the given
+/// directories, keeping the highest resolution copy of each
+pub fn run(args: &DedupeArgs) {
+    logging::init(&args.log);
+
+    if !args.yes && !args.dry_run && !std::io::stdin().is_terminal() {
+        error!("Use --yes to remove the duplicates without asking");
+        std::process::exit(1);
+    }
+
+    let config = WallpaperConfig::new();
+    let Ok(wall_dir) = std::fs::canonicalize(&config.wallpapers_path) else {
+        error!("Could not read {:?}", config.wallpapers_path);
+        std::process::exit(1);
+    };
+
+    let mut dirs = vec![wall_dir.clone()];
+    for dir in config.dedupe_dirs.iter().chain(&args.dirs) {
+        match std::fs::canonicalize(dir) {
+            Ok(dir) if !dirs.contains(&dir) => dirs.push(dir),
+            Ok(_) => {}
+            Err(e) => warn!("Could not read {dir:?}: {e}"),
+        }
+    }
+
+    let mut copies = Vec::new();
+    for dir in &dirs {
+        // the ignore patterns are relative to the wallpapers directory
+        let ignore = if *dir == wall_dir {
+            config.ignore.as_slice()
+        } else {
+            &[]
+        };
+        let files = match scan_images(dir, ignore) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Could not read {dir:?}: {e}");
+                continue;
+            }
+        };
+
+        copies.par_extend(files.into_par_iter().filter_map(|path| {
+            let (width, height) = image::image_dimensions(&path)
+                .inspect_err(|e| warn!("Could not read {path:?}: {e}"))
+                .ok()?;
+            let size = path.metadata().map(|m| m.len()).unwrap_or_default();
+            Some(ImageFile {
+                in_wallpapers: *dir == wall_dir,
+                path,
+                width,
+                height,
+                size,
+            })
+        }));
+    }
+
+    let groups = group_copies(&copies, args.exact, args.threshold);
+    if groups.is_empty() {
+        println!("No duplicates found.");
+        return;
+    }
+
+    let mut wallpapers_csv = WallpapersCsv::open_or_default().unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    let mut removed = 0;
+    let mut failed = false;
+    for (n, group) in groups.iter().enumerate() {
+        let group: Vec<_> = group.iter().map(|&i| &copies[i]).collect();
+        print_group(n + 1, &group);
+
+        if args.dry_run {
+            continue;
+        }
+
+        // the copies are sorted with the suggested copy first
+        let keep = if args.yes {
+            0
+        } else {
+            let Some(keep) = ask_keep(group.len()) else {
+                continue;
+            };
+            keep
+        };
+
+        match dedupe_group(&mut wallpapers_csv, &group, keep) {
+            Ok(trashed) => removed += trashed,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+            }
+        }
+    }
+
+    if removed > 0 {
+        // the rows of the removed wallpapers are dropped when the csv is saved
+        if let Err(e) = wallpapers_csv.save(&config.sorted_resolutions()) {
+            error!("{e}");
+            failed = true;
+        }
+        println!("{removed} duplicates moved to the trash, see `wallpapers trash list`");
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
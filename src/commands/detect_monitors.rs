@@ -0,0 +1,156 @@
+use std::io::IsTerminal;
+
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    cli::DetectMonitorsArgs,
+    commands::add_resolution::{add_resolution, wallpaper_paths},
+    config::WallpaperConfig,
+    confirm, logging,
+    monitors::{self, Monitor},
+    print_json, run_wallpaper_ui, WallpaperConfigExt,
+};
+
+/// monitors with aspect ratios that are not in the config yet, one per aspect ratio
+fn new_monitors(config: &WallpaperConfig, monitors: &[Monitor]) -> Vec<Monitor> {
+    let mut seen: Vec<AspectRatio> = config.sorted_resolutions();
+
+    monitors
+        .iter()
+        .filter(|mon| {
+            let ratio = mon.aspect_ratio();
+            if seen.contains(&ratio) {
+                return false;
+            }
+            seen.push(ratio);
+            true
+        })
+        .cloned()
+        .collect()
+}
+
+/// adds the resolutions of the given monitors, returns the wallpapers to be processed in the ui
+fn add_monitors(config: &mut WallpaperConfig, monitors: &[Monitor]) -> Vec<String> {
+    let mut to_process = Vec::new();
+
+    for mon in monitors {
+        let ratio = mon.aspect_ratio();
+
+        // nothing to backfill before wallpapers have been added
+        if config.csv_path.exists() {
+            let (_, fnames) = add_resolution(config, &mon.name, &ratio);
+            to_process.extend(fnames);
+        } else {
+            config.add_resolution(&mon.name, ratio);
+        }
+    }
+
+    to_process.sort();
+    to_process.dedup();
+    to_process
+}
+
+fn save_config(config: &WallpaperConfig) {
+    if config.save().is_err() {
+        error!(
+            "Could not save config to {:?}!",
+            WallpaperConfig::config_file()
+        );
+        std::process::exit(1);
+    }
+}
+
+/// offers to add the resolutions of the connected monitors when there is no config yet
+pub fn first_run() {
+    if WallpaperConfig::config_file().exists() || !std::io::stdin().is_terminal() {
+        return;
+    }
+
+    let mut config = WallpaperConfig::new();
+    let monitors = new_monitors(&config, &monitors::detect());
+
+    for mon in &monitors {
+        if confirm(&format!(
+            "No config found, add {} ({}x{}) as resolution {}?",
+            mon.name,
+            mon.width,
+            mon.height,
+            mon.aspect_ratio()
+        )) {
+            config.add_resolution(&mon.name, mon.aspect_ratio());
+        }
+    }
+
+    // always write the config so this is only asked once
+    save_config(&config);
+    info!("Created config at {:?}", WallpaperConfig::config_file());
+}
+
+pub fn run(args: &DetectMonitorsArgs) {
+    logging::init(&args.log);
+
+    let detected = monitors::detect();
+    if detected.is_empty() {
+        error!("No monitors found, is hyprctl, wlr-randr or xrandr installed?");
+        std::process::exit(1);
+    }
+
+    let mut config = WallpaperConfig::new();
+    let candidates = new_monitors(&config, &detected);
+
+    // json output cannot be mixed with prompts, so only add with --yes
+    let selected: Vec<Monitor> = if args.yes {
+        candidates.clone()
+    } else if args.json {
+        Vec::new()
+    } else {
+        for mon in &detected {
+            println!(
+                "{}: {}x{} ({})",
+                mon.name,
+                mon.width,
+                mon.height,
+                mon.aspect_ratio()
+            );
+        }
+
+        candidates
+            .iter()
+            .filter(|mon| {
+                confirm(&format!(
+                    "Add {} as resolution {}?",
+                    mon.name,
+                    mon.aspect_ratio()
+                ))
+            })
+            .cloned()
+            .collect()
+    };
+
+    let to_process = add_monitors(&mut config, &selected);
+    if !selected.is_empty() {
+        save_config(&config);
+    }
+
+    if args.json {
+        print_json(&json!({
+            "monitors": detected,
+            "added": selected,
+            "to_process": to_process,
+        }));
+    } else {
+        if candidates.is_empty() {
+            println!("All monitor resolutions are already in the config.");
+        }
+
+        for fname in &to_process {
+            println!("{fname}");
+        }
+    }
+
+    if !to_process.is_empty() {
+        run_wallpaper_ui(wallpaper_paths(&config, &to_process));
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::BTreeSet, path::Path};
+
+use tracing::error;
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    cli::DiffArgs,
+    config::WallpaperConfig,
+    geometry::Geometry,
+    logging,
+    wallpapers::{WallInfo, WallpapersCsv},
+    WallpaperConfigExt,
+};
+
+fn open(path: &Path, config: &WallpaperConfig) -> WallpapersCsv {
+    WallpapersCsv::open_path(path, config.clone()).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    })
+}
+
+/// how the crop moved, e.g. 120px left, 40px down
+fn movement(old: &Geometry, new: &Geometry) -> String {
+    if (old.w, old.h) != (new.w, new.h) {
+        return format!("resized from {}x{} to {}x{}", old.w, old.h, new.w, new.h);
+    }
+
+    let axis = |from: u32, to: u32, less: &str, more: &str| match from.cmp(&to) {
+        std::cmp::Ordering::Less => Some(format!("{}px {more}", to - from)),
+        std::cmp::Ordering::Greater => Some(format!("{}px {less}", from - to)),
+        std::cmp::Ordering::Equal => None,
+    };
+    let moved: Vec<_> = [
+        axis(old.x, new.x, "left", "right"),
+        axis(old.y, new.y, "up", "down"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if moved.is_empty() {
+        "scale or padding changed".to_string()
+    } else {
+        format!("moved {}", moved.join(", "))
+    }
+}
+
+fn crop_change(
+    ratio: &AspectRatio,
+    old: Option<&Geometry>,
+    new: Option<&Geometry>,
+) -> Option<String> {
+    match (old, new) {
+        (Some(old), Some(new)) if old != new => {
+            Some(format!("{ratio}: {old} -> {new} ({})", movement(old, new)))
+        }
+        (None, Some(new)) => Some(format!("{ratio}: added {new}")),
+        (Some(old), None) => Some(format!("{ratio}: removed {old}")),
+        _ => None,
+    }
+}
+
+/// the changes to the wallpaper, one per line
+fn changes(old: &WallInfo, new: &WallInfo) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if (old.width, old.height) != (new.width, new.height) {
+        changes.push(format!(
+            "size: {}x{} -> {}x{}",
+            old.width, old.height, new.width, new.height
+        ));
+    }
+
+    if old.faces != new.faces {
+        if old.faces.len() == new.faces.len() {
+            changes.push(format!("faces: {} faces moved", new.faces.len()));
+        } else {
+            changes.push(format!("faces: {} -> {}", old.faces.len(), new.faces.len()));
+        }
+    }
+
+    let ratios: BTreeSet<_> = old.geometries.keys().chain(new.geometries.keys()).collect();
+    changes.extend(ratios.into_iter().filter_map(|ratio| {
+        crop_change(ratio, old.geometries.get(ratio), new.geometries.get(ratio))
+    }));
+
+    let added: Vec<_> = new
+        .tags
+        .iter()
+        .filter(|tag| !old.tags.contains(tag))
+        .map(|tag| format!("+{tag}"))
+        .collect();
+    let removed: Vec<_> = old
+        .tags
+        .iter()
+        .filter(|tag| !new.tags.contains(tag))
+        .map(|tag| format!("-{tag}"))
+        .collect();
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(format!("tags: {}", [added, removed].concat().join(" ")));
+    }
+
+    if old.schedule != new.schedule {
+        changes.push(format!(
+            "schedule: {:?} -> {:?}",
+            old.schedule.join(","),
+            new.schedule.join(",")
+        ));
+    }
+
+    for (field, old, new) in [
+        ("wallust", &old.wallust, &new.wallust),
+        ("depth", &old.depth, &new.depth),
+        ("detector", &old.detector, &new.detector),
+    ] {
+        if old != new {
+            changes.push(format!("{field}: {old:?} -> {new:?}"));
+        }
+    }
+
+    changes
+}
+
+pub fn run(args: &DiffArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let old = open(&args.old, &config);
+    let new = open(args.new.as_ref().unwrap_or(&config.csv_path), &config);
+
+    let filenames: BTreeSet<_> = old
+        .iter()
+        .chain(new.iter())
+        .map(|(fname, _)| fname)
+        .collect();
+    let mut changed = 0;
+    for fname in filenames {
+        match (old.get(fname), new.get(fname)) {
+            (Some(old), Some(new)) => {
+                let changes = changes(old, new);
+                if changes.is_empty() {
+                    continue;
+                }
+                println!("~ {fname}");
+                for change in changes {
+                    println!("    {change}");
+                }
+            }
+            (None, Some(new)) => println!(
+                "+ {fname} ({}x{}, {} faces)",
+                new.width,
+                new.height,
+                new.faces.len()
+            ),
+            (Some(_), None) => println!("- {fname}"),
+            (None, None) => continue,
+        }
+        changed += 1;
+    }
+
+    if changed == 0 {
+        println!("no changes");
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    cli::DoctorArgs,
+    doctor::{self, Status},
+    logging, print_json,
+};
+
+pub fn run(args: &DoctorArgs) {
+    if args.version {
+        println!("doctor {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let checks = doctor::run();
+    let failed = checks.iter().any(|check| check.status == Status::Fail);
+
+    if args.json {
+        print_json(&checks);
+    } else {
+        for check in &checks {
+            println!("[{}] {}: {}", check.status, check.name, check.message);
+            if let Some(fix) = &check.fix {
+                println!("       {fix}");
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
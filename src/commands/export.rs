@@ -0,0 +1,638 @@
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use tracing::{error, info};
+
+use crate::{
+    cache::{thumbnail, THUMBNAIL_SIZE},
+    cli::{ColorschemesArgs, GalleryArgs, LockscreenArgs, LowPowerArgs, ScriptArgs},
+    colorscheme::{self, Palette, PaletteOptions},
+    config::WallpaperConfig,
+    export::{
+        crop_command, export_path, is_stale, mark_exported, shell_quote, wallpaper_crops,
+        ExportCrop,
+    },
+    image_ops::lockscreen_variant,
+    logging, paths,
+    tools::ExternalTools,
+    wallpapers::{WallInfo, WallpapersCsv, WallpapersReader},
+    WallpaperConfigExt, WallpapersCsvExt,
+};
+
+/// exports the crops of all wallpapers that are missing or changed, returns the number exported
+/// and if any of the exports failed
+///
+/// target gives the dimensions and extension of the exported image for the naming template
+fn export_crops<T, F>(
+    config: &WallpaperConfig,
+    wallpapers: WallpapersReader,
+    output: &Path,
+    force: bool,
+    target: T,
+    export: F,
+) -> (usize, bool)
+where
+    T: Fn(&ExportCrop) -> ((u32, u32), &str),
+    F: Fn(&ExportCrop, &Path) -> image::ImageResult<()>,
+{
+    let mut exported = 0;
+    let mut failed = false;
+    for row in wallpapers {
+        let info = match row {
+            Ok(info) => info,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+        let fname = &info.filename;
+        if !config.wallpapers_path.join(fname).exists() {
+            continue;
+        }
+
+        let crops = match wallpaper_crops(config, &info) {
+            Ok(crops) => crops,
+            Err(e) => {
+                error!("Could not render crops of {fname}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+
+        for crop in crops {
+            let (size, ext) = target(&crop);
+            let dest = export_path(output, &config.export_template, &crop, size, ext);
+            if !force && !is_stale(&crop.crop, &dest) {
+                continue;
+            }
+
+            match export(&crop, &dest) {
+                Ok(()) => {
+                    info!("Exported {dest:?}");
+                    exported += 1;
+                }
+                Err(e) => {
+                    error!("Could not export {fname} for {}: {e}", crop.resolution);
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    (exported, failed)
+}
+
+/// dimensions of the crop after downscaling to the maximum height
+fn fit_height(crop: &ExportCrop, height: u32) -> (u32, u32) {
+    if crop.height <= height {
+        return (crop.width, crop.height);
+    }
+
+    let width = f64::from(crop.width) * f64::from(height) / f64::from(crop.height);
+    (width.round() as u32, height)
+}
+
+fn export_lockscreen(
+    crop: &ExportCrop,
+    dest: &Path,
+    blur: f32,
+    brightness: u32,
+) -> image::ImageResult<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let img = image::open(&crop.crop)?;
+    lockscreen_variant(&img, blur, brightness).save(dest)?;
+    mark_exported(&crop.crop, dest)?;
+    Ok(())
+}
+
+pub fn lockscreen(args: &LockscreenArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers = WallpapersCsv::load_stream(&config);
+
+    let output = args.output.as_ref().unwrap_or(&config.lockscreen_dir);
+    let blur = args.blur.unwrap_or(config.lockscreen_blur);
+    let brightness = args.brightness.unwrap_or(config.lockscreen_brightness);
+
+    let (exported, failed) = export_crops(
+        &config,
+        wallpapers,
+        output,
+        args.force,
+        |crop| ((crop.width, crop.height), "png"),
+        |crop, dest| export_lockscreen(crop, dest, blur, brightness),
+    );
+
+    println!(
+        "{exported} lockscreen crops exported to {}",
+        output.display()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// the rendered templates are older than the wallpaper or their templates
+fn colorschemes_stale(image: &Path, templates: &[PathBuf], out_dir: &Path) -> bool {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+
+    let Some(newest_src) = std::iter::once(image)
+        .chain(templates.iter().map(PathBuf::as_path))
+        .map(modified)
+        .max()
+        .flatten()
+    else {
+        return true;
+    };
+
+    templates.iter().any(|template| {
+        template
+            .file_name()
+            .and_then(|name| modified(&out_dir.join(name)))
+            .map_or(true, |rendered| rendered < newest_src)
+    })
+}
+
+pub fn colorschemes(args: &ColorschemesArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers = WallpapersCsv::load_stream(&config);
+
+    let templates_dir = args
+        .templates
+        .as_ref()
+        .unwrap_or(&config.colorscheme_templates);
+    let output = args.output.as_ref().unwrap_or(&config.colorscheme_dir);
+
+    let templates = colorscheme::templates(templates_dir);
+    if templates.is_empty() {
+        error!("No templates found in {templates_dir:?}");
+        std::process::exit(1);
+    }
+
+    let mut exported = 0;
+    let mut failed = false;
+    for row in wallpapers {
+        let info = match row {
+            Ok(info) => info,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+        let fname = info.filename.as_str();
+        let image = config.wallpapers_path.join(fname);
+        let out_dir = output.join(
+            Path::new(fname)
+                .file_stem()
+                .unwrap_or_else(|| OsStr::new(fname)),
+        );
+        if !image.exists() || (!args.force && !colorschemes_stale(&image, &templates, &out_dir)) {
+            continue;
+        }
+
+        let palette = image::open(&image).ok().and_then(|img| {
+            Palette::from_image(&img, PaletteOptions::from_wallust_args(&info.wallust))
+        });
+        let Some(palette) = palette else {
+            error!("Could not generate a palette for {fname}");
+            failed = true;
+            continue;
+        };
+
+        match colorscheme::export(&palette, &image, &templates, &out_dir) {
+            Ok(()) => {
+                info!("Exported {out_dir:?}");
+                exported += 1;
+            }
+            Err(e) => {
+                error!("Could not export the colorschemes of {fname}: {e}");
+                failed = true;
+            }
+        }
+    }
+
+    println!(
+        "colorschemes of {exported} wallpapers exported to {}",
+        output.display()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// encodes the downscaled crop as webp or jpg
+fn export_low_power(
+    crop: &ExportCrop,
+    dest: &Path,
+    tools: &ExternalTools,
+    height: u32,
+    format: &str,
+    quality: u32,
+) -> image::ImageResult<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let img = image::open(&crop.crop)?;
+    let img = if img.height() > height {
+        img.resize(u32::MAX, height, image::imageops::FilterType::Lanczos3)
+    } else {
+        img
+    };
+
+    if format == "jpg" {
+        let writer = std::io::BufWriter::new(std::fs::File::create(dest)?);
+        let encoder =
+            image::codecs::jpeg::JpegEncoder::new_with_quality(writer, quality.min(100) as u8);
+        img.to_rgb8().write_with_encoder(encoder)?;
+    } else {
+        // the webp encoder of the image crate is lossless only
+        let tmp = paths::cache_dir().join("low-power.png");
+        img.save(&tmp)?;
+        let status = tools
+            .get("cwebp")
+            .command()
+            .args(["-quiet", "-q", &quality.to_string()])
+            .arg(&tmp)
+            .arg("-o")
+            .arg(dest)
+            .status()?;
+        std::fs::remove_file(&tmp).ok();
+
+        if !status.success() {
+            return Err(std::io::Error::other(format!("cwebp exited with {status}")).into());
+        }
+    }
+
+    mark_exported(&crop.crop, dest)?;
+    Ok(())
+}
+
+pub fn low_power(args: &LowPowerArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers = WallpapersCsv::load_stream(&config);
+
+    let output = args.output.as_ref().unwrap_or(&config.low_power_dir);
+    let height = args.height.unwrap_or(config.low_power_height);
+    let format = args.format.as_ref().unwrap_or(&config.low_power_format);
+    let quality = args.quality.unwrap_or(config.low_power_quality);
+
+    let (exported, failed) = export_crops(
+        &config,
+        wallpapers,
+        output,
+        args.force,
+        |crop| (fit_height(crop, height), format.as_str()),
+        |crop, dest| export_low_power(crop, dest, &config.tools, height, format, quality),
+    );
+
+    println!(
+        "{exported} low power crops exported to {}",
+        output.display()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// maximum height of the crops in the gallery
+const GALLERY_HEIGHT: u32 = 1080;
+
+const GALLERY_STYLE: &str = "
+body { margin: 0; padding: 1rem; background: #1e1e2e; color: #cdd6f4; font-family: sans-serif; }
+h1 { font-size: 1.5rem; }
+main { display: grid; grid-template-columns: repeat(auto-fill, minmax(260px, 1fr)); gap: 1rem; }
+article { background: #313244; border-radius: 0.5rem; overflow: hidden; }
+article > a > img { display: block; width: 100%; aspect-ratio: 16 / 10; object-fit: cover; }
+section { padding: 0.5rem; font-size: 0.875rem; }
+h2 { margin: 0 0 0.25rem; font-size: 0.875rem; word-break: break-all; }
+.palette { display: flex; height: 1rem; margin: 0.5rem 0; }
+.palette span { flex: 1; }
+.tag { display: inline-block; margin: 0 0.25rem 0.25rem 0; padding: 0 0.5rem; border-radius: 1rem; background: #45475a; }
+.crops a { color: #89b4fa; margin-right: 0.5rem; }
+";
+
+/// escapes the text for html content and attributes
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// relative url of the file in the gallery, the characters with a meaning in urls are encoded
+fn gallery_url(output: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(output).unwrap_or(path);
+    let url = relative
+        .components()
+        .map(|part| {
+            part.as_os_str()
+                .to_string_lossy()
+                .replace('%', "%25")
+                .replace('#', "%23")
+                .replace('?', "%3F")
+                .replace(' ', "%20")
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    escape_html(&url)
+}
+
+/// copies the file to the gallery if it changed since the last export
+fn copy_to_gallery(src: &Path, dest: &Path, force: bool) -> std::io::Result<bool> {
+    if !force && !is_stale(src, dest) {
+        return Ok(false);
+    }
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(src, dest)?;
+    mark_exported(src, dest)?;
+    Ok(true)
+}
+
+/// exports the thumbnail and the crops of the wallpaper to the gallery, returns the html of its
+/// card and the number of files that were written
+fn gallery_card(
+    config: &WallpaperConfig,
+    info: &WallInfo,
+    output: &Path,
+    force: bool,
+) -> image::ImageResult<(String, usize)> {
+    let fname = &info.filename;
+    let stem = Path::new(fname)
+        .file_stem()
+        .unwrap_or_else(|| OsStr::new(fname))
+        .to_string_lossy()
+        .to_string();
+    let mut written = 0;
+
+    let (thumb_src, _) = thumbnail(&config.wallpapers_path.join(fname), THUMBNAIL_SIZE)?;
+    let thumb = output.join("thumbnails").join(format!("{stem}.jpg"));
+    if copy_to_gallery(&thumb_src, &thumb, force)? {
+        written += 1;
+    }
+
+    let mut crops = String::new();
+    for crop in wallpaper_crops(config, info)? {
+        let dest = export_path(
+            &output.join("crops"),
+            "{resolution}/{name}.{ext}",
+            &crop,
+            fit_height(&crop, GALLERY_HEIGHT),
+            "jpg",
+        );
+        if force || is_stale(&crop.crop, &dest) {
+            export_low_power(
+                &crop,
+                &dest,
+                &config.tools,
+                GALLERY_HEIGHT,
+                "jpg",
+                config.low_power_quality,
+            )?;
+            written += 1;
+        }
+        write!(
+            crops,
+            r#"<a href="{}" title="{}">{}</a>"#,
+            gallery_url(output, &dest),
+            crop.ratio,
+            escape_html(&crop.resolution),
+        )
+        .ok();
+    }
+
+    // the palette of the thumbnail is close enough to the full image and a lot faster to decode
+    let palette = image::open(&thumb_src)
+        .ok()
+        .and_then(|img| Palette::from_image(&img, PaletteOptions::from_wallust_args(&info.wallust)))
+        .map(|palette| {
+            palette.colors[..8]
+                .iter()
+                .map(|color| {
+                    let hex = colorscheme::hex(*color);
+                    format!(r#"<span style="background: {hex}" title="{hex}"></span>"#)
+                })
+                .collect::<String>()
+        })
+        .unwrap_or_default();
+
+    let tags: String = info
+        .tags
+        .iter()
+        .map(|tag| format!(r#"<span class="tag">{}</span>"#, escape_html(tag)))
+        .collect();
+
+    let card = format!(
+        r#"<article>
+<a href="{thumb}"><img src="{thumb}" alt="{name}" loading="lazy"></a>
+<section>
+<h2>{name}</h2>
+<div>{width}x{height}</div>
+<div class="palette">{palette}</div>
+<div>{tags}</div>
+<div class="crops">{crops}</div>
+</section>
+</article>
+"#,
+        thumb = gallery_url(output, &thumb),
+        name = escape_html(fname),
+        width = info.width,
+        height = info.height,
+    );
+
+    Ok((card, written))
+}
+
+pub fn gallery(args: &GalleryArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers = WallpapersCsv::load_stream(&config);
+    let output = args.output.as_ref().unwrap_or(&config.gallery_dir);
+
+    let mut cards = Vec::new();
+    let mut written = 0;
+    let mut failed = false;
+    for row in wallpapers {
+        let info = match row {
+            Ok(info) => info,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+        let fname = &info.filename;
+        if !config.wallpapers_path.join(fname).exists() {
+            continue;
+        }
+
+        match gallery_card(&config, &info, output, args.force) {
+            Ok((card, files)) => {
+                if files > 0 {
+                    info!("Exported {fname}");
+                }
+                written += files;
+                cards.push((fname.clone(), card));
+            }
+            Err(e) => {
+                error!("Could not export {fname} to the gallery: {e}");
+                failed = true;
+            }
+        }
+    }
+    cards.sort();
+
+    let index = output.join("index.html");
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Wallpapers</title>
+<style>{GALLERY_STYLE}</style>
+</head>
+<body>
+<h1>{count} wallpapers</h1>
+<main>
+{cards}</main>
+</body>
+</html>
+"#,
+        count = cards.len(),
+        cards = cards.into_iter().map(|(_, card)| card).collect::<String>(),
+    );
+    if let Err(e) = std::fs::create_dir_all(output).and_then(|()| std::fs::write(&index, html)) {
+        error!("Could not write {index:?}: {e}");
+        std::process::exit(1);
+    }
+
+    println!(
+        "gallery with {written} new files written to {}",
+        index.display()
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+pub fn script(args: &ScriptArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers = WallpapersCsv::load_stream(&config);
+
+    let mut script = format!(
+        "#!/bin/sh
+# renders the crops of the wallpapers with {tool}, set WALLPAPERS and OUTPUT to override the
+# directories
+set -e
+[ -n \"$WALLPAPERS\" ] || WALLPAPERS={wallpapers}
+[ -n \"$OUTPUT\" ] || OUTPUT=crops
+",
+        tool = args.tool,
+        wallpapers = shell_quote(&config.wallpapers_path.to_string_lossy()),
+    );
+
+    let mut failed = false;
+    for row in wallpapers {
+        let info = match row {
+            Ok(info) => info,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+
+        writeln!(script).ok();
+        for (name, ratio) in &config.resolutions {
+            let geom = info.get_geometry(ratio);
+            let (width, height) = geom
+                .padded_canvas(ratio)
+                .map_or_else(|| geom.output_size(), |(w, h, _, _)| (w, h));
+            let crop = ExportCrop {
+                filename: info.filename.clone(),
+                resolution: name.clone(),
+                ratio: ratio.clone(),
+                crop: PathBuf::new(),
+                width,
+                height,
+            };
+            let dest = export_path(
+                Path::new(""),
+                &config.export_template,
+                &crop,
+                (width, height),
+                "png",
+            );
+
+            let quoted =
+                |path: &Path| format!("\"$OUTPUT\"/{}", shell_quote(&path.to_string_lossy()));
+            if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+                writeln!(script, "mkdir -p {}", quoted(parent)).ok();
+            }
+            writeln!(
+                script,
+                "{}",
+                crop_command(
+                    &args.tool,
+                    &format!("\"$WALLPAPERS\"/{}", shell_quote(&info.filename)),
+                    &quoted(&dest),
+                    &geom,
+                    ratio,
+                )
+            )
+            .ok();
+        }
+    }
+
+    match &args.output {
+        Some(output) => {
+            if let Err(e) = write_script(output, &script) {
+                error!("Could not write {output:?}: {e}");
+                std::process::exit(1);
+            }
+            info!("Wrote {output:?}");
+        }
+        None => print!("{script}"),
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// writes the script and makes it executable
+fn write_script(path: &Path, script: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::write(path, script)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
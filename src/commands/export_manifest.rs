@@ -0,0 +1,89 @@
+use serde_json::{json, Map, Value};
+use tracing::{error, info};
+
+use crate::{
+    cli::ExportManifestArgs,
+    config::WallpaperConfig,
+    logging,
+    wallpapers::{WallInfo, WallpapersCsv},
+    write_atomic, WallpaperConfigExt, WallpapersCsvExt,
+};
+
+/// wallpaper with its crops for each resolution in the config, also used by the api server
+pub fn wallpaper_json(config: &WallpaperConfig, info: &WallInfo) -> Value {
+    let crops: Map<String, Value> = config
+        .resolutions
+        .iter()
+        .map(|(name, ratio)| {
+            let geom = info.get_geometry(ratio);
+            (
+                name.clone(),
+                json!({
+                    "ratio": ratio,
+                    "geometry": geom.to_string(),
+                    "x": geom.x,
+                    "y": geom.y,
+                    "w": geom.w,
+                    "h": geom.h,
+                }),
+            )
+        })
+        .collect();
+
+    json!({
+        "path": config.wallpapers_path.join(&info.filename),
+        "width": info.width,
+        "height": info.height,
+        "faces": info.faces.len(),
+        "crops": crops,
+        "wallust": info.wallust,
+        "tags": info.tags,
+        "depth": (!info.depth.is_empty()).then_some(&info.depth),
+        "detector": (!info.detector.is_empty()).then_some(&info.detector),
+        "state": info.state,
+        "schedule": info.schedule,
+    })
+}
+
+pub fn run(args: &ExportManifestArgs) {
+    if args.version {
+        println!("export-manifest {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let wallpapers_csv = WallpapersCsv::load();
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| config.csv_path.with_file_name("manifest.json"));
+
+    let wallpapers: Map<String, Value> = wallpapers_csv
+        .iter()
+        .map(|(fname, info)| (fname.clone(), wallpaper_json(&config, info)))
+        .collect();
+
+    let count = wallpapers.len();
+    let manifest = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "wallpapers_path": config.wallpapers_path,
+        "resolutions": config
+            .resolutions
+            .iter()
+            .map(|(name, ratio)| (name.clone(), json!(ratio)))
+            .collect::<Map<String, Value>>(),
+        "wallpapers": wallpapers,
+    });
+
+    let contents = serde_json::to_vec_pretty(&manifest).expect("could not serialize manifest");
+    if let Err(e) = write_atomic(&output, &contents) {
+        error!("Could not write manifest to {output:?}: {e}");
+        std::process::exit(1);
+    }
+
+    info!("Wrote {count} wallpapers to {output:?}");
+    println!("{}", output.display());
+}
@@ -0,0 +1,69 @@
+use tracing::{error, info};
+
+use crate::{
+    cli::MigrateArgs,
+    config::WallpaperConfig,
+    logging, store,
+    wallpapers::{WallInfo, WallpapersCsv, WallpapersError},
+    WallpaperConfigExt,
+};
+
+/// the wallpapers of the store that isn't the destination
+fn read_source(config: &WallpaperConfig, to: &str) -> Result<Vec<WallInfo>, WallpapersError> {
+    if to == "sqlite" {
+        let csv = WallpapersCsv::open_path(&config.csv_path, config.clone())?;
+        Ok(csv.iter().map(|(_, info)| info.clone()).collect())
+    } else {
+        store::load_db(config)
+    }
+}
+
+pub fn run(args: &MigrateArgs) {
+    logging::init(&args.log);
+
+    let mut config = WallpaperConfig::new();
+    let wallpapers = read_source(&config, &args.to).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    let mut dest_config = config.clone();
+    dest_config.store.clone_from(&args.to);
+    let dest = dest_config.store_path().to_path_buf();
+
+    let has_wallpapers = if args.to == "sqlite" {
+        store::load_db(&dest_config).is_ok_and(|existing| !existing.is_empty())
+    } else {
+        dest.exists()
+    };
+    if has_wallpapers && !args.force {
+        error!("{dest:?} already has wallpapers, use --force to replace them");
+        std::process::exit(1);
+    }
+
+    let count = wallpapers.len();
+    let ratios = config.sorted_resolutions();
+    // the database is replaced as a whole, saving a WallpapersCsv only writes its changes
+    let saved = if args.to == "sqlite" {
+        store::save_db(&dest_config, &wallpapers, &ratios)
+    } else {
+        WallpapersCsv::from_wallpapers(wallpapers, dest_config).save(&ratios)
+    };
+    if let Err(e) = saved {
+        error!("{e}");
+        std::process::exit(1);
+    }
+    info!("Copied {count} wallpapers to {dest:?}");
+
+    if config.store != args.to {
+        config.store.clone_from(&args.to);
+        config.save().unwrap_or_else(|_| {
+            error!(
+                "Could not save config to {:?}!",
+                WallpaperConfig::config_file()
+            );
+            std::process::exit(1);
+        });
+        info!("Set store = {} in the config", args.to);
+    }
+}
@@ -0,0 +1,19 @@
+pub mod add_resolution;
+pub mod add_wallpapers;
+pub mod apply;
+pub mod batch;
+pub mod cache;
+pub mod daemon;
+pub mod dedupe;
+pub mod detect_monitors;
+pub mod diff;
+pub mod doctor;
+pub mod export;
+pub mod export_manifest;
+pub mod migrate;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod state;
+pub mod sync;
+pub mod trash;
+pub mod waybar;
@@ -0,0 +1,364 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::{error, info};
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    cache::render_cached,
+    cli::ServeArgs,
+    colorscheme::{hex, Palette, PaletteOptions},
+    commands::export_manifest::wallpaper_json,
+    config::WallpaperConfig,
+    geometry::Geometry,
+    hooks, logging,
+    store::{self, Store},
+    validation::validate,
+    wallpapers::WallInfo,
+    WallpaperConfigExt,
+};
+
+type ApiResult<T> = Result<T, (StatusCode, String)>;
+
+/// runs the reads and writes of the store and the hooks on the blocking threads, so a large csv
+/// or a slow hook doesn't stall the other requests
+async fn blocking<T, F>(f: F) -> ApiResult<T>
+where
+    F: FnOnce() -> ApiResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+}
+
+#[derive(Clone)]
+struct Api {
+    config: Arc<WallpaperConfig>,
+    token: Option<String>,
+    /// writes to wallpapers.csv are serialized so concurrent requests don't lose changes
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl Api {
+    /// the store of the config, single wallpapers are looked up in the database with
+    /// store = sqlite instead of reading all of them
+    fn open_store(&self) -> ApiResult<Box<dyn Store>> {
+        store::open(&self.config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+
+    async fn wallpaper(&self, fname: &str) -> ApiResult<WallInfo> {
+        let (api, fname) = (self.clone(), fname.to_string());
+        blocking(move || Self::find(api.open_store()?.as_ref(), &fname)).await
+    }
+
+    fn find(store: &dyn Store, fname: &str) -> ApiResult<WallInfo> {
+        store
+            .get(fname)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown wallpaper {fname}")))
+    }
+
+    fn ratio(&self, resolution: &str) -> ApiResult<AspectRatio> {
+        self.config
+            .resolutions
+            .iter()
+            .find(|(name, _)| name == resolution)
+            .map(|(_, ratio)| ratio.clone())
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("unknown resolution {resolution}"),
+                )
+            })
+    }
+
+    /// applies the change to the wallpaper and saves it to the store
+    async fn update<F>(&self, fname: &str, change: F) -> ApiResult<Json<Value>>
+    where
+        F: FnOnce(&mut WallInfo) -> ApiResult<()> + Send + 'static,
+    {
+        let (api, fname) = (self.clone(), fname.to_string());
+        blocking(move || {
+            let _guard = api.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+            let mut store = api.open_store()?;
+            let mut info = Self::find(store.as_ref(), &fname)?;
+            change(&mut info)?;
+
+            let json = wallpaper_json(&api.config, &info);
+            store
+                .upsert(&info, &api.config.sorted_resolutions())
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            hooks::post_save(&api.config, &info);
+
+            Ok(Json(json))
+        })
+        .await
+    }
+}
+
+/// requests need the token as a bearer token if one is set
+async fn authorize(State(api): State<Api>, req: Request, next: Next) -> Response {
+    if let Some(token) = &api.token {
+        let authorized = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            == Some(token.as_str());
+
+        if !authorized {
+            return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+async fn resolutions(State(api): State<Api>) -> Json<Value> {
+    let resolutions: Map<String, Value> = api
+        .config
+        .resolutions
+        .iter()
+        .map(|(name, ratio)| (name.clone(), json!(ratio)))
+        .collect();
+    Json(Value::Object(resolutions))
+}
+
+#[derive(Deserialize)]
+struct WallpapersQuery {
+    /// comma separated tags, wallpapers with any of them are returned
+    tag: Option<String>,
+    /// review state of the wallpapers
+    state: Option<String>,
+}
+
+async fn wallpapers(
+    State(api): State<Api>,
+    Query(query): Query<WallpapersQuery>,
+) -> ApiResult<Json<Value>> {
+    let tags: Vec<_> = query.tag.into_iter().collect();
+    blocking(move || {
+        let wallpapers: Map<String, Value> = api
+            .open_store()?
+            .wallpapers()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .into_iter()
+            .filter(|info| {
+                info.matches_tags(&tags, &[])
+                    && info.matches_state(query.state.as_deref().unwrap_or("all"))
+            })
+            .map(|info| (info.filename.clone(), wallpaper_json(&api.config, &info)))
+            .collect();
+        Ok(Json(Value::Object(wallpapers)))
+    })
+    .await
+}
+
+async fn wallpaper(State(api): State<Api>, Path(fname): Path<String>) -> ApiResult<Json<Value>> {
+    let info = api.wallpaper(&fname).await?;
+    Ok(Json(wallpaper_json(&api.config, &info)))
+}
+
+#[derive(Deserialize)]
+struct CropBody {
+    /// WxH+X+Y, with the optional scale and padding suffixes
+    geometry: String,
+}
+
+#[derive(Deserialize)]
+struct CropQuery {
+    /// saves the crop even if it fails validation
+    #[serde(default)]
+    force: bool,
+}
+
+async fn set_crop(
+    State(api): State<Api>,
+    Path((fname, resolution)): Path<(String, String)>,
+    Query(query): Query<CropQuery>,
+    Json(body): Json<CropBody>,
+) -> ApiResult<Json<Value>> {
+    let ratio = api.ratio(&resolution)?;
+    let geom = Geometry::try_from(body.geometry)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+
+    let config = api.config.clone();
+    api.update(&fname, move |info| {
+        if geom.w == 0 || geom.h == 0 {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("{geom} is empty")));
+        }
+        // checked so large offsets can't overflow past the bounds
+        let inside = geom.x.checked_add(geom.w).is_some_and(|x| x <= info.width)
+            && geom.y.checked_add(geom.h).is_some_and(|y| y <= info.height);
+        if !inside {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "{geom} is outside of the {}x{} image",
+                    info.width, info.height
+                ),
+            ));
+        }
+        info.set_geometry(&ratio, &geom);
+        info.state = "reviewed".to_string();
+
+        let warnings = validate(&config, info);
+        if !query.force && !warnings.is_empty() {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, warnings.join("\n")));
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn set_tags(
+    State(api): State<Api>,
+    Path(fname): Path<String>,
+    Json(tags): Json<Vec<String>>,
+) -> ApiResult<Json<Value>> {
+    api.update(&fname, move |info| {
+        // commas split the tags like in the ui, the csv couldn't tell them apart
+        info.tags.clear();
+        for tag in &tags {
+            info.add_tags(tag);
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn palette(State(api): State<Api>, Path(fname): Path<String>) -> ApiResult<Json<Value>> {
+    let info = api.wallpaper(&fname).await?;
+    let image = api.config.wallpapers_path.join(&fname);
+    let options = PaletteOptions::from_wallust_args(&info.wallust);
+
+    let palette = tokio::task::spawn_blocking(move || {
+        image::open(image)
+            .ok()
+            .and_then(|img| Palette::from_image(&img, options))
+    })
+    .await
+    .ok()
+    .flatten()
+    .ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("could not generate a palette for {fname}"),
+        )
+    })?;
+
+    let colors: Map<String, Value> = palette
+        .variables()
+        .into_iter()
+        .map(|(name, color)| (name, json!(hex(color))))
+        .collect();
+    Ok(Json(json!({
+        "wallust": info.wallust,
+        "colors": colors,
+    })))
+}
+
+#[derive(Deserialize)]
+struct PaletteBody {
+    /// wallust arguments used for the palette of the wallpaper
+    wallust: String,
+}
+
+async fn set_palette(
+    State(api): State<Api>,
+    Path(fname): Path<String>,
+    Json(body): Json<PaletteBody>,
+) -> ApiResult<Json<Value>> {
+    api.update(&fname, move |info| {
+        info.wallust = body.wallust.trim().to_string();
+        Ok(())
+    })
+    .await
+}
+
+/// the rendered crop as an image, reusing the crop cache
+async fn crop_image(
+    State(api): State<Api>,
+    Path((fname, resolution)): Path<(String, String)>,
+) -> ApiResult<Response> {
+    let ratio = api.ratio(&resolution)?;
+    let info = api.wallpaper(&fname).await?;
+    let image = api.config.wallpapers_path.join(&fname);
+
+    let crop = tokio::task::spawn_blocking(move || {
+        let geom = info.get_geometry(&ratio);
+        render_cached(&image, &ratio, &geom)
+            .map_err(|e| e.to_string())
+            .and_then(|crop| std::fs::read(crop).map_err(|e| e.to_string()))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|res| res)
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("could not render {fname} for {resolution}: {e}"),
+        )
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], Body::from(crop)).into_response())
+}
+
+pub fn run(args: &ServeArgs) {
+    if args.version {
+        println!("serve {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let api = Api {
+        config: Arc::new(WallpaperConfig::new()),
+        token: args.token.clone(),
+        write_lock: Arc::new(Mutex::new(())),
+    };
+
+    let app = Router::new()
+        .route("/api/resolutions", get(resolutions))
+        .route("/api/wallpapers", get(wallpapers))
+        .route("/api/wallpapers/:filename", get(wallpaper))
+        .route("/api/wallpapers/:filename/tags", put(set_tags))
+        .route(
+            "/api/wallpapers/:filename/palette",
+            get(palette).put(set_palette),
+        )
+        .route("/api/wallpapers/:filename/crops/:resolution", put(set_crop))
+        .route(
+            "/api/wallpapers/:filename/crops/:resolution/image",
+            get(crop_image),
+        )
+        .layer(middleware::from_fn_with_state(api.clone(), authorize))
+        .with_state(api);
+
+    let run = async {
+        let listener = tokio::net::TcpListener::bind(&args.addr).await?;
+        info!("Serving the api on http://{}", listener.local_addr()?);
+        axum::serve(listener, app).await
+    };
+
+    let res = tokio::runtime::Runtime::new()
+        .expect("could not create tokio runtime")
+        .block_on(run);
+
+    if let Err(e) = res {
+        error!("Could not serve the api on {}: {e}", args.addr);
+        std::process::exit(1);
+    }
+}
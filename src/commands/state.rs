@@ -0,0 +1,51 @@
+use tracing::{error, info};
+
+use crate::{
+    cli::StateArgs, config::WallpaperConfig, logging, wallpapers::WallpapersCsv, WallpaperConfigExt,
+};
+
+pub fn run(args: &StateArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let mut wallpapers_csv = WallpapersCsv::open_or_default().unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    let fnames: Vec<_> = if args.filenames.is_empty() {
+        wallpapers_csv
+            .iter()
+            .map(|(fname, _)| fname.clone())
+            .collect()
+    } else {
+        args.filenames.clone()
+    };
+
+    let mut failed = false;
+    let mut changed = 0;
+    for fname in &fnames {
+        let Some(info) = wallpapers_csv.get_mut(fname) else {
+            error!("{fname} is not in wallpapers.csv");
+            failed = true;
+            continue;
+        };
+        if args.from.as_ref().is_some_and(|from| *from != info.state) || info.state == args.state {
+            continue;
+        }
+        info.state.clone_from(&args.state);
+        changed += 1;
+    }
+
+    if changed > 0 {
+        if let Err(e) = wallpapers_csv.save(&config.sorted_resolutions()) {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    }
+    info!("Set {changed} wallpapers to {}", args.state);
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,152 @@
+use std::process::Command;
+
+use tracing::{error, info, warn};
+
+use crate::{
+    cli::SyncArgs, config::WallpaperConfig, logging, paths, tools::ExternalTool,
+    wallpapers::WallpapersCsv, WallpaperConfigExt,
+};
+
+/// runs rsync, logging the error if it fails
+fn run_rsync(mut cmd: Command) -> bool {
+    match cmd.status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            error!("rsync exited with {status}");
+            false
+        }
+        Err(e) => {
+            error!("Could not run rsync: {e}");
+            false
+        }
+    }
+}
+
+/// copies the contents of the directory, newer files on the destination are kept
+fn rsync_dir(rsync: &ExternalTool, src: &str, dest: &str, dry_run: bool) -> bool {
+    let mut cmd = rsync.command();
+    cmd.args(["--archive", "--update", "--exclude", "/wallpapers.csv"]);
+    if dry_run {
+        cmd.args(["--dry-run", "--itemize-changes"]);
+    }
+    cmd.arg(src).arg(dest);
+
+    run_rsync(cmd)
+}
+
+/// trailing slash so rsync copies the contents of the directory
+fn dir_arg(dir: &str) -> String {
+    format!("{}/", dir.trim_end_matches('/'))
+}
+
+pub fn run(args: &SyncArgs) {
+    if args.version {
+        println!("sync {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let rsync = config.tools.get("rsync");
+    let local_dir = dir_arg(&config.wallpapers_path.to_string_lossy());
+    let remote_dir = dir_arg(&args.remote);
+    let remote_csv = args
+        .remote_csv
+        .clone()
+        .unwrap_or_else(|| format!("{remote_dir}wallpapers.csv"));
+
+    // pull first, so the rows of the remote csv have their images when the merged csv is saved
+    info!("Pulling wallpapers from {remote_dir}");
+    if !rsync_dir(&rsync, &remote_dir, &local_dir, args.dry_run) {
+        std::process::exit(1);
+    }
+
+    let remote_copy = paths::cache_dir().join("sync").join("wallpapers.csv");
+    if let Some(parent) = remote_copy.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            error!("Could not create {parent:?}: {e}");
+            std::process::exit(1);
+        }
+    }
+    // don't merge a stale copy if the remote csv can't be fetched
+    std::fs::remove_file(&remote_copy).ok();
+
+    let mut fetch = rsync.command();
+    fetch.arg(&remote_csv).arg(&remote_copy);
+
+    let mut wallpapers_csv = WallpapersCsv::open_or_default().unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+    // rows edited on both machines can't be merged, so the remote csv isn't overwritten with them
+    let mut conflicts = Vec::new();
+    if run_rsync(fetch) {
+        match WallpapersCsv::open_path(&remote_copy, config.clone()) {
+            Ok(remote) => {
+                let merge = wallpapers_csv.merge(remote);
+                info!("Merged {} new wallpapers from {remote_csv}", merge.added);
+                conflicts = merge.conflicts;
+            }
+            Err(e) => {
+                error!("Could not read {remote_csv}: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        warn!("Could not fetch {remote_csv}, pushing the local wallpapers.csv as is");
+    }
+
+    if !args.dry_run {
+        if let Err(e) = wallpapers_csv.save(&config.sorted_resolutions()) {
+            error!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    info!("Pushing wallpapers to {remote_dir}");
+    if !rsync_dir(&rsync, &local_dir, &remote_dir, args.dry_run) {
+        std::process::exit(1);
+    }
+
+    if !conflicts.is_empty() {
+        for fname in &conflicts {
+            warn!("{fname} differs between the machines");
+        }
+        error!(
+            "Not pushing {remote_csv}, {} wallpapers have different crops or other columns on \
+             the remote, keep the rows of one machine and sync again",
+            conflicts.len()
+        );
+        std::process::exit(1);
+    }
+
+    if !args.dry_run {
+        // with store = sqlite the merged wallpapers are in the database, so the remote gets them
+        // as a csv exported next to the fetched copy
+        let local_csv = if config.store == "sqlite" {
+            let mut export_config = config.clone();
+            export_config.store = "csv".to_string();
+            export_config.csv_path = remote_copy.with_file_name("merged.csv");
+
+            let wallpapers = wallpapers_csv
+                .iter()
+                .map(|(_, info)| info.clone())
+                .collect();
+            let mut export = WallpapersCsv::from_wallpapers(wallpapers, export_config);
+            if let Err(e) = export.save(&config.sorted_resolutions()) {
+                error!("{e}");
+                std::process::exit(1);
+            }
+            export.config().csv_path.clone()
+        } else {
+            config.csv_path.clone()
+        };
+
+        let mut push = rsync.command();
+        push.arg(&local_csv).arg(&remote_csv);
+        if !run_rsync(push) {
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use tracing::{error, info};
+
+use crate::{
+    cli::{TrashDeleteArgs, TrashListArgs, TrashRestoreArgs},
+    config::WallpaperConfig,
+    logging, print_json, trash,
+    wallpapers::WallpapersCsv,
+    WallpaperConfigExt,
+};
+
+pub fn list(args: &TrashListArgs) {
+    logging::init(&args.log);
+
+    let entries = trash::load().unwrap_or_else(|e| {
+        error!("Could not read {:?}: {e}", trash::log_file());
+        std::process::exit(1);
+    });
+
+    if args.json {
+        print_json(&entries);
+        return;
+    }
+
+    for entry in &entries {
+        let emptied = if entry.is_restorable() {
+            ""
+        } else {
+            " (removed from the trash)"
+        };
+        println!(
+            "{}  {}  {}{emptied}",
+            entry.deletion_date(),
+            entry.filename,
+            entry.path.display()
+        );
+    }
+}
+
+pub fn delete(args: &TrashDeleteArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let mut wallpapers_csv = WallpapersCsv::open_or_default().unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
+
+    let mut failed = false;
+    let mut deleted = 0;
+    for fname in &args.filenames {
+        match trash::trash_wallpaper(&config, fname, wallpapers_csv.get(fname)) {
+            Ok(entry) => {
+                info!("Moved {fname} to {:?}", entry.trashed);
+                wallpapers_csv.remove(fname);
+                deleted += 1;
+            }
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+            }
+        }
+    }
+
+    if deleted > 0 {
+        if let Err(e) = wallpapers_csv.save(&config.sorted_resolutions()) {
+            error!("{e}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+pub fn restore(args: &TrashRestoreArgs) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let mut failed = false;
+    for fname in &args.filenames {
+        match trash::restore(&config, fname) {
+            Ok(entry) => info!("Restored {:?}", entry.path),
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde_json::json;
+use tracing::debug;
+
+use crate::{
+    cli::WaybarArgs,
+    config::WallpaperConfig,
+    daemon::{self, DaemonCommand, DaemonStatus},
+    image_ops::accent_color,
+    logging, paths, WallpaperConfigExt,
+};
+
+/// escapes the pango markup used by waybar
+fn escape_markup(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&#39;")
+}
+
+/// remaining time as minutes and seconds
+fn countdown(status: &DaemonStatus) -> String {
+    status.remaining.map_or_else(
+        || "paused".to_string(),
+        |secs| format!("{}:{:02}", secs / 60, secs % 60),
+    )
+}
+
+struct Waybar<'a> {
+    args: &'a WaybarArgs,
+    config: WallpaperConfig,
+    /// accent colors by filename, so each wallpaper is only read once
+    accents: HashMap<String, String>,
+}
+
+impl Waybar<'_> {
+    fn accent(&mut self, fname: &str) -> String {
+        if let Some(accent) = self.accents.get(fname) {
+            return accent.clone();
+        }
+
+        let accent = accent_color(&self.config.wallpapers_path.join(fname))
+            .unwrap_or_else(|| "#ffffff".to_string());
+        self.accents.insert(fname.to_string(), accent.clone());
+        accent
+    }
+
+    fn render(
+        &mut self,
+        format: &str,
+        status: &DaemonStatus,
+        monitor: &str,
+        fname: &str,
+    ) -> String {
+        format
+            .replace("{filename}", &escape_markup(fname))
+            .replace("{accent}", &self.accent(fname))
+            .replace("{countdown}", &countdown(status))
+            .replace("{monitor}", &escape_markup(monitor))
+    }
+
+    /// waybar json for the current state of the daemon
+    fn output(&mut self, status: Option<&DaemonStatus>) -> serde_json::Value {
+        let current = status.and_then(|status| {
+            let (monitor, fname) = match &self.args.monitor {
+                Some(name) => status.current.get_key_value(name)?,
+                None => status.current.iter().next()?,
+            };
+            Some((status, monitor.clone(), fname.clone()?))
+        });
+
+        let Some((status, monitor, fname)) = current else {
+            return json!({ "text": "", "class": "stopped", "alt": "stopped" });
+        };
+
+        let args = self.args;
+        let class = if status.paused { "paused" } else { "running" };
+        json!({
+            "text": self.render(&args.format, status, &monitor, &fname),
+            "tooltip": self.render(&args.tooltip_format, status, &monitor, &fname),
+            "class": class,
+            "alt": class,
+        })
+    }
+}
+
+pub fn run(args: &WaybarArgs) {
+    if args.version {
+        println!("waybar {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    logging::init(&args.log);
+
+    let socket = paths::socket_file();
+    let mut waybar = Waybar {
+        args,
+        config: WallpaperConfig::new(),
+        accents: HashMap::new(),
+    };
+
+    let mut last = None;
+    loop {
+        let status = daemon::send(&socket, &DaemonCommand::Status)
+            .map_err(|e| debug!("daemon is not running: {e}"))
+            .ok()
+            .and_then(|reply| serde_json::from_str::<DaemonStatus>(&reply).ok());
+
+        // waybar only needs a new line when the output changes
+        let output = waybar.output(status.as_ref());
+        if last.as_ref() != Some(&output) {
+            println!("{output}");
+            last = Some(output);
+        }
+
+        if args.once {
+            break;
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -6,9 +6,14 @@ use dioxus_free_icons::icons::{
         MdFormatAlignCenter, MdFormatAlignLeft, MdFormatAlignRight, MdVerticalAlignBottom,
         MdVerticalAlignCenter, MdVerticalAlignTop,
     },
+    md_image_icons::MdCropPortrait,
 };
 use dioxus_free_icons::Icon;
-use wallpaper_ui::{cropper::Direction, geometry::Geometry};
+use wallpaper_ui::{
+    cropper::Direction,
+    geometry::{Geometry, PadAlign, Padding},
+    i18n::{t, t_args},
+};
 
 use crate::{
     app_state::{PreviewMode, UiState, Wallpapers},
@@ -46,6 +51,21 @@ fn AlignButton(
     }
 }
 
+/// toggles between padding the entire image and the default crop
+pub fn toggle_pad(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
+    let walls = wallpapers();
+    let geom = if walls.get_geometry().padding.is_some() {
+        walls.cropper().crop(&walls.ratio)
+    } else {
+        walls.cropper().pad(Padding {
+            color: ui().pad_color,
+            align: PadAlign::Center,
+        })
+    };
+
+    set_align(&geom, wallpapers, ui);
+}
+
 pub fn toggle_pan(ui: &mut Signal<UiState>) {
     ui.with_mut(|ui| {
         ui.preview_mode = if matches!(&ui.preview_mode, PreviewMode::Pan) {
@@ -56,6 +76,74 @@ pub fn toggle_pan(ui: &mut Signal<UiState>) {
     });
 }
 
+/// parses a percentage typed into the position input, e.g. 38 or 38%
+fn parse_percent(s: &str) -> Option<f64> {
+    s.trim()
+        .trim_end_matches('%')
+        .trim()
+        .parse()
+        .ok()
+        .filter(|percent: &f64| percent.is_finite())
+}
+
+fn from_left(percent: f64) -> String {
+    t_args("from-left", &[("percent", &format!("{percent:.0}"))])
+}
+
+fn from_top(percent: f64) -> String {
+    t_args("from-top", &[("percent", &format!("{percent:.0}"))])
+}
+
+/// position of the crop along its free axis, typing a percentage and pressing enter moves the
+/// crop there
+#[component]
+fn CropPosition(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element {
+    let mut typed = use_signal(String::new);
+    let walls = wallpapers();
+    let geom = walls.get_geometry();
+    let (img_w, img_h) = (walls.current.width, walls.current.height);
+
+    let readout = match geom.position(img_w, img_h) {
+        (Some(x), Some(y)) => format!("{}, {}", from_left(x), from_top(y)),
+        (Some(x), None) => from_left(x),
+        (None, Some(y)) => from_top(y),
+        (None, None) => return None,
+    };
+
+    rsx! {
+        input {
+            r#type: "text",
+            class: "w-52 rounded-md border-0 bg-white/5 py-1.5 px-2 text-sm text-white ring-1 ring-inset ring-white/10 focus:ring-2 focus:ring-inset focus:ring-indigo-500",
+            title: t("crop-position-help"),
+            placeholder: "{readout}",
+            value: "{typed}",
+            oninput: move |evt| {
+                evt.stop_propagation();
+                typed.set(evt.value());
+            },
+            onkeydown: move |evt| {
+                // typing shouldn't trigger the editor shortcuts
+                evt.stop_propagation();
+                if evt.key() != Key::Enter {
+                    return;
+                }
+
+                if let Some(percent) = parse_percent(&typed()) {
+                    let walls = wallpapers();
+                    let geom = walls.get_geometry().at_position(
+                        walls.current.width,
+                        walls.current.height,
+                        percent,
+                    );
+                    drop(walls);
+                    set_align(&geom, &mut wallpapers, &mut ui);
+                }
+                typed.set(String::new());
+            },
+        }
+    }
+}
+
 #[component]
 pub fn AlignSelector(
     class: Option<String>,
@@ -67,9 +155,13 @@ pub fn AlignSelector(
     let align = ui().preview_mode;
     let geom: Geometry = wallpapers().get_geometry();
     let dir = info.direction(&geom);
+    // portrait crops can also frame the main face with headroom above it
+    let headroom = wallpapers().cropper().headroom(&ratio);
 
     rsx! {
         div { class: "flex gap-x-6",
+            CropPosition { wallpapers, ui }
+
             span {
                 class: "isolate inline-flex rounded-md shadow-sm",
                 AlignButton {
@@ -77,14 +169,14 @@ pub fn AlignSelector(
                     geom: wallpapers().source.get_geometry(&ratio),
                     wallpapers,
                     ui,
-                    "Source"
+                    {t("source")}
                 }
                 AlignButton {
                     class: "text-sm rounded-r-md",
-                    geom: info.cropper().crop(&ratio),
+                    geom: wallpapers().cropper().crop(&ratio),
                     wallpapers,
                     ui,
-                    "Default"
+                    {t("default")}
                 }
             }
 
@@ -96,7 +188,7 @@ pub fn AlignSelector(
                     geom: geom.align_start(info.width, info.height),
                     wallpapers,
                     ui,
-                    if dir == Direction::X {
+                    if dir != Direction::Y {
                         Icon { fill: "white", icon:  MdFormatAlignLeft }
                     } else {
                         Icon { fill: "white", icon: MdVerticalAlignTop }
@@ -107,29 +199,48 @@ pub fn AlignSelector(
                     geom: geom.align_center(info.width, info.height),
                     wallpapers,
                     ui,
-                    if dir == Direction::X {
+                    if dir != Direction::Y {
                         Icon { fill: "white", icon:  MdFormatAlignCenter }
                     } else {
                         Icon { fill: "white", icon: MdVerticalAlignCenter }
                     }
                }
                 AlignButton {
-                    class: "text-sm rounded-r-md",
+                    class: if headroom.is_some() { "text-sm -ml-px" } else { "text-sm rounded-r-md" },
                     geom: geom.align_end(info.width, info.height),
                     wallpapers,
                     ui,
-                    if dir == Direction::X {
+                    if dir != Direction::Y {
                         Icon { fill: "white", icon:  MdFormatAlignRight }
                     } else {
                         Icon { fill: "white", icon: MdVerticalAlignBottom }
                     }
                 }
+                if headroom.is_some() {
+                    AlignButton {
+                        class: "text-sm rounded-r-md",
+                        geom: headroom.clone().unwrap_or_default(),
+                        wallpapers,
+                        ui,
+                        span { title: t("headroom"),
+                            Icon { fill: "white", icon: MdCropPortrait }
+                        }
+                    }
+                }
             }
 
             span {
                 class: "isolate inline-flex rounded-md shadow-sm",
                 Button {
-                    class: "text-sm rounded-md",
+                    class: "text-sm rounded-l-md",
+                    active: geom.padding.is_some(),
+                    onclick: move |_| {
+                        toggle_pad(&mut wallpapers, &mut ui);
+                    },
+                    {t("pad")}
+                }
+                Button {
+                    class: "text-sm rounded-r-md",
                     active: align == PreviewMode::Pan,
                     onclick: move |_| {
                         toggle_pan(&mut ui);
@@ -1,30 +1,92 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
 use dioxus_free_icons::icons::{
+    md_action_icons::{MdDelete, MdDoneAll, MdFace},
     md_image_icons::{MdFaceRetouchingNatural, MdPalette},
     md_navigation_icons::{MdChevronLeft, MdChevronRight},
+    md_social_icons::MdPerson,
 };
 use dioxus_free_icons::Icon;
-use wallpaper_ui::wallpapers::WallpapersCsv;
+use tracing::info;
+#[cfg(feature = "onnx")]
+use wallpaper_ui::face_detection;
+use wallpaper_ui::{
+    config::WallpaperConfig,
+    hooks,
+    i18n::{t, t_args},
+    trash,
+    validation::validate,
+    wallpapers::WallpapersError,
+};
+
+use crate::{
+    app_state::{CsvCache, UiMode, UiState, Wallpapers},
+    components::{button::Button, command_menu::CommandMenu, tag_editor::TagEditor},
+};
 
-use crate::app_state::{PreviewMode, UiMode, UiState, Wallpapers};
+/// reads config.ini again so it can be edited while the ui is open, an invalid config is shown as
+/// the error instead of closing the ui
+pub fn load_config(ui: &mut Signal<UiState>) -> Option<WallpaperConfig> {
+    match WallpaperConfig::load() {
+        Ok(config) => Some(config),
+        Err(e) => {
+            ui.with_mut(|ui| ui.error = Some(WallpapersError::from(e).to_string()));
+            None
+        }
+    }
+}
 
 pub fn save_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
     let info = wallpapers().current;
-    let mut wallpapers_csv = WallpapersCsv::load();
-    wallpapers_csv.insert(info.filename.clone(), info);
+    let Some(config) = load_config(ui) else {
+        return;
+    };
+
+    // show the warnings first, saving again without any changes ignores them
+    let warnings = validate(&config, &info);
+    if !warnings.is_empty() && warnings != ui().warnings {
+        ui.with_mut(|ui| {
+            ui.warnings = warnings;
+        });
+        return;
+    }
+
+    // saving from the editor marks the crops as reviewed
+    wallpapers.with_mut(|wallpapers| wallpapers.set_state("reviewed"));
+    let info = wallpapers().current;
+
+    let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
     let resolutions: Vec<_> = wallpapers()
         .resolutions
         .iter()
         .map(|(_, ratio)| ratio.clone())
         .collect();
-    wallpapers_csv.save(&resolutions);
+    if let Err(e) = wallpapers_csv.write().save(&info, &resolutions) {
+        ui.with_mut(|ui| {
+            ui.error = Some(format!("could not save {}: {e}", info.filename));
+        });
+        return;
+    }
+    hooks::post_save(&config, &info);
 
-    wallpapers.with_mut(|wallpapers| {
-        wallpapers.remove();
-    });
+    let mut error = None;
+    if ui().auto_advance {
+        wallpapers.with_mut(|wallpapers| {
+            error = wallpapers
+                .remove(&mut wallpapers_csv.write())
+                .err()
+                .map(|e| e.to_string());
+        });
+    } else {
+        // the saved crops are the new baseline for the dirty markers
+        wallpapers.with_mut(|wallpapers| {
+            wallpapers.source = wallpapers.current.clone();
+        });
+    }
     ui.with_mut(|ui| {
-        ui.preview_mode = PreviewMode::Candidate(None);
+        ui.reset_preview_mode();
+        ui.warnings.clear();
+        ui.error = error;
         ui.is_saving = true;
     });
 }
@@ -49,7 +111,7 @@ pub fn SaveButton(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Elemen
     } else {
         "bg-indigo-600"
     };
-    let btn_text = if clicked { "Saved" } else { "Save" };
+    let btn_text = if clicked { t("saved") } else { t("save") };
 
     rsx! {
         a {
@@ -63,21 +125,166 @@ pub fn SaveButton(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Elemen
     }
 }
 
-pub fn prev_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
-    wallpapers.with_mut(|wallpapers| {
-        wallpapers.prev_wall();
+/// deletes the current wallpaper and its row of wallpapers.csv, then moves on to the next one,
+/// with trash_deleted it is moved to the trash with its saved crops and can be restored with
+/// `wallpapers trash restore`
+pub fn delete_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
+    let fname = wallpapers().current.filename;
+    let Some(config) = load_config(ui) else {
+        ui.with_mut(|ui| ui.confirm_delete = false);
+        return;
+    };
+    let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+
+    let deleted = if config.trash_deleted {
+        let row = wallpapers_csv.write().get(&fname).ok();
+        trash::trash_wallpaper(&config, &fname, row.as_ref())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    } else {
+        std::fs::remove_file(config.wallpapers_path.join(&fname))
+            .map_err(|e| format!("could not delete {fname}: {e}"))
+    };
+    if let Err(e) = deleted {
+        ui.with_mut(|ui| {
+            ui.confirm_delete = false;
+            ui.error = Some(e);
+        });
+        return;
+    }
+
+    let resolutions: Vec<_> = wallpapers()
+        .resolutions
+        .iter()
+        .map(|(_, ratio)| ratio.clone())
+        .collect();
+    let removed = wallpapers_csv.write().remove(&fname, &resolutions);
+    let res = removed.and_then(|()| {
+        wallpapers.with_mut(|wallpapers| wallpapers.remove(&mut wallpapers_csv.write()))
     });
     ui.with_mut(|ui| {
-        ui.preview_mode = PreviewMode::Candidate(None);
+        ui.confirm_delete = false;
+        ui.reset_preview_mode();
+        ui.warnings.clear();
+        ui.error = res.err().map(|e| e.to_string());
     });
 }
 
-pub fn next_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
-    wallpapers.with_mut(|wallpapers| {
-        wallpapers.next_wall();
+/// asks before deleting the current wallpaper, enter or delete confirms and escape cancels
+#[component]
+pub fn DeleteDialog(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element {
+    // an invalid config is only shown once deleting, which needs the config to find the file
+    let trash_deleted = WallpaperConfig::load().map_or(true, |config| config.trash_deleted);
+    let message = t_args(
+        if trash_deleted {
+            "confirm-trash"
+        } else {
+            "confirm-delete"
+        },
+        &[("filename", &wallpapers().current.filename)],
+    );
+
+    rsx! {
+        div { class: "fixed inset-0 z-50 flex items-center justify-center bg-black/60",
+            div { class: "flex flex-col gap-6 max-w-md rounded-lg bg-surface0 p-6 shadow-xl",
+                p { class: "text-sm text-text", "{message}" }
+                div { class: "flex justify-end gap-x-3",
+                    Button {
+                        class: "text-sm rounded-md",
+                        onclick: move |_| {
+                            ui.with_mut(|ui| ui.confirm_delete = false);
+                        },
+                        {t("cancel")}
+                    }
+                    Button {
+                        class: "text-sm rounded-md !bg-red-600 hover:!bg-red-500 text-white",
+                        onclick: move |_| delete_image(&mut wallpapers, &mut ui),
+                        {t("delete-wallpaper")}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// writes the default crops to all the filtered wallpapers, the first call only asks for
+/// confirmation as the crops that were edited are overwritten
+pub fn apply_default_crops(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
+    if !ui().confirm_default_crops {
+        ui.with_mut(|ui| ui.confirm_default_crops = true);
+        return;
+    }
+
+    let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+    let res = wallpapers
+        .with_mut(|wallpapers| wallpapers.apply_default_crops(&mut wallpapers_csv.write()));
+    if let Ok(count) = res {
+        info!("Applied the default crops to {count} wallpapers");
+    }
+    ui.with_mut(|ui| {
+        ui.confirm_default_crops = false;
+        ui.reset_preview_mode();
+        ui.warnings.clear();
+        ui.error = res.err().map(|e| e.to_string());
     });
+}
+
+pub fn prev_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
+    let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+    let res = wallpapers.with_mut(|wallpapers| wallpapers.prev_wall(&mut wallpapers_csv.write()));
+    ui.with_mut(|ui| {
+        ui.reset_preview_mode();
+        ui.warnings.clear();
+        ui.error = res.err().map(|e| e.to_string());
+    });
+}
+
+pub fn next_image(wallpapers: &mut Signal<Wallpapers>, ui: &mut Signal<UiState>) {
+    let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+    let res = wallpapers.with_mut(|wallpapers| wallpapers.next_wall(&mut wallpapers_csv.write()));
     ui.with_mut(|ui| {
-        ui.preview_mode = PreviewMode::Candidate(None);
+        ui.reset_preview_mode();
+        ui.warnings.clear();
+        ui.error = res.err().map(|e| e.to_string());
+    });
+}
+
+/// detects the faces of the current wallpaper with the onnx model, the faces and crops are
+/// replaced but not saved
+///
+/// wallpapers added with the photo detector are detected with the photo model again
+#[cfg(feature = "onnx")]
+fn detect_faces(mut wallpapers: Signal<Wallpapers>, mut ui: Signal<UiState>) {
+    let path = wallpapers().files[wallpapers().index].clone();
+    let Some(config) = load_config(&mut ui) else {
+        return;
+    };
+    let (detector, model) = if wallpapers().current.detector == "photo" {
+        ("photo", config.photo_face_model)
+    } else {
+        ("anime", config.face_model)
+    };
+
+    spawn(async move {
+        let fname = wallpaper_ui::filename(&path);
+        let res = tokio::task::spawn_blocking(move || {
+            face_detection::detector(&model)?.detect_path(&path)
+        })
+        .await;
+
+        // the user might have moved on to another wallpaper while detecting
+        if wallpapers().current.filename != fname {
+            return;
+        }
+        match res
+            .map_err(|e| e.to_string())
+            .and_then(|res| res.map_err(|e| e.to_string()))
+        {
+            Ok((faces, _)) => {
+                wallpapers.with_mut(|wallpapers| wallpapers.set_faces(faces, detector));
+            }
+            Err(e) => ui.with_mut(|ui| ui.error = Some(e)),
+        }
     });
 }
 
@@ -90,6 +297,14 @@ pub fn AppHeader(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element
             .is_ok()
     });
     let info = wallpapers().current;
+    let apply_defaults_title = t_args(
+        if ui().confirm_default_crops {
+            "confirm-apply-defaults"
+        } else {
+            "apply-defaults"
+        },
+        &[("count", &wallpapers().files.len().to_string())],
+    );
 
     let pagination_cls = "relative inline-flex items-center rounded-md bg-surface1 py-1 px-2 text-sm font-semibold text-text ring-1 ring-inset ring-surface2 hover:bg-crust focus-visible:outline-offset-0 cursor-pointer";
 
@@ -101,10 +316,11 @@ pub fn AppHeader(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element
 
                 // left
                 div {
-                    class: "flex-1 justify-start ml-2",
+                    class: "flex flex-1 gap-x-4 items-center justify-start ml-2",
                     a { class: "text-base font-semibold leading-6 text-white",
                         "{wallpapers().index + 1} / {wallpapers().files.len()}"
                     }
+                    TagEditor { wallpapers }
                 }
 
                 // center
@@ -165,6 +381,58 @@ pub fn AppHeader(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element
                         Icon { fill: "white", icon:  MdFaceRetouchingNatural }
                     }
 
+                    if cfg!(feature = "onnx") {
+                        a {
+                            class: "rounded-md px-3 py-2 text-sm font-semibold text-white shadow-sm bg-surface1 hover:bg-crust focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 cursor-pointer",
+                            title: t("detect-faces"),
+                            onclick: move |_| {
+                                #[cfg(feature = "onnx")]
+                                detect_faces(wallpapers, ui);
+                            },
+                            Icon { fill: "white", icon:  MdFace }
+                        }
+                    }
+
+                    a {
+                        class: "rounded-md px-3 py-2 text-sm font-semibold text-white shadow-sm focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 cursor-pointer",
+                        class: if wallpapers().crop_options.largest_face {
+                            "bg-indigo-600 hover:bg-indigo-500"
+                        } else {
+                            "bg-surface1 hover:bg-crust"
+                        },
+                        title: t("largest-face"),
+                        onclick: move |_| {
+                            wallpapers.with_mut(|wallpapers| {
+                                wallpapers.crop_options.largest_face =
+                                    !wallpapers.crop_options.largest_face;
+                            });
+                        },
+                        Icon { fill: "white", icon:  MdPerson }
+                    }
+
+                    a {
+                        class: "rounded-md px-3 py-2 text-sm font-semibold text-white shadow-sm focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 cursor-pointer",
+                        class: if ui().confirm_default_crops {
+                            "bg-red-600 hover:bg-red-500"
+                        } else {
+                            "bg-surface1 hover:bg-crust"
+                        },
+                        title: apply_defaults_title,
+                        onclick: move |_| apply_default_crops(&mut wallpapers, &mut ui),
+                        Icon { fill: "white", icon:  MdDoneAll }
+                    }
+
+                    a {
+                        class: "rounded-md px-3 py-2 text-sm font-semibold text-white shadow-sm bg-surface1 hover:bg-red-600 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 cursor-pointer",
+                        title: t("delete-wallpaper"),
+                        onclick: move |_| {
+                            ui.with_mut(|ui| ui.confirm_delete = true);
+                        },
+                        Icon { fill: "white", icon:  MdDelete }
+                    }
+
+                    CommandMenu { wallpapers, ui }
+
                     SaveButton { wallpapers, ui }
                 }
             }
@@ -0,0 +1,69 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+use dioxus_free_icons::icons::md_navigation_icons::MdMoreVert;
+use dioxus_free_icons::Icon;
+use wallpaper_ui::{config::WallpaperConfig, custom_commands, i18n::t};
+
+use crate::app_state::{UiState, Wallpapers};
+
+/// menu of the custom commands from the config, run with the current wallpaper and ratio
+#[component]
+pub fn CommandMenu(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element {
+    // the menu is hidden if the config became invalid, the editor shows the error when it is used
+    let config = use_signal(|| WallpaperConfig::load().unwrap_or_default());
+
+    if config().commands.is_empty() {
+        return None;
+    }
+
+    rsx! {
+        div { class: "relative",
+            a {
+                class: "rounded-md px-3 py-2 text-sm font-semibold text-white shadow-sm focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 cursor-pointer block",
+                class: if ui().show_commands {
+                    "bg-indigo-600 hover:bg-indigo-500"
+                } else {
+                    "bg-surface1 hover:bg-crust"
+                },
+                title: t("commands"),
+                onclick: move |_| {
+                    ui.with_mut(|ui| {
+                        ui.show_commands = !ui.show_commands;
+                    });
+                },
+                Icon { fill: "white", icon: MdMoreVert }
+            }
+
+            if ui().show_commands {
+                ul {
+                    role: "menu",
+                    class: "absolute right-0 z-10 mt-2 w-56 rounded-md bg-surface0 py-1 shadow-lg ring-1 ring-surface2",
+                    for (idx, (name, template)) in config().commands.into_iter().enumerate() {
+                        li {
+                            role: "menuitem",
+                            class: "block px-4 py-2 text-sm text-text hover:bg-surface1 cursor-pointer",
+                            title: template,
+                            onclick: move |_| {
+                                let config = config();
+                                let walls = wallpapers();
+                                if let Some((name, template)) = config.commands.get(idx) {
+                                    custom_commands::run(
+                                        &config,
+                                        name,
+                                        template,
+                                        &walls.current,
+                                        &walls.ratio,
+                                    );
+                                }
+                                ui.with_mut(|ui| {
+                                    ui.show_commands = false;
+                                });
+                            },
+                            {name}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
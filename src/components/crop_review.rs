@@ -0,0 +1,199 @@
+#![allow(non_snake_case)]
+use std::{path::PathBuf, rc::Rc};
+
+use dioxus::prelude::*;
+use dioxus_free_icons::icons::md_navigation_icons::{MdChevronLeft, MdChevronRight};
+use dioxus_free_icons::Icon;
+use tracing::error;
+use wallpaper_ui::{
+    crop_review::{self, CropReview},
+    filename,
+    geometry::Geometry,
+    i18n::{t, t_args},
+    wallpapers::WallpapersCsv,
+};
+
+use crate::components::{button::Button, load_error::LoadError};
+
+/// the crop of the image, the image is scaled and offset within the box so only the crop shows
+#[component]
+fn CropThumbnail(label: String, src: String, width: u32, geom: Geometry) -> Element {
+    let img_width = f64::from(width) / f64::from(geom.w) * 100.0;
+    let left = f64::from(geom.x) / f64::from(geom.w) * 100.0;
+    let top = f64::from(geom.y) / f64::from(geom.h) * 100.0;
+
+    rsx! {
+        div { class: "flex flex-col gap-2 items-center",
+            div {
+                class: "relative h-64 overflow-hidden ring-1 ring-surface1",
+                style: "aspect-ratio: {geom.w} / {geom.h};",
+                img {
+                    class: "absolute max-w-none select-none",
+                    src: src,
+                    draggable: false,
+                    style: "left: {-left}%; top: {-top}%; width: {img_width}%;",
+                }
+            }
+            span { class: "text-sm font-semibold text-text", {label} }
+        }
+    }
+}
+
+/// shows the default crops of the new wallpapers, to accept them or queue them for editing
+#[component]
+pub fn CropReviewer(review_file: PathBuf, theme: String) -> Element {
+    // loaded once, the error screen is shown instead of the reviews if it fails
+    let loaded = use_hook(|| {
+        Rc::new(
+            crop_review::load(&review_file)
+                .map_err(|e| format!("could not read {review_file:?}: {e}"))
+                .and_then(|reviews| {
+                    WallpapersCsv::open_or_default()
+                        .map(|wallpapers_csv| (reviews, wallpapers_csv))
+                        .map_err(|e| e.to_string())
+                }),
+        )
+    });
+    let (reviews, wallpapers_csv) = match loaded.as_ref() {
+        Ok(loaded) => loaded.clone(),
+        Err(e) => {
+            return rsx! {
+                LoadError { theme, error: e.clone() }
+            };
+        }
+    };
+
+    let mut reviews = use_signal(|| reviews);
+    let resolutions = use_signal(|| wallpapers_csv.config().resolutions.clone());
+    let wallpapers_csv = use_signal(|| wallpapers_csv);
+    // signals can be copied into each of the event handlers
+    let review_file = use_signal(|| review_file);
+    let mut index = use_signal(|| 0);
+
+    let total = reviews().len();
+
+    let mut decide = move |accepted: bool| {
+        reviews.with_mut(|reviews| reviews[index()].accepted = Some(accepted));
+        if let Err(e) = crop_review::save(&review_file(), &reviews()) {
+            error!("Could not write {:?}: {e}", review_file());
+        }
+        index.set(index() + 1);
+    };
+
+    let Some(review) = reviews().get(index()).cloned() else {
+        let to_edit = reviews().iter().filter(|r| r.needs_editing()).count();
+        return rsx! {
+            main {
+                class: "dark {theme} flex flex-col gap-4 items-center h-full justify-center bg-base overflow-hidden",
+                h1 { class: "text-4xl font-bold tracking-tight text-text text-center",
+                    {t("all-crops-reviewed")}
+                }
+                p { class: "text-text",
+                    {t_args("crops-to-edit", &[("count", &to_edit.to_string()), ("total", &total.to_string())])}
+                }
+                Button {
+                    class: "rounded-md",
+                    onclick: move |_| index.set(total.saturating_sub(1)),
+                    {t("back")}
+                }
+            }
+        };
+    };
+
+    let CropReview { path, accepted } = review;
+    let fname = filename(&path);
+    let src = path.to_string_lossy().to_string();
+
+    let crops = match wallpapers_csv.read().get(&fname) {
+        Some(info) => {
+            let image_ratio = f64::from(info.width) / f64::from(info.height);
+            let thumbnails = resolutions()
+                .into_iter()
+                // images of the same ratio only have a single possible crop
+                .filter(|(_, ratio)| (image_ratio - f64::from(ratio)).abs() > f64::EPSILON)
+                .map(|(name, ratio)| {
+                    rsx! {
+                        CropThumbnail {
+                            key: "{name}",
+                            label: "{name} ({ratio})",
+                            src: src.clone(),
+                            width: info.width,
+                            geom: info.get_geometry(&ratio),
+                        }
+                    }
+                });
+            rsx! {
+                {thumbnails}
+            }
+        }
+        None => rsx! {
+            p { class: "text-red-400",
+                {t_args("missing-info", &[("filename", &fname)])}
+            }
+        },
+    };
+
+    let pagination_cls = "relative inline-flex items-center rounded-md bg-surface1 py-1 px-2 text-sm font-semibold text-text ring-1 ring-inset ring-surface2 hover:bg-crust focus-visible:outline-offset-0 cursor-pointer";
+
+    rsx! {
+        main {
+            class: "dark {theme} flex flex-col h-full bg-base overflow-hidden",
+            tabindex: 0,
+            autofocus: true,
+            onkeydown: move |event| {
+                match event.key() {
+                    Key::Character(shortcut) if shortcut == "y" => decide(true),
+                    Key::Character(shortcut) if shortcut == "n" => decide(false),
+                    Key::ArrowLeft => index.set(index().saturating_sub(1)),
+                    Key::ArrowRight => index.set((index() + 1).min(total)),
+                    _ => {}
+                }
+            },
+
+            header { class: "bg-surface0",
+                nav { class: "mx-auto flex max-w-full items-center py-6 px-4",
+                    div { class: "flex-1 justify-start ml-2",
+                        a { class: "text-base font-semibold leading-6 text-white",
+                            "{index() + 1} / {total}"
+                        }
+                    }
+
+                    div { class: "flex flex-1 gap-x-3 items-center justify-center",
+                        a { class: pagination_cls,
+                            onclick: move |_| index.set(index().saturating_sub(1)),
+                            Icon { fill: "white", icon: MdChevronLeft, width: 16, height: 16 }
+                        }
+                        a { class: "text-sm font-semibold leading-6 text-white text-center w-48",
+                            {fname}
+                        }
+                        a { class: pagination_cls,
+                            onclick: move |_| index.set((index() + 1).min(total)),
+                            Icon { fill: "white", icon: MdChevronRight, width: 16, height: 16 }
+                        }
+                    }
+
+                    div { class: "flex flex-1 justify-end",
+                        span { class: "isolate inline-flex rounded-md shadow-sm",
+                            Button {
+                                class: "rounded-l-md",
+                                active: accepted == Some(false),
+                                onclick: move |_| decide(false),
+                                {t("edit-crops")}
+                            }
+                            Button {
+                                class: "-ml-px rounded-r-md",
+                                active: accepted == Some(true),
+                                onclick: move |_| decide(true),
+                                {t("keep-crops")}
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "flex flex-1 flex-wrap gap-4 p-4 min-h-0 items-start justify-center overflow-auto",
+                {crops}
+            }
+        }
+    }
+}
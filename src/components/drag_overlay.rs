@@ -26,6 +26,7 @@ pub fn DragOverlay(
             class: match &direction {
                 Direction::X => "cursor-ew-resize",
                 Direction::Y => "cursor-ns-resize",
+                Direction::Both => "cursor-move",
             },
             style: "height: {final_h}px",
             onmousedown: {
@@ -42,9 +43,19 @@ pub fn DragOverlay(
                             let pct = y / final_h;
                             overlay_ratios.0 < pct || pct < overlay_ratios.1
                         },
+                        Direction::Both => {
+                            // compare in image coordinates
+                            let (img_x, img_y) = (x / final_w * img_w, y / final_h * img_h);
+                            f64::from(geometry.x) < img_x
+                                && img_x < f64::from(geometry.x + geometry.w)
+                                && f64::from(geometry.y) < img_y
+                                && img_y < f64::from(geometry.y + geometry.h)
+                        },
                     };
 
                     if in_clear_zone {
+                        // the whole drag is a single undo step
+                        wallpapers.with_mut(Wallpapers::checkpoint);
                         is_dragging.set(true);
                         drag_coords.set((x, y));
                     }
@@ -76,9 +87,17 @@ pub fn DragOverlay(
                                     ..geometry.clone()
                                 }
                             },
+                            Direction::Both => {
+                                let (scaled_dx, scaled_dy) = (img_w / final_w * dx, img_h / final_h * dy);
+                                Geometry {
+                                    x: (f64::from(geometry.x) + scaled_dx).clamp(0.0, img_w - f64::from(geometry.w)) as u32,
+                                    y: (f64::from(geometry.y) + scaled_dy).clamp(0.0, img_h - f64::from(geometry.h)) as u32,
+                                    ..geometry.clone()
+                                }
+                            },
                         };
                         wallpapers.with_mut(|wallpapers| {
-                            wallpapers.set_geometry(&new_geom);
+                            wallpapers.drag_geometry(&new_geom);
                         });
                         drag_coords.set((new_x, new_y));
                     }
@@ -2,14 +2,18 @@
 use dioxus::prelude::*;
 use std::{path::PathBuf, time::Instant};
 
+use wallpaper_ui::i18n::t;
+
 use crate::{
     app_state::{PreviewMode, UiState, Wallpapers},
     components::{
-        align_selector::{set_align, toggle_pan, AlignSelector},
-        app_header::{next_image, prev_image},
+        align_selector::{set_align, toggle_pad, toggle_pan, AlignSelector},
+        app_header::{apply_default_crops, next_image, prev_image},
+        button::Button,
         candidates::Candidates,
         preview::Previewer,
         ratio_selector::RatioSelector,
+        schedule_selector::ScheduleSelector,
     },
 };
 
@@ -29,8 +33,15 @@ pub fn handle_arrow_keys_down(
     let start_time_ms = ui()
         .arrow_key_start
         .map_or(0, |start_time| start_time.elapsed().as_millis());
-    // minimum move distance is 2px
-    let delta = (start_time_ms as f64 / 100.0 * 4.0).max(2.0) as i32;
+    // minimum move distance is the arrow step
+    let delta = (start_time_ms as f64 / 100.0 * 4.0).max(f64::from(ui().arrow_step)) as i32;
+    let (dx, dy) = match arrow_key {
+        Key::ArrowLeft => (-delta, 0),
+        Key::ArrowRight => (delta, 0),
+        Key::ArrowUp => (0, -delta),
+        Key::ArrowDown => (0, delta),
+        _ => (0, 0),
+    };
 
     match arrow_key {
         Key::ArrowLeft | Key::ArrowUp => {
@@ -62,16 +73,23 @@ pub fn handle_arrow_keys_down(
                                     ui.with_mut(|ui| {
                                         ui.preview_mode = PreviewMode::Pan;
                                     });
-                                    wallpapers().move_geometry_by(-delta)
+                                    walls.move_geometry_by(dx, dy)
                                 }
                             },
                         )
                 }
-                PreviewMode::Pan => wallpapers().move_geometry_by(-delta),
+                PreviewMode::Pan => walls.move_geometry_by(dx, dy),
             };
 
+            // the wallpaper info is only copied on the first nudge if nothing else shares it,
+            // holding down the key is a single undo step
+            drop(walls);
             wallpapers.with_mut(|wallpapers| {
-                wallpapers.set_geometry(&new_geom);
+                if start_time_ms == 0 {
+                    wallpapers.set_geometry(&new_geom);
+                } else {
+                    wallpapers.drag_geometry(&new_geom);
+                }
             });
         }
 
@@ -100,16 +118,23 @@ pub fn handle_arrow_keys_down(
                                     ui.with_mut(|ui| {
                                         ui.preview_mode = PreviewMode::Pan;
                                     });
-                                    wallpapers().move_geometry_by(delta)
+                                    walls.move_geometry_by(dx, dy)
                                 }
                             },
                         )
                 }
-                PreviewMode::Pan => wallpapers().move_geometry_by(delta),
+                PreviewMode::Pan => walls.move_geometry_by(dx, dy),
             };
 
+            // the wallpaper info is only copied on the first nudge if nothing else shares it,
+            // holding down the key is a single undo step
+            drop(walls);
             wallpapers.with_mut(|wallpapers| {
-                wallpapers.set_geometry(&new_geom);
+                if start_time_ms == 0 {
+                    wallpapers.set_geometry(&new_geom);
+                } else {
+                    wallpapers.drag_geometry(&new_geom);
+                }
             });
         }
 
@@ -135,6 +160,10 @@ pub fn handle_editor_shortcuts(
                     });
                 }
 
+                "e" => {
+                    ui.with_mut(UiState::toggle_face_edit);
+                }
+
                 "h" => {
                     prev_image(wallpapers, ui);
                 }
@@ -178,32 +207,38 @@ pub fn handle_editor_shortcuts(
                 }
 
                 "d" => {
-                    set_align(&walls.current.cropper().crop(&walls.ratio), wallpapers, ui);
+                    set_align(&walls.cropper().crop(&walls.ratio), wallpapers, ui);
+                }
+
+                "D" => {
+                    apply_default_crops(wallpapers, ui);
                 }
 
                 " " => {
                     toggle_pan(ui);
                 }
 
+                "b" => {
+                    toggle_pad(wallpapers, ui);
+                }
+
                 // tab through ratios
                 "t" => {
-                    let ratios = walls
-                        .image_ratios()
-                        .into_iter()
-                        .map(|(_, r)| r)
-                        .collect::<Vec<_>>();
-
-                    if let Some(pos) = ratios.iter().position(|r| *r == walls.ratio) {
-                        let next = (pos + 1) % ratios.len();
-                        wallpapers.with_mut(|wallpapers| {
-                            wallpapers.ratio = ratios[next].clone();
-                        });
-                    }
+                    wallpapers.with_mut(|wallpapers| wallpapers.cycle_ratio(true));
                 }
                 _ => {}
             }
         }
 
+        Key::Tab => {
+            let forward = !event.modifiers().shift();
+            wallpapers.with_mut(|wallpapers| wallpapers.cycle_ratio(forward));
+        }
+
+        Key::Delete => {
+            ui.with_mut(|ui| ui.confirm_delete = true);
+        }
+
         key => handle_arrow_keys_down(&key, wallpapers, ui),
     };
 }
@@ -223,7 +258,35 @@ pub fn Editor(
                 RatioSelector { wallpapers, ui },
 
                 div{
-                    class: "flex justify-end",
+                    class: "flex justify-end gap-x-6",
+                    span {
+                        class: "isolate inline-flex rounded-md shadow-sm",
+                        title: t("zoom-help"),
+                        Button {
+                            class: "text-sm rounded-l-md",
+                            active: ui().zoom.is_none(),
+                            onclick: move |_| {
+                                ui.with_mut(|ui| ui.zoom = None);
+                            },
+                            {t("fit")}
+                        }
+                        Button {
+                            class: "text-sm rounded-r-md",
+                            active: ui().zoom == Some(100),
+                            onclick: move |_| {
+                                ui.with_mut(|ui| ui.zoom = Some(100));
+                            },
+                            "100%"
+                        }
+                    }
+                    Button {
+                        class: "text-sm rounded-md",
+                        active: ui().face_edit,
+                        onclick: move |_| {
+                            ui.with_mut(UiState::toggle_face_edit);
+                        },
+                        {t("face-edit")}
+                    }
                     AlignSelector { wallpapers, ui },
                 }
             }
@@ -231,6 +294,8 @@ pub fn Editor(
             Previewer { wallpapers, ui, wallpapers_path }
 
             Candidates { wallpapers, ui }
+
+            ScheduleSelector { wallpapers }
         }
     }
 }
@@ -0,0 +1,167 @@
+#![allow(non_snake_case)]
+use dioxus::{html::input_data::MouseButton, prelude::*};
+
+use wallpaper_ui::{i18n::t, wallpapers::Face};
+
+use crate::app_state::Wallpapers;
+
+/// boxes smaller than this in pixels of the image are discarded, e.g. from a click without a drag
+const MIN_FACE_SIZE: f64 = 8.0;
+
+/// a face box being drawn or resized, in the coordinates of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FaceDrag {
+    /// index of the face being resized, None for a new face
+    index: Option<usize>,
+    /// the corner that stays in place
+    anchor: (f64, f64),
+    cursor: (f64, f64),
+}
+
+impl FaceDrag {
+    /// the box spanned by the anchor and the cursor, clamped to the image
+    fn face(&self, (img_w, img_h): (f64, f64), eye_y: Option<u32>) -> Face {
+        let (ax, ay) = self.anchor;
+        let (cx, cy) = (
+            self.cursor.0.clamp(0.0, img_w),
+            self.cursor.1.clamp(0.0, img_h),
+        );
+        let (ymin, ymax) = (ay.min(cy).round() as u32, ay.max(cy).round() as u32);
+        Face {
+            xmin: ax.min(cx).round() as u32,
+            xmax: ax.max(cx).round() as u32,
+            ymin,
+            ymax,
+            // the eye line of a resized face is kept while it is still within the face
+            eye_y: eye_y.filter(|y| (ymin..=ymax).contains(y)),
+        }
+    }
+
+    fn is_large_enough(&self) -> bool {
+        (self.anchor.0 - self.cursor.0).abs() >= MIN_FACE_SIZE
+            && (self.anchor.1 - self.cursor.1).abs() >= MIN_FACE_SIZE
+    }
+}
+
+/// the faces with the box being drawn or resized, boxes that are too small are left out
+fn edited_faces(faces: &[Face], drag: &FaceDrag, image_dimensions: (f64, f64)) -> Vec<Face> {
+    let mut faces = faces.to_vec();
+    match drag.index {
+        Some(idx) if drag.is_large_enough() => {
+            faces[idx] = drag.face(image_dimensions, faces[idx].eye_y);
+        }
+        Some(_) => {}
+        None if drag.is_large_enough() => faces.push(drag.face(image_dimensions, None)),
+        None => {}
+    }
+    faces
+}
+
+/// edits the faces of the current wallpaper: dragging on the image draws a new face, dragging
+/// the corner of a face resizes it and the cross deletes it, the default crops are recomputed
+/// from the edited faces
+#[component]
+pub fn FaceEditor(
+    faces: Vec<Face>,
+    dimensions: (f64, f64),
+    image_dimensions: (f64, f64),
+    color: String,
+    wallpapers: Signal<Wallpapers>,
+) -> Element {
+    let mut drag = use_signal(|| None::<FaceDrag>);
+
+    let (img_w, img_h) = image_dimensions;
+    let (final_w, final_h) = dimensions;
+    // from the coordinates of the overlay to the coordinates of the image
+    let to_image = move |(x, y): (f64, f64)| (x / final_w * img_w, y / final_h * img_h);
+
+    let shown = drag().map_or_else(
+        || faces.clone(),
+        |drag| edited_faces(&faces, &drag, image_dimensions),
+    );
+    // the boxes don't take the events while dragging, so the coordinates stay relative to the
+    // overlay
+    let is_dragging = drag().is_some();
+
+    let finish = {
+        let faces = faces.clone();
+        move || {
+            if let Some(drag) = drag() {
+                let edited = edited_faces(&faces, &drag, image_dimensions);
+                if edited != faces {
+                    wallpapers.with_mut(|wallpapers| wallpapers.edit_faces(edited));
+                }
+            }
+            drag.set(None);
+        }
+    };
+    let mut finish_up = finish.clone();
+    let mut finish_leave = finish;
+
+    rsx! {
+        div {
+            class: "absolute inset-0 cursor-crosshair",
+            onmousedown: move |evt| {
+                if evt.trigger_button() == Some(MouseButton::Primary) {
+                    let point = to_image(evt.element_coordinates().into());
+                    drag.set(Some(FaceDrag { index: None, anchor: point, cursor: point }));
+                }
+            },
+            onmousemove: move |evt| {
+                if let Some(current) = drag() {
+                    if evt.held_buttons().contains(MouseButton::Primary) {
+                        let cursor = to_image(evt.element_coordinates().into());
+                        drag.set(Some(FaceDrag { cursor, ..current }));
+                    }
+                }
+            },
+            onmouseup: move |_| finish_up(),
+            onmouseleave: move |_| finish_leave(),
+
+            {shown.iter().enumerate().map(|(idx, face)| {
+                let left = f64::from(face.xmin) / img_w * 100.0;
+                let top = f64::from(face.ymin) / img_h * 100.0;
+                let w = f64::from(face.xmax - face.xmin) / img_w * 100.0;
+                let h = f64::from(face.ymax - face.ymin) / img_h * 100.0;
+                // the corner opposite of the handle stays in place while resizing
+                let anchor = (f64::from(face.xmin), f64::from(face.ymin));
+                let cursor = (f64::from(face.xmax), f64::from(face.ymax));
+
+                rsx! {
+                    div {
+                        key: "{idx}",
+                        class: "absolute border-2 pointer-events-none",
+                        style: "top: {top}%; left: {left}%; width: {w}%; height: {h}%; border-color: {color};",
+
+                        if !is_dragging {
+                            div {
+                                class: "absolute -top-3 -right-3 w-6 h-6 rounded-full flex items-center justify-center text-sm font-bold text-white cursor-pointer pointer-events-auto",
+                                style: "background-color: {color};",
+                                title: t("delete-face"),
+                                onmousedown: move |evt| evt.stop_propagation(),
+                                onclick: {
+                                    let faces = faces.clone();
+                                    move |evt: MouseEvent| {
+                                        evt.stop_propagation();
+                                        let mut faces = faces.clone();
+                                        faces.remove(idx);
+                                        wallpapers.with_mut(|wallpapers| wallpapers.edit_faces(faces));
+                                    }
+                                },
+                                "×"
+                            }
+                            div {
+                                class: "absolute -bottom-2 -right-2 w-4 h-4 cursor-nwse-resize pointer-events-auto",
+                                style: "background-color: {color};",
+                                onmousedown: move |evt| {
+                                    evt.stop_propagation();
+                                    drag.set(Some(FaceDrag { index: Some(idx), anchor, cursor }));
+                                },
+                            }
+                        }
+                    }
+                }
+            })}
+        }
+    }
+}
@@ -1,13 +1,55 @@
 #![allow(non_snake_case)]
+use std::path::PathBuf;
+
 use dioxus::prelude::*;
-use wallpaper_ui::filename;
+use wallpaper_ui::{
+    cache::{thumbnail, BrightnessCache, BRIGHTNESS_FILTERS, FILELIST_THUMBNAIL_SIZE},
+    filename,
+    i18n::t,
+};
 
-use crate::app_state::{PreviewMode, UiState, Wallpapers};
+use crate::{
+    app_state::{CsvCache, UiState, Wallpapers},
+    components::button::Button,
+};
 
 #[component]
-fn WallpaperFile(filename: String, bytes: u64, onclick: EventHandler<MouseEvent>) -> Element {
+fn WallpaperFile(
+    path: PathBuf,
+    filename: String,
+    bytes: u64,
+    onclick: EventHandler<MouseEvent>,
+) -> Element {
     let size_in_mb = format!("{:.2} MB", bytes as f64 / 1024.0 / 1024.0);
 
+    // thumbnails are rendered in the background and cached, so only new wallpapers are decoded
+    let thumb = use_resource(move || {
+        let path = path.clone();
+        async move {
+            tokio::task::spawn_blocking(move || thumbnail(&path, FILELIST_THUMBNAIL_SIZE).ok())
+                .await
+                .ok()
+                .flatten()
+                .map(|(thumb, _)| thumb.to_string_lossy().to_string())
+        }
+    });
+    let thumbnail_el = match thumb() {
+        Some(Some(thumb)) => rsx! {
+            img {
+                alt: "",
+                src: thumb,
+                class: "h-12 w-20 flex-none rounded object-cover bg-gray-800"
+            }
+        },
+        // still rendering
+        None => rsx! {
+            div { class: "h-12 w-20 flex-none rounded bg-gray-800 animate-pulse" }
+        },
+        Some(None) => rsx! {
+            div { class: "h-12 w-20 flex-none rounded bg-gray-800" }
+        },
+    };
+
     rsx! {
         li {
             class: "flex justify-between gap-x-6 py-5 cursor-pointer",
@@ -15,12 +57,7 @@ fn WallpaperFile(filename: String, bytes: u64, onclick: EventHandler<MouseEvent>
                 onclick.call(evt);
             },
             div { class: "flex min-w-0 gap-x-4",
-                // TODO: thumbnail of wallpaper?
-                // img {
-                //     alt: "",
-                //     src: "https://images.unsplash.com/photo-1494790108377-be9c29b29330?ixlib=rb-1.2.1&ixid=eyJhcHBfaWQiOjEyMDd9&auto=format&fit=facearea&facepad=2&w=256&h=256&q=80",
-                //     class: "h-12 w-12 flex-none rounded-full bg-gray-800"
-                // }
+                {thumbnail_el}
                 div { class: "min-w-0 flex-auto",
                     p { class: "text-sm font-semibold leading-6 text-white",
                         {filename}
@@ -47,25 +84,53 @@ pub fn FileList(
     wallpapers: Signal<Wallpapers>,
     ui: Signal<UiState>,
 ) -> Element {
+    let mut wallpapers_csv = use_context::<Signal<CsvCache>>();
     let mut search = use_signal(String::new);
     let normalized = search().to_lowercase();
+    let mut brightness = use_signal(|| "all".to_string());
+
+    // the luminance of the wallpapers is computed in the background the first time the filter
+    // is used, then read from the cache
+    let luminances = use_resource(move || {
+        let files = wallpapers.peek().files.to_vec();
+        let filter = brightness();
+        async move {
+            if filter == "all" {
+                return None;
+            }
+            tokio::task::spawn_blocking(move || {
+                let mut cache = BrightnessCache::load();
+                if cache.update(&files) {
+                    cache.save().ok();
+                }
+                cache
+            })
+            .await
+            .ok()
+        }
+    });
+    let luminances = luminances.read();
+    let luminances = luminances.as_ref().and_then(Option::as_ref);
 
     let wallpaper_files = wallpapers().files;
     let images = wallpaper_files.iter().filter_map(|path| {
+        if brightness() != "all"
+            && !luminances.is_some_and(|cache| cache.matches(path, &brightness()))
+        {
+            return None;
+        }
+
         let fname = filename(path);
-        let size = path
-            .metadata()
-            .unwrap_or_else(|_| panic!("could not get file size for {fname}"))
-            .len();
+        let size = path.metadata().map_or(0, |meta| meta.len());
 
         if search().is_empty() {
             // TODO: add number of faces?
-            return Some((fname, size));
+            return Some((path.clone(), fname, size));
         }
 
         if fname.to_lowercase().contains(&normalized) {
             // TODO: add number of faces?
-            Some((fname, size))
+            Some((path.clone(), fname, size))
         } else {
             None
         }
@@ -81,7 +146,7 @@ pub fn FileList(
                 div { class: "flex rounded-md bg-white/5 ring-1 ring-inset ring-white/10 focus-within:ring-2 focus-within:ring-inset focus-within:ring-indigo-500",
                     input {
                         r#type: "text",
-                        placeholder: " Search",
+                        placeholder: format!(" {}", t("search")),
                         name: "search_wallpapers",
                         class: "flex-1 border-0 bg-transparent py-1.5 pl-1 text-white focus:ring-0 sm:text-sm sm:leading-6",
                         id: "search_wallpapers",
@@ -93,20 +158,41 @@ pub fn FileList(
                 }
             }
 
+            // brightness filter
+            span { class: "isolate inline-flex rounded-md shadow-sm mx-2",
+                for (i, filter) in BRIGHTNESS_FILTERS.iter().enumerate() {
+                    Button {
+                        class: match i {
+                            0 => "flex-1 rounded-l-md",
+                            _ if i == BRIGHTNESS_FILTERS.len() - 1 => "-ml-px flex-1 rounded-r-md",
+                            _ => "-ml-px flex-1",
+                        },
+                        active: brightness() == *filter,
+                        onclick: move |_| brightness.set((*filter).to_string()),
+                        {t(filter)}
+                    }
+                }
+            }
+
             ul {
                 role: "list",
                 class: "divide-y divide-gray-800 overflow-y-auto mx-2",
                 // HACK: render only the first 50 matches since there is no virtualized list
-                for (fname, bytes) in images.take(50) {
+                for (path, fname, bytes) in images.take(50) {
                     WallpaperFile {
+                        key: "{fname}",
+                        path,
                         filename: fname.clone(),
                         bytes,
                         onclick: move |_| {
-                            wallpapers.with_mut(|wallpapers| {
-                                wallpapers.set_from_filename(&fname);
-                            });
+                            let res = wallpapers
+                                .with_mut(|wallpapers| {
+                                    wallpapers.set_from_filename(&fname, &mut wallpapers_csv.write())
+                                });
                             ui.with_mut(|ui| {
-                                ui.preview_mode = PreviewMode::Candidate(None);
+                                ui.reset_preview_mode();
+                                ui.warnings.clear();
+                                ui.error = res.err().map(|e| e.to_string());
                                 ui.toggle_filelist();
                             });
                         },
@@ -0,0 +1,19 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+use wallpaper_ui::i18n::t;
+
+/// shown instead of the editor or the reviews if they can't be loaded, e.g. for an invalid config
+#[component]
+pub fn LoadError(theme: String, error: String) -> Element {
+    rsx! {
+        main {
+            class: "dark {theme} flex items-center h-full justify-center bg-base overflow-hidden",
+            div { class: "text-center",
+                h1 { class: "mt-4 text-4xl font-bold tracking-tight text-text",
+                    {t("load-error")}
+                }
+                p { class: "mt-4 text-red-400 whitespace-pre-line", "{error}" }
+            }
+        }
+    }
+}
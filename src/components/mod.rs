@@ -2,11 +2,19 @@ pub mod align_selector;
 pub mod app_header;
 pub mod button;
 pub mod candidates;
+pub mod command_menu;
+pub mod crop_review;
 pub mod drag_overlay;
 pub mod dropdown;
 pub mod editor;
+pub mod face_editor;
 pub mod filelist;
+pub mod load_error;
 pub mod palette;
 pub mod preview;
 pub mod ratio_selector;
+pub mod resize_overlay;
+pub mod schedule_selector;
 pub mod slider;
+pub mod tag_editor;
+pub mod upscale_review;
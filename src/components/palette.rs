@@ -1,11 +1,18 @@
 #![allow(non_snake_case)]
 use clap::Parser;
 use dioxus::prelude::*;
-use wallpaper_ui::config::WallpaperConfig;
+use std::path::Path;
+use wallpaper_ui::{
+    colorscheme::{self, PaletteOptions},
+    config::WallpaperConfig,
+    i18n::t,
+    jobs::{Job, Scheduler},
+};
 
 use crate::{
-    app_state::Wallpapers,
+    app_state::{CsvCache, UiState, Wallpapers},
     components::{
+        app_header::load_config,
         button::Button,
         dropdown::{Dropdown, DropdownOptions},
         slider::Slider,
@@ -103,13 +110,11 @@ impl WallustConfig {
         new_args.trim().to_lowercase()
     }
 
-    fn preview(
-        &self,
-        img_filename: &str,
-    ) -> impl std::future::Future<Output = Result<async_process::ExitStatus, std::io::Error>> {
-        let img = WallpaperConfig::new().wallpapers_path.join(img_filename);
+    fn preview(&self, config: &WallpaperConfig, img_filename: &str) -> Job {
+        let img = config.wallpapers_path.join(img_filename);
 
-        async_process::Command::new("wallust")
+        let mut command = config.tools.get("wallust").command();
+        command
             .arg("run")
             .args([
                 "--quiet",
@@ -118,15 +123,73 @@ impl WallustConfig {
                 "--skip-templates",
             ])
             .args(self.to_args_str().split_whitespace())
-            .arg(img)
-            .status()
+            .arg(img);
+        Job::new(
+            "wallust",
+            format!("Previewing palette for {img_filename}"),
+            command,
+        )
+    }
+}
+
+/// the palette generated with the stored wallust arguments and the one with the edited arguments
+#[derive(Debug, Clone, PartialEq)]
+struct RegeneratedPalette {
+    stored: colorscheme::Palette,
+    new: colorscheme::Palette,
+    /// the edited wallust arguments, saved when the new palette is accepted
+    wallust: String,
+}
+
+impl RegeneratedPalette {
+    fn generate(img: &Path, stored_args: &str, new_args: String) -> Option<Self> {
+        let img = image::open(img).ok()?;
+        Some(Self {
+            stored: colorscheme::Palette::from_image(
+                &img,
+                PaletteOptions::from_wallust_args(stored_args),
+            )?,
+            new: colorscheme::Palette::from_image(
+                &img,
+                PaletteOptions::from_wallust_args(&new_args),
+            )?,
+            wallust: new_args,
+        })
     }
 }
 
 #[component]
-pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
+fn PaletteSwatches(name: String, palette: colorscheme::Palette) -> Element {
+    rsx! {
+        div {
+            class: "flex items-center gap-4",
+            span { class: "w-24 text-sm text-text", {name} }
+            div {
+                class: "flex flex-1 h-8 rounded-md overflow-hidden",
+                for hex in palette.colors.map(colorscheme::hex) {
+                    span {
+                        class: "flex-1",
+                        style: "background-color: {hex};",
+                        title: "{hex}",
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn Palette(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element {
     let mut conf = use_signal(|| WallustConfig::from_args_str(&wallpapers.read().source.wallust));
     let mut is_running = use_signal(|| false);
+    let mut regenerated = use_signal(|| None::<RegeneratedPalette>);
+    let mut is_regenerating = use_signal(|| false);
+    // wallust is killed if the palette is closed while it is running
+    let scheduler = use_signal(|| Scheduler::new(1));
+    use_drop({
+        let scheduler = scheduler.peek().clone();
+        move || scheduler.cancel()
+    });
     let preview_cls = if is_running() {
         "!bg-surface0"
     } else {
@@ -164,11 +227,43 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
     ])
     .to_label(|v| v.to_string().replace("16", ""));
 
+    // the stored and the new palette side by side, the new wallust arguments are only saved when
+    // accepted
+    let comparison = regenerated().and_then(|palette| {
+        rsx! {
+            PaletteSwatches { name: t("stored-palette"), palette: palette.stored }
+            PaletteSwatches { name: t("new-palette"), palette: palette.new }
+
+            div {
+                class: "flex gap-4",
+                Button {
+                    class: "rounded-md px-5 py-2 flex-1 text-sm font-semibold justify-center text-white shadow-sm !bg-indigo-600 hover:bg-indigo-500 cursor-pointer",
+                    onclick: move |_| {
+                        let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+                        let res = wallpapers.with_mut(|wallpapers| {
+                            wallpapers.save_wallust(&palette.wallust, &mut wallpapers_csv.write())
+                        });
+                        ui.with_mut(|ui| {
+                            ui.error = res.err().map(|e| e.to_string());
+                        });
+                        regenerated.set(None);
+                    },
+                    {t("accept-palette")}
+                }
+                Button {
+                    class: "rounded-md px-5 py-2 flex-1 text-sm font-semibold justify-center text-white shadow-sm cursor-pointer",
+                    onclick: move |_| regenerated.set(None),
+                    {t("reject-palette")}
+                }
+            }
+        }
+    });
+
     rsx! {
         div {
             class: "flex flex-wrap w-full gap-y-6",
             Dropdown {
-                name: "Palette",
+                name: t("palette"),
                 class: "w-1/2 py-4 px-8",
                 options: palettes,
                 value: conf.read().palette,
@@ -180,7 +275,7 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
             }
 
             Dropdown {
-                name: "Backend",
+                name: t("backend"),
                 class: "w-1/2 py-4 px-8",
                 options: backend,
                 value: conf.read().backend,
@@ -192,7 +287,7 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
             }
 
             Dropdown {
-                name: "Colorspace",
+                name: t("colorspace"),
                 class: "w-1/2 py-4 px-8",
                 options: colorspace,
                 value: conf.read().colorspace,
@@ -204,7 +299,7 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
             }
 
             Dropdown {
-                name: "Fallback Generator",
+                name: t("fallback-generator"),
                 class: "w-1/2 py-4 px-8",
                 options: fallback_generator,
                 value: conf.read().fallback_generator,
@@ -216,7 +311,7 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
             }
 
             Slider {
-                name: "Saturation",
+                name: t("saturation"),
                 class: "w-1/2 py-4 px-8",
                 value: conf.read().saturation.unwrap_or_default(),
                 onchange: move |new_value| {
@@ -231,7 +326,7 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
             }
 
             Slider {
-                name: "Threshold",
+                name: t("threshold"),
                 class: "w-1/2 py-4 px-8",
                 value: conf.read().threshold.unwrap_or_default(),
                 onchange: move |new_value| {
@@ -251,13 +346,18 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
                     class: "rounded-md px-5 py-2 w-full text-sm font-semibold justify-center text-white shadow-sm !bg-indigo-600 hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600 cursor-pointer",
                     onclick: move |_| {
                         conf.set(WallustConfig::from_args_str(&wallpapers.read().source.wallust));
+                        let Some(config) = load_config(&mut ui) else {
+                            return;
+                        };
                         spawn(async move {
                             is_running.set(true);
-                            let _ = conf.read().preview(&wallpapers.read().current.filename).await;
+                            let job = conf.read().preview(&config, &wallpapers.read().current.filename);
+                            let scheduler = scheduler.peek().clone();
+                            let _ = scheduler.run(job).await;
                             is_running.set(false);
                         });
                     },
-                    "Reset"
+                    {t("reset")}
                 }
             }
 
@@ -267,14 +367,54 @@ pub fn Palette(wallpapers: Signal<Wallpapers>) -> Element {
                     spin: Some(is_running()),
                     class: "rounded-md px-5 py-2 w-full text-sm font-semibold justify-center text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600 cursor-pointer {preview_cls}",
                     onclick: move |_| {
+                        let Some(config) = load_config(&mut ui) else {
+                            return;
+                        };
                         spawn(async move {
                             is_running.set(true);
-                            let _ = conf.read().preview(&wallpapers.read().current.filename).await;
+                            let job = conf.read().preview(&config, &wallpapers.read().current.filename);
+                            let scheduler = scheduler.peek().clone();
+                            let _ = scheduler.run(job).await;
                             is_running.set(false);
                         });
                     },
-                    "Preview"
+                    {t("preview")}
+                }
+            }
+
+            div {
+                class: "w-full py-4 px-8 flex flex-col gap-4",
+                Button {
+                    spin: Some(is_regenerating()),
+                    class: "rounded-md px-5 py-2 w-full text-sm font-semibold justify-center text-white shadow-sm !bg-indigo-600 hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600 cursor-pointer",
+                    onclick: move |_| {
+                        let Some(config) = load_config(&mut ui) else {
+                            return;
+                        };
+                        let img = config.wallpapers_path.join(&wallpapers.read().current.filename);
+                        let stored_args = wallpapers.read().source.wallust.clone();
+                        let new_args = conf.read().to_args_str();
+                        spawn(async move {
+                            is_regenerating.set(true);
+                            let palette = tokio::task::spawn_blocking(move || {
+                                RegeneratedPalette::generate(&img, &stored_args, new_args)
+                            })
+                            .await
+                            .ok()
+                            .flatten();
+                            ui.with_mut(|ui| {
+                                ui.error = palette
+                                    .is_none()
+                                    .then(|| "could not generate a palette".to_string());
+                            });
+                            regenerated.set(palette);
+                            is_regenerating.set(false);
+                        });
+                    },
+                    {t("regenerate-palette")}
                 }
+
+                {comparison}
             }
         }
     }
@@ -1,17 +1,34 @@
 #![allow(non_snake_case)]
-use std::path::PathBuf;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use dioxus::prelude::*;
 use dioxus_sdk::utils::window::{use_window_size, WindowSize};
-use wallpaper_ui::{cropper::Direction, wallpapers::Face};
+use wallpaper_ui::{
+    cropper::Direction,
+    filename,
+    image_ops::{contrast_color, preview_path, preview_size},
+    wallpapers::Face,
+};
 
 use crate::{
     app_state::{PreviewMode, UiState, Wallpapers},
-    components::drag_overlay::DragOverlay,
+    components::{
+        drag_overlay::DragOverlay, face_editor::FaceEditor, resize_overlay::ResizeOverlay,
+    },
 };
 
 #[component]
-fn FacesOverlay(faces: Vec<Face>, image_dimensions: (f64, f64)) -> Element {
+fn FacesOverlay(
+    faces: Vec<Face>,
+    image_dimensions: (f64, f64),
+    color: String,
+    high_contrast: bool,
+) -> Element {
     if faces.is_empty() {
         return None;
     }
@@ -28,23 +45,25 @@ fn FacesOverlay(faces: Vec<Face>, image_dimensions: (f64, f64)) -> Element {
 
             rsx! {
                 div {
-                    class: "absolute border-2 border-red-500",
-                    style: format!("top: {start_y}%; left: {start_x}%; width: {w}%; height: {h}%;"),
+                    class: "absolute",
+                    class: if high_contrast { "border-4" } else { "border-2" },
+                    style: format!("top: {start_y}%; left: {start_x}%; width: {w}%; height: {h}%; border-color: {color};"),
                 }
             }
         })}
     }
 }
 
-/// fit the image within the max preview area
+/// fit the image within the max preview area, the margins grow with the scale of the ui
 fn get_preview_size(
     min_y: f64,
     win_size: WindowSize,
     img: (f64, f64),
     has_candidates: bool,
+    scale: f64,
 ) -> (f64, f64) {
-    let margin: f64 = 16.0;
-    let candidate_btns: f64 = 36.0;
+    let margin: f64 = 16.0 * scale;
+    let candidate_btns: f64 = 36.0 * scale;
 
     let max_w = margin.mul_add(-2.0, f64::from(win_size.width));
     // handle extra space for candidate buttons
@@ -68,6 +87,136 @@ fn get_preview_size(
     (final_w, final_h)
 }
 
+/// ctrl + mouse wheel zooms the preview, fit_zoom is the zoom in percent at which it fits the
+/// window
+fn zoom_on_wheel(evt: &WheelEvent, ui: &mut Signal<UiState>, fit_zoom: f64) {
+    if evt.modifiers().ctrl() {
+        let zoom_in = evt.delta().strip_units().y < 0.0;
+        ui.with_mut(|ui| ui.zoom_by(zoom_in, fit_zoom));
+    }
+}
+
+/// image and preview size of a preview
+type PreviewKey = (PathBuf, Option<u32>);
+
+/// previews are rendered off the ui thread, so navigating never waits for images to be decoded
+#[derive(Clone)]
+struct PreviewLoader {
+    previews: Signal<HashMap<PreviewKey, String>>,
+    /// previews being rendered, not a signal since it doesn't change what is shown
+    pending: Rc<RefCell<HashSet<PreviewKey>>>,
+    /// colors standing out against each image, for the overlays with an auto color
+    contrast_colors: Signal<HashMap<PathBuf, String>>,
+    pending_colors: Rc<RefCell<HashSet<PathBuf>>>,
+}
+
+impl PreviewLoader {
+    fn use_loader() -> Self {
+        Self {
+            previews: use_signal(HashMap::new),
+            pending: use_hook(|| Rc::new(RefCell::new(HashSet::new()))),
+            contrast_colors: use_signal(HashMap::new),
+            pending_colors: use_hook(|| Rc::new(RefCell::new(HashSet::new()))),
+        }
+    }
+
+    /// color standing out against the image if it has been computed, otherwise it is computed
+    /// in the background from the smallest preview
+    fn contrast_color(&self, image: &Path) -> Option<String> {
+        if let Some(color) = self.contrast_colors.read().get(image).cloned() {
+            return Some(color);
+        }
+
+        if self.pending_colors.borrow_mut().insert(image.to_path_buf()) {
+            let mut contrast_colors = self.contrast_colors;
+            let image = image.to_path_buf();
+            spawn(async move {
+                let path = image.clone();
+                let color = tokio::task::spawn_blocking(move || {
+                    image::open(preview_path(&path, preview_size(0.0)))
+                        .ok()
+                        .map(|img| contrast_color(&img))
+                })
+                .await;
+                if let Ok(Some(color)) = color {
+                    contrast_colors.with_mut(|colors| {
+                        colors.insert(image, color);
+                    });
+                }
+            });
+        }
+
+        None
+    }
+
+    /// the overlay color, auto is resolved to the color standing out against the image and the
+    /// fallback is used until it is computed
+    fn overlay_color(&self, color: &str, image: &Path, fallback: &str) -> String {
+        if color == "auto" {
+            self.contrast_color(image)
+                .unwrap_or_else(|| fallback.to_string())
+        } else {
+            color.to_string()
+        }
+    }
+
+    /// path of the preview if it has been rendered, otherwise it is rendered in the background
+    fn get(&self, key: &PreviewKey) -> Option<String> {
+        // reading subscribes to the previews, so the preview is shown once it is rendered
+        if let Some(src) = self.previews.read().get(key).cloned() {
+            return Some(src);
+        }
+
+        if self.pending.borrow_mut().insert(key.clone()) {
+            let mut previews = self.previews;
+            let key = key.clone();
+            spawn(async move {
+                let (image, max_size) = key.clone();
+                let path = tokio::task::spawn_blocking(move || preview_path(&image, max_size))
+                    .await
+                    .unwrap_or_else(|_| key.0.clone());
+                previews.with_mut(|previews| {
+                    previews.insert(key, path.to_string_lossy().to_string());
+                });
+            });
+        }
+
+        None
+    }
+
+    /// the preview of the image, or a placeholder while it is rendered, the neighbours are
+    /// loaded as hidden images so the webview has already decoded them
+    fn render(
+        &self,
+        image: PathBuf,
+        neighbours: Vec<PathBuf>,
+        (preview_w, preview_h): (f64, f64),
+        class: &str,
+    ) -> Element {
+        // twice the pixels for hidpi displays
+        let max_size = preview_size(preview_w.max(preview_h) * 2.0);
+
+        match self.get(&(image, max_size)) {
+            Some(src) => {
+                let prefetched: Vec<_> = neighbours
+                    .into_iter()
+                    .filter_map(|neighbour| self.get(&(neighbour, max_size)))
+                    .collect();
+
+                rsx! {
+                    img { class: "{class}", src: src }
+                    for src in prefetched {
+                        img { class: "hidden", src: src }
+                    }
+                }
+            }
+            None => rsx! {
+                div { class: "absolute inset-0 animate-pulse bg-surface0" }
+            },
+        }
+    }
+}
+
 #[component]
 pub fn Previewer(
     wallpapers: Signal<Wallpapers>,
@@ -76,92 +225,219 @@ pub fn Previewer(
 ) -> Element {
     // store y coordinate of the previewer
     let mut preview_y = use_signal(|| 0.0);
-    let info = wallpapers().current;
+    let loader = PreviewLoader::use_loader();
+    // cloning the queue only clones the Rc of the files and wallpaper info
+    let walls = wallpapers();
+    let info = Rc::clone(&walls.current);
+    let mut ui_signal = ui;
     let ui = ui();
 
-    let path = wallpapers_path.join(&info.filename);
-    let path = path
-        .to_str()
-        .unwrap_or_else(|| panic!("could not convert {path:?} to str"))
-        .to_string();
+    let image = wallpapers_path.join(&info.filename);
+    // the previous and next wallpapers are rendered ahead of time
+    let neighbours: Vec<_> = [
+        (walls.index + walls.files.len() - 1) % walls.files.len(),
+        (walls.index + 1) % walls.files.len(),
+    ]
+    .iter()
+    .map(|idx| wallpapers_path.join(filename(&walls.files[*idx])))
+    .collect();
 
     let is_manual = matches!(ui.preview_mode, PreviewMode::Pan);
-    let overlay_cls = "absolute bg-black bg-opacity-60 w-full h-full";
+    let overlay_cls = "absolute w-full h-full";
+    let dim = f64::from(ui.overlay_opacity) / 100.0;
+    let face_color = loader.overlay_color(&ui.face_color, &image, "#ef4444");
+    let border_color = loader.overlay_color(&ui.crop_border_color, &image, "#ffffff");
+    let border_width = ui.crop_border_width;
 
     // preview geometry takes precedence
     let geom = if let PreviewMode::Candidate(Some(mouseover_geom)) = ui.preview_mode {
         mouseover_geom
     } else {
-        wallpapers().get_geometry()
+        walls.get_geometry()
     };
 
-    let (direction, start_ratio, end_ratio) = info.overlay_transforms(&geom);
-
     let img_w = f64::from(info.width);
     let img_h = f64::from(info.height);
+
+    // padded crops show the entire image within a canvas of the target ratio
+    if let (Some((canvas_w, canvas_h, offset_x, offset_y)), Some(padding)) =
+        (geom.padded_canvas(&walls.ratio), geom.padding.as_ref())
+    {
+        let (out_w, out_h) = geom.output_size();
+        let (canvas_w, canvas_h) = (f64::from(canvas_w), f64::from(canvas_h));
+        let left = f64::from(offset_x) / canvas_w * 100.0;
+        let top = f64::from(offset_y) / canvas_h * 100.0;
+        let w = f64::from(out_w) / canvas_w * 100.0;
+        let h = f64::from(out_h) / canvas_h * 100.0;
+
+        let (preview_w, preview_h) = get_preview_size(
+            preview_y(),
+            use_window_size()(),
+            (canvas_w, canvas_h),
+            false,
+            ui.scale(),
+        );
+        let fit_zoom = preview_w * w / img_w;
+        let zoom = ui.zoom_factor(fit_zoom);
+        let (zoomed_w, zoomed_h) = (preview_w * zoom, preview_h * zoom);
+        let preview = loader.render(image, neighbours, (zoomed_w, zoomed_h), "w-full h-full");
+
+        return rsx! {
+            div {
+                class: "relative m-auto overflow-auto",
+                style: "width: {preview_w}px; height: {preview_h}px;",
+                onmounted: move |evt| {
+                    async move {
+                        let coords = evt.get_client_rect().await.expect("could not get client rect");
+                        preview_y.set(coords.min_y());
+                    }
+                },
+                onwheel: move |evt| zoom_on_wheel(&evt, &mut ui_signal, fit_zoom),
+                div {
+                    class: "relative",
+                    style: "width: {zoomed_w}px; height: {zoomed_h}px; background-color: {padding.color};",
+                    div {
+                        class: "absolute",
+                        style: "left: {left}%; top: {top}%; width: {w}%; height: {h}%;",
+                        {preview}
+
+                        if ui.face_edit {
+                            FaceEditor {
+                                faces: info.faces.clone(),
+                                dimensions: (zoomed_w * w / 100.0, zoomed_h * h / 100.0),
+                                image_dimensions: (img_w, img_h),
+                                color: face_color,
+                                wallpapers,
+                            }
+                        } else if ui.show_faces {
+                            FacesOverlay {
+                                faces: info.faces.clone(),
+                                image_dimensions: (img_w, img_h),
+                                color: face_color,
+                                high_contrast: ui.high_contrast,
+                            }
+                        }
+                    }
+                }
+            }
+        };
+    }
+
+    let (direction, start_ratio, end_ratio) = info.overlay_transforms(&geom);
     let start_cls = match direction {
         Direction::X => "origin-left top-0 left-0",
         Direction::Y => "origin-top top-0 left-0",
+        Direction::Both => "",
     };
 
     let end_cls = match direction {
         Direction::X => "origin-right top-0 right-0",
         Direction::Y => "origin-bottom bottom-0 left-0",
+        Direction::Both => "",
     };
+    let (box_left, box_top, box_w, box_h) = info.crop_box(&geom);
 
     // get preview size of the image
     let (preview_w, preview_h) = get_preview_size(
         preview_y(),
         use_window_size()(),
         (img_w, img_h),
-        wallpapers().crop_candidates().len() > 1,
+        walls.candidate_geometries().len() > 1,
+        ui.scale(),
     );
+    let fit_zoom = preview_w / img_w * 100.0;
+    let zoom = ui.zoom_factor(fit_zoom);
+    let (zoomed_w, zoomed_h) = (preview_w * zoom, preview_h * zoom);
+    let preview = loader.render(image, neighbours, (zoomed_w, zoomed_h), "w-full h-full");
 
     rsx! {
         div {
-            class: "relative m-auto",
+            class: "relative m-auto overflow-auto",
             style: "width: {preview_w}px; height: {preview_h}px;",
-            img {
-                src: path,
-                // store the final rendered width and height of the image
-                onmounted: move |evt| {
-                    async move {
-                        let coords = evt.get_client_rect().await.expect("could not get client rect");
-                        // store the y coordinate of the previewer, the rest can be calculated from there
-                        preview_y.set(coords.min_y());
-                    }
-                },
-            }
-            div {
-                class: overlay_cls,
-                class: start_cls,
-                // don't apply transitions in manual mode
-                class: if !is_manual { "transition transition-transform ease-linear" },
-                style: format!("transform: scale{}({})", direction, start_ratio),
-            }
+            // the image may not be loaded yet, so the position is from the container
+            onmounted: move |evt| {
+                async move {
+                    let coords = evt.get_client_rect().await.expect("could not get client rect");
+                    // store the y coordinate of the previewer, the rest can be calculated from there
+                    preview_y.set(coords.min_y());
+                }
+            },
+            onwheel: move |evt| zoom_on_wheel(&evt, &mut ui_signal, fit_zoom),
+            // the overlays are positioned in percent of the zoomed image, so they zoom with it
             div {
-                class: overlay_cls,
-                class: end_cls,
-                // don't apply transitions in manual mode
-                class: if !is_manual { "transition" },
-                style: format!("transform: scale{}({})", direction, end_ratio),
-            }
+                class: "relative",
+                style: "width: {zoomed_w}px; height: {zoomed_h}px;",
+                {preview}
+                if direction == Direction::Both {
+                    // crops free in both axes darken everything outside of the crop box
+                    div {
+                        class: "absolute inset-0 overflow-hidden",
+                        div {
+                            class: "absolute",
+                            // don't apply transitions in manual mode
+                            class: if !is_manual { "transition-all ease-linear" },
+                            style: "left: {box_left}%; top: {box_top}%; width: {box_w}%; height: {box_h}%; box-shadow: 0 0 0 9999px rgb(0 0 0 / {dim});",
+                        }
+                    }
+                } else {
+                    div {
+                        class: overlay_cls,
+                        class: start_cls,
+                        // don't apply transitions in manual mode
+                        class: if !is_manual { "transition transition-transform ease-linear" },
+                        style: format!("transform: scale{}({}); background-color: rgb(0 0 0 / {dim});", direction, start_ratio),
+                    }
+                    div {
+                        class: overlay_cls,
+                        class: end_cls,
+                        // don't apply transitions in manual mode
+                        class: if !is_manual { "transition" },
+                        style: format!("transform: scale{}({}); background-color: rgb(0 0 0 / {dim});", direction, end_ratio),
+                    }
+                }
 
-            if is_manual {
-                DragOverlay {
-                    dimensions: (preview_w, preview_h),
-                    image_dimensions: (img_w, img_h),
-                    overlay_ratios: (start_ratio, 1.0 - end_ratio),
-                    direction,
-                    geometry: geom,
-                    wallpapers,
+                if border_width > 0 {
+                    div {
+                        class: "absolute pointer-events-none",
+                        // don't apply transitions in manual mode
+                        class: if !is_manual { "transition-all ease-linear" },
+                        style: "left: {box_left}%; top: {box_top}%; width: {box_w}%; height: {box_h}%; box-shadow: inset 0 0 0 {border_width}px {border_color};",
+                    }
+                }
+
+                if ui.face_edit {
+                    FaceEditor {
+                        faces: info.faces.clone(),
+                        dimensions: (zoomed_w, zoomed_h),
+                        image_dimensions: (img_w, img_h),
+                        color: face_color.clone(),
+                        wallpapers,
+                    }
+                } else if is_manual {
+                    DragOverlay {
+                        dimensions: (zoomed_w, zoomed_h),
+                        image_dimensions: (img_w, img_h),
+                        overlay_ratios: (start_ratio, 1.0 - end_ratio),
+                        direction,
+                        geometry: geom.clone(),
+                        wallpapers,
+                    }
+                    ResizeOverlay {
+                        dimensions: (zoomed_w, zoomed_h),
+                        image_dimensions: (img_w, img_h),
+                        aspect_ratio: walls.ratio.clone(),
+                        geometry: geom,
+                        wallpapers,
+                    }
                 }
-            }
 
-            if ui.show_faces {
-                FacesOverlay {
-                    faces: info.faces,
-                    image_dimensions: (img_w, img_h),
+                if ui.show_faces && !ui.face_edit {
+                    FacesOverlay {
+                        faces: info.faces.clone(),
+                        image_dimensions: (img_w, img_h),
+                        color: face_color,
+                        high_contrast: ui.high_contrast,
+                    }
                 }
             }
         }
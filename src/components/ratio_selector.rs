@@ -1,12 +1,59 @@
 #![allow(non_snake_case)]
 
 use dioxus::prelude::*;
+use wallpaper_ui::{aspect_ratio::AspectRatio, i18n::t};
 
 use crate::{
-    app_state::{UiState, Wallpapers},
+    app_state::{CsvCache, UiState, Wallpapers},
     components::button::Button,
 };
 
+/// crops for a ratio that isn't in the config, typing it and pressing enter adds it to the config
+/// like a new monitor
+#[component]
+fn CustomRatio(wallpapers: Signal<Wallpapers>, ui: Signal<UiState>) -> Element {
+    let mut typed = use_signal(String::new);
+
+    rsx! {
+        input {
+            r#type: "text",
+            class: "w-24 rounded-md border-0 bg-white/5 py-1.5 px-2 text-sm text-white ring-1 ring-inset ring-white/10 focus:ring-2 focus:ring-inset focus:ring-indigo-500",
+            title: t("custom-ratio"),
+            placeholder: "WxH",
+            value: "{typed}",
+            oninput: move |evt| {
+                evt.stop_propagation();
+                typed.set(evt.value());
+            },
+            onkeydown: move |evt| {
+                // typing shouldn't trigger the editor shortcuts
+                evt.stop_propagation();
+                if evt.key() != Key::Enter {
+                    return;
+                }
+
+                let Ok(ratio) = AspectRatio::try_from(typed().as_str()) else {
+                    ui.with_mut(|ui| {
+                        ui.error = Some(format!("invalid ratio: {}, expected WxH, W:H or a decimal ratio", typed()));
+                    });
+                    return;
+                };
+
+                let mut wallpapers_csv = consume_context::<Signal<CsvCache>>();
+                let res = wallpapers.with_mut(|wallpapers| {
+                    wallpapers
+                        .add_config_resolution(&ratio.to_string(), &ratio, &mut wallpapers_csv.write())
+                        .map(|()| wallpapers.set_ratio(&ratio))
+                });
+                ui.with_mut(|ui| {
+                    ui.error = res.err().map(|e| format!("could not add the ratio {ratio}: {e}"));
+                });
+                typed.set(String::new());
+            },
+        }
+    }
+}
+
 #[component]
 pub fn RatioSelector(
     class: Option<String>,
@@ -35,14 +82,20 @@ pub fn RatioSelector(
         };
 
         let btn_text = format!("{}{}", res_name, dirty_marker);
+        // ratios that haven't been looked at yet are dimmed
+        let visited_cls = if walls.visited_ratios.contains(&res) {
+            ""
+        } else {
+            "opacity-60"
+        };
 
         rsx! {
             Button {
-                class: "text-sm {cls}",
+                class: "text-sm {cls} {visited_cls}",
                 active: is_active,
                 onclick: move |_|{
                     wallpapers.with_mut(|wallpapers| {
-                        wallpapers.ratio = res.clone();
+                        wallpapers.set_ratio(&res);
                     });
                 }
                 {btn_text}
@@ -51,9 +104,13 @@ pub fn RatioSelector(
     });
 
     rsx! {
-        span {
-            class: "isolate inline-flex rounded-md shadow-sm",
-            {buttons}
+        div {
+            class: "flex gap-x-4",
+            span {
+                class: "isolate inline-flex rounded-md shadow-sm",
+                {buttons}
+            }
+            CustomRatio { wallpapers, ui }
         }
     }
 }
@@ -0,0 +1,181 @@
+#![allow(non_snake_case)]
+use dioxus::{html::input_data::MouseButton, prelude::*};
+
+use wallpaper_ui::{aspect_ratio::AspectRatio, geometry::Geometry};
+
+use crate::app_state::Wallpapers;
+
+/// crops can't be resized smaller than this in pixels of the image
+const MIN_CROP_SIZE: f64 = 64.0;
+
+/// the edges moved by each handle, -1 for the left / top edge, 1 for the right / bottom edge and
+/// 0 if the handle doesn't move the axis
+const HANDLES: [(i8, i8); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+];
+
+const fn handle_cursor(handle: (i8, i8)) -> &'static str {
+    match handle {
+        (0, _) => "cursor-ns-resize",
+        (_, 0) => "cursor-ew-resize",
+        (-1, -1) | (1, 1) => "cursor-nwse-resize",
+        _ => "cursor-nesw-resize",
+    }
+}
+
+/// the crop resized by dragging the handle to the cursor, the opposite edges stay in place, or the
+/// center for an axis the handle doesn't move, and the width is limited so the crop stays within
+/// the image at the ratio
+fn resize(
+    geom: &Geometry,
+    (hx, hy): (i8, i8),
+    (cx, cy): (f64, f64),
+    ratio: f64,
+    (img_w, img_h): (f64, f64),
+) -> Geometry {
+    let (x0, y0) = (f64::from(geom.x), f64::from(geom.y));
+    let (x1, y1) = (x0 + f64::from(geom.w), y0 + f64::from(geom.h));
+    let (mid_x, mid_y) = (x0 + f64::from(geom.w) / 2.0, y0 + f64::from(geom.h) / 2.0);
+
+    // the width the cursor asks for along each axis the handle moves, corners use the larger one
+    let from_x = match hx {
+        1 => Some(cx - x0),
+        -1 => Some(x1 - cx),
+        _ => None,
+    };
+    let from_y = match hy {
+        1 => Some((cy - y0) * ratio),
+        -1 => Some((y1 - cy) * ratio),
+        _ => None,
+    };
+    let wanted = match (from_x, from_y) {
+        (Some(w_x), Some(w_y)) => w_x.max(w_y),
+        (Some(w), None) | (None, Some(w)) => w,
+        (None, None) => f64::from(geom.w),
+    };
+
+    let max_w = match hx {
+        1 => img_w - x0,
+        -1 => x1,
+        _ => 2.0 * mid_x.min(img_w - mid_x),
+    };
+    let max_h = match hy {
+        1 => img_h - y0,
+        -1 => y1,
+        _ => 2.0 * mid_y.min(img_h - mid_y),
+    };
+    let max_w = max_w.min(max_h * ratio);
+    let min_w = MIN_CROP_SIZE.max(MIN_CROP_SIZE * ratio).min(max_w);
+
+    let w = wanted.clamp(min_w, max_w);
+    let h = w / ratio;
+    let x = match hx {
+        1 => x0,
+        -1 => x1 - w,
+        _ => mid_x - w / 2.0,
+    }
+    .round()
+    .max(0.0);
+    let y = match hy {
+        1 => y0,
+        -1 => y1 - h,
+        _ => mid_y - h / 2.0,
+    }
+    .round()
+    .max(0.0);
+
+    Geometry {
+        w: w.round().min(img_w - x) as u32,
+        h: h.round().min(img_h - y) as u32,
+        x: x as u32,
+        y: y as u32,
+        ..geom.clone()
+    }
+}
+
+/// handles on the corners and edges of the crop that resize it at the aspect ratio, so the crop
+/// can be tighter than the full width or height of the image
+#[component]
+pub fn ResizeOverlay(
+    dimensions: (f64, f64),
+    image_dimensions: (f64, f64),
+    aspect_ratio: AspectRatio,
+    geometry: Geometry,
+    wallpapers: Signal<Wallpapers>,
+) -> Element {
+    let mut resizing = use_signal(|| None::<(i8, i8)>);
+
+    let (img_w, img_h) = image_dimensions;
+    let (final_w, final_h) = dimensions;
+    let ratio = f64::from(&aspect_ratio);
+
+    let left = f64::from(geometry.x) / img_w * 100.0;
+    let top = f64::from(geometry.y) / img_h * 100.0;
+    let w = f64::from(geometry.w) / img_w * 100.0;
+    let h = f64::from(geometry.h) / img_h * 100.0;
+
+    let handles = HANDLES.into_iter().map(|handle| {
+        let (hx, hy) = handle;
+        let handle_left = f64::from(hx + 1) * 50.0;
+        let handle_top = f64::from(hy + 1) * 50.0;
+        let cursor = handle_cursor(handle);
+
+        rsx! {
+            div {
+                class: "absolute w-3 h-3 -translate-x-1/2 -translate-y-1/2 rounded-sm bg-white ring-1 ring-black pointer-events-auto {cursor}",
+                style: "left: {handle_left}%; top: {handle_top}%;",
+                onmousedown: move |evt| {
+                    if evt.trigger_button() == Some(MouseButton::Primary) {
+                        // the handle is above the drag overlay, which would move the crop instead
+                        evt.stop_propagation();
+                        // the whole resize is a single undo step
+                        wallpapers.with_mut(Wallpapers::checkpoint);
+                        resizing.set(Some(handle));
+                    }
+                },
+            }
+        }
+    });
+
+    // takes the events while resizing, so the crop follows the cursor outside of the handle
+    let capture = resizing().and_then(|handle| {
+        let cursor = handle_cursor(handle);
+        rsx! {
+            div {
+                class: "absolute inset-0 {cursor}",
+                onmousemove: move |evt| {
+                    if !evt.held_buttons().contains(MouseButton::Primary) {
+                        resizing.set(None);
+                        return;
+                    }
+
+                    let (x, y): (f64, f64) = evt.element_coordinates().into();
+                    let cursor = (x / final_w * img_w, y / final_h * img_h);
+                    let new_geom = resize(&geometry, handle, cursor, ratio, image_dimensions);
+                    if new_geom != geometry {
+                        wallpapers.with_mut(|wallpapers| wallpapers.drag_geometry(&new_geom));
+                    }
+                },
+                onmouseup: move |_| resizing.set(None),
+                onmouseleave: move |_| resizing.set(None),
+            }
+        }
+    });
+
+    rsx! {
+        {capture}
+
+        div {
+            class: "absolute pointer-events-none",
+            style: "left: {left}%; top: {top}%; width: {w}%; height: {h}%;",
+            {handles}
+        }
+    }
+}
@@ -0,0 +1,51 @@
+#![allow(non_snake_case)]
+use dioxus::prelude::*;
+use wallpaper_ui::{
+    i18n::t,
+    schedule::{MONTHS, SEASONS, TIMES_OF_DAY, WEEKDAYS},
+};
+
+use crate::{app_state::Wallpapers, components::button::Button};
+
+/// rounds the ends of a group of buttons
+fn segment_class(i: usize, len: usize) -> &'static str {
+    match i {
+        0 => "rounded-l-md",
+        _ if i == len - 1 => "-ml-px rounded-r-md",
+        _ => "-ml-px",
+    }
+}
+
+/// toggles the times of day, weekdays, seasons and months the daemon shows the wallpaper in, it is
+/// always shown if none are selected
+#[component]
+pub fn ScheduleSelector(wallpapers: Signal<Wallpapers>) -> Element {
+    let schedule = wallpapers().current.schedule.clone();
+    let groups: Vec<Vec<&'static str>> = vec![
+        TIMES_OF_DAY.iter().map(|(name, _)| *name).collect(),
+        WEEKDAYS.to_vec(),
+        SEASONS.to_vec(),
+        MONTHS.to_vec(),
+    ];
+
+    rsx! {
+        div { class: "flex flex-wrap gap-4 items-center justify-center",
+            span { class: "text-sm font-semibold text-text", {t("schedule")} }
+
+            for group in groups {
+                span { class: "isolate inline-flex rounded-md shadow-sm",
+                    for (i, entry) in group.iter().copied().enumerate() {
+                        Button {
+                            class: "text-sm {segment_class(i, group.len())}",
+                            active: schedule.iter().any(|e| e == entry),
+                            onclick: move |_| {
+                                wallpapers.with_mut(|wallpapers| wallpapers.toggle_schedule(entry));
+                            },
+                            {t(entry)}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
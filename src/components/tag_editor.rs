@@ -0,0 +1,78 @@
+#![allow(non_snake_case)]
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use wallpaper_ui::i18n::t;
+
+use crate::app_state::{CsvCache, Wallpapers};
+
+/// tags of the current wallpaper, e.g. its series or artist, typing comma separated tags and
+/// pressing enter adds them, they are saved with the crops
+#[component]
+pub fn TagEditor(wallpapers: Signal<Wallpapers>) -> Element {
+    let mut typed = use_signal(String::new);
+    let wallpapers_csv = use_context::<Signal<CsvCache>>();
+    // the tags of all the wallpapers are suggested while typing, updated when wallpapers are
+    // saved or the csv is read again
+    let known_tags = use_memo(move || {
+        // subscribe to the csv, reading it doesn't rerun the memo
+        drop(wallpapers_csv.read());
+        let mut wallpapers_csv = wallpapers_csv.write_silent();
+        let tags: BTreeSet<String> = wallpapers_csv.csv().map_or_else(
+            |_| BTreeSet::new(),
+            |csv| {
+                csv.iter()
+                    .flat_map(|(_, info)| info.tags.iter().cloned())
+                    .collect()
+            },
+        );
+        tags
+    });
+    let tags = wallpapers().current.tags.clone();
+
+    rsx! {
+        div { class: "flex flex-wrap gap-2 items-center",
+            for tag in tags {
+                span {
+                    key: "{tag}",
+                    class: "inline-flex items-center gap-x-1 rounded-md bg-surface1 px-2 py-1 text-xs font-medium text-text",
+                    "{tag}"
+                    button {
+                        class: "text-subtext0 hover:text-text cursor-pointer",
+                        title: t("remove-tag"),
+                        onclick: {
+                            let tag = tag.clone();
+                            move |_| wallpapers.with_mut(|wallpapers| wallpapers.remove_tag(&tag))
+                        },
+                        "×"
+                    }
+                }
+            }
+
+            input {
+                r#type: "text",
+                class: "w-28 rounded-md border-0 bg-white/5 py-1 px-2 text-xs text-white ring-1 ring-inset ring-white/10 focus:ring-2 focus:ring-inset focus:ring-indigo-500",
+                list: "known_tags",
+                placeholder: t("add-tag"),
+                value: "{typed}",
+                oninput: move |evt| {
+                    evt.stop_propagation();
+                    typed.set(evt.value());
+                },
+                onkeydown: move |evt| {
+                    // typing shouldn't trigger the editor shortcuts
+                    evt.stop_propagation();
+                    if evt.key() == Key::Enter {
+                        wallpapers.with_mut(|wallpapers| wallpapers.add_tags(&typed()));
+                        typed.set(String::new());
+                    }
+                },
+            }
+            datalist { id: "known_tags",
+                for tag in known_tags() {
+                    option { value: "{tag}" }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+#![allow(non_snake_case)]
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use dioxus::prelude::*;
+use dioxus_free_icons::icons::md_navigation_icons::{MdChevronLeft, MdChevronRight};
+use dioxus_free_icons::Icon;
+use tracing::error;
+use wallpaper_ui::{
+    filename,
+    i18n::t,
+    upscale_review::{self, UpscaleReview},
+};
+
+use crate::components::{button::Button, load_error::LoadError};
+
+fn img_src(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// image at the size of the upscaled image, offset by the shared pan position
+#[component]
+fn ComparePanel(
+    label: String,
+    src: String,
+    width: u32,
+    offset: Signal<(f64, f64)>,
+    max_offset: (f64, f64),
+) -> Element {
+    let mut offset = offset;
+    let mut drag_coords = use_signal(|| None::<(f64, f64)>);
+    let (x, y) = offset();
+
+    rsx! {
+        div {
+            class: "relative flex-1 overflow-hidden cursor-move ring-1 ring-surface1",
+            onmousedown: move |evt| {
+                drag_coords.set(Some(evt.client_coordinates().into()));
+            },
+            onmouseup: move |_| {
+                drag_coords.set(None);
+            },
+            onmouseleave: move |_| {
+                drag_coords.set(None);
+            },
+            onmousemove: move |evt| {
+                if let Some((start_x, start_y)) = drag_coords() {
+                    let (new_x, new_y) = evt.client_coordinates().into();
+                    offset.with_mut(|(x, y)| {
+                        *x = (*x - (new_x - start_x)).clamp(0.0, max_offset.0);
+                        *y = (*y - (new_y - start_y)).clamp(0.0, max_offset.1);
+                    });
+                    drag_coords.set(Some((new_x, new_y)));
+                }
+            },
+
+            img {
+                class: "absolute max-w-none select-none",
+                src: src,
+                draggable: false,
+                // the source is scaled with nearest neighbour so its pixels are visible
+                style: "left: {-x}px; top: {-y}px; width: {width}px; image-rendering: pixelated;",
+            }
+            span { class: "absolute top-2 left-2 rounded-md bg-crust px-2 py-1 text-sm font-semibold text-text",
+                {label}
+            }
+        }
+    }
+}
+
+/// compares upscaled images at 100% with their source, to accept or reject each upscale
+#[component]
+pub fn UpscaleReviewer(review_file: PathBuf, theme: String) -> Element {
+    // loaded once, the error screen is shown instead of the reviews if it fails
+    let loaded = use_hook(|| {
+        Rc::new(
+            upscale_review::load(&review_file)
+                .map_err(|e| format!("could not read {review_file:?}: {e}")),
+        )
+    });
+    let reviews = match loaded.as_ref() {
+        Ok(reviews) => reviews.clone(),
+        Err(e) => {
+            return rsx! {
+                LoadError { theme, error: e.clone() }
+            };
+        }
+    };
+
+    let mut reviews = use_signal(|| reviews);
+    // signals can be copied into each of the event handlers
+    let review_file = use_signal(|| review_file);
+    let mut index = use_signal(|| 0);
+    let offset = use_signal(|| (0.0, 0.0));
+
+    let total = reviews().len();
+
+    let mut decide = move |accepted: bool| {
+        reviews.with_mut(|reviews| reviews[index()].accepted = Some(accepted));
+        if let Err(e) = upscale_review::save(&review_file(), &reviews()) {
+            error!("Could not write {:?}: {e}", review_file());
+        }
+        index.set(index() + 1);
+    };
+
+    let Some(review) = reviews().get(index()).cloned() else {
+        let rejected = reviews().iter().filter(|r| r.is_rejected()).count();
+        return rsx! {
+            main {
+                class: "dark {theme} flex flex-col gap-4 items-center h-full justify-center bg-base overflow-hidden",
+                h1 { class: "text-4xl font-bold tracking-tight text-text text-center",
+                    {t("all-reviewed")}
+                }
+                p { class: "text-text",
+                    "{rejected} of {total} rejected, close the window to continue adding the wallpapers."
+                }
+                Button {
+                    class: "rounded-md",
+                    onclick: move |_| index.set(total.saturating_sub(1)),
+                    {t("back")}
+                }
+            }
+        };
+    };
+
+    let UpscaleReview {
+        source,
+        upscaled,
+        scale_factor,
+        accepted,
+    } = review;
+    let (width, height) = image::image_dimensions(&upscaled).unwrap_or_default();
+    let max_offset = (f64::from(width), f64::from(height));
+
+    let pagination_cls = "relative inline-flex items-center rounded-md bg-surface1 py-1 px-2 text-sm font-semibold text-text ring-1 ring-inset ring-surface2 hover:bg-crust focus-visible:outline-offset-0 cursor-pointer";
+
+    rsx! {
+        main {
+            class: "dark {theme} flex flex-col h-full bg-base overflow-hidden",
+            tabindex: 0,
+            autofocus: true,
+            onkeydown: move |event| {
+                match event.key() {
+                    Key::Character(shortcut) if shortcut == "a" => decide(true),
+                    Key::Character(shortcut) if shortcut == "r" => decide(false),
+                    Key::ArrowLeft => index.set(index().saturating_sub(1)),
+                    Key::ArrowRight => index.set((index() + 1).min(total)),
+                    _ => {}
+                }
+            },
+
+            header { class: "bg-surface0",
+                nav { class: "mx-auto flex max-w-full items-center py-6 px-4",
+                    div { class: "flex-1 justify-start ml-2",
+                        a { class: "text-base font-semibold leading-6 text-white",
+                            "{index() + 1} / {total}"
+                        }
+                    }
+
+                    div { class: "flex flex-1 gap-x-3 items-center justify-center",
+                        a { class: pagination_cls,
+                            onclick: move |_| index.set(index().saturating_sub(1)),
+                            Icon { fill: "white", icon: MdChevronLeft, width: 16, height: 16 }
+                        }
+                        a { class: "text-sm font-semibold leading-6 text-white text-center w-48",
+                            "{filename(&source)} ({scale_factor}x)"
+                        }
+                        a { class: pagination_cls,
+                            onclick: move |_| index.set((index() + 1).min(total)),
+                            Icon { fill: "white", icon: MdChevronRight, width: 16, height: 16 }
+                        }
+                    }
+
+                    div { class: "flex flex-1 justify-end",
+                        span { class: "isolate inline-flex rounded-md shadow-sm",
+                            Button {
+                                class: "rounded-l-md",
+                                active: accepted == Some(false),
+                                onclick: move |_| decide(false),
+                                {t("reject")}
+                            }
+                            Button {
+                                class: "-ml-px rounded-r-md",
+                                active: accepted == Some(true),
+                                onclick: move |_| decide(true),
+                                {t("accept")}
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "flex flex-1 gap-4 p-4 min-h-0",
+                ComparePanel {
+                    label: t("source"),
+                    src: img_src(&source),
+                    width,
+                    offset,
+                    max_offset,
+                }
+                ComparePanel {
+                    label: t("upscaled"),
+                    src: img_src(&upscaled),
+                    width,
+                    offset,
+                    max_offset,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{paths, write_atomic};
+
+/// wallpaper added by the pipeline, waiting for its default crops to be accepted or queued for
+/// editing in the ui
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropReview {
+    pub path: PathBuf,
+    /// None until the crops have been reviewed
+    pub accepted: Option<bool>,
+}
+
+impl CropReview {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            accepted: None,
+        }
+    }
+
+    /// wallpapers that weren't reviewed are still edited
+    pub fn needs_editing(&self) -> bool {
+        self.accepted != Some(true)
+    }
+}
+
+/// file used to pass the reviews between the pipeline and the ui
+pub fn review_file() -> PathBuf {
+    paths::cache_dir().join("crop-review.json")
+}
+
+pub fn load(path: &Path) -> std::io::Result<Vec<CropReview>> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(std::io::Error::other)
+}
+
+pub fn save(path: &Path, reviews: &[CropReview]) -> std::io::Result<()> {
+    let contents = serde_json::to_string_pretty(reviews).map_err(std::io::Error::other)?;
+    write_atomic(path, contents.as_bytes())
+}
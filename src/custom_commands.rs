@@ -0,0 +1,65 @@
+use std::process::Stdio;
+
+use tracing::{info, warn};
+
+use crate::{aspect_ratio::AspectRatio, config::WallpaperConfig, wallpapers::WallInfo};
+
+/// arguments of the custom command for the wallpaper, {path}, {filename}, {dir}, {width},
+/// {height}, {wallust}, {ratio} and {geometry} are replaced in each argument, with the geometry
+/// of the crop for the ratio
+pub fn command_args(
+    config: &WallpaperConfig,
+    template: &str,
+    info: &WallInfo,
+    ratio: &AspectRatio,
+) -> Vec<String> {
+    let path = config.wallpapers_path.join(&info.filename);
+
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{path}", &path.to_string_lossy())
+                .replace("{filename}", &info.filename)
+                .replace("{dir}", &config.wallpapers_path.to_string_lossy())
+                .replace("{width}", &info.width.to_string())
+                .replace("{height}", &info.height.to_string())
+                .replace("{wallust}", &info.wallust)
+                .replace("{ratio}", &ratio.to_string())
+                .replace("{geometry}", &info.get_geometry(ratio).to_string())
+        })
+        .collect()
+}
+
+/// runs the custom command from the commands section of the config in the background, so
+/// editors and other long running programs don't block the ui
+pub fn run(
+    config: &WallpaperConfig,
+    name: &str,
+    template: &str,
+    info: &WallInfo,
+    ratio: &AspectRatio,
+) {
+    let args = command_args(config, template, info, ratio);
+    let Some((program, args)) = args.split_first() else {
+        return;
+    };
+
+    info!("running {name} for {}", info.filename);
+    let child = std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+
+    let name = name.to_string();
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!("{name} exited with {status}"),
+                Err(e) => warn!("could not wait for {name}: {e}"),
+            });
+        }
+        Err(e) => warn!("could not run {name}: {e}"),
+    }
+}
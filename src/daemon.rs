@@ -0,0 +1,223 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    hash::BuildHasher,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    sync::mpsc::{self, Sender},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::monitors::{Monitor, MonitorEvent};
+
+/// commands accepted on the control socket, one per line
+///
+/// each command gets a single line reply, either ok, json or an error starting with "error:"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaemonCommand {
+    Pause,
+    Resume,
+    Next,
+    Prev,
+    /// current wallpaper of each monitor
+    Current,
+    /// current wallpapers, whether the rotation is paused and the seconds until the next change
+    Status,
+    /// stored crop of the wallpaper for a resolution, e.g. crop wall.png 16x9
+    Crop {
+        filename: String,
+        resolution: String,
+    },
+    /// sets the wallpaper on all monitors
+    Set(String),
+}
+
+impl std::fmt::Display for DaemonCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pause => write!(f, "pause"),
+            Self::Resume => write!(f, "resume"),
+            Self::Next => write!(f, "next"),
+            Self::Prev => write!(f, "prev"),
+            Self::Current => write!(f, "current"),
+            Self::Status => write!(f, "status"),
+            Self::Crop {
+                filename,
+                resolution,
+            } => write!(f, "crop {filename} {resolution}"),
+            Self::Set(filename) => write!(f, "set {filename}"),
+        }
+    }
+}
+
+impl std::str::FromStr for DaemonCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (cmd, rest) = s.split_once(' ').unwrap_or((s, ""));
+        let rest = rest.trim();
+
+        match (cmd, rest) {
+            ("pause", "") => Ok(Self::Pause),
+            ("resume", "") => Ok(Self::Resume),
+            ("next", "") => Ok(Self::Next),
+            ("prev", "") => Ok(Self::Prev),
+            ("current", "") => Ok(Self::Current),
+            ("status", "") => Ok(Self::Status),
+            // filenames can contain spaces, so the resolution is the last word
+            ("crop", rest) => rest
+                .rsplit_once(' ')
+                .map(|(filename, resolution)| Self::Crop {
+                    filename: filename.trim().to_string(),
+                    resolution: resolution.to_string(),
+                })
+                .ok_or_else(|| "usage: crop FILENAME RESOLUTION".to_string()),
+            ("set", rest) if !rest.is_empty() => Ok(Self::Set(rest.to_string())),
+            _ => Err(format!("unknown command: {s}")),
+        }
+    }
+}
+
+/// reply to the status command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// current wallpaper of each monitor
+    pub current: BTreeMap<String, Option<String>>,
+    pub paused: bool,
+    /// seconds until the next change, none while paused
+    pub remaining: Option<u64>,
+}
+
+/// events handled by the main loop of the daemon
+#[derive(Debug, Clone)]
+pub enum DaemonEvent {
+    /// command from the control socket, with the channel for its reply
+    Command(DaemonCommand, Sender<String>),
+    Monitor(MonitorEvent),
+}
+
+/// wallpapers that are rotated on a monitor
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    pub monitor: Monitor,
+    files: Vec<String>,
+    index: usize,
+}
+
+impl Rotation {
+    /// shuffles the wallpapers, so monitors sharing wallpapers show different ones
+    pub fn new(monitor: Monitor, files: &[String]) -> Self {
+        // RandomState is seeded randomly, so hashing gives a cheap shuffle
+        let state = RandomState::new();
+        let mut files = files.to_vec();
+        files.sort_by_cached_key(|f| state.hash_one(f));
+
+        Self {
+            monitor,
+            files,
+            index: 0,
+        }
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.files.get(self.index).map(String::as_str)
+    }
+
+    /// shows the wallpaper next, even if it does not match the filters of the rotation
+    pub fn set(&mut self, filename: &str) {
+        self.index = self
+            .files
+            .iter()
+            .position(|f| f == filename)
+            .unwrap_or_else(|| {
+                self.files.push(filename.to_string());
+                self.files.len() - 1
+            });
+    }
+
+    /// moves to the next or previous wallpaper that is available, e.g. scheduled for the current
+    /// time, looping around at either end
+    ///
+    /// stays on the current wallpaper if no other wallpaper is available
+    pub fn step<F>(&mut self, forward: bool, is_available: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let len = self.files.len();
+        let next = (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.index + offset) % len
+                } else {
+                    (self.index + len - offset) % len
+                }
+            })
+            .find(|index| is_available(&self.files[*index]));
+
+        if let Some(index) = next {
+            self.index = index;
+        }
+    }
+
+    /// moves on from the current wallpaper if it is not available, e.g. once its time is over
+    pub fn skip_unavailable<F>(&mut self, is_available: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        if !self.current().map_or(true, &is_available) {
+            self.step(true, is_available);
+        }
+    }
+}
+
+fn handle_client(stream: UnixStream, events: &Sender<DaemonEvent>) -> std::io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let reply = match line.parse::<DaemonCommand>() {
+        Ok(cmd) => {
+            debug!("received {cmd}");
+            let (reply_tx, reply_rx) = mpsc::channel();
+            events
+                .send(DaemonEvent::Command(cmd, reply_tx))
+                .ok()
+                .and_then(|()| reply_rx.recv().ok())
+                .unwrap_or_else(|| "error: daemon is shutting down".to_string())
+        }
+        Err(e) => format!("error: {e}"),
+    };
+
+    writeln!(&stream, "{reply}")
+}
+
+/// listens for commands on the socket in a background thread
+pub fn listen(socket: &Path, events: Sender<DaemonEvent>) -> std::io::Result<()> {
+    // remove the socket left behind by a previous daemon
+    if socket.exists() && UnixStream::connect(socket).is_err() {
+        std::fs::remove_file(socket)?;
+    }
+
+    let listener = UnixListener::bind(socket)?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(|stream| handle_client(stream, &events)) {
+                warn!("control socket: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// sends the command to the running daemon, returns its reply
+pub fn send(socket: &Path, cmd: &DaemonCommand) -> std::io::Result<String> {
+    let stream = UnixStream::connect(socket)?;
+    writeln!(&stream, "{cmd}")?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::WallpaperConfig,
+    filename, filter_images,
+    tools::ExternalTools,
+    wallpapers::{WallpapersCsv, WallpapersError},
+    WallpaperConfigExt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Pass,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// external tools used by the pipeline and the ui, with the arguments to get their version
+const TOOLS: &[(&str, &[&str], &str)] = &[
+    (
+        "realcugan-ncnn-vulkan",
+        &[],
+        "needed to upscale small images",
+    ),
+    ("cwebp", &["-version"], "needed to optimize webp images"),
+    ("jpegoptim", &["--version"], "needed to optimize jpg images"),
+    ("oxipng", &["--version"], "needed to optimize png images"),
+    ("anime-face-detector", &[], "needed to detect faces"),
+    ("wallust", &["--version"], "needed to preview palettes"),
+    (
+        "swww",
+        &["--version"],
+        "needed to apply wallpapers with swww",
+    ),
+    ("rsync", &["--version"], "needed to sync wallpapers"),
+    ("heif-convert", &[], "needed to add heic images"),
+];
+
+/// first line of the version output of the tool, if any
+fn tool_version(path: &Path, version_args: &[&str]) -> Option<String> {
+    if version_args.is_empty() {
+        return None;
+    }
+
+    let output = Command::new(path)
+        .args(version_args)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    // some tools print their version to stderr
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+
+    String::from_utf8_lossy(&text)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+fn check_config(config_file: &Path) -> Check {
+    let name = "config";
+
+    if !config_file.exists() {
+        return Check::warn(
+            name,
+            format!("{config_file:?} not found, using the default config"),
+            format!("create {config_file:?} to customize the wallpaper directory and resolutions"),
+        );
+    }
+
+    match WallpaperConfig::load() {
+        Ok(_) => Check::pass(name, format!("{config_file:?} is valid")),
+        Err(errors) => Check::fail(
+            name,
+            errors
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+            format!("fix the errors in {config_file:?}"),
+        ),
+    }
+}
+
+fn check_tools(tools: &ExternalTools) -> Vec<Check> {
+    TOOLS
+        .iter()
+        .map(|(tool, version_args, purpose)| {
+            let tool_path = tools.get(tool).path;
+
+            tools.get(tool).find().map_or_else(
+                || {
+                    // wallpapers-add falls back to the onnx detector
+                    if *tool == "anime-face-detector" && cfg!(feature = "onnx") {
+                        return Check::pass(
+                            tool,
+                            format!("{tool_path:?} not found, the onnx face detector is used instead"),
+                        );
+                    }
+                    // and optimizes in process
+                    if ["cwebp", "jpegoptim", "oxipng"].contains(tool) {
+                        return Check::pass(
+                            tool,
+                            format!("{tool_path:?} not found, images are optimized in process instead"),
+                        );
+                    }
+                    Check::warn(
+                        tool,
+                        format!("{tool_path:?} not found"),
+                        format!(
+                            "install {tool} or set its path in the [tools] section of the config, it is {purpose}"
+                        ),
+                    )
+                },
+                |path| {
+                    let version = tool_version(&path, version_args)
+                        .map_or_else(String::new, |version| format!(" ({version})"));
+                    Check::pass(tool, format!("found at {path:?}{version}"))
+                },
+            )
+        })
+        .collect()
+}
+
+/// the onnx face detector needs the onnx feature and a model, unless one is embedded in the binary
+fn check_face_model(config: &WallpaperConfig) -> Check {
+    let name = "face-model";
+
+    if !cfg!(feature = "onnx") {
+        return Check::fail(
+            name,
+            "face_detector is onnx, but wallpapers was built without the onnx feature",
+            "rebuild with --features onnx or set face_detector to anime-face-detector",
+        );
+    }
+
+    if config.face_model.exists() {
+        Check::pass(name, format!("found at {:?}", config.face_model))
+    } else if cfg!(bundled_face_model) {
+        Check::pass(name, "using the model embedded in the binary")
+    } else {
+        Check::fail(
+            name,
+            format!("{:?} not found", config.face_model),
+            format!(
+                "download an anime face detection model in onnx format to {:?} or set face_model in the config",
+                config.face_model
+            ),
+        )
+    }
+}
+
+fn check_wallpapers_dir(wall_dir: &Path) -> Check {
+    let name = "wallpapers directory";
+
+    match wall_dir.metadata() {
+        Err(_) => Check::fail(
+            name,
+            format!("{wall_dir:?} does not exist"),
+            "create the directory or set wallpapers_path in the config",
+        ),
+        Ok(meta) if !meta.is_dir() => Check::fail(
+            name,
+            format!("{wall_dir:?} is not a directory"),
+            "set wallpapers_path in the config to a directory",
+        ),
+        Ok(meta) if meta.permissions().readonly() => Check::fail(
+            name,
+            format!("{wall_dir:?} is not writable"),
+            format!("fix the permissions of {wall_dir:?}"),
+        ),
+        Ok(_) => Check::pass(name, format!("{wall_dir:?} exists")),
+    }
+}
+
+/// checks the rows of wallpapers.csv or the sqlite database of the config, returns the filenames
+/// of the valid rows
+fn check_csv(config: &WallpaperConfig) -> (Vec<Check>, Option<HashSet<String>>) {
+    let path = config.store_path();
+    let name = if config.store == "sqlite" {
+        "wallpapers database"
+    } else {
+        "wallpapers.csv"
+    };
+
+    if !path.exists() {
+        return (
+            vec![Check::fail(
+                name,
+                format!("{path:?} not found"),
+                "run wallpapers-add to create it",
+            )],
+            None,
+        );
+    }
+
+    let mut checks = Vec::new();
+    // opening for appending doesn't change the file, unlike the permission bits it also checks
+    // the owner and read only filesystems
+    if let Err(e) = std::fs::OpenOptions::new().append(true).open(path) {
+        checks.push(Check::fail(
+            name,
+            format!("{path:?} is not writable: {e}"),
+            format!("fix the permissions of {path:?}"),
+        ));
+    }
+
+    let rows = match WallpapersCsv::open_stream(config) {
+        Ok(rows) => rows,
+        Err(e) => {
+            checks.push(Check::fail(
+                name,
+                e.to_string(),
+                format!("fix or restore {path:?}"),
+            ));
+            return (checks, None);
+        }
+    };
+
+    let mut filenames = HashSet::new();
+    let mut errors = Vec::new();
+    for row in rows {
+        match row {
+            Ok(info) => {
+                if !filenames.insert(info.filename.clone()) {
+                    errors.push(format!("duplicate row for {}", info.filename));
+                }
+            }
+            Err(WallpapersError::Parse { source, .. }) => errors.push(source.to_string()),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    if errors.is_empty() {
+        checks.push(Check::pass(
+            name,
+            format!("{} wallpapers, no invalid rows", filenames.len()),
+        ));
+    } else {
+        checks.push(Check::fail(
+            name,
+            format!("{} invalid rows: {}", errors.len(), errors.join("; ")),
+            format!("fix or remove the invalid rows in {path:?}"),
+        ));
+    }
+
+    (checks, Some(filenames))
+}
+
+/// reports the wallpapers that are smaller than the strictest minimum size
+fn check_small_images(config: &WallpaperConfig) -> Check {
+    let name = "small images";
+    let (min_width, min_height) = config.strictest_min_size(config.min_width, config.min_height);
+
+    let images = match filter_images(&config.wallpapers_path, &config.ignore) {
+        Ok(images) => images,
+        Err(e) => {
+            return Check::fail(
+                name,
+                format!("could not read {:?}: {e}", config.wallpapers_path),
+                "check the permissions of wallpapers_path",
+            )
+        }
+    };
+    let mut small: Vec<_> = images
+        .filter(|img| {
+            image::image_dimensions(img)
+                .is_ok_and(|(width, height)| width < min_width || height < min_height)
+        })
+        .map(filename)
+        .collect();
+    small.sort();
+
+    if small.is_empty() {
+        Check::pass(
+            name,
+            format!("all images are at least {min_width}x{min_height}"),
+        )
+    } else {
+        Check::warn(
+            name,
+            format!(
+                "smaller than {min_width}x{min_height}: {}",
+                small.join(", ")
+            ),
+            "run wallpapers-add --replace on the originals to upscale them",
+        )
+    }
+}
+
+fn check_orphans(
+    wall_dir: &Path,
+    ignore: &[glob::Pattern],
+    csv_filenames: &HashSet<String>,
+) -> Vec<Check> {
+    let images: HashSet<_> = match filter_images(wall_dir, ignore) {
+        Ok(images) => images.map(filename).collect(),
+        Err(e) => {
+            return vec![Check::fail(
+                "orphan images",
+                format!("could not read {wall_dir:?}: {e}"),
+                "check the permissions of wallpapers_path",
+            )]
+        }
+    };
+
+    let mut not_in_csv: Vec<_> = images.difference(csv_filenames).cloned().collect();
+    not_in_csv.sort();
+    let mut missing: Vec<_> = csv_filenames.difference(&images).cloned().collect();
+    missing.sort();
+
+    vec![
+        if not_in_csv.is_empty() {
+            Check::pass("orphan images", "all images are in wallpapers.csv")
+        } else {
+            Check::warn(
+                "orphan images",
+                format!("not in wallpapers.csv: {}", not_in_csv.join(", ")),
+                "run wallpapers-add to detect their faces",
+            )
+        },
+        if missing.is_empty() {
+            Check::pass("missing images", "all rows in wallpapers.csv have an image")
+        } else {
+            Check::warn(
+                "missing images",
+                format!("in wallpapers.csv but missing: {}", missing.join(", ")),
+                "run batch to remove them from wallpapers.csv",
+            )
+        },
+    ]
+}
+
+/// runs all the checks
+pub fn run() -> Vec<Check> {
+    let mut checks = vec![check_config(&WallpaperConfig::config_file())];
+
+    // the config has to be valid to check anything else
+    if checks[0].status == Status::Fail {
+        return checks;
+    }
+
+    let config = WallpaperConfig::new();
+    checks.extend(check_tools(&config.tools));
+    // the onnx detector is also used when anime-face-detector isn't installed
+    let anime_missing = config.tools.get("anime-face-detector").find().is_none();
+    if config.face_detector == "onnx" || (cfg!(feature = "onnx") && anime_missing) {
+        checks.push(check_face_model(&config));
+    }
+
+    let dir_check = check_wallpapers_dir(&config.wallpapers_path);
+    let has_dir = dir_check.status != Status::Fail;
+    checks.push(dir_check);
+
+    let (csv_checks, csv_filenames) = check_csv(&config);
+    checks.extend(csv_checks);
+
+    if has_dir {
+        checks.push(check_small_images(&config));
+    }
+
+    if let (true, Some(csv_filenames)) = (has_dir, csv_filenames) {
+        checks.extend(check_orphans(
+            &config.wallpapers_path,
+            &config.ignore,
+            &csv_filenames,
+        ));
+    }
+
+    checks
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    aspect_ratio::AspectRatio, cache::render_all, config::WallpaperConfig, geometry::Geometry,
+    wallpapers::WallInfo,
+};
+
+/// tools the crops can be exported with as a shell script
+pub const SCRIPT_TOOLS: &[&str] = &["magick", "ffmpeg"];
+
+/// crop of a wallpaper for a resolution in the config
+#[derive(Debug, Clone)]
+pub struct ExportCrop {
+    pub filename: String,
+    pub resolution: String,
+    pub ratio: AspectRatio,
+    /// rendered crop in the cache
+    pub crop: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// renders the crops of the wallpaper for the resolutions in the config, reusing cached crops
+pub fn wallpaper_crops(
+    config: &WallpaperConfig,
+    info: &WallInfo,
+) -> image::ImageResult<Vec<ExportCrop>> {
+    let image = config.wallpapers_path.join(&info.filename);
+    let geometries: Vec<_> = config
+        .resolutions
+        .iter()
+        .map(|(_, ratio)| (ratio.clone(), info.get_geometry(ratio)))
+        .collect();
+
+    let crops = render_all(&image, &geometries)?;
+    config
+        .resolutions
+        .iter()
+        .zip(crops)
+        .map(|((name, ratio), (crop, _))| {
+            let (width, height) = image::image_dimensions(&crop)?;
+            Ok(ExportCrop {
+                filename: info.filename.clone(),
+                resolution: name.clone(),
+                ratio: ratio.clone(),
+                crop,
+                width,
+                height,
+            })
+        })
+        .collect()
+}
+
+/// output path of the exported crop from the export_template in the config
+///
+/// the template can contain {name}, {ext}, {width}, {height}, {ratio} and {resolution}, with
+/// the dimensions of the exported image, e.g. {resolution}/{name}_{width}x{height}.{ext}
+pub fn export_path(
+    dir: &Path,
+    template: &str,
+    crop: &ExportCrop,
+    (width, height): (u32, u32),
+    ext: &str,
+) -> PathBuf {
+    let stem = Path::new(&crop.filename)
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().to_string());
+
+    dir.join(
+        template
+            .replace("{name}", &stem)
+            .replace("{ext}", ext)
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+            .replace("{ratio}", &crop.ratio.to_string())
+            .replace("{resolution}", &crop.resolution),
+    )
+}
+
+fn modified(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified())
+}
+
+/// the export is missing or was not made from the current source crop
+///
+/// exports get the modification time of their source, so going back to an older crop is also
+/// detected
+pub fn is_stale(src: &Path, dest: &Path) -> bool {
+    match (modified(src), modified(dest)) {
+        (Ok(src), Ok(dest)) => src != dest,
+        _ => true,
+    }
+}
+
+/// marks the export as made from the source crop
+pub fn mark_exported(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let mtime = modified(src)?;
+    std::fs::File::options()
+        .write(true)
+        .open(dest)?
+        .set_modified(mtime)
+}
+
+/// quotes the argument for a posix shell
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// shell command that renders the crop of the image like render_geometry, src and dest are
+/// already quoted for the shell
+pub fn crop_command(
+    tool: &str,
+    src: &str,
+    dest: &str,
+    geom: &Geometry,
+    ratio: &AspectRatio,
+) -> String {
+    let (out_w, out_h) = geom.output_size();
+    let scaled = (out_w, out_h) != (geom.w, geom.h);
+    let padding = geom.padded_canvas(ratio).zip(
+        geom.padding
+            .as_ref()
+            .map(|padding| padding.rgb().unwrap_or_default()),
+    );
+
+    if tool == "ffmpeg" {
+        let mut filters = vec![format!("crop={}:{}:{}:{}", geom.w, geom.h, geom.x, geom.y)];
+        if scaled {
+            filters.push(format!("scale={out_w}:{out_h}:flags=lanczos"));
+        }
+        if let Some(((canvas_w, canvas_h, x, y), [r, g, b])) = padding {
+            filters.push(format!(
+                "pad={canvas_w}:{canvas_h}:{x}:{y}:color=0x{r:02x}{g:02x}{b:02x}"
+            ));
+        }
+        return format!(
+            "ffmpeg -loglevel error -y -i {src} -vf {} -frames:v 1 {dest}",
+            shell_quote(&filters.join(","))
+        );
+    }
+
+    let mut command = format!(
+        "magick {src} -crop {}x{}+{}+{} +repage",
+        geom.w, geom.h, geom.x, geom.y
+    );
+    if scaled {
+        write!(command, " -filter Lanczos -resize {out_w}x{out_h}!").ok();
+    }
+    if let Some(((canvas_w, canvas_h, x, y), [r, g, b])) = padding {
+        write!(
+            command,
+            " -background '#{r:02x}{g:02x}{b:02x}' -gravity northwest -extent {canvas_w}x{canvas_h}-{x}-{y}"
+        )
+        .ok();
+    }
+    command.push(' ');
+    command.push_str(dest);
+    command
+}
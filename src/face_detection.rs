@@ -0,0 +1,216 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use thiserror::Error;
+use tract_onnx::prelude::*;
+
+use crate::wallpapers::Face;
+
+/// the model takes square images of this size
+const INPUT_SIZE: u32 = 640;
+/// detections below this confidence are discarded
+const MIN_CONFIDENCE: f32 = 0.5;
+/// overlapping detections of the same face above this iou are merged
+const MAX_IOU: f32 = 0.45;
+/// rows of the output of yolov8 pose models of faces, e.g. yolov8-face, the box, the confidence
+/// and the x, y and visibility of 5 landmarks, starting with the eyes
+const LANDMARK_ROWS: usize = 4 + 1 + 5 * 3;
+
+/// model embedded with WALLPAPER_UI_FACE_MODEL when building
+#[cfg(bundled_face_model)]
+const BUNDLED_MODEL: Option<&[u8]> = Some(include_bytes!(env!("WALLPAPER_UI_FACE_MODEL")));
+#[cfg(not(bundled_face_model))]
+const BUNDLED_MODEL: Option<&[u8]> = None;
+
+/// the detectors are loaded once per model and shared by the pipeline and the ui
+static DETECTORS: Mutex<Vec<(PathBuf, Arc<FaceDetector>)>> = Mutex::new(Vec::new());
+
+#[derive(Error, Debug)]
+pub enum FaceDetectionError {
+    #[error("face model {0:?} not found, download a face model in onnx format or set the path in the config")]
+    MissingModel(PathBuf),
+    #[error("could not load the face model: {0}")]
+    Load(String),
+    #[error("could not detect faces: {0}")]
+    Run(String),
+    #[error("could not read {path:?}: {source}")]
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+}
+
+/// detects faces with a single class yolov8 model exported to onnx, the model runs in
+/// process so no python environment is needed
+///
+/// pose models with face landmarks also find the eyes, which are used for eye_line
+pub struct FaceDetector {
+    model: TypedRunnableModel<TypedModel>,
+}
+
+/// a detected box in the coordinates of the image
+#[derive(Debug, Clone, Copy)]
+struct Detection {
+    xmin: f32,
+    ymin: f32,
+    xmax: f32,
+    ymax: f32,
+    confidence: f32,
+    /// between the eyes, only from models with landmarks
+    eye_y: Option<f32>,
+}
+
+impl Detection {
+    fn area(&self) -> f32 {
+        (self.xmax - self.xmin).max(0.0) * (self.ymax - self.ymin).max(0.0)
+    }
+
+    fn iou(&self, other: &Self) -> f32 {
+        let overlap = Self {
+            xmin: self.xmin.max(other.xmin),
+            ymin: self.ymin.max(other.ymin),
+            xmax: self.xmax.min(other.xmax),
+            ymax: self.ymax.min(other.ymax),
+            confidence: 0.0,
+            eye_y: None,
+        }
+        .area();
+        overlap / (self.area() + other.area() - overlap).max(f32::EPSILON)
+    }
+}
+
+/// the shared detector of the model, the embedded model is used unless the model in the config
+/// exists
+pub fn detector(model_path: &Path) -> Result<Arc<FaceDetector>, FaceDetectionError> {
+    let mut detectors = DETECTORS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, detector)) = detectors.iter().find(|(path, _)| path == model_path) {
+        return Ok(Arc::clone(detector));
+    }
+
+    let loaded = Arc::new(FaceDetector::load(model_path)?);
+    detectors.push((model_path.to_path_buf(), Arc::clone(&loaded)));
+    Ok(loaded)
+}
+
+impl FaceDetector {
+    pub fn load(model_path: &Path) -> Result<Self, FaceDetectionError> {
+        let onnx = tract_onnx::onnx();
+        let model = match BUNDLED_MODEL {
+            Some(bytes) if !model_path.exists() => {
+                onnx.model_for_read(&mut std::io::Cursor::new(bytes))
+            }
+            _ if !model_path.exists() => {
+                return Err(FaceDetectionError::MissingModel(model_path.to_path_buf()))
+            }
+            _ => onnx.model_for_path(model_path),
+        };
+
+        let size = INPUT_SIZE as usize;
+        let model = model
+            .and_then(|model| model.with_input_fact(0, f32::fact([1, 3, size, size]).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| FaceDetectionError::Load(e.to_string()))?;
+
+        Ok(Self { model })
+    }
+
+    /// the faces in the image, the image is scaled to fit the input of the model and padded at
+    /// the bottom and right
+    pub fn detect(&self, img: &DynamicImage) -> Result<Vec<Face>, FaceDetectionError> {
+        let (img_w, img_h) = img.dimensions();
+        let scale = INPUT_SIZE as f32 / img_w.max(img_h) as f32;
+        let resized = img
+            .resize(INPUT_SIZE, INPUT_SIZE, FilterType::Triangle)
+            .to_rgb8();
+
+        let size = INPUT_SIZE as usize;
+        let input: Tensor =
+            tract_ndarray::Array4::from_shape_fn((1, 3, size, size), |(_, c, y, x)| {
+                resized
+                    .get_pixel_checked(x as u32, y as u32)
+                    // the padding is the gray used when training yolo models
+                    .map_or(114.0 / 255.0, |pixel| f32::from(pixel[c]) / 255.0)
+            })
+            .into();
+
+        let outputs = self
+            .model
+            .run(tvec!(input.into()))
+            .map_err(|e| FaceDetectionError::Run(e.to_string()))?;
+        let output = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(|e| FaceDetectionError::Run(e.to_string()))?;
+
+        // yolov8 outputs [1, 4 + classes, boxes], with the box centers and sizes
+        let shape = output.shape();
+        if shape.len() != 3 || shape[1] < 5 {
+            return Err(FaceDetectionError::Run(format!(
+                "unexpected output shape {shape:?}, expected a yolov8 model"
+            )));
+        }
+
+        // the rows after the confidence of pose models are the landmarks instead of classes
+        let has_landmarks = shape[1] == LANDMARK_ROWS;
+        let classes = if has_landmarks { 4..5 } else { 4..shape[1] };
+
+        let mut detections: Vec<_> = (0..shape[2])
+            .filter_map(|i| {
+                let confidence = classes
+                    .clone()
+                    .map(|class| output[[0, class, i]])
+                    .fold(0.0, f32::max);
+                if confidence < MIN_CONFIDENCE {
+                    return None;
+                }
+
+                let (cx, cy) = (output[[0, 0, i]], output[[0, 1, i]]);
+                let (w, h) = (output[[0, 2, i]], output[[0, 3, i]]);
+                Some(Detection {
+                    xmin: ((cx - w / 2.0) / scale).clamp(0.0, img_w as f32),
+                    ymin: ((cy - h / 2.0) / scale).clamp(0.0, img_h as f32),
+                    xmax: ((cx + w / 2.0) / scale).clamp(0.0, img_w as f32),
+                    ymax: ((cy + h / 2.0) / scale).clamp(0.0, img_h as f32),
+                    confidence,
+                    // the y of the left and right eyes
+                    eye_y: has_landmarks.then(|| {
+                        ((output[[0, 6, i]] + output[[0, 9, i]]) / 2.0 / scale)
+                            .clamp(0.0, img_h as f32)
+                    }),
+                })
+            })
+            .collect();
+
+        // non maximum suppression, keeping the most confident box of each face
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        let mut kept: Vec<Detection> = Vec::new();
+        for detection in detections {
+            if kept.iter().all(|k| k.iou(&detection) <= MAX_IOU) {
+                kept.push(detection);
+            }
+        }
+
+        Ok(kept
+            .iter()
+            .map(|d| Face {
+                xmin: d.xmin.round() as u32,
+                xmax: d.xmax.round() as u32,
+                ymin: d.ymin.round() as u32,
+                ymax: d.ymax.round() as u32,
+                eye_y: d.eye_y.map(|y| y.round() as u32),
+            })
+            .collect())
+    }
+
+    /// the faces and dimensions of the image
+    pub fn detect_path(&self, path: &Path) -> Result<(Vec<Face>, (u32, u32)), FaceDetectionError> {
+        let img = image::open(path).map_err(|source| FaceDetectionError::Image {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok((self.detect(&img)?, img.dimensions()))
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use itertools::Itertools;
+use serde_json::{json, Map, Value};
+use tracing::{debug, warn};
+
+use crate::{
+    commands::export_manifest::wallpaper_json, config::WallpaperConfig, wallpapers::WallInfo,
+};
+
+type Envs = Vec<(&'static str, String)>;
+
+/// runs the hook command with sh, with the event in $WALLPAPERS_HOOK and the payload as json
+/// on stdin
+fn run(event: &str, command: &str, envs: &Envs, payload: &Value) {
+    debug!("running {event} hook: {command}");
+
+    let run_hook = || -> std::io::Result<std::process::ExitStatus> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("WALLPAPERS_HOOK", event)
+            .envs(envs.iter().cloned())
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // hooks that don't read stdin close it early, which is fine
+            stdin.write_all(payload.to_string().as_bytes()).ok();
+        }
+        child.wait()
+    };
+
+    match run_hook() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("{event} hook exited with {status}"),
+        Err(e) => warn!("could not run the {event} hook: {e}"),
+    }
+}
+
+/// environment variables describing the wallpaper, the geometries are space separated
+/// resolution=geometry pairs
+pub fn wallpaper_envs(config: &WallpaperConfig, info: &WallInfo) -> Envs {
+    vec![
+        ("WALLPAPER_FILENAME", info.filename.clone()),
+        (
+            "WALLPAPER_PATH",
+            config
+                .wallpapers_path
+                .join(&info.filename)
+                .to_string_lossy()
+                .to_string(),
+        ),
+        (
+            "WALLPAPER_GEOMETRIES",
+            config
+                .resolutions
+                .iter()
+                .map(|(name, ratio)| format!("{name}={}", info.get_geometry(ratio)))
+                .join(" "),
+        ),
+        ("WALLPAPER_WALLUST", info.wallust.clone()),
+        ("WALLPAPER_TAGS", info.tags.join(",")),
+    ]
+}
+
+/// runs the post_save_hook after the crops of a wallpaper are saved, in the background so the
+/// ui doesn't wait for it
+pub fn post_save(config: &WallpaperConfig, info: &WallInfo) {
+    if config.post_save_hook.is_empty() {
+        return;
+    }
+
+    let command = config.post_save_hook.clone();
+    let envs = wallpaper_envs(config, info);
+    let mut payload = wallpaper_json(config, info);
+    payload["filename"] = json!(info.filename);
+
+    std::thread::spawn(move || run("save", &command, &envs, &payload));
+}
+
+/// runs the post_add_hook after a batch of wallpapers has been added, the filenames are newline
+/// separated in $WALLPAPER_FILENAMES
+pub fn post_add(config: &WallpaperConfig, infos: &[WallInfo]) {
+    if config.post_add_hook.is_empty() || infos.is_empty() {
+        return;
+    }
+
+    let envs = vec![(
+        "WALLPAPER_FILENAMES",
+        infos.iter().map(|info| &info.filename).join("\n"),
+    )];
+    let wallpapers: Map<String, Value> = infos
+        .iter()
+        .map(|info| (info.filename.clone(), wallpaper_json(config, info)))
+        .collect();
+
+    run(
+        "add",
+        &config.post_add_hook,
+        &envs,
+        &json!({ "wallpapers": wallpapers }),
+    );
+}
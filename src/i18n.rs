@@ -0,0 +1,79 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use ini::Ini;
+
+/// translations of the ui strings, english is the fallback for missing strings
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ini")),
+    ("de", include_str!("../locales/de.ini")),
+];
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+struct Messages {
+    selected: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+fn parse_locale(language: &str) -> HashMap<String, String> {
+    LOCALES
+        .iter()
+        .find(|(lang, _)| *lang == language)
+        .and_then(|(_, contents)| Ini::load_from_str(contents).ok())
+        .map(|ini| {
+            ini.general_section()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// language of the locale from the environment, e.g. de for de_DE.UTF-8
+pub fn detect_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .and_then(|locale| {
+            let lang = locale.split(['_', '.', '@']).next()?.to_lowercase();
+            LOCALES.iter().any(|(l, _)| *l == lang).then_some(lang)
+        })
+}
+
+/// selects the language of the ui, auto detects it from the locale
+/// only the first call takes effect, strings used before are in english
+pub fn init(language: &str) {
+    let language = if language == "auto" {
+        detect_language().unwrap_or_else(|| "en".to_string())
+    } else {
+        language.to_string()
+    };
+
+    MESSAGES.get_or_init(|| Messages {
+        selected: parse_locale(&language),
+        fallback: parse_locale("en"),
+    });
+}
+
+/// the translated string, the key itself is returned if there is no translation
+pub fn t(key: &str) -> String {
+    let messages = MESSAGES.get_or_init(|| Messages {
+        selected: HashMap::new(),
+        fallback: parse_locale("en"),
+    });
+
+    messages
+        .selected
+        .get(key)
+        .or_else(|| messages.fallback.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// the translated string with each {name} replaced by its value
+pub fn t_args(key: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(t(key), |msg, (name, value)| {
+        msg.replace(&format!("{{{name}}}"), value)
+    })
+}
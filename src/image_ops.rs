@@ -1,87 +1,615 @@
-use clap::Parser;
 use std::{
+    collections::HashMap,
+    io::IsTerminal,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::Command,
+    sync::{Arc, Mutex, PoisonError},
 };
 
+use image::{codecs::jpeg::JpegEncoder, metadata::Orientation, ImageDecoder};
+use rayon::prelude::*;
+use thiserror::Error;
+use tracing::{debug, error, info, warn};
+
 use crate::{
     aspect_ratio::AspectRatio,
     cli::WallpapersAddArgs,
+    commands::dedupe,
     config::WallpaperConfig,
-    cropper::Cropper,
-    filename, filter_images, run_wallpaper_ui,
-    wallpapers::{WallInfo, WallpapersCsv},
+    crop_review::{self, CropReview},
+    cropper::{CropOptions, Cropper},
+    filename, filter_images,
+    geometry::Geometry,
+    hooks, is_heif,
+    jobs::{Job, JobError, Scheduler},
+    optimizers, paths, run_wallpaper_ui,
+    tools::{ExternalTool, ExternalTools},
+    upscale_review::{self, UpscaleReview, REJECTED_TAG},
+    wallpapers::{Face, WallInfo, WallpapersCsv, WallpapersError},
     FaceJson, PathBufExt,
 };
 
+#[cfg(feature = "onnx")]
+use crate::face_detection;
+
+#[derive(Error, Debug)]
+pub enum ImageOpsError {
+    #[error(transparent)]
+    Job(#[from] JobError),
+    #[error("could not read {path:?}: {source}")]
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("{path:?} is too small to be upscaled to {min_width}x{min_height}")]
+    TooSmall {
+        path: PathBuf,
+        min_width: u32,
+        min_height: u32,
+    },
+    #[error("unsupported image format: {0:?}")]
+    UnsupportedFormat(PathBuf),
+    #[error("could not parse the faces of {path:?}: {source}")]
+    Faces {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("got unprocessed image {0:?}")]
+    Unprocessed(PathBuf),
+    #[error("{0:?} has not been added yet, add it without --detect-only first")]
+    NotAdded(PathBuf),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Csv(#[from] WallpapersError),
+    #[cfg(feature = "onnx")]
+    #[error(transparent)]
+    FaceDetection(#[from] face_detection::FaceDetectionError),
+    #[error("onnx face detection needs wallpapers to be built with the onnx feature")]
+    OnnxDisabled,
+    #[error("the command detector needs face_command to be set in config.ini")]
+    MissingFaceCommand,
+    #[error("anime-face-detector was not found, install it or rebuild wallpapers with --features onnx to detect faces in process")]
+    MissingFaceDetector,
+}
+
+/// path, filename, faces, width and height of a detected image
+type Detected = (PathBuf, String, Vec<Face>, u32, u32);
+
+impl ImageOpsError {
+    /// cancelled jobs are expected after ctrl+c, so they aren't logged
+    fn log(&self, action: &str) {
+        if !matches!(self, Self::Job(JobError::Cancelled { .. })) {
+            error!("Could not {action}: {self}");
+        }
+    }
+}
+
+/// dimensions of the image from its header
+fn dimensions(path: &Path) -> Result<(u32, u32), ImageOpsError> {
+    image::image_dimensions(path).map_err(|source| ImageOpsError::Image {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// copies the image to the wallpapers directory as is, it is only converted if the format changes
+fn copy_image(src: &Path, dest: &Path) -> Result<(), ImageOpsError> {
+    if src == dest {
+        return Ok(());
+    }
+    if src.extension() == dest.extension() {
+        std::fs::copy(src, dest)?;
+        return Ok(());
+    }
+
+    image::open(src)
+        .and_then(|img| img.save(dest))
+        .map_err(|source| ImageOpsError::Image {
+            path: src.to_path_buf(),
+            source,
+        })
+}
+
 /// waits for the images to be written to disk
-fn wait_for_image(path: &Path) {
+async fn wait_for_image(path: &Path) {
     while !path.exists() {
-        std::thread::sleep(std::time::Duration::from_millis(200));
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+/// color type of the image file from its header, without decoding it
+fn color_type(path: &Path) -> Option<image::ColorType> {
+    image::ImageReader::open(path)
+        .and_then(image::ImageReader::with_guessed_format)
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .map(|decoder| decoder.color_type())
+}
+
+/// more than 8 bits per channel, e.g. 16 bit pngs or hdr images
+fn is_high_bit_color(color: image::ColorType) -> bool {
+    color.bits_per_pixel() / u16::from(color.channel_count()) > 8
+}
+
+pub fn is_high_bit_depth(img: &image::DynamicImage) -> bool {
+    is_high_bit_color(img.color())
+}
+
+/// images waiting between the stages of WallpaperPipeline::process
+const STAGE_CAPACITY: usize = 4;
+
+/// sizes the previews are downsampled to, so resizing the window doesn't keep rendering new
+/// previews
+const PREVIEW_SIZES: &[u32] = &[1280, 1920, 2560, 3840];
+
+/// smallest preview size covering the largest dimension the image is shown at, None if only the
+/// full image is large enough
+pub fn preview_size(shown_size: f64) -> Option<u32> {
+    PREVIEW_SIZES
+        .iter()
+        .find(|size| f64::from(**size) >= shown_size)
+        .copied()
+}
+
+/// path of the image to show in the ui, downsampled to max_size so huge images aren't decoded
+/// by the webview, hdr images are also tone mapped to 8 bits as the webview would clip them
+pub fn preview_path(path: &Path, max_size: Option<u32>) -> PathBuf {
+    let is_hdr = matches!(
+        color_type(path),
+        Some(image::ColorType::Rgb32F | image::ColorType::Rgba32F)
+    );
+    let (img_w, img_h) = image::image_dimensions(path).unwrap_or_default();
+    let downsample = max_size.filter(|size| img_w.max(img_h) > *size);
+    if !is_hdr && downsample.is_none() {
+        return path.to_path_buf();
+    }
+
+    // keyed by the mtime so replaced wallpapers get new previews
+    let mtime = path
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |mtime| mtime.as_secs());
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().to_string());
+    let size = downsample.map_or_else(|| "full".to_string(), |size| size.to_string());
+    let dest = paths::cache_dir()
+        .join("preview")
+        .join(format!("{stem}-{size}-{mtime}.jpg"));
+    if dest.exists() {
+        return dest;
+    }
+
+    let render = || -> image::ImageResult<()> {
+        let mut img = image::open(path)?;
+        if let Some(size) = downsample {
+            img = img.resize(size, size, image::imageops::FilterType::Triangle);
+        }
+
+        if is_hdr {
+            let mut hdr = img.to_rgb32f();
+            // reinhard tone mapping of the linear values, then encoded with the srgb gamma
+            for c in hdr.iter_mut() {
+                let mapped = *c / (1.0 + *c);
+                *c = if mapped <= 0.003_130_8 {
+                    mapped * 12.92
+                } else {
+                    1.055f32.mul_add(mapped.powf(1.0 / 2.4), -0.055)
+                };
+            }
+            img = image::DynamicImage::ImageRgb32F(hdr);
+        }
+
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = std::io::BufWriter::new(std::fs::File::create(&dest)?);
+        img.to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(writer, 90))
+    };
+
+    match render() {
+        Ok(()) => dest,
+        Err(e) => {
+            warn!("{}: could not render the preview: {e}", filename(path));
+            std::fs::remove_file(&dest).ok();
+            path.to_path_buf()
+        }
+    }
+}
+
+/// saves the image to the subdirectory of the cache with the same filename, returns its path
+fn save_intermediate(
+    img: &image::DynamicImage,
+    path: &PathBuf,
+    subdir: &str,
+) -> image::ImageResult<PathBuf> {
+    let dest_dir = paths::cache_dir().join(subdir);
+    std::fs::create_dir_all(&dest_dir)?;
+    let dest = path.with_directory(&dest_dir);
+
+    if image::ImageFormat::from_path(&dest)? == image::ImageFormat::Jpeg {
+        // avoid the low default quality, the jpg is encoded again when optimizing anyway
+        let writer = std::io::BufWriter::new(std::fs::File::create(&dest)?);
+        img.to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(writer, 95))?;
+    } else {
+        img.save(&dest)?;
+    }
+    Ok(dest)
+}
+
+/// applies the exif orientation of the image, as the upscaler and optimizers drop the exif data
+///
+/// returns the path of the rotated copy in the cache, or the image itself if it is not rotated
+fn auto_orient(path: &PathBuf) -> PathBuf {
+    let rotate = || -> image::ImageResult<Option<PathBuf>> {
+        let mut decoder = image::ImageReader::open(path)?
+            .with_guessed_format()?
+            .into_decoder()?;
+        let orientation = decoder.orientation()?;
+        if orientation == Orientation::NoTransforms {
+            return Ok(None);
+        }
+
+        let mut img = image::DynamicImage::from_decoder(decoder)?;
+        img.apply_orientation(orientation);
+
+        save_intermediate(&img, path, "oriented").map(Some)
+    };
+
+    match rotate() {
+        Ok(Some(dest)) => {
+            debug!("{}: applied exif orientation", filename(path));
+            dest
+        }
+        Ok(None) => path.clone(),
+        Err(e) => {
+            warn!("{}: could not apply exif orientation: {e}", filename(path));
+            path.clone()
+        }
+    }
+}
+
+/// decodes heic / heif images to png with heif-convert, as the image crate can't read them
+async fn convert_heif(
+    scheduler: &Scheduler,
+    heif_convert: &ExternalTool,
+    path: &Path,
+) -> Option<PathBuf> {
+    let dest = paths::cache_dir()
+        .join("heif")
+        .join(filename(path))
+        .with_extension("png");
+
+    if let Some(dir) = dest.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!("{}: could not create {dir:?}: {e}", filename(path));
+            return None;
+        }
+    }
+
+    let mut command = heif_convert.command();
+    command.arg(path).arg(&dest);
+    let job = Job::new(
+        "heif-convert",
+        format!("Converting {}", filename(path)),
+        command,
+    )
+    .output(&dest);
+
+    match scheduler.run(job).await {
+        Ok(()) => {
+            debug!("{}: converted to png", filename(path));
+            Some(dest)
+        }
+        Err(JobError::Cancelled { .. }) => None,
+        Err(e) => {
+            error!("{}: {e}", filename(path));
+            None
+        }
+    }
+}
+
+/// converts images with an embedded icc profile other than srgb to srgb, as the upscaler,
+/// the ui and wallust ignore the profile
+///
+/// returns the path of the converted copy in the cache, or the image itself if it is not converted
+fn convert_to_srgb(path: &PathBuf) -> PathBuf {
+    let convert = || -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        let mut decoder = image::ImageReader::open(path)?
+            .with_guessed_format()?
+            .into_decoder()?;
+        let Some(icc) = decoder.icc_profile()? else {
+            return Ok(None);
+        };
+
+        let profile = lcms2::Profile::new_icc(&icc)?;
+        let is_srgb = profile
+            .info(lcms2::InfoType::Description, lcms2::Locale::none())
+            .is_some_and(|desc| desc.contains("sRGB"));
+        if is_srgb {
+            return Ok(None);
+        }
+
+        let img = image::DynamicImage::from_decoder(decoder)?;
+        let srgb = lcms2::Profile::new_srgb();
+        let intent = lcms2::Intent::Perceptual;
+        let (width, height) = (img.width(), img.height());
+
+        // keep the bit depth of 16 bit images
+        let converted = if is_high_bit_depth(&img) {
+            let mut pixels: Vec<[u16; 4]> = img.to_rgba16().pixels().map(|px| px.0).collect();
+            lcms2::Transform::new(
+                &profile,
+                lcms2::PixelFormat::RGBA_16,
+                &srgb,
+                lcms2::PixelFormat::RGBA_16,
+                intent,
+            )?
+            .transform_in_place(&mut pixels);
+
+            image::ImageBuffer::from_raw(width, height, pixels.concat())
+                .map(image::DynamicImage::ImageRgba16)
+        } else {
+            let mut pixels: Vec<[u8; 4]> = img.to_rgba8().pixels().map(|px| px.0).collect();
+            lcms2::Transform::new(
+                &profile,
+                lcms2::PixelFormat::RGBA_8,
+                &srgb,
+                lcms2::PixelFormat::RGBA_8,
+                intent,
+            )?
+            .transform_in_place(&mut pixels);
+
+            image::RgbaImage::from_raw(width, height, pixels.concat())
+                .map(image::DynamicImage::ImageRgba8)
+        }
+        .ok_or("could not create the converted image")?;
+
+        Ok(Some(save_intermediate(&converted, path, "srgb")?))
+    };
+
+    match convert() {
+        Ok(Some(dest)) => {
+            debug!("{}: converted to srgb", filename(path));
+            dest
+        }
+        Ok(None) => path.clone(),
+        Err(e) => {
+            warn!("{}: could not convert to srgb: {e}", filename(path));
+            path.clone()
+        }
     }
 }
 
 /// get scale factor for the image
-fn get_scale_factor(width: u32, height: u32, min_width: u32, min_height: u32) -> u32 {
-    for scale_factor in 1..=4 {
-        if width * scale_factor >= min_width && height * scale_factor >= min_height {
-            return scale_factor;
+fn get_scale_factor(
+    path: &Path,
+    (width, height): (u32, u32),
+    min_width: u32,
+    min_height: u32,
+) -> Result<u32, ImageOpsError> {
+    (1..=4)
+        .find(|scale_factor| {
+            width * scale_factor >= min_width && height * scale_factor >= min_height
+        })
+        .ok_or_else(|| ImageOpsError::TooSmall {
+            path: path.to_path_buf(),
+            min_width,
+            min_height,
+        })
+}
+
+/// renders the crop of the image for the aspect ratio, applying its scale and padding
+pub fn render_geometry(
+    img: &image::DynamicImage,
+    geom: &Geometry,
+    ratio: &AspectRatio,
+) -> image::DynamicImage {
+    let cropped = img.crop_imm(geom.x, geom.y, geom.w, geom.h);
+    let (out_w, out_h) = geom.output_size();
+    let scaled = if (out_w, out_h) == (geom.w, geom.h) {
+        cropped
+    } else {
+        cropped.resize_exact(out_w, out_h, image::imageops::FilterType::Lanczos3)
+    };
+
+    let (Some((canvas_w, canvas_h, offset_x, offset_y)), Some(padding)) =
+        (geom.padded_canvas(ratio), &geom.padding)
+    else {
+        return scaled;
+    };
+
+    let [r, g, b] = padding.rgb().unwrap_or_default();
+    let (x, y) = (i64::from(offset_x), i64::from(offset_y));
+
+    // keep the bit depth of 16 bit images
+    if is_high_bit_depth(img) {
+        let [r, g, b] = [r, g, b].map(|c| u16::from(c) * 257);
+        let mut canvas =
+            image::ImageBuffer::from_pixel(canvas_w, canvas_h, image::Rgba([r, g, b, u16::MAX]));
+        image::imageops::overlay(&mut canvas, &scaled.to_rgba16(), x, y);
+        return image::DynamicImage::ImageRgba16(canvas);
+    }
+
+    let mut canvas = image::RgbaImage::from_pixel(canvas_w, canvas_h, image::Rgba([r, g, b, 255]));
+    image::imageops::overlay(&mut canvas, &scaled.to_rgba8(), x, y);
+
+    image::DynamicImage::ImageRgba8(canvas)
+}
+
+/// blurs and dims the image for lockscreens, brightness is a percentage of the original
+pub fn lockscreen_variant(
+    img: &image::DynamicImage,
+    blur: f32,
+    brightness: u32,
+) -> image::DynamicImage {
+    let blurred = if blur > 0.0 {
+        img.blur(blur)
+    } else {
+        img.clone()
+    };
+
+    let mut dimmed = blurred.to_rgb8();
+    for image::Rgb(rgb) in dimmed.pixels_mut() {
+        for c in rgb.iter_mut() {
+            *c = (u32::from(*c) * brightness / 100).min(255) as u8;
         }
     }
 
-    panic!(
-        "image is too small to be upscaled to {}x{}",
-        min_width, min_height
-    );
+    image::DynamicImage::ImageRgb8(dimmed)
+}
+
+/// average colors of the image grouped by the upper 4 bits of each channel, most prominent first
+///
+/// saturated colors are preferred over grays
+pub fn dominant_colors(img: &image::DynamicImage) -> Vec<[u8; 3]> {
+    let thumb = img.thumbnail(64, 64).to_rgb8();
+
+    // (weight, count, channel sums) of each group
+    let mut buckets: HashMap<[u8; 3], (u64, u64, [u64; 3])> = HashMap::new();
+    for image::Rgb(rgb) in thumb.pixels() {
+        let saturation = rgb.iter().max().unwrap_or(&0) - rgb.iter().min().unwrap_or(&0);
+        let (weight, count, sums) = buckets.entry(rgb.map(|c| c >> 4)).or_default();
+        *weight += 1 + u64::from(saturation);
+        *count += 1;
+        for (sum, c) in sums.iter_mut().zip(rgb) {
+            *sum += u64::from(*c);
+        }
+    }
+
+    let mut buckets: Vec<_> = buckets.into_values().collect();
+    buckets.sort_by_key(|(weight, _, _)| std::cmp::Reverse(*weight));
+    buckets
+        .into_iter()
+        .map(|(_, count, sums)| sums.map(|sum| (sum / count) as u8))
+        .collect()
+}
+
+/// most prominent color of the image as hex
+pub fn accent_color(path: &Path) -> Option<String> {
+    let [r, g, b] = *dominant_colors(&image::open(path).ok()?).first()?;
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
 }
 
-pub fn optimize_webp(infile: &PathBuf, outfile: &PathBuf) {
-    Command::new("cwebp")
+/// hex color that stands out against the image, the complement of its most prominent color or
+/// white / black if the complement is too close in brightness
+pub fn contrast_color(img: &image::DynamicImage) -> String {
+    let dominant = dominant_colors(img).first().copied().unwrap_or_default();
+    let luma = |[r, g, b]: [u8; 3]| {
+        0.0722f64.mul_add(
+            f64::from(b),
+            0.2126f64.mul_add(f64::from(r), 0.7152 * f64::from(g)),
+        )
+    };
+
+    let complement = dominant.map(|c| 255 - c);
+    let [r, g, b] = if (luma(complement) - luma(dominant)).abs() >= 96.0 {
+        complement
+    } else if luma(dominant) < 128.0 {
+        [255, 255, 255]
+    } else {
+        [0, 0, 0]
+    };
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// runs the optimizer through the scheduler, the output is removed if it fails or is cancelled
+async fn run_optimizer(
+    scheduler: &Scheduler,
+    tool: &str,
+    command: Command,
+    infile: &Path,
+    outfile: &Path,
+) -> Result<(), ImageOpsError> {
+    let job = Job::new(tool, format!("Optimizing {}", filename(infile)), command)
+        .stage("optimize")
+        .output(outfile)
+        .quiet();
+    Ok(scheduler.run(job).await?)
+}
+
+pub async fn optimize_webp(
+    scheduler: &Scheduler,
+    cwebp: &ExternalTool,
+    infile: &PathBuf,
+    outfile: &PathBuf,
+) -> Result<(), ImageOpsError> {
+    let mut command = cwebp.command();
+    command
         .args(["-q", "100", "-m", "6", "-mt", "-af"])
         .arg(infile)
         .arg("-o")
-        .arg(outfile)
-        // silence output
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("could not spawn cwebp")
-        .wait()
-        .expect("could not wait for cwebp");
-}
-
-pub fn optimize_jpg(infile: &PathBuf, outfile: &Path) {
-    Command::new("jpegoptim")
+        .arg(outfile);
+    run_optimizer(scheduler, "cwebp", command, infile, outfile).await
+}
+
+pub async fn optimize_jpg(
+    scheduler: &Scheduler,
+    jpegoptim: &ExternalTool,
+    infile: &PathBuf,
+    outfile: &Path,
+) -> Result<(), ImageOpsError> {
+    let mut command = jpegoptim.command();
+    command
         .arg("--strip-all")
-        .arg(infile)
-        .arg("--dest")
-        .arg(
-            outfile
-                .parent()
-                .unwrap_or_else(|| panic!("could not get parent directory for {infile:?}")),
-        )
-        // silence output
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("could not spawn jpegoptim")
-        .wait()
-        .expect("could not wait for jpegoptim");
+        // needed to replace existing wallpapers when using --dest
+        .arg("--overwrite")
+        .arg(infile);
+    if let Some(dest) = outfile.parent() {
+        command.arg("--dest").arg(dest);
+    }
+    run_optimizer(scheduler, "jpegoptim", command, infile, outfile).await
 }
 
-pub fn optimize_png(infile: &PathBuf, outfile: &PathBuf) {
-    Command::new("oxipng")
+pub async fn optimize_png(
+    scheduler: &Scheduler,
+    oxipng: &ExternalTool,
+    infile: &PathBuf,
+    outfile: &PathBuf,
+) -> Result<(), ImageOpsError> {
+    let mut command = oxipng.command();
+    command
         .args(["--opt", "max"])
         .arg(infile)
         .arg("--out")
-        .arg(outfile)
-        // silence output
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("could not spawn oxipng")
-        .wait()
-        .expect("could not wait for oxipng");
+        .arg(outfile);
+    run_optimizer(scheduler, "oxipng", command, infile, outfile).await
+}
+
+/// optimizes the image in process with the encoders of the optimizers module
+pub async fn optimize_native(
+    scheduler: &Scheduler,
+    infile: &Path,
+    outfile: &Path,
+) -> Result<(), ImageOpsError> {
+    let (src, dest) = (infile.to_path_buf(), outfile.to_path_buf());
+    scheduler
+        .run_blocking(
+            "native optimizer",
+            "optimize",
+            format!("Optimizing {}", filename(infile)),
+            outfile,
+            move || optimizers::optimize(&src, &dest),
+        )
+        .await?;
+    Ok(())
+}
+
+/// how the images are optimized when they are added
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Optimizer {
+    /// copies the images as is
+    None,
+    /// cwebp, jpegoptim and oxipng, the native encoders are used for tools that aren't installed
+    #[default]
+    External,
+    /// the encoders of the optimizers module
+    Native,
 }
 
 #[derive(Debug, Clone)]
@@ -93,123 +621,377 @@ pub enum WallpaperInput {
 }
 
 impl WallpaperInput {
-    #[must_use]
-    pub fn upscale(&self, format: &Option<String>) -> Self {
+    /// upscales with realcugan on the gpu index, -1 is the cpu and None lets realcugan pick
+    pub async fn upscale(
+        &self,
+        scheduler: &Scheduler,
+        format: &Option<String>,
+        tools: &ExternalTools,
+        gpu: Option<i32>,
+    ) -> Result<Self, ImageOpsError> {
         match self {
             Self::Upscale((src, scale_factor)) => {
                 // nothing to do here
                 if *scale_factor == 1 {
-                    Self::Optimize(src.clone())
+                    Ok(Self::Optimize(src.clone()))
                 } else {
-                    let mut dest = src.with_directory("/tmp");
+                    let cache_dir = paths::cache_dir();
+                    std::fs::create_dir_all(&cache_dir)?;
+                    let mut dest = src.with_directory(cache_dir);
 
                     if let Some(ext) = &format {
                         dest = dest.with_extension(ext);
                     }
 
-                    println!("Upscaling {}...", &filename(src));
-
-                    Command::new("realcugan-ncnn-vulkan")
+                    let mut command = tools.get("realcugan-ncnn-vulkan").command();
+                    command
                         .arg("-i")
                         .arg(src)
                         .arg("-s")
                         .arg(scale_factor.to_string())
                         .arg("-o")
-                        .arg(&dest)
-                        // silence output
-                        .stderr(Stdio::null())
-                        .spawn()
-                        .expect("could not spawn realcugan-ncnn-vulkan")
-                        .wait()
-                        .expect("could not wait for realcugan-ncnn-vulkan");
-                    Self::Optimize(dest)
+                        .arg(&dest);
+                    if let Some(gpu) = gpu {
+                        command.arg("-g").arg(gpu.to_string());
+                    }
+                    let job = Job::new(
+                        "realcugan-ncnn-vulkan",
+                        format!("Upscaling {}", filename(src)),
+                        command,
+                    )
+                    .stage("upscale")
+                    .output(&dest)
+                    .quiet();
+                    scheduler.run(job).await?;
+                    Ok(Self::Optimize(dest))
                 }
             }
-            _ => self.clone(),
+            _ => Ok(self.clone()),
         }
     }
 
-    #[must_use]
-    pub fn optimize(&self, format: &Option<String>, wall_dir: &PathBuf) -> Self {
+    /// optimizes the image into the wallpapers directory with the optimizer
+    pub async fn optimize(
+        &self,
+        scheduler: &Scheduler,
+        format: &Option<String>,
+        wall_dir: &PathBuf,
+        tools: &ExternalTools,
+        optimizer: Optimizer,
+    ) -> Result<Self, ImageOpsError> {
         match self {
-            Self::Upscale(_) => {
-                eprintln!("Optimize: got unprocessed image: {:?}", &self);
-                std::process::exit(1);
-            }
+            Self::Upscale((src, _)) => Err(ImageOpsError::Unprocessed(src.clone())),
             Self::Optimize(src) => {
-                wait_for_image(src);
+                wait_for_image(src).await;
 
                 let out_img = format
                     .as_ref()
                     .map_or_else(|| src.clone(), |format| src.with_extension(format))
                     .with_directory(wall_dir);
 
-                println!("Optimizing {}...", &filename(src));
+                let is_png = out_img.extension().is_some_and(|ext| ext == "png");
+                if !is_png && color_type(src).is_some_and(is_high_bit_color) {
+                    warn!(
+                        "{}: reducing to 8 bits per channel, use --format png to keep the bit depth",
+                        filename(src)
+                    );
+                }
 
-                if let Some(ext) = out_img.extension() {
-                    match ext.to_str().expect("could not convert extension to str") {
-                        "jpg" | "jpeg" => optimize_jpg(src, &out_img),
-                        "png" => optimize_png(src, &out_img),
-                        "webp" => optimize_webp(src, &out_img),
-                        _ => panic!("unsupported image format: {ext:?}"),
-                    }
+                // minimal systems without the tools use the native encoders
+                let external = |tool: &str| {
+                    optimizer == Optimizer::External && tools.get(tool).find().is_some()
                 };
 
-                Self::Detect(out_img)
+                let ext = out_img
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(str::to_lowercase);
+                match ext.as_deref() {
+                    _ if optimizer == Optimizer::None => copy_image(src, &out_img)?,
+                    Some("jpg" | "jpeg") if external("jpegoptim") => {
+                        optimize_jpg(scheduler, &tools.get("jpegoptim"), src, &out_img).await?;
+                    }
+                    Some("png") if external("oxipng") => {
+                        optimize_png(scheduler, &tools.get("oxipng"), src, &out_img).await?;
+                    }
+                    Some("webp") if external("cwebp") => {
+                        optimize_webp(scheduler, &tools.get("cwebp"), src, &out_img).await?;
+                    }
+                    Some("jpg" | "jpeg" | "png" | "webp") => {
+                        optimize_native(scheduler, src, &out_img).await?;
+                    }
+                    _ => return Err(ImageOpsError::UnsupportedFormat(out_img)),
+                }
+
+                Ok(Self::Detect(out_img))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+/// what optimizing needs from the pipeline, cloned into the tasks so the images are optimized in
+/// parallel, the number of optimizers running at once is limited by the scheduler
+#[derive(Clone)]
+struct OptimizeStage {
+    scheduler: Scheduler,
+    format: Option<String>,
+    wall_dir: PathBuf,
+    tools: ExternalTools,
+    optimizer: Optimizer,
+}
+
+impl OptimizeStage {
+    async fn optimize(&self, img: &WallpaperInput) -> Result<WallpaperInput, ImageOpsError> {
+        img.optimize(
+            &self.scheduler,
+            &self.format,
+            &self.wall_dir,
+            &self.tools,
+            self.optimizer,
+        )
+        .await
+    }
+
+    /// optimizes the images from the receiver as soon as they arrive and sends them on in the
+    /// order they are done
+    async fn run(
+        self,
+        mut rx: tokio::sync::mpsc::Receiver<WallpaperInput>,
+        tx: tokio::sync::mpsc::Sender<WallpaperInput>,
+    ) {
+        let mut optimizing = tokio::task::JoinSet::new();
+        let mut receiving = true;
+
+        while receiving || !optimizing.is_empty() {
+            tokio::select! {
+                img = rx.recv(), if receiving => match img {
+                    Some(img) => {
+                        let stage = self.clone();
+                        optimizing.spawn(async move { stage.optimize(&img).await });
+                    }
+                    None => receiving = false,
+                },
+                Some(res) = optimizing.join_next() => match res {
+                    Ok(Ok(optimized)) => {
+                        if tx.send(optimized).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Err(e)) => e.log("optimize"),
+                    Err(e) => error!("Optimizing stopped unexpectedly: {e}"),
+                },
             }
-            _ => self.clone(),
         }
     }
 }
 
+/// result of upscaling the image at the index
+type Upscaled = (usize, WallpaperInput, Result<WallpaperInput, ImageOpsError>);
+
+/// upscales the images with a worker per gpu, each worker takes the next image as soon as it is
+/// done so faster gpus upscale more of them, the workers stop when the receiver is dropped
+fn upscale_all(
+    scheduler: &Scheduler,
+    images: Vec<WallpaperInput>,
+    format: &Option<String>,
+    tools: &ExternalTools,
+    gpus: &[i32],
+) -> tokio::sync::mpsc::Receiver<Upscaled> {
+    let (tx, rx) = tokio::sync::mpsc::channel(STAGE_CAPACITY);
+    let queue = Arc::new(Mutex::new(images.into_iter().enumerate()));
+    let workers: Vec<_> = if gpus.is_empty() {
+        vec![None]
+    } else {
+        gpus.iter().copied().map(Some).collect()
+    };
+
+    for gpu in workers {
+        let (scheduler, format, tools) = (scheduler.clone(), format.clone(), tools.clone());
+        let (queue, tx) = (Arc::clone(&queue), tx.clone());
+        tokio::spawn(async move {
+            loop {
+                let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                let Some((idx, img)) = next else {
+                    break;
+                };
+                let upscaled = img.upscale(&scheduler, &format, &tools, gpu).await;
+                if tx.send((idx, img, upscaled)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    rx
+}
+
 #[derive(Default)]
 pub struct WallpaperPipeline {
     pub images: Vec<WallpaperInput>,
     format: Option<String>,
     min_width: u32,
     min_height: u32,
+    replace: bool,
+    no_upscale: bool,
+    optimizer: Optimizer,
+    /// only the faces of existing wallpapers are detected, their edited crops are kept
+    detect_only: bool,
     wall_dir: PathBuf,
     resolutions: Vec<AspectRatio>,
+    tools: ExternalTools,
+    crop_options: CropOptions,
+    wallust_args: String,
+    convert_to_srgb: bool,
+    depth_command: String,
+    depth_dir: PathBuf,
+    /// anime, photo, none or command, recorded in the csv with the faces
+    detector: String,
+    /// one of FACE_DETECTORS, used by the anime detector
+    face_detector: String,
+    face_model: PathBuf,
+    photo_face_model: PathBuf,
+    face_command: String,
+    review_upscales: bool,
+    review_crops: bool,
+    /// gpus the upscales are spread across, -1 is the cpu and empty lets realcugan pick
+    upscale_gpus: Vec<i32>,
+    scheduler: Scheduler,
+    /// images upscaled in this run
+    upscales: Vec<UpscaleReview>,
+    /// filenames of the wallpapers that kept their source after rejecting the upscale
+    rejected_upscales: Vec<String>,
+    /// wallpapers added to the csv in this run
+    added: Vec<PathBuf>,
     wallpapers_csv: WallpapersCsv,
 }
 
 impl WallpaperPipeline {
-    pub fn new(cfg: &WallpaperConfig) -> Self {
+    pub fn new(
+        cfg: &WallpaperConfig,
+        args: &WallpapersAddArgs,
+        upscale_gpus: Vec<i32>,
+    ) -> Result<Self, WallpapersError> {
         // create the csv if it doesn't exist
         let mut images = Vec::new();
-        let wallpapers_csv = WallpapersCsv::open().unwrap_or_default();
+        let mut wallpapers_csv = WallpapersCsv::open_or_default()?;
 
-        // do a check for duplicates
-        wallpapers_csv.find_duplicates();
+        // do a check for duplicates, they can be resolved interactively from a terminal
+        if let Err(e) = wallpapers_csv.find_duplicates() {
+            if !std::io::stdin().is_terminal() {
+                return Err(e);
+            }
+            warn!("{e}");
+            dedupe::resolve_duplicates(cfg, &mut wallpapers_csv)?;
+        }
 
-        let args = WallpapersAddArgs::parse();
         let wall_dir = &cfg.wallpapers_path;
 
         // add images from wallpapers dir that are not in the csv
-        for img in filter_images(&wall_dir) {
+        let wall_images =
+            filter_images(wall_dir, &cfg.ignore).map_err(|source| WallpapersError::Read {
+                path: wall_dir.clone(),
+                source,
+            })?;
+        for img in wall_images {
             if wallpapers_csv.get(&filename(&img)).is_none() {
+                debug!("{}: not in wallpapers.csv, detecting faces", filename(&img));
                 images.push(WallpaperInput::Detect(img.clone()));
             }
         }
 
-        Self {
+        let (min_width, min_height) = cfg.strictest_min_size(
+            args.min_width.unwrap_or(cfg.min_width),
+            args.min_height.unwrap_or(cfg.min_height),
+        );
+
+        Ok(Self {
             images,
-            min_width: args.min_width.unwrap_or(cfg.min_width),
-            min_height: args.min_height.unwrap_or(cfg.min_height),
+            min_width,
+            min_height,
+            replace: args.replace,
+            no_upscale: args.no_upscale,
+            optimizer: if args.no_optimize {
+                Optimizer::None
+            } else if cfg.native_optimize {
+                Optimizer::Native
+            } else {
+                Optimizer::External
+            },
+            detect_only: args.detect_only,
             wall_dir: cfg.wallpapers_path.clone(),
-            format: args.format,
+            format: args.format.clone(),
             resolutions: cfg.sorted_resolutions(),
+            tools: cfg.tools.clone(),
+            crop_options: cfg.crop_options(),
+            wallust_args: cfg.wallust_args.clone(),
+            convert_to_srgb: cfg.convert_to_srgb,
+            depth_command: cfg.depth_command.clone(),
+            depth_dir: cfg.depth_dir.clone(),
+            detector: args.detector.clone(),
+            face_detector: cfg.face_detector.clone(),
+            face_model: cfg.face_model.clone(),
+            photo_face_model: cfg.photo_face_model.clone(),
+            face_command: cfg.face_command.clone(),
+            review_upscales: cfg.review_upscales,
+            review_crops: args.review || cfg.review_crops,
+            upscale_gpus,
+            scheduler: Scheduler::new(args.jobs.unwrap_or(cfg.max_jobs)),
+            upscales: Vec::new(),
+            rejected_upscales: Vec::new(),
+            added: Vec::new(),
             wallpapers_csv,
-        }
+        })
     }
 
-    pub fn save_csv(&self) {
-        self.wallpapers_csv.save(&self.resolutions);
+    /// runs the external tools of the pipeline, cancelling it stops the pipeline
+    pub const fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
     }
 
-    pub fn add_image(&mut self, img: &PathBuf) {
-        let (width, height) = image::image_dimensions(img)
-            .unwrap_or_else(|_| panic!("could not get image dimensions for {img:?}"));
+    pub fn save_csv(&mut self) -> Result<(), WallpapersError> {
+        self.wallpapers_csv.save(&self.resolutions)
+    }
+
+    /// adds the image to the pipeline, images that can't be processed are skipped
+    pub async fn add_image(&mut self, img: &PathBuf) {
+        if let Err(e) = self.try_add_image(img).await {
+            error!("{}: {e}", filename(img));
+        }
+    }
+
+    async fn try_add_image(&mut self, img: &PathBuf) -> Result<(), ImageOpsError> {
+        // the wallpaper is detected as is, without converting or processing the input
+        if self.detect_only {
+            let added = self
+                .format
+                .as_ref()
+                .map_or_else(|| img.clone(), |ext| img.with_extension(ext))
+                .with_directory(&self.wall_dir);
+            if !added.exists() {
+                return Err(ImageOpsError::NotAdded(img.clone()));
+            }
+            self.images.push(WallpaperInput::Detect(added));
+            return Ok(());
+        }
+
+        let img = if is_heif(img) {
+            let heif_convert = self.tools.get("heif-convert");
+            let Some(converted) = convert_heif(&self.scheduler, &heif_convert, img).await else {
+                return Ok(());
+            };
+            converted
+        } else {
+            img.clone()
+        };
+
+        // rotate before the dimensions are used, phone photos are often stored sideways
+        let mut img = auto_orient(&img);
+        if self.convert_to_srgb {
+            img = convert_to_srgb(&img);
+        }
+        let img = &img;
+        let (width, height) = dimensions(img)?;
 
         let out_path = self
             .format
@@ -217,65 +999,245 @@ impl WallpaperPipeline {
             .map_or_else(|| img.clone(), |ext| img.with_extension(ext))
             .with_directory(&self.wall_dir);
 
-        if out_path.exists() {
+        // replaced images always go through the full pipeline
+        if out_path.exists() && !self.replace {
             // check if corresponding WallInfo exists
             if let Some(info) = self.wallpapers_csv.get(&filename(&out_path)) {
                 // image has been edited, re-process the image
                 if info.width / width != info.height / height {
+                    debug!("{}: image has been edited, reprocessing", filename(img));
                     self.images.push(WallpaperInput::Upscale((
                         img.clone(),
-                        get_scale_factor(width, height, self.min_width, self.min_height),
+                        self.scale_factor(img, (width, height))?,
                     )));
-                    return;
+                    return Ok(());
                 }
 
                 // re-preview if no / multiple faces detected and still using default crop
-                if info.faces.len() != 1 && info.is_default_crops(&self.resolutions) {
+                if info.faces.len() != 1
+                    && info.is_default_crops(&self.resolutions, self.crop_options)
+                {
+                    debug!(
+                        "{}: already added, previewing default crops",
+                        filename(&out_path)
+                    );
                     self.images.push(WallpaperInput::Preview(out_path));
-                    return;
+                    return Ok(());
                 }
             // no WallInfo, redetect faces to write to csv
             } else {
+                debug!(
+                    "{}: already added but not in wallpapers.csv, detecting faces",
+                    filename(&out_path)
+                );
                 self.images.push(WallpaperInput::Detect(out_path));
-                return;
+                return Ok(());
             }
         }
 
         self.images.push(WallpaperInput::Upscale((
             img.clone(),
-            get_scale_factor(width, height, self.min_width, self.min_height),
+            self.scale_factor(img, (width, height))?,
         )));
+        Ok(())
     }
 
-    pub fn upscale_images(&mut self) {
-        self.images = self
-            .images
-            .iter()
-            .map(|img| img.upscale(&self.format))
-            .collect();
+    /// images are not upscaled with --no-upscale, even if they are too small
+    fn scale_factor(&self, img: &Path, dimensions: (u32, u32)) -> Result<u32, ImageOpsError> {
+        if self.no_upscale {
+            return Ok(1);
+        }
+        get_scale_factor(img, dimensions, self.min_width, self.min_height)
     }
 
-    pub fn optimize_images(&mut self) {
-        println!();
-        self.images = self
-            .images
-            .iter()
-            .map(|img| img.optimize(&self.format, &self.wall_dir))
-            .collect();
+    pub async fn upscale_images(&mut self) {
+        let mut upscaled_rx = upscale_all(
+            &self.scheduler,
+            std::mem::take(&mut self.images),
+            &self.format,
+            &self.tools,
+            &self.upscale_gpus,
+        );
+
+        let mut upscaled = Vec::new();
+        while let Some((idx, img, res)) = upscaled_rx.recv().await {
+            match res {
+                Ok(dest) => upscaled.push((idx, img, dest)),
+                Err(e) => e.log("upscale"),
+            }
+        }
+        // the workers finish in any order
+        upscaled.sort_by_key(|(idx, _, _)| *idx);
+
+        let mut upscales = Vec::new();
+        for (_, img, dest) in &upscaled {
+            if let (WallpaperInput::Upscale((src, scale_factor)), WallpaperInput::Optimize(dest)) =
+                (img, dest)
+            {
+                if *scale_factor > 1 {
+                    upscales.push(UpscaleReview::new(src, dest, *scale_factor));
+                }
+            }
+        }
+        self.images = upscaled.into_iter().map(|(_, _, dest)| dest).collect();
+        self.upscales = upscales;
     }
 
-    pub async fn detect_faces(&mut self) {
-        use tokio::io::{AsyncBufReadExt, BufReader};
-        use tokio::process::Command;
+    /// shows the upscaled images next to their sources in the ui, rejected upscales are
+    /// replaced by their source and tagged so they can be retried later
+    pub fn review_upscales(&mut self) {
+        if !self.review_upscales || self.upscales.is_empty() {
+            return;
+        }
 
+        let review_file = upscale_review::review_file();
+        if let Err(e) = upscale_review::save(&review_file, &self.upscales) {
+            error!("Could not write {review_file:?}: {e}");
+            return;
+        }
+
+        let mut review_arg = std::ffi::OsString::from("--review-upscales=");
+        review_arg.push(&review_file);
+        run_wallpaper_ui([review_arg]);
+
+        let reviews = match upscale_review::load(&review_file) {
+            Ok(reviews) => reviews,
+            Err(e) => {
+                error!("Could not read {review_file:?}: {e}");
+                return;
+            }
+        };
+        std::fs::remove_file(&review_file).ok();
+
+        for review in reviews.iter().filter(|review| review.is_rejected()) {
+            info!("{}: keeping the original image", filename(&review.source));
+
+            for img in &mut self.images {
+                if matches!(img, WallpaperInput::Optimize(dest) if *dest == review.upscaled) {
+                    *img = WallpaperInput::Optimize(review.source.clone());
+                }
+            }
+            // the output has the same name as the upscaled image
+            self.rejected_upscales.push(filename(&review.upscaled));
+        }
+    }
+
+    fn optimize_stage(&self) -> OptimizeStage {
+        OptimizeStage {
+            scheduler: self.scheduler.clone(),
+            format: self.format.clone(),
+            wall_dir: self.wall_dir.clone(),
+            tools: self.tools.clone(),
+            optimizer: self.optimizer,
+        }
+    }
+
+    /// optimizes the images in parallel, keeping their order
+    pub async fn optimize_images(&mut self) {
+        let mut optimizing = tokio::task::JoinSet::new();
+        for (idx, img) in std::mem::take(&mut self.images).into_iter().enumerate() {
+            let stage = self.optimize_stage();
+            optimizing.spawn(async move { (idx, stage.optimize(&img).await) });
+        }
+
+        let mut optimized = Vec::new();
+        while let Some(res) = optimizing.join_next().await {
+            match res {
+                Ok((idx, Ok(img))) => optimized.push((idx, img)),
+                Ok((_, Err(e))) => e.log("optimize"),
+                Err(e) => error!("Optimizing stopped unexpectedly: {e}"),
+            }
+        }
+        optimized.sort_by_key(|(idx, _)| *idx);
+        self.images = optimized.into_iter().map(|(_, img)| img).collect();
+    }
+
+    /// upscales, optimizes and detects the faces of the images, each image moves on to the next
+    /// stage as soon as it is ready so the stages run at the same time
+    ///
+    /// reviewing the upscales needs all of them at once, so the stages run one after another
+    pub async fn process(&mut self) -> Result<(), ImageOpsError> {
+        if self.review_upscales {
+            self.upscale_images().await;
+            self.review_upscales();
+            self.optimize_images().await;
+            return self.detect_faces().await;
+        }
+
+        let (to_upscale, ready): (Vec<_>, Vec<_>) = std::mem::take(&mut self.images)
+            .into_iter()
+            .partition(|img| matches!(img, WallpaperInput::Upscale(_)));
+        let (optimize_tx, optimize_rx) = tokio::sync::mpsc::channel(STAGE_CAPACITY);
+        let (detect_tx, mut detect_rx) = tokio::sync::mpsc::channel(STAGE_CAPACITY);
+
+        // each gpu upscales one image at a time
+        let mut upscaled_rx = upscale_all(
+            &self.scheduler,
+            to_upscale,
+            &self.format,
+            &self.tools,
+            &self.upscale_gpus,
+        );
+        let upscaler = tokio::spawn(async move {
+            while let Some((_, _, res)) = upscaled_rx.recv().await {
+                match res {
+                    Ok(upscaled) => {
+                        if optimize_tx.send(upscaled).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => e.log("upscale"),
+                }
+            }
+        });
+
+        // the gpus upscale one image at a time each, while the optimizers run in parallel
+        let optimizer = tokio::spawn(self.optimize_stage().run(optimize_rx, detect_tx));
+
+        // images that don't need upscaling are detected while the others are being upscaled, then
+        // faces are detected in batches of the images that are ready
+        let mut to_preview = self.detect_batch(ready).await?;
+        while let Some(img) = detect_rx.recv().await {
+            let mut batch = vec![img];
+            while let Ok(img) = detect_rx.try_recv() {
+                batch.push(img);
+            }
+            to_preview.extend(self.detect_batch(batch).await?);
+        }
+
+        for (stage, task) in [("Upscaling", upscaler), ("Optimizing", optimizer)] {
+            if let Err(e) = task.await {
+                error!("{stage} stopped unexpectedly: {e}");
+            }
+        }
+
+        self.images = to_preview;
+        self.wallpapers_csv.save(&self.resolutions)?;
+        Ok(())
+    }
+
+    /// detects the faces of the optimized images and adds them to wallpapers.csv, images that
+    /// fail are skipped
+    pub async fn detect_faces(&mut self) -> Result<(), ImageOpsError> {
+        let images = std::mem::take(&mut self.images);
+        self.images = self.detect_batch(images).await?;
+        self.wallpapers_csv.save(&self.resolutions)?;
+        Ok(())
+    }
+
+    /// detects the faces of the optimized images and adds them to the csv without saving it,
+    /// returns the images to preview
+    async fn detect_batch(
+        &mut self,
+        images: Vec<WallpaperInput>,
+    ) -> Result<Vec<WallpaperInput>, ImageOpsError> {
         let mut to_preview = Vec::new();
-        let paths: Vec<_> = self
-            .images
+        let paths: Vec<_> = images
             .iter()
             .filter_map(|img| match img {
-                WallpaperInput::Upscale(_) | WallpaperInput::Optimize(_) => {
-                    eprintln!("Detect: got unprocessed image: {:?}", &img);
-                    std::process::exit(1);
+                WallpaperInput::Upscale((path, _)) | WallpaperInput::Optimize(path) => {
+                    error!("Could not detect faces: got unprocessed image {path:?}");
+                    None
                 }
                 WallpaperInput::Detect(path) => Some(path),
                 WallpaperInput::Preview(_) => {
@@ -285,42 +1247,95 @@ impl WallpaperPipeline {
             })
             .collect();
 
+        if paths.is_empty() {
+            return Ok(to_preview);
+        }
+
         // wait for all images before proceeding
         for path in &paths {
-            wait_for_image(path);
+            wait_for_image(path).await;
         }
 
-        println!();
-        let mut child = Command::new("anime-face-detector")
-            .args(&paths)
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("failed to spawn anime-face-detector");
-
-        let reader = BufReader::new(
-            child
-                .stdout
-                .take()
-                .expect("failed to read stdout of anime-face-detector"),
-        );
-        let mut lines = reader.lines();
-        let mut paths_iter = paths.iter();
+        let detected = match self.detector.as_str() {
+            "none" => Self::detect_none(&paths),
+            "photo" => self.detect_onnx(&paths, &self.photo_face_model).await?,
+            "command" => {
+                let args: Vec<_> = self.face_command.split_whitespace().collect();
+                let Some((program, args)) = args.split_first() else {
+                    return Err(ImageOpsError::MissingFaceCommand);
+                };
+                let mut command = Command::new(program);
+                command.args(args);
+                self.detect_external("face_command", command, &paths)
+                    .await?
+            }
+            _ if self.face_detector == "onnx" => self.detect_onnx(&paths, &self.face_model).await?,
+            _ => {
+                let tool = self.tools.get("anime-face-detector");
+                // machines without the python detector use the onnx model instead
+                if tool.find().is_none() {
+                    if !cfg!(feature = "onnx") {
+                        return Err(ImageOpsError::MissingFaceDetector);
+                    }
+                    warn!("anime-face-detector not found, detecting faces with the onnx model");
+                    self.detect_onnx(&paths, &self.face_model).await?
+                } else {
+                    self.detect_external("anime-face-detector", tool.command(), &paths)
+                        .await?
+                }
+            }
+        };
 
-        // read each line of anime-face-detector's output async
-        while let (Some(path), Ok(Some(line))) = (paths_iter.next(), lines.next_line().await) {
-            let fname = filename(path);
-            println!("Detecting faces in {fname}...");
+        // the crops of each image are independent, so they are computed in parallel after the
+        // detector is done
+        let crop_options = self.crop_options;
+        let resolutions = &self.resolutions;
+        let geometries: Vec<HashMap<AspectRatio, Geometry>> = detected
+            .par_iter()
+            .map(|(_, _, faces, width, height)| {
+                let cropper = Cropper::new(faces, *width, *height).with_options(crop_options);
+                resolutions
+                    .iter()
+                    .map(|ratio| (ratio.clone(), cropper.crop(ratio)))
+                    .collect()
+            })
+            .collect();
 
-            let faces: Vec<FaceJson> =
-                serde_json::from_str(&line).expect("could not deserialize faces");
-            let faces: Vec<_> = faces
-                .into_iter()
-                .map(|f: FaceJson| FaceJson::to_face(&f))
-                .collect();
+        for ((path, fname, faces, width, height), mut geometries) in
+            detected.into_iter().zip(geometries)
+        {
+            // keep the tags, palette and depth map of existing wallpapers
+            let existing = self.wallpapers_csv.get(&fname);
 
-            let (width, height) = image::image_dimensions(path)
-                .unwrap_or_else(|_| panic!("could not get image dimensions: {fname:?}"));
-            let cropper = Cropper::new(&faces, width, height);
+            // crops that were edited are kept when only redetecting the faces
+            if let Some(info) = existing
+                .filter(|info| self.detect_only && (info.width, info.height) == (width, height))
+            {
+                let cropper = info.cropper().with_options(crop_options);
+                for ratio in resolutions {
+                    let geom = info.get_geometry(ratio);
+                    if geom != cropper.crop(ratio) {
+                        geometries.insert(ratio.clone(), geom);
+                    }
+                }
+            }
+            let mut tags = existing.map(|info| info.tags.clone()).unwrap_or_default();
+            if self.rejected_upscales.contains(&fname) && !tags.iter().any(|t| t == REJECTED_TAG) {
+                tags.push(REJECTED_TAG.to_string());
+            }
+            let depth = existing.map(|info| info.depth.clone()).unwrap_or_default();
+            let schedule = existing
+                .map(|info| info.schedule.clone())
+                .unwrap_or_default();
+            let wallust =
+                existing.map_or_else(|| self.wallust_args.clone(), |info| info.wallust.clone());
+            // wallpapers with no or multiple faces are previewed, so their crops still need work
+            let previewed = self.detector != "none" && faces.len() != 1;
+            let state = match existing {
+                Some(info) if self.detect_only => info.state.clone(),
+                _ if previewed => "needs-crop".to_string(),
+                _ => "new".to_string(),
+            };
 
             // create WallInfo and save it
             let wall_info = WallInfo {
@@ -328,29 +1343,294 @@ impl WallpaperPipeline {
                 width,
                 height,
                 faces,
-                geometries: self
-                    .resolutions
-                    .iter()
-                    .map(|ratio| (ratio.clone(), cropper.crop(ratio)))
-                    .collect(),
-                wallust: String::new(),
+                geometries,
+                wallust,
+                tags,
+                depth,
+                detector: self.detector.clone(),
+                state,
+                schedule,
             };
 
-            // preview both multiple faces and no faces
-            if wall_info.faces.len() != 1 {
+            // preview both multiple faces and no faces, unless detection was skipped
+            if previewed {
                 to_preview.push(WallpaperInput::Preview(path.with_directory(&self.wall_dir)));
             }
 
             self.wallpapers_csv.insert(fname, wall_info);
+            self.added.push(path.with_directory(&self.wall_dir));
         }
 
-        self.wallpapers_csv.save(&self.resolutions);
+        Ok(to_preview)
+    }
 
-        self.images = to_preview;
+    /// skips face detection, e.g. for abstract wallpapers, so the default crops are centered
+    fn detect_none(paths: &[&PathBuf]) -> Vec<Detected> {
+        paths
+            .iter()
+            .filter_map(|path| {
+                let fname = filename(path);
+                match dimensions(path) {
+                    Ok((width, height)) => {
+                        Some(((*path).clone(), fname, Vec::new(), width, height))
+                    }
+                    Err(e) => {
+                        error!("{fname}: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// detects the faces with an external command like anime-face-detector, which prints the
+    /// faces of each image as a line of json, with an optional eye_y for eye_line
+    async fn detect_external(
+        &self,
+        tool: &str,
+        mut command: Command,
+        paths: &[&PathBuf],
+    ) -> Result<Vec<Detected>, ImageOpsError> {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        command.args(paths);
+        let name = format!("Detecting faces in {} images", paths.len());
+        let job = Job::new(tool, name, command).stage("detect").piped();
+        let mut job = match self.scheduler.spawn(job).await {
+            Ok(job) => job,
+            Err(JobError::Cancelled { .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let Some(stdout) = job.stdout() else {
+            return Err(JobError::Spawn {
+                tool: tool.to_string(),
+                source: std::io::Error::other("could not read stdout"),
+            }
+            .into());
+        };
+        let reader = BufReader::new(stdout);
+        let mut lines = reader.lines();
+        let mut detected = Vec::new();
+
+        // read each line of the detector's output async, the images detected before a
+        // cancellation are still added
+        for path in &paths {
+            let line = tokio::select! {
+                line = lines.next_line() => line,
+                () = self.scheduler.cancelled() => break,
+            };
+            let Ok(Some(line)) = line else {
+                break;
+            };
+            let fname = filename(path);
+            debug!("{fname}: detected faces");
+
+            let faces = serde_json::from_str::<Vec<FaceJson>>(&line).map_err(|source| {
+                ImageOpsError::Faces {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            });
+            let (faces, (width, height)) =
+                match faces.and_then(|faces| Ok((faces, dimensions(path)?))) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        error!("{fname}: {e}");
+                        continue;
+                    }
+                };
+            let faces: Vec<_> = faces
+                .into_iter()
+                .map(|f: FaceJson| FaceJson::to_face(&f))
+                .collect();
+            detected.push(((*path).clone(), fname, faces, width, height));
+        }
+
+        match job.wait().await {
+            Ok(()) | Err(JobError::Cancelled { .. }) => {}
+            Err(e) => warn!("{e}"),
+        }
+
+        Ok(detected)
+    }
+
+    /// detects the faces with the onnx model in process, the images are detected in parallel on
+    /// the blocking threads
+    #[cfg(feature = "onnx")]
+    async fn detect_onnx(
+        &self,
+        paths: &[&PathBuf],
+        model: &Path,
+    ) -> Result<Vec<Detected>, ImageOpsError> {
+        // the embedded model only detects anime faces
+        if self.detector == "photo" && !model.exists() {
+            return Err(
+                face_detection::FaceDetectionError::MissingModel(model.to_path_buf()).into(),
+            );
+        }
+
+        let model = model.to_path_buf();
+        let scheduler = self.scheduler.clone();
+        let paths: Vec<PathBuf> = paths.iter().map(|path| (*path).clone()).collect();
+        info!("Detecting faces in {} images", paths.len());
+
+        tokio::task::spawn_blocking(move || {
+            let detector = face_detection::detector(&model)?;
+            Ok(paths
+                .into_par_iter()
+                .filter_map(|path| {
+                    // the images detected before a cancellation are still added
+                    if scheduler.is_cancelled() {
+                        return None;
+                    }
+                    let fname = filename(&path);
+                    match detector.detect_path(&path) {
+                        Ok((faces, (width, height))) => {
+                            debug!("{fname}: detected faces");
+                            Some((path, fname, faces, width, height))
+                        }
+                        Err(e) => {
+                            error!("{fname}: {e}");
+                            None
+                        }
+                    }
+                })
+                .collect())
+        })
+        .await
+        .map_err(|e| ImageOpsError::Io(std::io::Error::other(e)))?
+    }
+
+    /// async to match the onnx version
+    #[cfg(not(feature = "onnx"))]
+    #[allow(clippy::unused_async)]
+    async fn detect_onnx(
+        &self,
+        _paths: &[&PathBuf],
+        _model: &Path,
+    ) -> Result<Vec<Detected>, ImageOpsError> {
+        Err(ImageOpsError::OnnxDisabled)
+    }
+
+    /// runs the post_add_hook with the wallpapers added in this run
+    pub fn run_post_add_hook(&self, cfg: &WallpaperConfig) {
+        let infos: Vec<_> = self
+            .added
+            .iter()
+            .filter_map(|path| self.wallpapers_csv.get(&filename(path)).cloned())
+            .collect();
+        hooks::post_add(cfg, &infos);
+    }
+
+    /// runs wallust on the added wallpapers, so their palettes are cached before they are applied
+    ///
+    /// the wallust column keeps the arguments instead of the palette, running wallust with them
+    /// later, e.g. with $WALLPAPER_WALLUST in a hook or {wallust} in a command, reads the palette
+    /// from its cache
+    pub async fn generate_palettes(&self) {
+        let wallust = self.tools.get("wallust");
+        let mut palettes = tokio::task::JoinSet::new();
+
+        for path in &self.added {
+            let fname = filename(path);
+            let Some(info) = self.wallpapers_csv.get(&fname) else {
+                continue;
+            };
+
+            let mut command = wallust.command();
+            command
+                .arg("run")
+                .args([
+                    "--quiet",
+                    "--check-contrast",
+                    "--skip-sequences",
+                    "--skip-templates",
+                ])
+                .args(info.wallust.split_whitespace())
+                .arg(path);
+            let job = Job::new(
+                "wallust",
+                format!("Generating palette for {fname}"),
+                command,
+            );
+
+            let scheduler = self.scheduler.clone();
+            palettes.spawn(async move { (fname, scheduler.run(job).await) });
+        }
+
+        while let Some(res) = palettes.join_next().await {
+            match res {
+                Ok((_, Ok(()) | Err(JobError::Cancelled { .. }))) => {}
+                Ok((fname, Err(e))) => warn!("{fname}: {e}"),
+                Err(e) => warn!("Generating palettes stopped unexpectedly: {e}"),
+            }
+        }
+    }
+
+    /// runs depth_command on the added wallpapers and stores the paths of the depth maps
+    pub async fn generate_depth_maps(&mut self) {
+        if self.depth_command.is_empty() {
+            return;
+        }
+
+        if let Err(e) = std::fs::create_dir_all(&self.depth_dir) {
+            warn!("could not create {:?}: {e}", self.depth_dir);
+            return;
+        }
+
+        for path in &self.added {
+            let fname = filename(path);
+            let Some(info) = self.wallpapers_csv.get(&fname) else {
+                continue;
+            };
+
+            // replaced wallpapers need a new depth map
+            let output = path.with_directory(&self.depth_dir).with_extension("png");
+            std::fs::remove_file(&output).ok();
+
+            let args: Vec<_> = self
+                .depth_command
+                .split_whitespace()
+                .map(|arg| {
+                    arg.replace("{input}", &path.to_string_lossy())
+                        .replace("{output}", &output.to_string_lossy())
+                })
+                .collect();
+            let Some((program, args)) = args.split_first() else {
+                continue;
+            };
+
+            let mut command = Command::new(program);
+            command.args(args);
+            let job = Job::new(
+                "depth_command",
+                format!("Generating depth map for {fname}"),
+                command,
+            )
+            .output(&output);
+
+            match self.scheduler.run(job).await {
+                Ok(()) if output.exists() => {
+                    let mut info = info.clone();
+                    info.depth = output.to_string_lossy().to_string();
+                    self.wallpapers_csv.insert(fname, info);
+                }
+                Ok(()) => warn!("{fname}: depth_command did not write {output:?}"),
+                Err(JobError::Cancelled { .. }) => break,
+                Err(e) => warn!("{fname}: {e}"),
+            }
+        }
+
+        if let Err(e) = self.wallpapers_csv.save(&self.resolutions) {
+            error!("Could not save the depth maps: {e}");
+        }
     }
 
-    pub fn preview(self) {
-        let preview_images: Vec<_> = self
+    /// opens the editor with the wallpapers that have no or multiple faces, when reviewing the
+    /// crops all the new wallpapers are reviewed first and only the rejected ones are edited
+    pub fn preview(mut self) {
+        let mut preview_images: Vec<_> = self
             .images
             .into_iter()
             .filter_map(|img| match img {
@@ -359,8 +1639,73 @@ impl WallpaperPipeline {
             })
             .collect();
 
+        if self.review_crops {
+            let mut to_review = self.added;
+            for path in preview_images {
+                if !to_review.contains(&path) {
+                    to_review.push(path);
+                }
+            }
+            preview_images = review_crops(to_review.clone());
+
+            // the accepted crops are done, the rest are edited next
+            for path in &to_review {
+                let state = if preview_images.contains(path) {
+                    "needs-crop"
+                } else {
+                    "reviewed"
+                };
+                if let Some(info) = self.wallpapers_csv.get_mut(&filename(path)) {
+                    info.state = state.to_string();
+                }
+            }
+            if let Err(e) = self.wallpapers_csv.save(&self.resolutions) {
+                error!("Could not save the review states: {e}");
+            }
+        }
+
         if !preview_images.is_empty() {
             run_wallpaper_ui(preview_images);
         }
     }
 }
+
+/// shows the default crops of the wallpapers in the ui to accept them or queue them for editing,
+/// returns the wallpapers to edit
+fn review_crops(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    if paths.is_empty() {
+        return paths;
+    }
+
+    let review_file = crop_review::review_file();
+    let reviews: Vec<_> = paths.iter().map(|path| CropReview::new(path)).collect();
+    if let Err(e) = crop_review::save(&review_file, &reviews) {
+        error!("Could not write {review_file:?}: {e}");
+        return paths;
+    }
+
+    let mut review_arg = std::ffi::OsString::from("--review-crops=");
+    review_arg.push(&review_file);
+    run_wallpaper_ui([review_arg]);
+
+    let reviews = match crop_review::load(&review_file) {
+        Ok(reviews) => reviews,
+        Err(e) => {
+            error!("Could not read {review_file:?}: {e}");
+            return paths;
+        }
+    };
+    std::fs::remove_file(&review_file).ok();
+
+    let to_edit: Vec<_> = reviews
+        .into_iter()
+        .filter(CropReview::needs_editing)
+        .map(|review| review.path)
+        .collect();
+    info!(
+        "{} of {} wallpapers queued for editing",
+        to_edit.len(),
+        paths.len()
+    );
+    to_edit
+}
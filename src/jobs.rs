@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use thiserror::Error;
+use tokio::{
+    process::{Child, ChildStdout, Command},
+    sync::{broadcast, watch, OwnedSemaphorePermit, Semaphore},
+};
+use tracing::{info, warn};
+
+/// progress events kept for subscribers that fall behind
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("could not run {tool}: {source}")]
+    Spawn {
+        tool: String,
+        source: std::io::Error,
+    },
+    #[error("{tool} exited with {status}")]
+    Failed { tool: String, status: ExitStatus },
+    #[error("{tool} was cancelled")]
+    Cancelled { tool: String },
+    #[error("{tool} failed: {message}")]
+    InProcess { tool: String, message: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// progress of a job, done and total count all the jobs of the scheduler so far and the stage
+/// counts only the jobs of its stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobEvent {
+    pub name: String,
+    pub stage: String,
+    pub state: JobState,
+    pub done: usize,
+    pub total: usize,
+    pub stage_done: usize,
+    pub stage_total: usize,
+}
+
+/// an external tool run by the scheduler, its stdout is discarded unless it is piped
+pub struct Job {
+    tool: String,
+    stage: String,
+    name: String,
+    command: Command,
+    outputs: Vec<PathBuf>,
+}
+
+impl Job {
+    /// the name is shown in the progress, e.g. "Upscaling wallpaper.png"
+    pub fn new(tool: &str, name: impl Into<String>, command: std::process::Command) -> Self {
+        let mut command = Command::from(command);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true);
+
+        Self {
+            tool: tool.to_string(),
+            stage: tool.to_string(),
+            name: name.into(),
+            command,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// the progress of the stage, e.g. optimize, counts the jobs of all its tools, the stage is
+    /// the tool by default
+    #[must_use]
+    pub fn stage(mut self, stage: &str) -> Self {
+        self.stage = stage.to_string();
+        self
+    }
+
+    /// the file is removed if the job fails or is cancelled, so partially written files aren't
+    /// picked up later
+    #[must_use]
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.outputs.push(path.into());
+        self
+    }
+
+    /// discards stderr, for tools that are noisy even when they succeed
+    #[must_use]
+    pub fn quiet(mut self) -> Self {
+        self.command.stderr(Stdio::null());
+        self
+    }
+
+    /// pipes stdout, for tools that stream their results
+    #[must_use]
+    pub fn piped(mut self) -> Self {
+        self.command.stdout(Stdio::piped());
+        self
+    }
+}
+
+/// runs the external tools used by wallpapers-add and the ui, limiting how many run at once
+///
+/// cancelling kills the running jobs and removes their outputs, jobs started afterwards are
+/// cancelled right away
+#[derive(Clone)]
+pub struct Scheduler {
+    permits: Arc<Semaphore>,
+    cancel: Arc<watch::Sender<bool>>,
+    events: broadcast::Sender<JobEvent>,
+    total: Arc<AtomicUsize>,
+    done: Arc<AtomicUsize>,
+    /// done and total jobs of each stage
+    stages: Arc<Mutex<HashMap<String, (usize, usize)>>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get))
+    }
+}
+
+impl Scheduler {
+    pub fn new(max_jobs: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_jobs.max(1))),
+            cancel: Arc::new(watch::channel(false).0),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            total: Arc::default(),
+            done: Arc::default(),
+            stages: Arc::default(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.send_replace(true);
+        self.permits.close();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    /// resolves once the scheduler is cancelled
+    pub async fn cancelled(&self) {
+        let mut cancel = self.cancel.subscribe();
+        // the sender lives as long as the scheduler
+        cancel.wait_for(|cancelled| *cancelled).await.ok();
+    }
+
+    /// cancels on ctrl+c instead of exiting right away, so the children are killed and their
+    /// outputs are removed
+    pub fn cancel_on_ctrl_c(&self) {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Cancelling...");
+                scheduler.cancel();
+            }
+        });
+    }
+
+    /// logs each job as it starts with the number of jobs done so far
+    pub fn log_progress(&self) {
+        let mut events = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(JobEvent {
+                        name,
+                        stage,
+                        state: JobState::Running,
+                        done,
+                        total,
+                        stage_done,
+                        stage_total,
+                    }) => info!("[{done}/{total}] {name}... ({stage} {stage_done}/{stage_total})"),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// counts a new job of the stage
+    fn add(&self, stage: &str) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        let mut stages = self.stages.lock().unwrap_or_else(PoisonError::into_inner);
+        stages.entry(stage.to_string()).or_default().1 += 1;
+    }
+
+    fn emit(&self, name: &str, stage: &str, state: JobState) {
+        let done = if state == JobState::Running {
+            self.done.load(Ordering::Relaxed)
+        } else {
+            self.done.fetch_add(1, Ordering::Relaxed) + 1
+        };
+
+        let (stage_done, stage_total) = {
+            let mut stages = self.stages.lock().unwrap_or_else(PoisonError::into_inner);
+            let progress = stages.entry(stage.to_string()).or_default();
+            if state != JobState::Running {
+                progress.0 += 1;
+            }
+            *progress
+        };
+
+        // there might not be anyone listening
+        self.events
+            .send(JobEvent {
+                name: name.to_string(),
+                stage: stage.to_string(),
+                state,
+                done,
+                total: self.total.load(Ordering::Relaxed),
+                stage_done,
+                stage_total,
+            })
+            .ok();
+    }
+
+    /// starts the job once there is a free slot
+    pub async fn spawn(&self, mut job: Job) -> Result<RunningJob, JobError> {
+        self.add(&job.stage);
+
+        // the semaphore is closed when cancelled
+        let Ok(permit) = Arc::clone(&self.permits).acquire_owned().await else {
+            self.emit(&job.name, &job.stage, JobState::Cancelled);
+            return Err(JobError::Cancelled { tool: job.tool });
+        };
+
+        let child = match job.command.spawn() {
+            Ok(child) => child,
+            Err(source) => {
+                self.emit(&job.name, &job.stage, JobState::Failed);
+                return Err(JobError::Spawn {
+                    tool: job.tool,
+                    source,
+                });
+            }
+        };
+        self.emit(&job.name, &job.stage, JobState::Running);
+
+        Ok(RunningJob {
+            scheduler: self.clone(),
+            tool: job.tool,
+            stage: job.stage,
+            name: job.name,
+            outputs: job.outputs,
+            child,
+            finished: false,
+            _permit: permit,
+        })
+    }
+
+    /// runs the job to completion
+    pub async fn run(&self, job: Job) -> Result<(), JobError> {
+        self.spawn(job).await?.wait().await
+    }
+
+    /// runs work in process on the blocking threads like a job, it can't be interrupted so the
+    /// output is removed after it finishes if the scheduler was cancelled meanwhile
+    pub async fn run_blocking<F, E>(
+        &self,
+        tool: &str,
+        stage: &str,
+        name: impl Into<String>,
+        output: &Path,
+        work: F,
+    ) -> Result<(), JobError>
+    where
+        F: FnOnce() -> Result<(), E> + Send + 'static,
+        E: std::fmt::Display + Send + 'static,
+    {
+        let name = name.into();
+        self.add(stage);
+
+        let Ok(_permit) = Arc::clone(&self.permits).acquire_owned().await else {
+            self.emit(&name, stage, JobState::Cancelled);
+            return Err(JobError::Cancelled {
+                tool: tool.to_string(),
+            });
+        };
+        self.emit(&name, stage, JobState::Running);
+
+        let res = tokio::task::spawn_blocking(work)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|res| res.map_err(|e| e.to_string()));
+        let res = match res {
+            _ if self.is_cancelled() => Err(JobError::Cancelled {
+                tool: tool.to_string(),
+            }),
+            Ok(()) => Ok(()),
+            Err(message) => Err(JobError::InProcess {
+                tool: tool.to_string(),
+                message,
+            }),
+        };
+
+        let state = match &res {
+            Ok(()) => JobState::Finished,
+            Err(JobError::Cancelled { .. }) => JobState::Cancelled,
+            Err(_) => JobState::Failed,
+        };
+        if state != JobState::Finished {
+            std::fs::remove_file(output).ok();
+        }
+        self.emit(&name, stage, state);
+        res
+    }
+}
+
+/// a started job, the child is killed and the outputs are removed if it is dropped before it
+/// finishes
+pub struct RunningJob {
+    scheduler: Scheduler,
+    tool: String,
+    stage: String,
+    name: String,
+    outputs: Vec<PathBuf>,
+    child: Child,
+    finished: bool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl RunningJob {
+    /// stdout of jobs that are piped, can only be taken once
+    pub fn stdout(&mut self) -> Option<ChildStdout> {
+        self.child.stdout.take()
+    }
+
+    pub async fn wait(mut self) -> Result<(), JobError> {
+        let mut cancel = self.scheduler.cancel.subscribe();
+        let res = tokio::select! {
+            status = self.child.wait() => match status {
+                Ok(status) if status.success() => Ok(()),
+                Ok(status) => Err(JobError::Failed {
+                    tool: self.tool.clone(),
+                    status,
+                }),
+                Err(source) => Err(JobError::Spawn {
+                    tool: self.tool.clone(),
+                    source,
+                }),
+            },
+            _ = cancel.wait_for(|cancelled| *cancelled) => {
+                self.child.kill().await.ok();
+                Err(JobError::Cancelled {
+                    tool: self.tool.clone(),
+                })
+            }
+        };
+
+        self.finish(match &res {
+            Ok(()) => JobState::Finished,
+            Err(JobError::Cancelled { .. }) => JobState::Cancelled,
+            Err(_) => JobState::Failed,
+        });
+        res
+    }
+
+    fn finish(&mut self, state: JobState) {
+        self.finished = true;
+        if state != JobState::Finished {
+            for output in &self.outputs {
+                std::fs::remove_file(output).ok();
+            }
+        }
+        self.scheduler.emit(&self.name, &self.stage, state);
+    }
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.child.start_kill().ok();
+            self.finish(JobState::Cancelled);
+        }
+    }
+}
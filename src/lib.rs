@@ -1,39 +1,59 @@
-use serde::Deserialize;
+//! face aware wallpaper cropping
+//!
+//! [`Cropper`], [`Geometry`], [`AspectRatio`] and [`WallInfo`] are re-exported at the crate root
+//! and follow semver, the other modules are used by the binaries and can change at any time.
+//! the config, geometry, cropper and wallpapers.csv are in the wallpaper-core crate and are
+//! re-exported here, depend on wallpaper-core directly to use them without the ui
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
+    io::Write,
     path::{Path, PathBuf},
     process::Command,
 };
-use wallpapers::Face;
+use tracing::{error, warn};
 
-pub mod aspect_ratio;
+pub mod apply;
+pub mod cache;
 pub mod cli;
-pub mod config;
-pub mod cropper;
-pub mod geometry;
+pub mod colorscheme;
+pub mod commands;
+pub mod crop_review;
+pub mod custom_commands;
+pub mod daemon;
+pub mod doctor;
+pub mod export;
+#[cfg(feature = "onnx")]
+pub mod face_detection;
+pub mod hooks;
+pub mod i18n;
 pub mod image_ops;
-pub mod wallpapers;
-
-pub fn full_path(p: &str) -> PathBuf {
-    p.strip_prefix("~/").map_or_else(
-        || PathBuf::from(p),
-        |p| {
-            dirs::home_dir()
-                .expect("could not get home directory")
-                .join(p)
-        },
-    )
-}
+pub mod jobs;
+pub mod logging;
+pub mod monitors;
+pub mod optimizers;
+pub mod trash;
+pub mod upscale_review;
+pub mod validation;
+
+pub use wallpaper_core::{
+    aspect_ratio, config, cropper, full_path, geometry, paths, schedule, store, tools, wallpapers,
+    write_atomic, AspectRatio, CropOptions, Cropper, Direction, Face, Geometry, Padding, WallInfo,
+};
 
+#[cfg(feature = "sqlite")]
+pub use wallpaper_core::sqlite;
+
+/// the file name of the path, the images are only found if their names are valid utf-8 so the
+/// lossy conversion only changes names of other files, paths without a file name are empty
 pub fn filename<P>(path: P) -> String
 where
     P: AsRef<Path> + std::fmt::Debug,
 {
     path.as_ref()
         .file_name()
-        .unwrap_or_else(|| panic!("could not get filename: {:?}", path))
-        .to_str()
-        .unwrap_or_else(|| panic!("could not convert filename to str: {:?}", path))
-        .to_string()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
 }
 
 // extend PathBuf with utility methods
@@ -55,32 +75,217 @@ impl PathBufExt for PathBuf {
     }
 }
 
+/// exits with the errors instead of returning them, the core crate never exits so this is only
+/// for the commands, the ui and the api show the errors instead
+pub trait WallpaperConfigExt {
+    /// loads config.ini, exits with all the errors in the config if it is invalid
+    fn new() -> Self;
+}
+
+impl WallpaperConfigExt for config::WallpaperConfig {
+    fn new() -> Self {
+        Self::load().unwrap_or_else(|errors| {
+            for e in &errors.0 {
+                error!("{e}");
+            }
+            error!("Invalid config at {:?}", Self::config_file());
+            std::process::exit(1);
+        })
+    }
+}
+
+/// exits with the error instead of returning it, only for the commands like
+/// [`WallpaperConfigExt`]
+pub trait WallpapersCsvExt {
+    /// reads the wallpapers from the store of the config, exits with the error if they can't be
+    /// read
+    fn load() -> Self;
+
+    /// streams the wallpapers of the store, exits with the error if they can't be read
+    fn load_stream(config: &config::WallpaperConfig) -> wallpapers::WallpapersReader;
+}
+
+impl WallpapersCsvExt for wallpapers::WallpapersCsv {
+    fn load() -> Self {
+        Self::open().unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(1);
+        })
+    }
+
+    fn load_stream(config: &config::WallpaperConfig) -> wallpapers::WallpapersReader {
+        Self::open_stream(config).unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(1);
+        })
+    }
+}
+
+/// the extension is one of the supported image formats in any case, e.g. IMG_0001.HEIC from a
+/// phone, without checking the file
+///
+/// the name has to be valid utf-8 to be stored in wallpapers.csv
+fn has_image_extension(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_str().is_some())
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                matches!(
+                    ext.to_lowercase().as_str(),
+                    "jpg" | "jpeg" | "png" | "webp" | "heic" | "heif"
+                )
+            })
+}
+
 pub fn is_image<P>(path: P) -> Option<PathBuf>
 where
     P: AsRef<Path>,
 {
     let p = path.as_ref();
-    if p.is_file() {
-        if let Some(ext) = p.extension() {
-            match ext.to_str() {
-                Some("jpg" | "jpeg" | "png" | "webp") => return Some(p.to_path_buf()),
-                _ => return None,
-            }
-        }
-    }
+    (p.is_file() && has_image_extension(p)).then(|| p.to_path_buf())
+}
 
-    None
+/// heic / heif images can't be decoded directly and need to be converted first
+pub fn is_heif<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "heic" | "heif"))
 }
 
-pub fn filter_images<P>(dir: P) -> impl Iterator<Item = PathBuf>
+/// checks if the path relative to the directory matches any of the ignore patterns
+pub fn is_ignored(path: &Path, dir: &Path, ignore: &[glob::Pattern]) -> bool {
+    let rel = path.strip_prefix(dir).unwrap_or(path);
+    ignore.iter().any(|pattern| pattern.matches_path(rel))
+}
+
+pub fn filter_images<'a, P>(
+    dir: P,
+    ignore: &'a [glob::Pattern],
+) -> std::io::Result<impl Iterator<Item = PathBuf> + 'a>
 where
     P: AsRef<Path> + std::fmt::Debug,
 {
-    dir.as_ref()
-        .read_dir()
-        .unwrap_or_else(|_| panic!("could not read {:?}", &dir))
+    let dir = dir.as_ref().to_path_buf();
+
+    Ok(dir
+        .read_dir()?
         .flatten()
         .filter_map(|entry| is_image(entry.path()))
+        .filter(move |img| !is_ignored(img, &dir, ignore)))
+}
+
+/// finds the images in a directory, the file types come from the directory listing so only
+/// symlinks are read, in parallel as each read is slow on network filesystems
+///
+/// entries that can't be read are skipped
+pub fn scan_images(dir: &Path, ignore: &[glob::Pattern]) -> std::io::Result<Vec<PathBuf>> {
+    let entries: Vec<_> = dir.read_dir()?.flatten().collect();
+
+    Ok(entries
+        .into_par_iter()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if !has_image_extension(&path) || is_ignored(&path, dir, ignore) {
+                return None;
+            }
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() => is_image(path),
+                Ok(file_type) if file_type.is_file() => Some(path),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+/// recursively finds the images in a directory and all of its subdirectories, subdirectories
+/// that can't be read are skipped with a warning
+pub fn filter_images_recursive<P>(dir: P, ignore: &[glob::Pattern]) -> std::io::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path> + std::fmt::Debug,
+{
+    let root = dir.as_ref();
+    let mut images = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(e) if dir != root => {
+                warn!("could not read {dir:?}: {e}");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Some(img) = is_image(path) {
+                if !is_ignored(&img, root, ignore) {
+                    images.push(img);
+                }
+            }
+        }
+    }
+
+    images.sort();
+    Ok(images)
+}
+
+/// expands a path containing glob patterns, paths without any patterns are returned as is
+pub fn expand_glob<P>(path: P) -> Vec<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let pattern = path.to_string_lossy();
+
+    if path.exists() || !pattern.contains(['*', '?', '[']) {
+        return vec![path.to_path_buf()];
+    }
+
+    glob::glob(&pattern).map_or_else(
+        |_| {
+            warn!("Invalid glob pattern: {pattern}");
+            Vec::new()
+        },
+        |paths| paths.flatten().collect(),
+    )
+}
+
+/// reads the answer to the prompt from stdin, trimmed and lowercased
+pub fn ask(prompt: &str) -> String {
+    print!("{prompt} ");
+    std::io::stdout().flush().expect("could not flush stdout");
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .expect("could not read from stdin");
+
+    answer.trim().to_lowercase()
+}
+
+/// asks a yes / no question on stdin, defaults to no
+pub fn confirm(prompt: &str) -> bool {
+    matches!(ask(&format!("{prompt} [y/N]")).as_str(), "y" | "yes")
+}
+
+/// prints structured output for the --json flag of the binaries
+pub fn print_json<T>(value: &T)
+where
+    T: Serialize,
+{
+    println!(
+        "{}",
+        serde_json::to_string(value).expect("could not serialize json output")
+    );
 }
 
 #[derive(Debug, Deserialize)]
@@ -89,6 +294,8 @@ pub struct FaceJson {
     pub xmax: u32,
     pub ymin: u32,
     pub ymax: u32,
+    #[serde(default)]
+    pub eye_y: Option<u32>,
 }
 
 impl FaceJson {
@@ -98,6 +305,7 @@ impl FaceJson {
             xmax: self.xmax,
             ymin: self.ymin,
             ymax: self.ymax,
+            eye_y: self.eye_y,
         }
     }
 }
@@ -109,25 +317,33 @@ where
 {
     if cfg!(debug_assertions) {
         Command::new("cargo")
-            .args(["run", "--bin", "wallpaper-ui", "--"])
+            .args(["run", "--bin", "wallpapers", "--", "ui"])
             .args(args)
             .spawn()
-            .expect("could not spawn wallpaper-ui")
+            .expect("could not spawn wallpapers ui")
             .wait()
-            .expect("could not wait for wallpaper-ui");
+            .expect("could not wait for wallpapers ui");
     } else {
-        Command::new("wallpaper-ui")
+        // prefer the wallpapers binary next to the current one
+        let wallpapers_bin = std::env::current_exe()
+            .ok()
+            .map(|exe| exe.with_file_name("wallpapers"))
+            .filter(|exe| exe.exists())
+            .unwrap_or_else(|| PathBuf::from("wallpapers"));
+
+        Command::new(wallpapers_bin)
+            .arg("ui")
             .args(args.clone())
             .spawn()
             .unwrap_or_else(|_| {
                 // try running it via cargo instead
                 Command::new("cargo")
-                    .args(["run", "--release", "--bin", "wallpaper-ui", "--"])
+                    .args(["run", "--release", "--bin", "wallpapers", "--", "ui"])
                     .args(args)
                     .spawn()
-                    .expect("could not spawn wallpaper-ui")
+                    .expect("could not spawn wallpapers ui")
             })
             .wait()
-            .expect("could not wait for wallpaper-ui");
+            .expect("could not wait for wallpapers ui");
     }
 }
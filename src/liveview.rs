@@ -0,0 +1,245 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Request, State, WebSocketUpgrade},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use dioxus::prelude::*;
+use dioxus_liveview::LiveViewPool;
+use tracing::{error, info};
+use wallpaper_ui::{
+    cli::WallpaperUIArgs, config::WallpaperConfig, logging, paths, WallpaperConfigExt,
+};
+
+const TOKEN_COOKIE: &str = "wallpapers_token";
+
+#[derive(Clone)]
+struct Server {
+    args: Arc<WallpaperUIArgs>,
+    token: String,
+    pool: Arc<LiveViewPool>,
+    /// directories the images shown by the editor can be served from
+    image_dirs: Vec<PathBuf>,
+    /// font size of the page in percent, from the ui_scale of the config
+    ui_scale: u32,
+}
+
+/// random token for when none is provided, 16 bytes from the kernel's random number generator
+/// as hex
+fn generate_token() -> std::io::Result<String> {
+    let mut bytes = [0; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(format!("{:032x}", u128::from_ne_bytes(bytes)))
+}
+
+/// decodes the %XX escapes in the request path, e.g. spaces in filenames
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    match ext.as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("css") => "text/css",
+        _ => "application/octet-stream",
+    }
+}
+
+/// requests need the token either in the query, which sets the cookie, or in the cookie
+async fn authorize(State(server): State<Server>, req: Request, next: Next) -> Response {
+    let query_token = req.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .any(|param| param.strip_prefix("token=") == Some(server.token.as_str()))
+    });
+
+    let cookie_prefix = format!("{TOKEN_COOKIE}=");
+    let cookie_token = req
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .any(|cookie| cookie.trim().strip_prefix(&cookie_prefix) == Some(server.token.as_str()));
+
+    if !query_token && !cookie_token {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+    }
+
+    let mut response = next.run(req).await;
+    if query_token {
+        let cookie = format!(
+            "{cookie_prefix}{}; Path=/; HttpOnly; SameSite=Strict",
+            server.token
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().insert(header::SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+async fn index(State(server): State<Server>, headers: HeaderMap) -> Html<String> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    let glue = dioxus_liveview::interpreter_glue(&format!("ws://{host}/ws"));
+
+    // same as the index.html of the desktop ui
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html style="font-size: {}%;">
+    <head>
+        <title>Wallpaper UI</title>
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <link rel="stylesheet" href="/public/tailwind.css">
+    </head>
+    <body>
+        <div id="main" style="height: 100vh;"></div>
+        {glue}
+    </body>
+</html>"#,
+        server.ui_scale
+    ))
+}
+
+async fn websocket(State(server): State<Server>, ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| async move {
+        let args = server.args.clone();
+        let res = server
+            .pool
+            .launch_virtualdom(dioxus_liveview::axum_socket(socket), move || {
+                VirtualDom::new(crate::App).with_root_context(args)
+            })
+            .await;
+
+        if let Err(e) = res {
+            error!("liveview session ended with an error: {e:?}");
+        }
+    })
+}
+
+/// the stylesheet from the public directory, either in the current directory or next to the
+/// executable like the desktop ui
+async fn stylesheet() -> Response {
+    let candidates = std::iter::once(PathBuf::from("public")).chain(
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("public"))),
+    );
+
+    for dir in candidates {
+        if let Ok(css) = tokio::fs::read(dir.join("tailwind.css")).await {
+            return ([(header::CONTENT_TYPE, "text/css")], css).into_response();
+        }
+    }
+
+    StatusCode::NOT_FOUND.into_response()
+}
+
+/// the editor uses the absolute paths of the images as their src, so they are served from the
+/// same paths, as long as they are within the wallpapers or cache directory
+async fn image(State(server): State<Server>, uri: Uri) -> Response {
+    let Ok(path) = std::fs::canonicalize(percent_decode(uri.path())) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if !server.image_dirs.iter().any(|dir| path.starts_with(dir)) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, content_type(&path))], bytes).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// serves the editor over http with dioxus liveview, so it can be used from a browser on
+/// another machine
+pub fn serve(args: WallpaperUIArgs, addr: &str) {
+    logging::init(&args.log);
+
+    let config = WallpaperConfig::new();
+    let ui_scale = config.ui_scale;
+    let token = match args.token.clone().map_or_else(generate_token, Ok) {
+        Ok(token) => token,
+        Err(e) => {
+            error!("Could not generate a token, provide one with --token: {e}");
+            std::process::exit(1);
+        }
+    };
+    let image_dirs = [config.wallpapers_path, paths::cache_dir()]
+        .iter()
+        .filter_map(|dir| std::fs::canonicalize(dir).ok())
+        .collect();
+
+    let server = Server {
+        args: Arc::new(args),
+        token,
+        pool: Arc::new(LiveViewPool::new()),
+        image_dirs,
+        ui_scale,
+    };
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/ws", get(websocket))
+        .route("/public/tailwind.css", get(stylesheet))
+        .fallback(image)
+        .layer(middleware::from_fn_with_state(server.clone(), authorize))
+        .with_state(server.clone());
+
+    let run = async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        println!(
+            "Serving the wallpaper editor on http://{}/?token={}",
+            listener.local_addr()?,
+            server.token
+        );
+        info!("listening on {addr}");
+        axum::serve(listener, app).await
+    };
+
+    let res = tokio::runtime::Runtime::new()
+        .expect("could not create tokio runtime")
+        .block_on(run);
+
+    if let Err(e) = res {
+        error!("Could not serve the editor on {addr}: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,46 @@
+use std::{fs::OpenOptions, sync::Mutex};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::cli::LogArgs;
+
+impl LogArgs {
+    pub const fn level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::ERROR;
+        }
+
+        match self.verbose {
+            0 => LevelFilter::INFO,
+            1 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// sets up logging to stderr, and optionally to a log file
+/// only the first call takes effect, so commands can call each other
+pub fn init(args: &LogArgs) {
+    let file_layer = args.log_file.as_ref().map(|path| {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap_or_else(|_| panic!("could not open log file {path:?}"));
+
+        fmt::layer().with_ansi(false).with_writer(Mutex::new(file))
+    });
+
+    tracing_subscriber::registry()
+        .with(args.level())
+        .with(
+            fmt::layer()
+                .without_time()
+                .with_target(false)
+                .with_writer(std::io::stderr),
+        )
+        .with(file_layer)
+        .try_init()
+        .ok();
+}
@@ -1,32 +1,179 @@
 #![allow(non_snake_case)]
-use clap::Parser;
-use components::{app_header::save_image, editor::handle_arrow_keys_up};
+use clap::{CommandFactory, Parser};
+use components::{
+    app_header::{delete_image, save_image},
+    editor::handle_arrow_keys_up,
+};
 use dioxus::desktop::Config;
 use dioxus::prelude::*;
-use wallpaper_ui::config::WallpaperConfig;
+use std::{rc::Rc, sync::Arc, time::Duration};
+use tracing::error;
+use wallpaper_ui::{
+    cli::{
+        CacheCommand, ConfigCommand, ExportCommand, TrashCommand, WallpaperUIArgs, WallpapersArgs,
+        WallpapersCommand,
+    },
+    commands,
+    config::WallpaperConfig,
+    i18n::{self, t},
+    logging,
+    monitors::{self, MonitorEvent},
+    wallpapers::WallpapersError,
+    WallpaperConfigExt,
+};
 
 pub mod app_state;
-pub mod cli;
 pub mod components;
+#[cfg(feature = "web")]
+pub mod liveview;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 use crate::{
-    app_state::{UiMode, UiState, Wallpapers},
+    app_state::{CsvCache, QueueError, UiMode, UiState, Wallpapers},
     components::{
-        app_header::AppHeader,
+        app_header::{AppHeader, DeleteDialog},
+        crop_review::CropReviewer,
         editor::{handle_editor_shortcuts, Editor},
         filelist::FileList,
+        load_error::LoadError,
         palette::Palette,
+        upscale_review::UpscaleReviewer,
     },
 };
 
+/// how often the ui checks for saves to write in the background
+const SAVE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 fn main() {
-    let args = cli::WallpaperUIArgs::parse();
+    let args = WallpapersArgs::parse();
+    if args.version {
+        println!("wallpapers {}", env!("CARGO_PKG_VERSION"));
+        std::process::exit(0);
+    }
+
+    match args.command {
+        Some(WallpapersCommand::Ui(args)) => {
+            commands::detect_monitors::first_run();
+            launch_ui(args);
+        }
+        Some(WallpapersCommand::Add(args)) => {
+            commands::detect_monitors::first_run();
+            tokio::runtime::Runtime::new()
+                .expect("could not create tokio runtime")
+                .block_on(commands::add_wallpapers::run(&args));
+        }
+        Some(WallpapersCommand::AddResolution(args)) => commands::add_resolution::run(&args),
+        Some(WallpapersCommand::Batch(args)) => commands::batch::run(&args),
+        Some(WallpapersCommand::Doctor(args)) => commands::doctor::run(&args),
+        Some(WallpapersCommand::Apply(args)) => commands::apply::run(&args),
+        Some(WallpapersCommand::Daemon(args)) => commands::daemon::run(&args),
+        Some(WallpapersCommand::Waybar(args)) => commands::waybar::run(&args),
+        Some(WallpapersCommand::Sync(args)) => commands::sync::run(&args),
+        Some(WallpapersCommand::ExportManifest(args)) => commands::export_manifest::run(&args),
+        Some(WallpapersCommand::Export(args)) => match args.command {
+            ExportCommand::Lockscreen(args) => commands::export::lockscreen(&args),
+            ExportCommand::Colorschemes(args) => commands::export::colorschemes(&args),
+            ExportCommand::LowPower(args) => commands::export::low_power(&args),
+            ExportCommand::Gallery(args) => commands::export::gallery(&args),
+            ExportCommand::Script(args) => commands::export::script(&args),
+        },
+        Some(WallpapersCommand::Config(args)) => match args.command {
+            ConfigCommand::DetectMonitors(args) => commands::detect_monitors::run(&args),
+        },
+        Some(WallpapersCommand::Cache(args)) => match args.command {
+            CacheCommand::Build(args) => commands::cache::build(&args),
+        },
+        Some(WallpapersCommand::Dedupe(args)) => commands::dedupe::run(&args),
+        Some(WallpapersCommand::Diff(args)) => commands::diff::run(&args),
+        Some(WallpapersCommand::State(args)) => commands::state::run(&args),
+        Some(WallpapersCommand::Migrate(args)) => commands::migrate::run(&args),
+        Some(WallpapersCommand::Trash(args)) => match args.command {
+            TrashCommand::List(args) => commands::trash::list(&args),
+            TrashCommand::Delete(args) => commands::trash::delete(&args),
+            TrashCommand::Restore(args) => commands::trash::restore(&args),
+        },
+        #[cfg(feature = "server")]
+        Some(WallpapersCommand::Serve(args)) => commands::serve::run(&args),
+        #[cfg(not(feature = "server"))]
+        Some(WallpapersCommand::Serve(_)) => {
+            eprintln!("wallpapers was built without the server feature");
+            std::process::exit(1);
+        }
+        None => {
+            WallpapersArgs::command()
+                .print_help()
+                .expect("could not print help");
+        }
+    }
+}
+
+/// writes the default crops of the filtered wallpapers without opening the editor
+fn accept_defaults(args: &WallpaperUIArgs, config: &WallpaperConfig) {
+    let updated =
+        CsvCache::load(config)
+            .map_err(QueueError::from)
+            .and_then(|mut wallpapers_csv| {
+                let mut wallpapers =
+                    Wallpapers::from_args(args, &config.wallpapers_path, &mut wallpapers_csv)?;
+                Ok(wallpapers.apply_default_crops(&mut wallpapers_csv)?)
+            });
+
+    match updated {
+        Ok(count) => println!("{count} wallpapers updated."),
+        Err(e) => {
+            error!("could not apply the default crops: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn launch_ui(args: WallpaperUIArgs) {
     if args.version {
         println!("wallpaper-ui {}", env!("CARGO_PKG_VERSION"));
         std::process::exit(0);
     }
 
-    // use a custom index.html to set the height of body to the full height of the window
+    let config = WallpaperConfig::new();
+    i18n::init(&config.language);
+
+    if args.tui {
+        #[cfg(feature = "tui")]
+        {
+            tui::run(&args);
+            return;
+        }
+
+        #[cfg(not(feature = "tui"))]
+        {
+            eprintln!("wallpapers was built without the tui feature");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(addr) = args.serve.clone() {
+        #[cfg(feature = "web")]
+        {
+            liveview::serve(args, &addr);
+            return;
+        }
+
+        #[cfg(not(feature = "web"))]
+        {
+            eprintln!("wallpapers was built without the web feature, could not serve on {addr}");
+            std::process::exit(1);
+        }
+    }
+
+    logging::init(&args.log);
+
+    if args.accept_defaults {
+        accept_defaults(&args, &config);
+        return;
+    }
+
+    // use a custom index.html to set the height of body to the full height of the window, the
+    // font size of the root scales the rem based sizes of the ui
     LaunchBuilder::desktop()
         .with_cfg(
             Config::new()
@@ -34,9 +181,9 @@ fn main() {
                 .with_menu(None)
                 // disable on release builds
                 .with_disable_context_menu(!cfg!(debug_assertions))
-                .with_custom_index(
+                .with_custom_index(format!(
                     r#"<!DOCTYPE html>
-<html>
+<html style="font-size: {}%;">
     <head>
         <title>Dioxus app</title>
         <meta name="viewport" content="width=device-width, initial-scale=1.0">
@@ -45,10 +192,11 @@ fn main() {
     <body>
         <div id="main" style="height: 100vh;"></div>
     </body>
-</html>"#
-                        .to_string(),
-                ),
+</html>"#,
+                    config.ui_scale
+                )),
         )
+        .with_context(Arc::new(args))
         .launch(App);
 }
 
@@ -57,6 +205,16 @@ fn handle_shortcuts(
     wallpapers: &mut Signal<Wallpapers>,
     ui: &mut Signal<UiState>,
 ) {
+    // the delete dialog takes the keys while it is open
+    if ui().confirm_delete {
+        match event.key() {
+            Key::Enter | Key::Delete => delete_image(wallpapers, ui),
+            Key::Escape => ui.with_mut(|ui| ui.confirm_delete = false),
+            _ => {}
+        }
+        return;
+    }
+
     match event.key() {
         Key::Character(shortcut) => {
             let shortcut = shortcut.as_str();
@@ -80,6 +238,29 @@ fn handle_shortcuts(
                     }
                 }
 
+                // ctrl+k
+                "k" => {
+                    if event.modifiers().ctrl() && !wallpapers().files.is_empty() {
+                        ui.with_mut(|ui| {
+                            ui.show_commands = !ui.show_commands;
+                        });
+                    }
+                }
+
+                // ctrl+z to undo, ctrl+shift+z to redo
+                "z" | "Z" => {
+                    if event.modifiers().ctrl() && ui().mode == UiMode::Editor {
+                        let redo = event.modifiers().shift();
+                        wallpapers.with_mut(|wallpapers| {
+                            if redo {
+                                wallpapers.redo();
+                            } else {
+                                wallpapers.undo();
+                            }
+                        });
+                    }
+                }
+
                 // palette
                 "p" => {
                     if event.modifiers().ctrl() && !wallpapers().files.is_empty() {
@@ -101,23 +282,165 @@ fn handle_shortcuts(
     };
 }
 
+/// adds the ratio of a monitor connected while the ui is open to the ratio selector, the config
+/// is updated and the geometries are backfilled like add-resolution
+async fn add_monitor(
+    name: String,
+    mut wallpapers: Signal<Wallpapers>,
+    mut ui: Signal<UiState>,
+    mut wallpapers_csv: Signal<CsvCache>,
+) {
+    // the resolution of the new monitor is only known after it is added
+    let Ok(Some(mon)) = tokio::task::spawn_blocking(move || {
+        monitors::detect().into_iter().find(|mon| mon.name == name)
+    })
+    .await
+    else {
+        return;
+    };
+
+    let ratio = mon.aspect_ratio();
+    if wallpapers().resolutions.iter().any(|(_, r)| *r == ratio) {
+        return;
+    }
+
+    let res = wallpapers.with_mut(|wallpapers| {
+        wallpapers.add_config_resolution(&mon.name, &ratio, &mut wallpapers_csv.write())
+    });
+    if let Err(e) = res {
+        ui.with_mut(|ui| {
+            ui.error = Some(format!("could not add the resolution of {}: {e}", mon.name));
+        });
+    }
+}
+
 // define a component that renders a div with the text "Hello, world!"
 fn App() -> Element {
-    let config = WallpaperConfig::new();
-    let mut wallpapers = use_signal(|| Wallpapers::from_args(&config.wallpapers_path));
-    let mut ui = use_signal(|| UiState {
-        show_faces: config.show_faces,
-        ..UiState::default()
+    // read once, the event handlers read config.ini again so it can be edited while the ui is open
+    let config = use_hook(|| Rc::new(WallpaperConfig::load()));
+    let config = match config.as_ref() {
+        Ok(config) => config.clone(),
+        Err(e) => {
+            return rsx! {
+                LoadError {
+                    theme: WallpaperConfig::default().theme,
+                    error: WallpapersError::from(e.clone()).to_string(),
+                }
+            };
+        }
+    };
+    let args = use_context::<Arc<WallpaperUIArgs>>();
+
+    // upscaled images are not in the csv yet, so they are reviewed without the editor
+    if let Some(review_file) = &args.review_upscales {
+        return rsx! {
+            UpscaleReviewer { review_file: review_file.clone(), theme: config.theme }
+        };
+    }
+    if let Some(review_file) = &args.review_crops {
+        return rsx! {
+            CropReviewer { review_file: review_file.clone(), theme: config.theme }
+        };
+    }
+
+    // loaded once, the error screen is shown instead of the editor if it fails
+    let loaded = use_hook(|| {
+        Rc::new(
+            CsvCache::load(&config)
+                .map_err(QueueError::from)
+                .and_then(|mut wallpapers_csv| {
+                    Wallpapers::from_args(&args, &config.wallpapers_path, &mut wallpapers_csv)
+                        .map(|wallpapers| (wallpapers_csv, wallpapers))
+                })
+                .map_err(|e| e.to_string()),
+        )
+    });
+    let (csv, walls) = match loaded.as_ref() {
+        Ok(loaded) => loaded.clone(),
+        Err(e) => {
+            return rsx! {
+                LoadError { theme: config.theme, error: e.clone() }
+            };
+        }
+    };
+
+    let mut wallpapers_csv = use_context_provider(|| Signal::new(csv));
+    let mut wallpapers = use_signal(|| walls);
+    let mut ui = use_signal(|| UiState::from_config(&config));
+
+    // saves are written in the background once there haven't been any for the save_delay
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(SAVE_POLL_INTERVAL).await;
+            if !wallpapers_csv.peek().is_due() {
+                continue;
+            }
+            let Some(write) = wallpapers_csv.write().take_write() else {
+                continue;
+            };
+
+            let res = match tokio::task::spawn_blocking(move || write.run()).await {
+                Ok(res) => res.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            wallpapers_csv.write().finish_write(res.is_ok());
+            if let Err(e) = res {
+                ui.with_mut(|ui| {
+                    ui.error = Some(format!("could not save wallpapers.csv: {e}"));
+                });
+            }
+        }
+    });
+    // monitors connected while the ui is open add their ratio, only hyprland reports them
+    use_future(move || async move {
+        let (tx, mut rx) = std::sync::mpsc::channel();
+        if !monitors::watch_hyprland(tx, std::convert::identity) {
+            return;
+        }
+
+        // the receiver is moved to a blocking thread for each event
+        loop {
+            let Ok((event, next_rx)) = tokio::task::spawn_blocking(move || (rx.recv(), rx)).await
+            else {
+                break;
+            };
+            rx = next_rx;
+
+            match event {
+                Ok(MonitorEvent::Added(name)) => {
+                    add_monitor(name, wallpapers, ui, wallpapers_csv).await;
+                }
+                Ok(MonitorEvent::Removed(_)) => {}
+                Err(_) => break,
+            }
+        }
+    });
+    // write the remaining saves when the window is closed
+    use_drop(move || {
+        if let Ok(mut wallpapers_csv) = wallpapers_csv.try_write() {
+            if let Err(e) = wallpapers_csv.flush() {
+                error!("could not save wallpapers.csv: {e}");
+            }
+        }
+    });
+
+    let theme = ui().theme;
+    let error_banner = ui().error.map(|error| {
+        rsx! {
+            div { class: "mx-4 mt-4 rounded-md bg-red-900 px-4 py-2 text-sm text-red-100",
+                "{error}"
+            }
+        }
     });
     let has_files = !wallpapers().files.is_empty();
 
     if !has_files {
         return rsx! {
             main {
-                class: "dark flex items-center h-full justify-center bg-base overflow-hidden",
+                class: "dark {theme} flex items-center h-full justify-center bg-base overflow-hidden",
                 div {
                     h1 { class: "mt-4 text-4xl font-bold tracking-tight text-text text-center h-full",
-                        "No more wallpapers to process! 🎉"
+                        {t("no-wallpapers")}
                     }
                 }
             }
@@ -126,7 +449,7 @@ fn App() -> Element {
 
     rsx! {
         main {
-            class: "dark flex flex-col h-full bg-base overflow-hidden",
+            class: "dark {theme} flex flex-col h-full bg-base overflow-hidden",
             tabindex: 0,
             autofocus: true,
             onkeydown: move |event| {
@@ -138,13 +461,28 @@ fn App() -> Element {
 
             AppHeader { wallpapers, ui }
 
+            if ui().confirm_delete {
+                DeleteDialog { wallpapers, ui }
+            }
+
+            {error_banner}
+
+            if !ui().warnings.is_empty() {
+                div { class: "mx-4 mt-4 rounded-md bg-yellow-900 px-4 py-2 text-sm text-yellow-100",
+                    for warning in ui().warnings {
+                        p { "{warning}" }
+                    }
+                    p { class: "mt-1 font-semibold", {t("save-again")} }
+                }
+            }
+
             div {
                 class: "flex p-4 gap-4",
 
                 if ui().mode == UiMode::FileList {
                     FileList { wallpapers, ui }
                 } else if ui().mode == UiMode::Palette {
-                    Palette { wallpapers }
+                    Palette { wallpapers, ui }
                 } else if ui().mode == UiMode::Editor {
                     Editor { wallpapers, ui, wallpapers_path: config.wallpapers_path }
                 }
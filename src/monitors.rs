@@ -0,0 +1,221 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::Sender,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::{aspect_ratio::AspectRatio, geometry::Geometry};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Monitor {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Monitor {
+    pub const fn aspect_ratio(&self) -> AspectRatio {
+        AspectRatio::new(self.width, self.height)
+    }
+
+    /// swaps the dimensions for rotated monitors
+    const fn new(name: String, width: u32, height: u32, rotated: bool) -> Self {
+        if rotated {
+            Self {
+                name,
+                width: height,
+                height: width,
+            }
+        } else {
+            Self {
+                name,
+                width,
+                height,
+            }
+        }
+    }
+}
+
+/// stdout of the command, if it ran successfully
+fn command_output(cmd: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        debug!("{cmd} exited with {}", output.status);
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn json_u32(value: &Value, key: &str) -> Option<u32> {
+    value.get(key)?.as_u64()?.try_into().ok()
+}
+
+/// directory with the sockets of the running hyprland instance
+fn hyprland_socket_dir() -> Option<PathBuf> {
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+
+    // older versions of hyprland kept the sockets in /tmp
+    [dirs::runtime_dir(), Some(PathBuf::from("/tmp"))]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("hypr").join(&signature))
+        .find(|dir| dir.join(".socket.sock").exists())
+}
+
+/// sends a request over the hyprland ipc socket, e.g. j/monitors
+fn hyprland_request(request: &str) -> Option<String> {
+    let mut stream = UnixStream::connect(hyprland_socket_dir()?.join(".socket.sock")).ok()?;
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut output = String::new();
+    stream.read_to_string(&mut output).ok()?;
+    Some(output)
+}
+
+fn hyprland_monitors() -> Option<Vec<Monitor>> {
+    // fallback to hyprctl if the socket cannot be found
+    let output = hyprland_request("j/monitors")
+        .or_else(|| command_output("hyprctl", &["monitors", "-j"]))?;
+    let monitors: Vec<Value> = serde_json::from_str(&output).ok()?;
+
+    Some(
+        monitors
+            .iter()
+            .filter_map(|mon| {
+                // odd transforms are rotated by 90 or 270 degrees
+                let rotated = json_u32(mon, "transform").unwrap_or_default() % 2 == 1;
+
+                Some(Monitor::new(
+                    mon.get("name")?.as_str()?.to_string(),
+                    json_u32(mon, "width")?,
+                    json_u32(mon, "height")?,
+                    rotated,
+                ))
+            })
+            .collect(),
+    )
+}
+
+fn wlr_randr_monitors() -> Option<Vec<Monitor>> {
+    let output = command_output("wlr-randr", &["--json"])?;
+    let monitors: Vec<Value> = serde_json::from_str(&output).ok()?;
+
+    Some(
+        monitors
+            .iter()
+            .filter(|mon| mon.get("enabled").and_then(Value::as_bool) != Some(false))
+            .filter_map(|mon| {
+                let mode = mon
+                    .get("modes")?
+                    .as_array()?
+                    .iter()
+                    .find(|mode| mode.get("current").and_then(Value::as_bool) == Some(true))?;
+                let rotated = mon
+                    .get("transform")
+                    .and_then(Value::as_str)
+                    .is_some_and(|transform| transform.contains("90") || transform.contains("270"));
+
+                Some(Monitor::new(
+                    mon.get("name")?.as_str()?.to_string(),
+                    json_u32(mode, "width")?,
+                    json_u32(mode, "height")?,
+                    rotated,
+                ))
+            })
+            .collect(),
+    )
+}
+
+fn xrandr_monitors() -> Option<Vec<Monitor>> {
+    let output = command_output("xrandr", &["--query"])?;
+
+    Some(
+        output
+            .lines()
+            .filter(|line| line.contains(" connected"))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let name = parts.next()?.to_string();
+                // the geometry of the active mode already accounts for rotation,
+                // e.g. DP-1 connected primary 2560x1440+0+0
+                let geom = parts.find_map(|part| Geometry::try_from(part.to_string()).ok())?;
+
+                Some(Monitor::new(name, geom.w, geom.h, false))
+            })
+            .collect(),
+    )
+}
+
+/// detects the connected monitors via hyprctl, wlr-randr or xrandr, whichever works first
+pub fn detect() -> Vec<Monitor> {
+    [hyprland_monitors, wlr_randr_monitors, xrandr_monitors]
+        .iter()
+        .find_map(|detect| detect().filter(|monitors| !monitors.is_empty()))
+        .unwrap_or_default()
+}
+
+/// monitors being connected or disconnected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorEvent {
+    Added(String),
+    Removed(String),
+}
+
+/// parses a line from the hyprland event socket, e.g. monitoradded>>DP-1
+fn parse_hyprland_event(line: &str) -> Option<MonitorEvent> {
+    let (event, data) = line.split_once(">>")?;
+    match event {
+        "monitoradded" => Some(MonitorEvent::Added(data.to_string())),
+        "monitorremoved" => Some(MonitorEvent::Removed(data.to_string())),
+        _ => None,
+    }
+}
+
+/// sends the monitor hotplug events of hyprland from a background thread
+///
+/// returns false if hyprland is not running
+pub fn watch_hyprland<T, F>(events: Sender<T>, wrap: F) -> bool
+where
+    T: Send + 'static,
+    F: Fn(MonitorEvent) -> T + Send + 'static,
+{
+    let Some(stream) =
+        hyprland_socket_dir().and_then(|dir| UnixStream::connect(dir.join(".socket2.sock")).ok())
+    else {
+        return false;
+    };
+
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if let Some(event) = parse_hyprland_event(&line) {
+                        debug!("hyprland event: {event:?}");
+                        if events.send(wrap(event)).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("hyprland event socket: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    true
+}
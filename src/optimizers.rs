@@ -0,0 +1,107 @@
+//! in process versions of cwebp, jpegoptim and oxipng, used with native_optimize in the config
+//! or when the tools aren't installed
+//!
+//! the native-optimize feature uses libwebp and oxipng, without it the encoders of the image
+//! crate are used, which only write lossless webp and compress png less
+use std::{io::Cursor, path::Path};
+
+use image::{DynamicImage, ImageFormat};
+use thiserror::Error;
+
+/// quality of jpgs that are converted from other formats, jpgs are copied as is
+const JPG_QUALITY: u8 = 95;
+/// quality of lossy webps, the same as cwebp -q 100
+#[cfg(feature = "native-optimize")]
+const WEBP_QUALITY: f32 = 100.0;
+
+#[derive(Error, Debug)]
+pub enum OptimizeError {
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("could not encode webp: {0}")]
+    Webp(String),
+    #[cfg(feature = "native-optimize")]
+    #[error(transparent)]
+    Png(#[from] oxipng::PngError),
+}
+
+/// writes the image in the format of the output, like the external optimizers the metadata of
+/// re-encoded images is stripped
+pub fn optimize(infile: &Path, outfile: &Path) -> Result<(), OptimizeError> {
+    let is_jpg = |path: &Path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+    };
+
+    // re-encoding jpgs loses quality, jpegoptim is lossless too
+    if is_jpg(infile) && is_jpg(outfile) {
+        std::fs::copy(infile, outfile)?;
+        return Ok(());
+    }
+
+    let img = image::open(infile)?;
+    let bytes = match outfile.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => encode_png(&img)?,
+        Some("webp") => encode_webp(&img)?,
+        _ => encode_jpg(&img)?,
+    };
+    Ok(std::fs::write(outfile, bytes)?)
+}
+
+fn encode_jpg(img: &DynamicImage) -> Result<Vec<u8>, OptimizeError> {
+    let mut bytes = Vec::new();
+    // jpg has no alpha channel
+    DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, JPG_QUALITY),
+    )?;
+    Ok(bytes)
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, OptimizeError> {
+    #[cfg(feature = "native-optimize")]
+    {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, ImageFormat::Png)?;
+        Ok(oxipng::optimize_from_memory(
+            bytes.get_ref(),
+            &oxipng::Options::max_compression(),
+        )?)
+    }
+    #[cfg(not(feature = "native-optimize"))]
+    {
+        use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+
+        let mut bytes = Vec::new();
+        img.write_with_encoder(PngEncoder::new_with_quality(
+            &mut bytes,
+            CompressionType::Best,
+            FilterType::Adaptive,
+        ))?;
+        Ok(bytes)
+    }
+}
+
+fn encode_webp(img: &DynamicImage) -> Result<Vec<u8>, OptimizeError> {
+    // webp only has 8 bits per channel
+    let img = if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(img.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    };
+
+    #[cfg(feature = "native-optimize")]
+    {
+        let encoder =
+            webp::Encoder::from_image(&img).map_err(|e| OptimizeError::Webp(e.to_string()))?;
+        Ok(encoder.encode(WEBP_QUALITY).to_vec())
+    }
+    #[cfg(not(feature = "native-optimize"))]
+    {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, ImageFormat::WebP)?;
+        Ok(bytes.into_inner())
+    }
+}
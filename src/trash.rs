@@ -0,0 +1,329 @@
+use std::{
+    fmt::Write as _,
+    io::Write,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::WallpaperConfig,
+    filename, paths,
+    store::{self, Store},
+    wallpapers::{WallInfo, WallpapersCsv, WallpapersError},
+    write_atomic,
+};
+
+#[derive(Error, Debug)]
+pub enum TrashError {
+    #[error("could not move {path:?} to the trash: {source}")]
+    Trash {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("could not restore {path:?}: {source}")]
+    Restore {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("could not update the trash log {path:?}: {source}")]
+    Log {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{0} is not in the trash log")]
+    NotFound(String),
+    #[error("{0:?} has been removed from the trash")]
+    Emptied(PathBuf),
+    #[error("could not restore {0:?}, the file already exists")]
+    Exists(PathBuf),
+    #[error("could not read the row of {filename}: {reason}")]
+    Row { filename: String, reason: String },
+    #[error(transparent)]
+    Wallpapers(#[from] WallpapersError),
+}
+
+/// a wallpaper moved to the trash, with its row of wallpapers.csv so the crops, palette and tags
+/// are restored along with the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub filename: String,
+    /// where the wallpaper was before it was deleted
+    pub path: PathBuf,
+    /// the file in the xdg trash
+    pub trashed: PathBuf,
+    /// seconds since the unix epoch
+    pub deleted_at: u64,
+    /// header and record of wallpapers.csv, empty if the wallpaper wasn't in the csv
+    pub header: Vec<String>,
+    pub row: Vec<String>,
+}
+
+impl TrashEntry {
+    /// the time of the deletion in utc, e.g. 2024-05-01T12:00:00
+    pub fn deletion_date(&self) -> String {
+        format_date(self.deleted_at)
+    }
+
+    /// the file is still in the trash and can be restored
+    pub fn is_restorable(&self) -> bool {
+        self.trashed.exists()
+    }
+}
+
+/// deletions of the wallpapers, newest last
+pub fn log_file() -> PathBuf {
+    paths::data_dir().join("trash.json")
+}
+
+/// the xdg trash of the home directory, $XDG_DATA_HOME/Trash
+fn trash_dir() -> PathBuf {
+    dirs::data_dir()
+        .expect("could not get xdg data directory")
+        .join("Trash")
+}
+
+/// formats the seconds since the unix epoch as YYYY-MM-DDThh:mm:ss in utc
+fn format_date(secs: u64) -> String {
+    let days = i64::try_from(secs / 86400).unwrap_or_default();
+    let time = secs % 86400;
+
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// percent encodes the path for the Path key of the trashinfo
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            write!(encoded, "%{byte:02X}").ok();
+        }
+    }
+    encoded
+}
+
+/// renames the file, copying it if the destination is on another filesystem
+pub fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from).inspect_err(|_| {
+        std::fs::remove_file(to).ok();
+    })
+}
+
+/// reads the trash log, a missing log is empty
+pub fn load() -> std::io::Result<Vec<TrashEntry>> {
+    match std::fs::read_to_string(log_file()) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(std::io::Error::other),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(entries: &[TrashEntry]) -> Result<(), TrashError> {
+    let path = log_file();
+    serde_json::to_string_pretty(entries)
+        .map_err(std::io::Error::other)
+        .and_then(|contents| write_atomic(&path, contents.as_bytes()))
+        .map_err(|source| TrashError::Log { path, source })
+}
+
+/// moves the file to the xdg trash, the trashinfo is created first so the name in the trash is
+/// claimed before the file is moved
+fn trash_file(path: &Path, deleted_at: u64) -> std::io::Result<PathBuf> {
+    let files_dir = trash_dir().join("files");
+    let info_dir = trash_dir().join("info");
+    std::fs::create_dir_all(&files_dir)?;
+    std::fs::create_dir_all(&info_dir)?;
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut n = 0;
+    loop {
+        n += 1;
+        let name = if n == 1 {
+            format!("{stem}{ext}")
+        } else {
+            format!("{stem} {n}{ext}")
+        };
+        let info_path = info_dir.join(format!("{name}.trashinfo"));
+
+        let mut info = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(info) => info,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        write!(
+            info,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(path),
+            format_date(deleted_at)
+        )?;
+
+        let trashed = files_dir.join(&name);
+        return move_file(path, &trashed)
+            .map(|()| trashed)
+            .inspect_err(|_| {
+                std::fs::remove_file(&info_path).ok();
+            });
+    }
+}
+
+/// moves the wallpaper to the xdg trash and records it in the trash log, info is the row of
+/// wallpapers.csv to restore with it
+pub fn trash_wallpaper(
+    config: &WallpaperConfig,
+    fname: &str,
+    info: Option<&WallInfo>,
+) -> Result<TrashEntry, TrashError> {
+    // only the directory is resolved, so symlinked wallpapers trash the link
+    let path = std::fs::canonicalize(&config.wallpapers_path)
+        .map(|dir| dir.join(fname))
+        .map_err(|source| TrashError::Trash {
+            path: config.wallpapers_path.join(fname),
+            source,
+        })?;
+    trash_path(&path, info)
+}
+
+/// moves the file to the xdg trash and records it in the trash log, the path should be absolute
+/// so it can be restored from anywhere
+pub fn trash_path(path: &Path, info: Option<&WallInfo>) -> Result<TrashEntry, TrashError> {
+    let fname = filename(path);
+    let (header, row) = info
+        .map(WallInfo::to_row)
+        .transpose()
+        .map_err(|e| TrashError::Row {
+            filename: fname.clone(),
+            reason: e.to_string(),
+        })?
+        .unwrap_or_default();
+
+    let mut entries = load().map_err(|source| TrashError::Log {
+        path: log_file(),
+        source,
+    })?;
+
+    let deleted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let trashed = trash_file(path, deleted_at).map_err(|source| TrashError::Trash {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let entry = TrashEntry {
+        filename: fname,
+        path: path.to_path_buf(),
+        trashed,
+        deleted_at,
+        header,
+        row,
+    };
+    entries.push(entry.clone());
+    save(&entries)?;
+
+    Ok(entry)
+}
+
+/// moves the most recently deleted wallpaper with the filename back from the trash and adds its
+/// row to wallpapers.csv
+pub fn restore(config: &WallpaperConfig, fname: &str) -> Result<TrashEntry, TrashError> {
+    let mut entries = load().map_err(|source| TrashError::Log {
+        path: log_file(),
+        source,
+    })?;
+    let idx = entries
+        .iter()
+        .rposition(|entry| entry.filename == fname)
+        .ok_or_else(|| TrashError::NotFound(fname.to_string()))?;
+    let entry = entries[idx].clone();
+
+    if !entry.is_restorable() {
+        return Err(TrashError::Emptied(entry.trashed));
+    }
+    if entry.path.exists() {
+        return Err(TrashError::Exists(entry.path));
+    }
+
+    if let Some(dir) = entry.path.parent() {
+        std::fs::create_dir_all(dir).map_err(|source| TrashError::Restore {
+            path: entry.path.clone(),
+            source,
+        })?;
+    }
+    move_file(&entry.trashed, &entry.path).map_err(|source| TrashError::Restore {
+        path: entry.path.clone(),
+        source,
+    })?;
+    if let Some(info_name) = entry.trashed.file_name() {
+        let mut info_name = info_name.to_owned();
+        info_name.push(".trashinfo");
+        std::fs::remove_file(trash_dir().join("info").join(info_name)).ok();
+    }
+
+    entries.remove(idx);
+    save(&entries)?;
+
+    if !entry.row.is_empty() {
+        let info = WallInfo::from_row(&entry.header, &entry.row).map_err(|e| TrashError::Row {
+            filename: entry.filename.clone(),
+            reason: e.to_string(),
+        })?;
+
+        // the wallpaper might have been restored somewhere other than the wallpapers directory
+        if entry.path.parent()
+            == std::fs::canonicalize(&config.wallpapers_path)
+                .ok()
+                .as_deref()
+        {
+            let mut store: Box<dyn Store> = match store::open(config) {
+                Err(WallpapersError::NotFound(_)) => {
+                    Box::new(WallpapersCsv::from_wallpapers(Vec::new(), config.clone()))
+                }
+                res => res?,
+            };
+            if store.get(&entry.filename)?.is_none() {
+                let info = WallInfo {
+                    filename: filename(&entry.path),
+                    ..info
+                };
+                store.upsert(&info, &config.sorted_resolutions())?;
+            }
+        }
+    }
+
+    Ok(entry)
+}
@@ -0,0 +1,370 @@
+use std::path::PathBuf;
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListItem, Paragraph},
+    DefaultTerminal, Frame,
+};
+use ratatui_image::{picker::Picker, protocol::StatefulProtocol, Resize, StatefulImage};
+use wallpaper_ui::{
+    cli::WallpaperUIArgs, config::WallpaperConfig, geometry::Geometry, hooks,
+    image_ops::render_geometry, trash, validation::validate, WallpaperConfigExt,
+};
+
+use crate::app_state::{CsvCache, QueueError, Wallpapers};
+
+/// largest dimension of the preview, larger images only slow down the terminal graphics
+const PREVIEW_SIZE: u32 = 1280;
+
+const HELP: &[(&str, &str)] = &[
+    ("h / l", "previous / next wallpaper"),
+    ("t / tab", "next ratio"),
+    ("shift+tab", "previous ratio"),
+    ("1-9", "select candidate"),
+    ("arrows", "nudge crop, shift for 10x"),
+    ("0 / m / $", "align start / center / end"),
+    ("u / d", "source / default crop"),
+    ("z / Z", "undo / redo"),
+    ("s", "save"),
+    ("del", "move to trash"),
+    ("q", "quit"),
+];
+
+struct Tui {
+    wallpapers: Wallpapers,
+    wallpapers_csv: CsvCache,
+    config: WallpaperConfig,
+    picker: Picker,
+    /// decoded image of the current wallpaper, so it is only read once
+    image: Option<(String, image::DynamicImage)>,
+    /// rendered crop of the current geometry
+    preview: Option<Box<dyn StatefulProtocol>>,
+    status: String,
+    /// warnings from validating the last save, saving again with the same warnings saves anyway
+    warnings: Vec<String>,
+}
+
+impl Tui {
+    fn is_modified(&self) -> bool {
+        self.wallpapers.current != self.wallpapers.source
+    }
+
+    /// renders the crop of the current geometry for the terminal graphics
+    fn update_preview(&mut self) {
+        let fname = self.wallpapers.current.filename.clone();
+        if self.image.as_ref().map(|(name, _)| name) != Some(&fname) {
+            self.image = match image::open(self.config.wallpapers_path.join(&fname)) {
+                Ok(img) => Some((fname.clone(), img)),
+                Err(e) => {
+                    self.status = format!("could not open {fname}: {e}");
+                    None
+                }
+            };
+        }
+
+        self.preview = self.image.as_ref().map(|(_, img)| {
+            let crop =
+                render_geometry(img, &self.wallpapers.get_geometry(), &self.wallpapers.ratio)
+                    .thumbnail(PREVIEW_SIZE, PREVIEW_SIZE);
+            self.picker.new_resize_protocol(crop)
+        });
+    }
+
+    fn set_geometry(&mut self, geom: &Geometry) {
+        self.wallpapers.set_geometry(geom);
+        self.update_preview();
+    }
+
+    fn save(&mut self) {
+        let info = self.wallpapers.current.clone();
+
+        let warnings = validate(&self.config, &info);
+        if !warnings.is_empty() && warnings != self.warnings {
+            self.status = format!("{}, save again to ignore", warnings.join("; "));
+            self.warnings = warnings;
+            return;
+        }
+        self.warnings.clear();
+
+        let resolutions: Vec<_> = self
+            .wallpapers
+            .resolutions
+            .iter()
+            .map(|(_, ratio)| ratio.clone())
+            .collect();
+        // the tui writes immediately instead of after the save_delay
+        let res = self
+            .wallpapers_csv
+            .save(&info, &resolutions)
+            .and_then(|()| self.wallpapers_csv.flush());
+        if let Err(e) = res {
+            self.status = format!("could not save {}: {e}", info.filename);
+            return;
+        }
+        hooks::post_save(&self.config, &info);
+        self.status = format!("saved {}", info.filename);
+
+        if self.config.auto_advance && self.wallpapers.files.len() > 1 {
+            if let Err(e) = self.wallpapers.remove(&mut self.wallpapers_csv) {
+                self.status = e.to_string();
+            }
+            self.update_preview();
+        } else {
+            self.wallpapers.source = self.wallpapers.current.clone();
+        }
+    }
+
+    /// moves the current wallpaper to the trash with its saved crops
+    fn delete(&mut self) {
+        let fname = self.wallpapers.current.filename.clone();
+        let row = self.wallpapers_csv.get(&fname).ok();
+        if let Err(e) = trash::trash_wallpaper(&self.config, &fname, row.as_ref()) {
+            self.status = e.to_string();
+            return;
+        }
+
+        self.warnings.clear();
+        self.status = format!("moved {fname} to the trash");
+        if let Err(e) = self.wallpapers.remove(&mut self.wallpapers_csv) {
+            self.status = e.to_string();
+        }
+        self.update_preview();
+    }
+
+    /// returns true if the tui should quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let walls = &self.wallpapers;
+        let current_geom = walls.get_geometry();
+        let (img_w, img_h) = (walls.current.width, walls.current.height);
+
+        let multiplier = if key.modifiers.contains(KeyModifiers::SHIFT) {
+            10
+        } else {
+            1
+        };
+        let step = (self.config.arrow_step * multiplier) as i32;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+            KeyCode::Char('s') => self.save(),
+            KeyCode::Delete => {
+                self.delete();
+                // nothing left to edit
+                if self.wallpapers.files.is_empty() {
+                    return true;
+                }
+            }
+            KeyCode::Char('h' | 'l') => {
+                let was_modified = self.is_modified();
+                let res = if key.code == KeyCode::Char('h') {
+                    self.wallpapers.prev_wall(&mut self.wallpapers_csv)
+                } else {
+                    self.wallpapers.next_wall(&mut self.wallpapers_csv)
+                };
+                self.warnings.clear();
+                self.status = match res {
+                    Err(e) => e.to_string(),
+                    Ok(()) if was_modified => "discarded unsaved changes".to_string(),
+                    Ok(()) => String::new(),
+                };
+                self.update_preview();
+            }
+            KeyCode::Char('t') | KeyCode::Tab | KeyCode::BackTab => {
+                self.wallpapers.cycle_ratio(key.code != KeyCode::BackTab);
+                self.update_preview();
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let idx = c.to_digit(10).unwrap_or_default() as usize - 1;
+                if let Some(geom) = walls.candidate_geometries().get(idx).cloned() {
+                    self.set_geometry(&geom);
+                }
+            }
+            KeyCode::Char('0') => self.set_geometry(&current_geom.align_start(img_w, img_h)),
+            KeyCode::Char('m') => self.set_geometry(&current_geom.align_center(img_w, img_h)),
+            KeyCode::Char('$') => self.set_geometry(&current_geom.align_end(img_w, img_h)),
+            KeyCode::Char('u') => {
+                let geom = walls.source.get_geometry(&walls.ratio);
+                self.set_geometry(&geom);
+            }
+            KeyCode::Char('d') => {
+                let geom = walls.cropper().crop(&walls.ratio);
+                self.set_geometry(&geom);
+            }
+            KeyCode::Char('z') => {
+                self.wallpapers.undo();
+                self.update_preview();
+            }
+            KeyCode::Char('Z') => {
+                self.wallpapers.redo();
+                self.update_preview();
+            }
+            KeyCode::Left => self.set_geometry(&walls.move_geometry_by(-step, 0)),
+            KeyCode::Right => self.set_geometry(&walls.move_geometry_by(step, 0)),
+            KeyCode::Up => self.set_geometry(&walls.move_geometry_by(0, -step)),
+            KeyCode::Down => self.set_geometry(&walls.move_geometry_by(0, step)),
+            _ => {}
+        }
+
+        false
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let walls = &self.wallpapers;
+        let [header, main, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [preview, sidebar] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(36)]).areas(main);
+
+        let modified = if self.is_modified() {
+            " [modified]"
+        } else {
+            ""
+        };
+        frame.render_widget(
+            Line::from(format!(
+                "{} / {}  {}{modified}",
+                walls.index + 1,
+                walls.files.len(),
+                walls.current.filename
+            ))
+            .bold(),
+            header,
+        );
+
+        let preview_block = Block::bordered().title(format!(" {} ", walls.get_geometry()));
+        let preview_inner = preview_block.inner(preview);
+        frame.render_widget(preview_block, preview);
+        if let Some(protocol) = &mut self.preview {
+            frame.render_stateful_widget(
+                StatefulImage::new(None).resize(Resize::Fit(None)),
+                preview_inner,
+                protocol,
+            );
+        }
+
+        let current_geom = walls.get_geometry();
+        let selected = Style::default().add_modifier(Modifier::REVERSED);
+
+        let ratios: Vec<_> = walls
+            .image_ratios()
+            .into_iter()
+            .map(|(name, ratio)| {
+                let item = ListItem::new(format!("{name} ({ratio})"));
+                if ratio == walls.ratio {
+                    item.style(selected)
+                } else if walls.visited_ratios.contains(&ratio) {
+                    item
+                } else {
+                    // ratios that haven't been looked at yet are dimmed
+                    item.style(Style::default().add_modifier(Modifier::DIM))
+                }
+            })
+            .collect();
+        let candidates: Vec<_> = walls
+            .candidate_geometries()
+            .into_iter()
+            .take(9)
+            .enumerate()
+            .map(|(i, geom)| {
+                let item = ListItem::new(format!("{} {geom}", i + 1));
+                if geom == current_geom {
+                    item.style(selected)
+                } else {
+                    item
+                }
+            })
+            .collect();
+        let help: Vec<_> = HELP
+            .iter()
+            .map(|(key, desc)| Line::from(format!("{key:<10}{desc}")))
+            .collect();
+
+        let [ratios_area, candidates_area, help_area] = Layout::vertical([
+            Constraint::Length(ratios.len() as u16 + 2),
+            Constraint::Length(candidates.len() as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .areas(sidebar);
+        frame.render_widget(
+            List::new(ratios).block(Block::bordered().title(" Ratios ")),
+            ratios_area,
+        );
+        frame.render_widget(
+            List::new(candidates).block(Block::bordered().title(" Candidates ")),
+            candidates_area,
+        );
+        frame.render_widget(
+            Paragraph::new(help).block(Block::bordered().title(" Keys ")),
+            help_area,
+        );
+
+        frame.render_widget(Line::from(self.status.as_str()).italic(), footer);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        self.update_preview();
+
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// edits the crops in the terminal, the preview uses sixel, kitty or iterm graphics if the
+/// terminal supports them and falls back to unicode half blocks
+pub fn run(args: &WallpaperUIArgs) {
+    let config = WallpaperConfig::new();
+
+    // the terminal has to be queried before it is switched to the alternate screen
+    let mut picker = Picker::from_termios().unwrap_or_else(|_| Picker::new((8, 16)));
+    picker.guess_protocol();
+
+    let loaded =
+        CsvCache::load(&config)
+            .map_err(QueueError::from)
+            .and_then(|mut wallpapers_csv| {
+                Wallpapers::from_args(args, &config.wallpapers_path, &mut wallpapers_csv)
+                    .map(|wallpapers| (wallpapers, wallpapers_csv))
+            });
+    let (wallpapers, wallpapers_csv) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("could not load wallpapers: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut tui = Tui {
+        wallpapers,
+        wallpapers_csv,
+        config,
+        picker,
+        image: None,
+        preview: None,
+        status: String::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut terminal = ratatui::init();
+    let res = tui.run(&mut terminal);
+    ratatui::restore();
+
+    if let Err(e) = res {
+        eprintln!("could not run the tui: {e}");
+        std::process::exit(1);
+    }
+}
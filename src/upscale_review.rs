@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{paths, write_atomic};
+
+/// tag added to wallpapers whose upscale was rejected, so they can be found again to retry
+/// with a different upscaler or denoise level
+pub const REJECTED_TAG: &str = "upscale-rejected";
+
+/// image upscaled by the pipeline, waiting to be accepted or rejected in the ui
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpscaleReview {
+    pub source: PathBuf,
+    pub upscaled: PathBuf,
+    pub scale_factor: u32,
+    /// None until the image has been reviewed
+    pub accepted: Option<bool>,
+}
+
+impl UpscaleReview {
+    pub fn new(source: &Path, upscaled: &Path, scale_factor: u32) -> Self {
+        Self {
+            source: source.to_path_buf(),
+            upscaled: upscaled.to_path_buf(),
+            scale_factor,
+            accepted: None,
+        }
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.accepted == Some(false)
+    }
+}
+
+/// file used to pass the reviews between the pipeline and the ui
+pub fn review_file() -> PathBuf {
+    paths::cache_dir().join("upscale-review.json")
+}
+
+pub fn load(path: &Path) -> std::io::Result<Vec<UpscaleReview>> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(std::io::Error::other)
+}
+
+pub fn save(path: &Path, reviews: &[UpscaleReview]) -> std::io::Result<()> {
+    let contents = serde_json::to_string_pretty(reviews).map_err(std::io::Error::other)?;
+    write_atomic(path, contents.as_bytes())
+}
@@ -0,0 +1,99 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    commands::export_manifest::wallpaper_json, config::WallpaperConfig, geometry::Geometry,
+    hooks::wallpaper_envs, wallpapers::WallInfo,
+};
+
+pub use crate::config::VALIDATION_RULES;
+
+/// the crop contains all of the faces, padded crops always show the whole image
+fn contains_faces(info: &WallInfo, geom: &Geometry) -> bool {
+    geom.padding.is_some()
+        || info.faces.iter().all(|face| {
+            geom.x <= face.xmin
+                && face.xmax <= geom.x + geom.w
+                && geom.y <= face.ymin
+                && face.ymax <= geom.y + geom.h
+        })
+}
+
+/// runs the validation_command with the same environment and json as the hooks, a non zero exit
+/// is a failed validation with a warning on each line of its output
+fn run_command(config: &WallpaperConfig, info: &WallInfo) -> Vec<String> {
+    let mut payload = wallpaper_json(config, info);
+    payload["filename"] = serde_json::json!(info.filename);
+
+    let run = || -> std::io::Result<std::process::Output> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&config.validation_command)
+            .envs(wallpaper_envs(config, info))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(payload.to_string().as_bytes()).ok();
+        }
+        child.wait_with_output()
+    };
+
+    match run() {
+        Ok(output) if output.status.success() => Vec::new(),
+        Ok(output) => {
+            let warnings: Vec<_> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(ToString::to_string)
+                .collect();
+
+            if warnings.is_empty() {
+                vec![format!("validation command exited with {}", output.status)]
+            } else {
+                warnings
+            }
+        }
+        Err(e) => vec![format!("could not run the validation command: {e}")],
+    }
+}
+
+/// warnings for the crops of the wallpaper from the enabled rules and the validation command,
+/// checked before the wallpaper is saved
+pub fn validate(config: &WallpaperConfig, info: &WallInfo) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let enabled = |rule: &str| config.validation_rules.iter().any(|r| r == rule);
+
+    for (name, ratio) in &config.resolutions {
+        let geom = info.get_geometry(ratio);
+
+        if enabled("faces") && !contains_faces(info, &geom) {
+            warnings.push(format!("{name}: crop does not contain all faces"));
+        }
+
+        if enabled("min-size") {
+            let min_size = config
+                .min_sizes
+                .iter()
+                .find(|(res, _)| res == name)
+                .map(|(_, size)| *size);
+            // min_sizes are for the source like in wallpapers add, the crops are usually smaller
+            let (width, height) = (info.width, info.height);
+            if let Some((min_w, min_h)) = min_size.filter(|(w, h)| width < *w || height < *h) {
+                warnings.push(format!(
+                    "{name}: image is {width}x{height}, smaller than the minimum of {min_w}x{min_h}"
+                ));
+            }
+        }
+    }
+
+    if !config.validation_command.is_empty() {
+        warnings.extend(run_command(config, info));
+    }
+
+    warnings
+}
@@ -0,0 +1,76 @@
+//! crops for a 10k wallpaper collection, run with `cargo bench -p wallpaper-core`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rayon::prelude::*;
+use wallpaper_core::{AspectRatio, Cropper, Face};
+
+const COLLECTION_SIZE: u32 = 10_000;
+
+/// wallpapers with zero to three faces in different positions, alternating between landscape
+/// and portrait
+fn collection() -> Vec<(Vec<Face>, u32, u32)> {
+    (0..COLLECTION_SIZE)
+        .map(|i| {
+            let (width, height) = if i % 3 == 0 {
+                (2160, 3840)
+            } else {
+                (3840, 2160)
+            };
+            let faces = (0..i % 4)
+                .map(|j| {
+                    let xmin = (i * 37 + j * 400) % (width - 300);
+                    let ymin = (i * 53 + j * 200) % (height - 300);
+                    Face {
+                        xmin,
+                        xmax: xmin + 300,
+                        ymin,
+                        ymax: ymin + 300,
+                        eye_y: None,
+                    }
+                })
+                .collect();
+            (faces, width, height)
+        })
+        .collect()
+}
+
+fn crop_collection(c: &mut Criterion) {
+    let walls = collection();
+    let ratios = [
+        AspectRatio::new(1920, 1080),
+        AspectRatio::new(1440, 2560),
+        AspectRatio::new(3440, 1440),
+    ];
+
+    c.bench_function("crop 10k serial", |b| {
+        b.iter(|| {
+            walls
+                .iter()
+                .map(|(faces, width, height)| {
+                    let cropper = Cropper::new(faces, *width, *height);
+                    ratios
+                        .iter()
+                        .map(|ratio| u64::from(cropper.crop(black_box(ratio)).w))
+                        .sum::<u64>()
+                })
+                .sum::<u64>()
+        });
+    });
+
+    c.bench_function("crop 10k parallel", |b| {
+        b.iter(|| {
+            walls
+                .par_iter()
+                .map(|(faces, width, height)| {
+                    let cropper = Cropper::new(faces, *width, *height);
+                    ratios
+                        .iter()
+                        .map(|ratio| u64::from(cropper.crop(black_box(ratio)).w))
+                        .sum::<u64>()
+                })
+                .sum::<u64>()
+        });
+    });
+}
+
+criterion_group!(benches, crop_collection);
+criterion_main!(benches);
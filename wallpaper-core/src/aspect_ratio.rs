@@ -0,0 +1,209 @@
+use serde::Serialize;
+
+use crate::wallpapers::WallpapersError;
+
+/// euclid's algorithm to find the greatest common divisor
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let tmp = b;
+        b = a % b;
+        a = tmp;
+    }
+    a
+}
+
+/// aspect ratio of a target resolution, reduced to its lowest terms
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectRatio {
+    pub w: u32,
+    pub h: u32,
+}
+
+impl std::fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.w, self.h)
+    }
+}
+
+impl PartialOrd for AspectRatio {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AspectRatio {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        f64::from(self)
+            .partial_cmp(&f64::from(other))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    }
+}
+
+impl From<&AspectRatio> for f64 {
+    fn from(val: &AspectRatio) -> Self {
+        Self::from(val.w) / Self::from(val.h)
+    }
+}
+
+/// parses a decimal number into a fraction, e.g. 2.39 -> (239, 100)
+fn parse_decimal(s: &str) -> Option<(u32, u32)> {
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() && frac.is_empty() {
+        return None;
+    }
+
+    let denominator = 10_u32.checked_pow(frac.len().try_into().ok()?)?;
+    let numerator = format!("{whole}{frac}").parse::<u32>().ok()?;
+
+    Some((numerator, denominator))
+}
+
+impl TryFrom<&str> for AspectRatio {
+    type Error = ();
+
+    /// accepts "WxH", "W:H" or a decimal ratio such as "2.39"
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let s = s.trim().trim_matches('"');
+
+        let (width, height) = match s.split_once(['x', ':']) {
+            Some((w, h)) => (
+                w.trim().parse().map_err(|_| ())?,
+                h.trim().parse().map_err(|_| ())?,
+            ),
+            None => parse_decimal(s).ok_or(())?,
+        };
+
+        if width == 0 || height == 0 {
+            return Err(());
+        }
+
+        Ok(Self::new(width, height))
+    }
+}
+
+impl Serialize for AspectRatio {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_str(&format!("{}", self))
+    }
+}
+
+impl AspectRatio {
+    pub const fn new(width: u32, height: u32) -> Self {
+        let divisor = gcd(width, height);
+        Self {
+            w: width / divisor,
+            h: height / divisor,
+        }
+    }
+
+    /// taller than it is wide, e.g. phones and vertical monitors
+    pub const fn is_portrait(&self) -> bool {
+        self.h > self.w
+    }
+}
+
+/// parse a comma separated list of resolutions, "all" returns all the given resolutions
+///
+/// resolutions can be given by their names in the config or as aspect ratios
+pub fn resolutions_from_arg(
+    arg: &str,
+    resolutions: &[(String, AspectRatio)],
+) -> Result<Vec<AspectRatio>, WallpapersError> {
+    if arg == "all" {
+        return Ok(resolutions.iter().map(|(_, res)| res.clone()).collect());
+    }
+
+    arg.split(',')
+        .map(|s| {
+            let s = s.trim();
+            resolutions
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(s))
+                .map_or_else(
+                    || {
+                        AspectRatio::try_from(s)
+                            .map_err(|()| WallpapersError::InvalidResolution(s.to_string()))
+                    },
+                    |(_, res)| Ok(res.clone()),
+                )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolutions() -> Vec<(String, AspectRatio)> {
+        vec![
+            ("HD".to_string(), AspectRatio::new(1920, 1080)),
+            ("vertical".to_string(), AspectRatio::new(1440, 2560)),
+        ]
+    }
+
+    #[test]
+    fn aspect_ratio_is_reduced() {
+        assert_eq!(AspectRatio::new(3440, 1440), AspectRatio { w: 43, h: 18 });
+        assert_eq!(AspectRatio::new(1920, 1080).to_string(), "16x9");
+    }
+
+    #[test]
+    fn aspect_ratio_parse() {
+        assert_eq!(AspectRatio::try_from("16x9"), Ok(AspectRatio::new(16, 9)));
+        assert_eq!(AspectRatio::try_from("21:9"), Ok(AspectRatio::new(7, 3)));
+        assert_eq!(
+            AspectRatio::try_from("\"1440 x 2560\""),
+            Ok(AspectRatio::new(9, 16))
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_parse_decimal() {
+        assert_eq!(
+            AspectRatio::try_from("2.39"),
+            Ok(AspectRatio::new(239, 100))
+        );
+        assert_eq!(AspectRatio::try_from("1.5"), Ok(AspectRatio::new(3, 2)));
+        assert_eq!(AspectRatio::try_from("2"), Ok(AspectRatio::new(2, 1)));
+        assert_eq!(AspectRatio::try_from(".5"), Ok(AspectRatio::new(1, 2)));
+    }
+
+    #[test]
+    fn aspect_ratio_parse_errors() {
+        for s in ["", "wide", "16x", "0x9", "16:0", "0.0", "1.2.3"] {
+            assert_eq!(AspectRatio::try_from(s), Err(()), "{s}");
+        }
+    }
+
+    #[test]
+    fn resolutions_from_names() {
+        let resolutions = resolutions();
+        assert_eq!(
+            resolutions_from_arg("hd,Vertical", &resolutions).expect("known names"),
+            vec![AspectRatio::new(16, 9), AspectRatio::new(9, 16)]
+        );
+        assert_eq!(
+            resolutions_from_arg("all", &resolutions).expect("all resolutions"),
+            vec![AspectRatio::new(16, 9), AspectRatio::new(9, 16)]
+        );
+    }
+
+    #[test]
+    fn resolutions_from_ratios() {
+        assert_eq!(
+            resolutions_from_arg("HD, 21:9, 2.39", &resolutions()).expect("valid ratios"),
+            vec![
+                AspectRatio::new(16, 9),
+                AspectRatio::new(21, 9),
+                AspectRatio::new(239, 100)
+            ]
+        );
+        assert!(matches!(
+            resolutions_from_arg("HD,phone", &resolutions()),
+            Err(WallpapersError::InvalidResolution(name)) if name == "phone"
+        ));
+    }
+}
@@ -0,0 +1,1061 @@
+use std::path::{Path, PathBuf};
+
+use ini::{Ini, Properties};
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+use thiserror::Error;
+
+use crate::{
+    aspect_ratio::AspectRatio, cropper::CropOptions, full_path, geometry::parse_hex_color, paths,
+    tools::ExternalTools,
+};
+
+/// built-in rules that can be enabled with validation_rules in the config, the checks are in
+/// the validation module of wallpaper-ui
+pub const VALIDATION_RULES: &[&str] = &["faces", "min-size"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallpaperConfig {
+    pub wallpapers_path: PathBuf,
+    pub csv_path: PathBuf,
+    /// where the wallpapers are stored, one of STORES
+    pub store: String,
+    /// database of the wallpapers when store = sqlite
+    pub db_path: PathBuf,
+    pub min_width: u32,
+    pub min_height: u32,
+    pub show_faces: bool,
+    /// color of the face boxes, a hex color or auto to stand out against the wallpaper
+    pub face_color: String,
+    /// color of the border around the crop, a hex color or auto
+    pub crop_border_color: String,
+    /// in pixels, 0 hides the border
+    pub crop_border_width: u32,
+    /// opacity of the dimmed region outside of the crop, from 0 to 100
+    pub overlay_opacity: u32,
+    /// thicker face boxes and crop border with stronger dimming, for users with low vision
+    pub high_contrast: bool,
+    /// size of the ui text and spacing in percent, for hidpi screens
+    pub ui_scale: u32,
+    /// initial preview mode of the ui, one of PREVIEW_MODES
+    pub preview_mode: String,
+    /// catppuccin flavour of the ui, one of THEMES
+    pub theme: String,
+    /// language of the ui, one of LANGUAGES, auto uses the language of the locale
+    pub language: String,
+    /// move on to the next wallpaper after saving
+    pub auto_advance: bool,
+    /// wallpapers deleted in the ui are moved to the trash so they can be restored with
+    /// `wallpapers trash restore`, they are deleted permanently if false
+    pub trash_deleted: bool,
+    /// default order of the wallpapers, one of SORT_ORDERS
+    pub sort: String,
+    /// minimum number of pixels the crop moves by with the arrow keys
+    pub arrow_step: u32,
+    /// crop candidates within this many pixels of each other are shown as one
+    pub candidate_tolerance: u32,
+    /// in milliseconds, saves in the ui are written to the csv after this long without another
+    /// save, 0 writes them immediately
+    pub save_delay: u64,
+    /// weight default crops towards the largest face
+    pub largest_face: bool,
+    /// place the eye line of single faces on the upper third line, only for faces whose eyes
+    /// were found, e.g. by an onnx face model with landmarks or a face_command printing eye_y
+    pub eye_line: bool,
+    /// percentage of the face size kept around faces, doubled above them
+    pub face_padding: u32,
+    /// detects the faces of new wallpapers, one of FACE_DETECTORS, anime-face-detector falls
+    /// back to onnx if it isn't installed and wallpapers was built with the onnx feature
+    pub face_detector: String,
+    /// onnx model used by the onnx face detector, unless one is embedded in the binary
+    pub face_model: PathBuf,
+    /// onnx model of real faces used by the photo detector of wallpapers-add
+    pub photo_face_model: PathBuf,
+    /// command used by the command detector of wallpapers-add, the images are appended and it
+    /// prints the faces of each image as a line of json like anime-face-detector, optionally
+    /// with the eye_y of each face
+    pub face_command: String,
+    /// fill color for padded crops
+    pub pad_color: String,
+    /// program used to set wallpapers, one of APPLY_BACKENDS
+    pub apply_backend: String,
+    pub transition_type: String,
+    /// in seconds
+    pub transition_duration: f64,
+    /// wallust arguments for palettes generated when adding wallpapers, e.g. --backend full, they
+    /// are stored in the wallust column of the new wallpapers
+    pub wallust_args: String,
+    /// output directory of the blurred and dimmed crops for lockscreens
+    pub lockscreen_dir: PathBuf,
+    /// gaussian blur sigma of the lockscreen crops
+    pub lockscreen_blur: f32,
+    /// brightness of the lockscreen crops as a percentage of the original
+    pub lockscreen_brightness: u32,
+    /// directory of the colorscheme templates, e.g. kitty.conf or base16.yaml
+    pub colorscheme_templates: PathBuf,
+    /// output directory of the rendered colorschemes
+    pub colorscheme_dir: PathBuf,
+    /// convert images with other color profiles, e.g. display p3, to srgb when adding them
+    pub convert_to_srgb: bool,
+    /// optimize the images in process instead of with cwebp, jpegoptim and oxipng, the in process
+    /// encoders are also used for tools that aren't installed
+    pub native_optimize: bool,
+    /// command generating a depth map when adding wallpapers, with {input} and {output}
+    /// placeholders, depth maps are not generated if empty
+    pub depth_command: String,
+    /// output directory of the depth maps
+    pub depth_dir: PathBuf,
+    /// path of the exported crops relative to the output directory, see export::export_path
+    pub export_template: String,
+    /// output directory of the downscaled crops for low power devices
+    pub low_power_dir: PathBuf,
+    /// maximum height of the low power crops
+    pub low_power_height: u32,
+    /// one of LOW_POWER_FORMATS
+    pub low_power_format: String,
+    /// encoder quality of the low power crops, from 0 to 100
+    pub low_power_quality: u32,
+    /// output directory of the static html gallery
+    pub gallery_dir: PathBuf,
+    /// review upscaled images side by side with their source in the ui when adding them
+    pub review_upscales: bool,
+    /// accept or reject the default crops of the new wallpapers after adding them, only the
+    /// rejected wallpapers are opened in the editor
+    pub review_crops: bool,
+    /// gpus of realcugan-ncnn-vulkan that the upscales are spread across, -1 is the cpu and
+    /// empty lets realcugan pick
+    pub upscale_gpus: Vec<i32>,
+    /// external tools run at the same time by wallpapers-add, e.g. realcugan and the optimizers
+    pub max_jobs: usize,
+    /// shell command run after the crops of a wallpaper are saved, see hooks::post_save
+    pub post_save_hook: String,
+    /// shell command run after wallpapers are added, see hooks::post_add
+    pub post_add_hook: String,
+    /// built-in rules checked before saving, from VALIDATION_RULES, none unless enabled
+    pub validation_rules: Vec<String>,
+    /// shell command checking a wallpaper before saving, see validation::validate
+    pub validation_command: String,
+    pub resolutions: Vec<(String, AspectRatio)>,
+    /// minimum source dimensions for each resolution name, on top of min_width and min_height
+    pub min_sizes: Vec<(String, (u32, u32))>,
+    pub tools: ExternalTools,
+    /// glob patterns relative to the wallpapers directory for files that are not wallpapers
+    pub ignore: Vec<glob::Pattern>,
+    /// directories checked for copies of the wallpapers by the dedupe command, e.g. downloads
+    pub dedupe_dirs: Vec<PathBuf>,
+    /// names and templates of the custom commands for the current wallpaper, see
+    /// custom_commands::command_args
+    pub commands: Vec<(String, String)>,
+}
+
+pub const PREVIEW_MODES: &[&str] = &["candidate", "pan"];
+pub const THEMES: &[&str] = &["latte", "frappe", "macchiato", "mocha"];
+pub const LANGUAGES: &[&str] = &["auto", "en", "de"];
+pub const FACE_DETECTORS: &[&str] = &["anime-face-detector", "onnx"];
+pub const STORES: &[&str] = &["csv", "sqlite"];
+pub const APPLY_BACKENDS: &[&str] = &["swww", "hyprpaper", "gnome", "kde", "feh", "xwallpaper"];
+pub const LOW_POWER_FORMATS: &[&str] = &["webp", "jpg"];
+pub const SORT_ORDERS: &[&str] = &["name", "mtime", "size", "resolution", "faces", "random"];
+
+/// parses the gpus for upscaling, either auto, cpu or comma separated gpu indexes
+pub fn parse_gpus(s: &str) -> Option<Vec<i32>> {
+    match s.trim() {
+        "" | "auto" => Some(Vec::new()),
+        "cpu" => Some(vec![-1]),
+        gpus => gpus
+            .split(',')
+            .map(|gpu| gpu.trim().parse().ok().filter(|gpu| *gpu >= -1))
+            .collect(),
+    }
+}
+
+/// formats the gpus for upscaling as they are written in the config
+pub fn format_gpus(gpus: &[i32]) -> String {
+    match gpus {
+        [] => "auto".into(),
+        [-1] => "cpu".into(),
+        gpus => gpus.iter().join(","),
+    }
+}
+
+/// parses a minimum size in the format WIDTHxHEIGHT
+pub fn parse_min_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.trim().trim_matches('"').split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("could not read config: {0}")]
+    Read(String),
+    #[error("could not parse config: {0}")]
+    Syntax(String),
+    #[error("{}invalid {key} \"{value}\", expected {expected}", line_prefix(*.line))]
+    InvalidValue {
+        line: Option<usize>,
+        key: String,
+        value: String,
+        expected: String,
+    },
+    #[error(
+        "{}invalid resolution {name} \"{value}\", expected WIDTHxHEIGHT, WIDTH:HEIGHT or a decimal ratio",
+        line_prefix(*.line)
+    )]
+    InvalidResolution {
+        line: Option<usize>,
+        name: String,
+        value: String,
+    },
+    #[error("{}resolution {name} overlaps with {other}", line_prefix(*.line))]
+    OverlappingResolution {
+        line: Option<usize>,
+        name: String,
+        other: String,
+    },
+    #[error("{}min size for unknown resolution {name}", line_prefix(*.line))]
+    UnknownResolution { line: Option<usize>, name: String },
+    #[error("{}{key} {path:?} is not a directory", line_prefix(*.line))]
+    MissingDirectory {
+        line: Option<usize>,
+        key: String,
+        path: PathBuf,
+    },
+}
+
+fn line_prefix(line: Option<usize>) -> String {
+    line.map_or_else(String::new, |line| format!("line {line}: "))
+}
+
+/// all the errors found in the config
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{}", .0.iter().map(ToString::to_string).join("\n"))]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+/// collects the errors while parsing the config
+struct ConfigParser<'a> {
+    contents: &'a str,
+    errors: Vec<ConfigError>,
+}
+
+impl ConfigParser<'_> {
+    /// 1-based line number of the key within the section, None for the general section
+    fn line(&self, section: Option<&str>, key: &str) -> Option<usize> {
+        let mut current: Option<&str> = None;
+
+        self.contents.lines().enumerate().find_map(|(idx, line)| {
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                current = Some(name.trim());
+                return None;
+            }
+
+            let (k, _) = line.split_once(['=', ':'])?;
+            (current == section && k.trim() == key).then_some(idx + 1)
+        })
+    }
+
+    fn error<F>(&mut self, section: Option<&str>, key: &str, make_error: F)
+    where
+        F: FnOnce(Option<usize>) -> ConfigError,
+    {
+        let line = self.line(section, key);
+        self.errors.push(make_error(line));
+    }
+
+    fn invalid(&mut self, section: Option<&str>, key: &str, value: &str, expected: &str) {
+        self.error(section, key, |line| ConfigError::InvalidValue {
+            line,
+            key: key.to_string(),
+            value: value.to_string(),
+            expected: expected.to_string(),
+        });
+    }
+
+    /// parses an optional value in the general section
+    fn value<T: std::str::FromStr>(
+        &mut self,
+        props: &Properties,
+        key: &str,
+        expected: &str,
+    ) -> Option<T> {
+        let v = props.get(key)?;
+        v.parse().map_or_else(
+            |_| {
+                self.invalid(None, key, v, expected);
+                None
+            },
+            Some,
+        )
+    }
+
+    /// parses an optional value in the general section that must be one of the choices
+    fn choice(&mut self, props: &Properties, key: &str, choices: &[&str]) -> Option<String> {
+        let v = props.get(key)?;
+        if choices.contains(&v) {
+            Some(v.to_string())
+        } else {
+            self.invalid(None, key, v, &format!("one of {}", choices.join(", ")));
+            None
+        }
+    }
+
+    /// parses an optional color of the ui overlays, either a hex color or auto
+    fn overlay_color(&mut self, props: &Properties, key: &str) -> Option<String> {
+        let v = props.get(key)?;
+        if v == "auto" || parse_hex_color(v).is_some() {
+            Some(v.to_string())
+        } else {
+            self.invalid(None, key, v, "a hex color, e.g. #1e1e2e, or auto");
+            None
+        }
+    }
+
+    /// resolution names are matched case-insensitively and ratios should be unique
+    fn check_overlapping(&mut self, resolutions: &[(String, AspectRatio)]) {
+        for (i, (name, ratio)) in resolutions.iter().enumerate() {
+            let other = resolutions[..i].iter().find(|(other_name, other_ratio)| {
+                other_name.eq_ignore_ascii_case(name) || other_ratio == ratio
+            });
+
+            if let Some((other, _)) = other {
+                let other = other.clone();
+                self.error(Some("resolutions"), name, |line| {
+                    ConfigError::OverlappingResolution {
+                        line,
+                        name: name.to_string(),
+                        other,
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl Default for WallpaperConfig {
+    fn default() -> Self {
+        Self {
+            wallpapers_path: full_path("~/Pictures/Wallpapers"),
+            csv_path: paths::csv_file(),
+            store: "csv".into(),
+            db_path: paths::data_dir().join("wallpapers.db"),
+            min_width: 1920,
+            min_height: 1080,
+            show_faces: false,
+            face_color: "#ef4444".into(),
+            crop_border_color: "auto".into(),
+            crop_border_width: 0,
+            overlay_opacity: 60,
+            high_contrast: false,
+            ui_scale: 100,
+            preview_mode: "candidate".into(),
+            theme: "mocha".into(),
+            language: "auto".into(),
+            auto_advance: true,
+            trash_deleted: true,
+            sort: "mtime".into(),
+            arrow_step: 2,
+            candidate_tolerance: 8,
+            save_delay: 2000,
+            largest_face: false,
+            eye_line: false,
+            face_padding: 0,
+            face_detector: "anime-face-detector".into(),
+            face_model: paths::data_dir().join("anime-face.onnx"),
+            photo_face_model: paths::data_dir().join("photo-face.onnx"),
+            face_command: String::new(),
+            pad_color: "#000000".into(),
+            apply_backend: "swww".into(),
+            transition_type: "simple".into(),
+            transition_duration: 1.0,
+            wallust_args: String::new(),
+            lockscreen_dir: paths::data_dir().join("lockscreen"),
+            lockscreen_blur: 8.0,
+            lockscreen_brightness: 60,
+            colorscheme_templates: paths::config_dir().join("templates"),
+            colorscheme_dir: paths::data_dir().join("colorschemes"),
+            convert_to_srgb: true,
+            native_optimize: false,
+            depth_command: String::new(),
+            depth_dir: paths::data_dir().join("depth"),
+            export_template: "{resolution}/{name}.{ext}".into(),
+            low_power_dir: paths::data_dir().join("low-power"),
+            low_power_height: 1080,
+            low_power_format: "webp".into(),
+            low_power_quality: 80,
+            gallery_dir: paths::data_dir().join("gallery"),
+            review_upscales: true,
+            review_crops: false,
+            upscale_gpus: Vec::new(),
+            max_jobs: std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
+            post_save_hook: String::new(),
+            post_add_hook: String::new(),
+            validation_rules: Vec::new(),
+            validation_command: String::new(),
+            resolutions: vec![("HD".into(), AspectRatio::new(1920, 1080))],
+            min_sizes: Vec::new(),
+            tools: ExternalTools::default(),
+            ignore: Vec::new(),
+            dedupe_dirs: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl WallpaperConfig {
+    /// path to config.ini
+    pub fn config_file() -> PathBuf {
+        paths::config_file()
+    }
+
+    /// loads config.ini, the default config is used if it does not exist
+    pub fn load() -> Result<Self, ConfigErrors> {
+        let config_file = Self::config_file();
+        if !config_file.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&config_file)
+            .map_err(|e| ConfigErrors(vec![ConfigError::Read(e.to_string())]))?;
+        Self::parse(&contents)
+    }
+
+    /// parses the contents of config.ini, collecting all the errors
+    pub fn parse(contents: &str) -> Result<Self, ConfigErrors> {
+        let conf = Ini::load_from_str(contents)
+            .map_err(|e| ConfigErrors(vec![ConfigError::Syntax(e.to_string())]))?;
+        let mut parser = ConfigParser {
+            contents,
+            errors: Vec::new(),
+        };
+        let default_cfg = Self::default();
+        let general = conf.general_section();
+
+        let resolutions = conf.section(Some("resolutions")).map_or_else(
+            || default_cfg.resolutions.clone(),
+            |res| {
+                res.iter()
+                    .filter_map(|(k, v)| {
+                        let Ok(ratio) = AspectRatio::try_from(v) else {
+                            parser.error(Some("resolutions"), k, |line| {
+                                ConfigError::InvalidResolution {
+                                    line,
+                                    name: k.to_string(),
+                                    value: v.to_string(),
+                                }
+                            });
+                            return None;
+                        };
+                        Some((k.to_string(), ratio))
+                    })
+                    .sorted_by_key(|(_, ratio)| ratio.clone())
+                    .collect()
+            },
+        );
+        parser.check_overlapping(&resolutions);
+
+        let min_sizes = conf
+            .section(Some("min_sizes"))
+            .map(|sizes| {
+                sizes
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        if !resolutions.iter().any(|(name, _)| name == k) {
+                            parser.error(Some("min_sizes"), k, |line| {
+                                ConfigError::UnknownResolution {
+                                    line,
+                                    name: k.to_string(),
+                                }
+                            });
+                        }
+
+                        let Some(size) = parse_min_size(v) else {
+                            parser.invalid(Some("min_sizes"), k, v, "WIDTHxHEIGHT");
+                            return None;
+                        };
+                        Some((k.to_string(), size))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let wallpapers_path =
+            general
+                .get("wallpapers_path")
+                .map_or(default_cfg.wallpapers_path, |v| {
+                    let path = full_path(v);
+                    if !path.is_dir() {
+                        parser.error(None, "wallpapers_path", |line| {
+                            ConfigError::MissingDirectory {
+                                line,
+                                key: "wallpapers_path".to_string(),
+                                path: path.clone(),
+                            }
+                        });
+                    }
+                    path
+                });
+
+        let ignore = general
+            .get("ignore")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|pattern| !pattern.is_empty())
+                    .filter_map(|pattern| {
+                        glob::Pattern::new(pattern)
+                            .map_err(|_| {
+                                parser.invalid(None, "ignore", pattern, "comma separated globs");
+                            })
+                            .ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let commands = conf
+            .section(Some("commands"))
+            .map(|commands| {
+                commands
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        if v.trim().is_empty() {
+                            parser.invalid(Some("commands"), k, v, "a command");
+                            return None;
+                        }
+                        Some((k.to_string(), v.trim().to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let validation_rules =
+            general
+                .get("validation_rules")
+                .map_or(default_cfg.validation_rules, |v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|rule| !rule.is_empty())
+                        .filter(|rule| {
+                            let valid = VALIDATION_RULES.contains(rule);
+                            if !valid {
+                                parser.invalid(
+                                    None,
+                                    "validation_rules",
+                                    rule,
+                                    &format!("comma separated {}", VALIDATION_RULES.join(", ")),
+                                );
+                            }
+                            valid
+                        })
+                        .map(ToString::to_string)
+                        .collect()
+                });
+
+        let config = Self {
+            wallpapers_path,
+            csv_path: general
+                .get("csv_path")
+                .map_or(default_cfg.csv_path, full_path),
+            store: parser
+                .choice(general, "store", STORES)
+                .unwrap_or(default_cfg.store),
+            db_path: general
+                .get("db_path")
+                .map_or(default_cfg.db_path, full_path),
+            min_width: parser
+                .value(general, "min_width", "a positive integer")
+                .unwrap_or(default_cfg.min_width),
+            min_height: parser
+                .value(general, "min_height", "a positive integer")
+                .unwrap_or(default_cfg.min_height),
+            show_faces: parser
+                .value(general, "show_faces", "true or false")
+                .unwrap_or(default_cfg.show_faces),
+            face_color: parser
+                .overlay_color(general, "face_color")
+                .unwrap_or(default_cfg.face_color),
+            crop_border_color: parser
+                .overlay_color(general, "crop_border_color")
+                .unwrap_or(default_cfg.crop_border_color),
+            crop_border_width: parser
+                .value(general, "crop_border_width", "a non-negative integer")
+                .unwrap_or(default_cfg.crop_border_width),
+            overlay_opacity: parser
+                .value(general, "overlay_opacity", "a number from 0 to 100")
+                .map(|opacity: u32| opacity.min(100))
+                .unwrap_or(default_cfg.overlay_opacity),
+            high_contrast: parser
+                .value(general, "high_contrast", "true or false")
+                .unwrap_or(default_cfg.high_contrast),
+            ui_scale: parser
+                .value(general, "ui_scale", "a percentage from 50 to 300")
+                .map(|scale: u32| scale.clamp(50, 300))
+                .unwrap_or(default_cfg.ui_scale),
+            preview_mode: parser
+                .choice(general, "preview_mode", PREVIEW_MODES)
+                .unwrap_or(default_cfg.preview_mode),
+            theme: parser
+                .choice(general, "theme", THEMES)
+                .unwrap_or(default_cfg.theme),
+            language: parser
+                .choice(general, "language", LANGUAGES)
+                .unwrap_or(default_cfg.language),
+            auto_advance: parser
+                .value(general, "auto_advance", "true or false")
+                .unwrap_or(default_cfg.auto_advance),
+            trash_deleted: parser
+                .value(general, "trash_deleted", "true or false")
+                .unwrap_or(default_cfg.trash_deleted),
+            sort: parser
+                .choice(general, "sort", SORT_ORDERS)
+                .unwrap_or(default_cfg.sort),
+            arrow_step: parser
+                .value(general, "arrow_step", "a positive integer")
+                .unwrap_or(default_cfg.arrow_step),
+            candidate_tolerance: parser
+                .value(general, "candidate_tolerance", "a non-negative integer")
+                .unwrap_or(default_cfg.candidate_tolerance),
+            save_delay: parser
+                .value(general, "save_delay", "a non-negative integer")
+                .unwrap_or(default_cfg.save_delay),
+            largest_face: parser
+                .value(general, "largest_face", "true or false")
+                .unwrap_or(default_cfg.largest_face),
+            eye_line: parser
+                .value(general, "eye_line", "true or false")
+                .unwrap_or(default_cfg.eye_line),
+            face_padding: parser
+                .value(general, "face_padding", "a percentage, e.g. 10")
+                .unwrap_or(default_cfg.face_padding),
+            face_detector: parser
+                .choice(general, "face_detector", FACE_DETECTORS)
+                .unwrap_or(default_cfg.face_detector),
+            face_model: general
+                .get("face_model")
+                .map_or(default_cfg.face_model, full_path),
+            photo_face_model: general
+                .get("photo_face_model")
+                .map_or(default_cfg.photo_face_model, full_path),
+            face_command: general
+                .get("face_command")
+                .map_or(default_cfg.face_command, |v| v.trim().to_string()),
+            pad_color: general.get("pad_color").map_or(default_cfg.pad_color, |v| {
+                if parse_hex_color(v).is_none() {
+                    parser.invalid(None, "pad_color", v, "a hex color, e.g. #1e1e2e");
+                }
+                v.to_string()
+            }),
+            apply_backend: parser
+                .choice(general, "apply_backend", APPLY_BACKENDS)
+                .unwrap_or(default_cfg.apply_backend),
+            transition_type: general
+                .get("transition_type")
+                .map_or(default_cfg.transition_type, String::from),
+            transition_duration: parser
+                .value(general, "transition_duration", "a number of seconds")
+                .unwrap_or(default_cfg.transition_duration),
+            wallust_args: general
+                .get("wallust_args")
+                .map_or(default_cfg.wallust_args, |v| v.trim().to_string()),
+            lockscreen_dir: general
+                .get("lockscreen_dir")
+                .map_or(default_cfg.lockscreen_dir, full_path),
+            lockscreen_blur: parser
+                .value(general, "lockscreen_blur", "a non-negative number")
+                .unwrap_or(default_cfg.lockscreen_blur),
+            lockscreen_brightness: parser
+                .value(general, "lockscreen_brightness", "a percentage, e.g. 60")
+                .unwrap_or(default_cfg.lockscreen_brightness),
+            colorscheme_templates: general
+                .get("colorscheme_templates")
+                .map_or(default_cfg.colorscheme_templates, full_path),
+            colorscheme_dir: general
+                .get("colorscheme_dir")
+                .map_or(default_cfg.colorscheme_dir, full_path),
+            convert_to_srgb: parser
+                .value(general, "convert_to_srgb", "true or false")
+                .unwrap_or(default_cfg.convert_to_srgb),
+            native_optimize: parser
+                .value(general, "native_optimize", "true or false")
+                .unwrap_or(default_cfg.native_optimize),
+            depth_command: general
+                .get("depth_command")
+                .map_or(default_cfg.depth_command, |v| v.trim().to_string()),
+            depth_dir: general
+                .get("depth_dir")
+                .map_or(default_cfg.depth_dir, full_path),
+            export_template: general.get("export_template").map_or(
+                default_cfg.export_template,
+                |v| {
+                    // exports of different wallpapers would overwrite each other
+                    if !v.contains("{name}") {
+                        parser.invalid(None, "export_template", v, "a template containing {name}");
+                    }
+                    v.to_string()
+                },
+            ),
+            low_power_dir: general
+                .get("low_power_dir")
+                .map_or(default_cfg.low_power_dir, full_path),
+            low_power_height: parser
+                .value(general, "low_power_height", "a positive integer")
+                .unwrap_or(default_cfg.low_power_height),
+            low_power_format: parser
+                .choice(general, "low_power_format", LOW_POWER_FORMATS)
+                .unwrap_or(default_cfg.low_power_format),
+            low_power_quality: parser
+                .value(general, "low_power_quality", "a number from 0 to 100")
+                .unwrap_or(default_cfg.low_power_quality),
+            gallery_dir: general
+                .get("gallery_dir")
+                .map_or(default_cfg.gallery_dir, full_path),
+            review_upscales: parser
+                .value(general, "review_upscales", "true or false")
+                .unwrap_or(default_cfg.review_upscales),
+            review_crops: parser
+                .value(general, "review_crops", "true or false")
+                .unwrap_or(default_cfg.review_crops),
+            upscale_gpus: general
+                .get("upscale_gpus")
+                .map_or(default_cfg.upscale_gpus, |v| {
+                    parse_gpus(v).unwrap_or_else(|| {
+                        parser.invalid(
+                            None,
+                            "upscale_gpus",
+                            v,
+                            "auto, cpu or gpu indexes, e.g. 0,1",
+                        );
+                        Vec::new()
+                    })
+                }),
+            max_jobs: parser
+                .value(general, "max_jobs", "a positive integer")
+                .filter(|jobs| *jobs > 0)
+                .unwrap_or(default_cfg.max_jobs),
+            post_save_hook: general
+                .get("post_save_hook")
+                .map_or(default_cfg.post_save_hook, |v| v.trim().to_string()),
+            post_add_hook: general
+                .get("post_add_hook")
+                .map_or(default_cfg.post_add_hook, |v| v.trim().to_string()),
+            validation_rules,
+            validation_command: general
+                .get("validation_command")
+                .map_or(default_cfg.validation_command, |v| v.trim().to_string()),
+            resolutions,
+            min_sizes,
+            tools: conf
+                .section(Some("tools"))
+                .map(|tools| ExternalTools::from_section(tools.iter()))
+                .unwrap_or_default(),
+            ignore,
+            dedupe_dirs: general
+                .get("dedupe_dirs")
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|dir| !dir.is_empty())
+                        .map(full_path)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            commands,
+        };
+
+        if parser.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigErrors(parser.errors))
+        }
+    }
+
+    pub fn sorted_resolutions(&self) -> Vec<AspectRatio> {
+        self.resolutions.iter().map(|(_, v)| v.clone()).collect()
+    }
+
+    /// strictest minimum source dimensions, given the global minimums and the
+    /// minimums of each resolution
+    ///
+    /// the axes are combined, so a source that is large enough for a landscape resolution is
+    /// still upscaled for the rows a portrait resolution needs
+    pub fn strictest_min_size(&self, min_width: u32, min_height: u32) -> (u32, u32) {
+        self.min_sizes
+            .iter()
+            .filter(|(name, _)| self.resolutions.iter().any(|(res, _)| res == name))
+            .fold((min_width, min_height), |(w, h), (_, (min_w, min_h))| {
+                (w.max(*min_w), h.max(*min_h))
+            })
+    }
+
+    /// name of the resolution in the config, falls back to the aspect ratio
+    pub fn resolution_name(&self, ratio: &AspectRatio) -> String {
+        self.resolutions
+            .iter()
+            .find(|(_, res)| res == ratio)
+            .map_or_else(|| ratio.to_string(), |(name, _)| name.clone())
+    }
+
+    /// finds the closest resolution
+    pub fn closest_resolution(&self, new_res: &AspectRatio) -> Option<AspectRatio> {
+        self.resolutions
+            .iter()
+            .min_by_key(|(_, res)| {
+                let diff = OrderedFloat((f64::from(res) - f64::from(new_res)).abs());
+                // ignore if aspect ratio already exists in config
+                if diff == 0.0 {
+                    f64::INFINITY.into()
+                } else {
+                    diff
+                }
+            })
+            .map(|(_, res)| res.clone())
+    }
+
+    /// options for the cropper
+    pub const fn crop_options(&self) -> CropOptions {
+        CropOptions {
+            largest_face: self.largest_face,
+            eye_line: self.eye_line,
+            face_padding: self.face_padding,
+        }
+    }
+
+    /// file the wallpapers are stored in, wallpapers.csv or the sqlite database
+    pub fn store_path(&self) -> &Path {
+        if self.store == "sqlite" {
+            &self.db_path
+        } else {
+            &self.csv_path
+        }
+    }
+
+    /// adds a resolution in sorted order
+    pub fn add_resolution(&mut self, res_name: &str, res: AspectRatio) {
+        self.resolutions.push((res_name.to_string(), res));
+        self.resolutions.sort_by_key(|(_, r)| r.clone());
+    }
+
+    /// saves the current configuration
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut conf = Ini::new();
+        conf.with_general_section()
+            .set("wallpapers_path", self.wallpapers_path.to_string_lossy())
+            .set("csv_path", self.csv_path.to_string_lossy())
+            .set("store", &self.store)
+            .set("db_path", self.db_path.to_string_lossy())
+            .set("min_width", &self.min_width.to_string())
+            .set("min_height", &self.min_height.to_string())
+            .set("show_faces", &self.show_faces.to_string())
+            .set("face_color", &self.face_color)
+            .set("crop_border_color", &self.crop_border_color)
+            .set("crop_border_width", &self.crop_border_width.to_string())
+            .set("overlay_opacity", &self.overlay_opacity.to_string())
+            .set("high_contrast", &self.high_contrast.to_string())
+            .set("ui_scale", &self.ui_scale.to_string())
+            .set("preview_mode", &self.preview_mode)
+            .set("theme", &self.theme)
+            .set("language", &self.language)
+            .set("auto_advance", &self.auto_advance.to_string())
+            .set("trash_deleted", &self.trash_deleted.to_string())
+            .set("sort", &self.sort)
+            .set("arrow_step", &self.arrow_step.to_string())
+            .set("candidate_tolerance", &self.candidate_tolerance.to_string())
+            .set("save_delay", &self.save_delay.to_string())
+            .set("largest_face", &self.largest_face.to_string())
+            .set("eye_line", &self.eye_line.to_string())
+            .set("face_padding", &self.face_padding.to_string())
+            .set("face_detector", &self.face_detector)
+            .set("face_model", self.face_model.to_string_lossy())
+            .set("photo_face_model", self.photo_face_model.to_string_lossy())
+            .set("face_command", &self.face_command)
+            .set("pad_color", &self.pad_color)
+            .set("apply_backend", &self.apply_backend)
+            .set("transition_type", &self.transition_type)
+            .set("transition_duration", &self.transition_duration.to_string())
+            .set("wallust_args", &self.wallust_args)
+            .set("lockscreen_dir", self.lockscreen_dir.to_string_lossy())
+            .set("lockscreen_blur", &self.lockscreen_blur.to_string())
+            .set(
+                "lockscreen_brightness",
+                &self.lockscreen_brightness.to_string(),
+            )
+            .set(
+                "colorscheme_templates",
+                self.colorscheme_templates.to_string_lossy(),
+            )
+            .set("colorscheme_dir", self.colorscheme_dir.to_string_lossy())
+            .set("convert_to_srgb", &self.convert_to_srgb.to_string())
+            .set("native_optimize", &self.native_optimize.to_string())
+            .set("depth_command", &self.depth_command)
+            .set("depth_dir", self.depth_dir.to_string_lossy())
+            .set("export_template", &self.export_template)
+            .set("low_power_dir", self.low_power_dir.to_string_lossy())
+            .set("low_power_height", &self.low_power_height.to_string())
+            .set("low_power_format", &self.low_power_format)
+            .set("low_power_quality", &self.low_power_quality.to_string())
+            .set("gallery_dir", self.gallery_dir.to_string_lossy())
+            .set("review_upscales", &self.review_upscales.to_string())
+            .set("review_crops", &self.review_crops.to_string())
+            .set("upscale_gpus", format_gpus(&self.upscale_gpus))
+            .set("max_jobs", &self.max_jobs.to_string())
+            .set("post_save_hook", &self.post_save_hook)
+            .set("post_add_hook", &self.post_add_hook)
+            .set("validation_rules", self.validation_rules.join(", "))
+            .set("validation_command", &self.validation_command);
+
+        if !self.ignore.is_empty() {
+            conf.with_general_section().set(
+                "ignore",
+                self.ignore.iter().map(glob::Pattern::as_str).join(", "),
+            );
+        }
+
+        if !self.dedupe_dirs.is_empty() {
+            conf.with_general_section().set(
+                "dedupe_dirs",
+                self.dedupe_dirs
+                    .iter()
+                    .map(|dir| dir.to_string_lossy())
+                    .join(", "),
+            );
+        }
+
+        for (k, v) in &self.resolutions {
+            conf.with_section(Some("resolutions"))
+                .set(k, &v.to_string());
+        }
+
+        for (k, (w, h)) in &self.min_sizes {
+            conf.with_section(Some("min_sizes"))
+                .set(k, format!("{w}x{h}"));
+        }
+
+        for (k, v) in &self.commands {
+            conf.with_section(Some("commands")).set(k, v);
+        }
+
+        for (k, tool) in self.tools.overrides() {
+            conf.with_section(Some("tools"))
+                .set(k, tool.path.to_string_lossy());
+            if !tool.args.is_empty() {
+                conf.with_section(Some("tools"))
+                    .set(format!("{k}_args"), tool.args.join(" "));
+            }
+        }
+
+        std::fs::create_dir_all(paths::config_dir())?;
+        conf.write_to_file(Self::config_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_min_sizes(min_sizes: &[(&str, (u32, u32))]) -> WallpaperConfig {
+        WallpaperConfig {
+            resolutions: vec![
+                ("ultrawide".to_string(), AspectRatio::new(21, 9)),
+                ("vertical".to_string(), AspectRatio::new(9, 16)),
+            ],
+            min_sizes: min_sizes
+                .iter()
+                .map(|(name, size)| ((*name).to_string(), *size))
+                .collect(),
+            ..WallpaperConfig::default()
+        }
+    }
+
+    #[test]
+    fn strictest_min_size_without_min_sizes() {
+        let config = config_with_min_sizes(&[]);
+        assert_eq!(config.strictest_min_size(1920, 1080), (1920, 1080));
+    }
+
+    #[test]
+    fn strictest_min_size_mixed_orientations() {
+        let config =
+            config_with_min_sizes(&[("ultrawide", (5120, 2160)), ("vertical", (2160, 3840))]);
+        assert_eq!(config.strictest_min_size(1920, 1080), (5120, 3840));
+    }
+
+    #[test]
+    fn strictest_min_size_keeps_larger_global_minimum() {
+        let config = config_with_min_sizes(&[("vertical", (1080, 1920))]);
+        assert_eq!(config.strictest_min_size(3840, 2160), (3840, 2160));
+    }
+
+    #[test]
+    fn strictest_min_size_ignores_unknown_resolutions() {
+        let config = config_with_min_sizes(&[("vertical", (2160, 3840)), ("phone", (8000, 8000))]);
+        assert_eq!(config.strictest_min_size(1920, 1080), (2160, 3840));
+    }
+
+    #[test]
+    fn parse_empty_config_is_default() {
+        assert_eq!(WallpaperConfig::parse(""), Ok(WallpaperConfig::default()));
+    }
+
+    #[test]
+    fn parse_errors_have_line_numbers() {
+        let contents = "store = csv\nmin_width = wide\n\n# comment\neye_line = maybe\n";
+
+        let errors = WallpaperConfig::parse(contents).expect_err("invalid values");
+        assert_eq!(
+            errors.0,
+            vec![
+                ConfigError::InvalidValue {
+                    line: Some(2),
+                    key: "min_width".to_string(),
+                    value: "wide".to_string(),
+                    expected: "a positive integer".to_string(),
+                },
+                ConfigError::InvalidValue {
+                    line: Some(5),
+                    key: "eye_line".to_string(),
+                    value: "maybe".to_string(),
+                    expected: "true or false".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            errors.to_string(),
+            "line 2: invalid min_width \"wide\", expected a positive integer\n\
+             line 5: invalid eye_line \"maybe\", expected true or false"
+        );
+    }
+
+    #[test]
+    fn parse_errors_in_sections_have_line_numbers() {
+        let contents = "min_width = 1920\n\n\
+            [resolutions]\n\
+            HD = 16x9\n\
+            wide = ultra\n\n\
+            [min_sizes]\n\
+            HD = 1920x1080\n\
+            phone = 1080x1920\n";
+
+        let errors = WallpaperConfig::parse(contents).expect_err("invalid sections");
+        assert_eq!(
+            errors.0,
+            vec![
+                ConfigError::InvalidResolution {
+                    line: Some(5),
+                    name: "wide".to_string(),
+                    value: "ultra".to_string(),
+                },
+                ConfigError::UnknownResolution {
+                    line: Some(9),
+                    name: "phone".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_syntax_error() {
+        let errors =
+            WallpaperConfig::parse("[resolutions\nHD = 16x9\n").expect_err("unclosed section");
+        assert!(matches!(errors.0.as_slice(), [ConfigError::Syntax(_)]));
+    }
+}
@@ -0,0 +1,727 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    geometry::{Geometry, Padding},
+    wallpapers::Face,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    X,
+    Y,
+    /// zoomed or padded crops that are smaller than the image in both axes, default crops
+    /// from the cropper are always constrained in a single axis
+    Both,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::X => write!(f, "X"),
+            Self::Y => write!(f, "Y"),
+            // uniform in both axes, e.g. scale()
+            Self::Both => Ok(()),
+        }
+    }
+}
+
+/// options for how the faces are framed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CropOptions {
+    /// weight crops towards the largest face instead of treating all faces equally
+    pub largest_face: bool,
+    /// place the eye line of single faces on the upper third line, if the detector provided it
+    pub eye_line: bool,
+    /// percentage of the face size added around each face, doubled above it for hair and hats
+    pub face_padding: u32,
+}
+
+#[derive(Debug)]
+struct FaceArea {
+    area: u32,
+    start: u32,
+}
+
+/// computes face aware crops of an image for aspect ratios
+pub struct Cropper {
+    pub faces: Vec<Face>,
+    pub width: u32,
+    pub height: u32,
+    pub options: CropOptions,
+}
+
+fn sort_faces_by_direction(faces: Vec<Face>, direction: Direction) -> Vec<Face> {
+    let mut faces = faces;
+    faces.sort_by_key(|face| match direction {
+        Direction::X | Direction::Both => face.xmin,
+        Direction::Y => face.ymin,
+    });
+    faces
+}
+
+impl Cropper {
+    pub fn new(faces: &[Face], width: u32, height: u32) -> Self {
+        Self {
+            faces: faces.to_vec(),
+            width,
+            height,
+            options: CropOptions::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_options(self, options: CropOptions) -> Self {
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| self.pad_face(face, options.face_padding))
+            .collect();
+
+        Self {
+            faces,
+            options,
+            ..self
+        }
+    }
+
+    /// expands the face by the padding percentage, within the bounds of the image
+    fn pad_face(&self, face: &Face, padding: u32) -> Face {
+        let pad_x = (face.xmax - face.xmin) * padding / 100;
+        let pad_y = (face.ymax - face.ymin) * padding / 100;
+
+        Face {
+            xmin: face.xmin.saturating_sub(pad_x),
+            xmax: (face.xmax + pad_x).min(self.width),
+            ymin: face.ymin.saturating_sub(pad_y * 2),
+            ymax: (face.ymax + pad_y).min(self.height),
+            eye_y: face.eye_y,
+        }
+    }
+
+    /// the largest face, if crops are weighted towards it
+    fn primary_face(&self) -> Option<&Face> {
+        if self.options.largest_face {
+            self.faces.iter().max_by_key(|face| face.area())
+        } else {
+            None
+        }
+    }
+
+    /// faces count once, except the primary face which outweighs all the other faces
+    fn face_weight(&self, face: &Face, primary: Option<&Face>) -> u32 {
+        if primary == Some(face) {
+            self.faces.len() as u32
+        } else {
+            1
+        }
+    }
+
+    /// largest crop size for the aspect ratio, and the axis it can move in
+    pub fn crop_rect(&self, aspect_ratio: &AspectRatio) -> (u32, u32, Direction) {
+        use std::cmp::min;
+        let AspectRatio {
+            w: target_w,
+            h: target_h,
+        } = aspect_ratio;
+
+        // Calculate width and height that can be cropped while maintaining aspect ratio
+        let crop_w = min(self.width, self.height * target_w / target_h);
+        let crop_h = min(self.height, self.width * target_h / target_w);
+
+        // Choose the larger dimension to get the largest possible cropped rectangle
+        let (crop_w, crop_h) = if crop_w * target_h > crop_h * target_w {
+            (crop_w, crop_h)
+        } else {
+            (crop_h * target_w / target_h, crop_h)
+        };
+
+        (
+            crop_w,
+            crop_h,
+            if crop_w == self.width {
+                Direction::Y
+            } else {
+                Direction::X
+            },
+        )
+    }
+
+    /// keeps the entire image, padding it to the target aspect ratio instead
+    pub fn pad(&self, padding: Padding) -> Geometry {
+        Geometry {
+            x: 0,
+            y: 0,
+            w: self.width,
+            h: self.height,
+            scale: None,
+            padding: Some(padding),
+        }
+    }
+
+    /// crop starting at val along the direction, kept within the image
+    pub fn clamp(
+        &self,
+        val: f64,
+        direction: Direction,
+        target_width: u32,
+        target_height: u32,
+    ) -> Geometry {
+        let val = val as u32;
+        let (x, y) = match direction {
+            Direction::X | Direction::Both => (val.clamp(0, self.width - target_width), 0),
+            Direction::Y => (0, val.clamp(0, self.height - target_height)),
+        };
+
+        Geometry {
+            x,
+            y,
+            w: target_width,
+            h: target_height,
+            scale: None,
+            padding: None,
+        }
+    }
+
+    /// crop framing the face, with headroom the face of portrait crops moving vertically is on the
+    /// upper third line instead of the middle
+    fn frame_face(
+        &self,
+        face: &Face,
+        direction: Direction,
+        target_width: u32,
+        target_height: u32,
+        headroom: bool,
+    ) -> Geometry {
+        let mid = match direction {
+            Direction::X | Direction::Both => {
+                (f64::from(face.xmin + face.xmax) - f64::from(target_width)) / 2.0
+            }
+            Direction::Y => match face.eye_y.filter(|_| self.options.eye_line) {
+                // eye line on the upper third line of the crop
+                Some(eye_y) => f64::from(eye_y) - f64::from(target_height) / 3.0,
+                None if headroom && target_height > target_width => {
+                    f64::from(face.ymin + face.ymax) / 2.0 - f64::from(target_height) / 3.0
+                }
+                None => (f64::from(face.ymin + face.ymax) - f64::from(target_height)) / 2.0,
+            },
+        };
+        self.clamp(mid.max(0.0), direction, target_width, target_height)
+    }
+
+    fn crop_single_face(
+        &self,
+        direction: Direction,
+        target_width: u32,
+        target_height: u32,
+    ) -> Geometry {
+        // the default crop stays centered, so existing crops aren't seen as modified
+        self.frame_face(
+            &self.faces[0],
+            direction,
+            target_width,
+            target_height,
+            false,
+        )
+    }
+
+    /// portrait crop framing the primary or largest face with headroom, None for landscape
+    /// ratios or images without faces
+    pub fn headroom(&self, aspect_ratio: &AspectRatio) -> Option<Geometry> {
+        if !aspect_ratio.is_portrait() {
+            return None;
+        }
+
+        let face = self
+            .primary_face()
+            .or_else(|| self.faces.iter().max_by_key(|face| face.area()))?;
+        let (target_width, target_height, direction) = self.crop_rect(aspect_ratio);
+        Some(self.frame_face(face, direction, target_width, target_height, true))
+    }
+
+    /// trivial crops, either same aspect ratio (entire image), no facec or single face
+    fn crop_trivial(
+        &self,
+        direction: Direction,
+        target_width: u32,
+        target_height: u32,
+    ) -> Option<Geometry> {
+        // entire image
+        if self.width == target_width && self.height == target_height {
+            return Some(Geometry {
+                x: 0,
+                y: 0,
+                w: target_width,
+                h: target_height,
+                scale: None,
+                padding: None,
+            });
+        }
+
+        // midpoint of image
+        if self.faces.is_empty() {
+            let (x, y) = match direction {
+                Direction::X | Direction::Both => ((self.width - target_width) / 2, 0),
+                Direction::Y => (0, (self.height - target_height) / 2),
+            };
+            return Some(Geometry {
+                x,
+                y,
+                w: target_width,
+                h: target_height,
+                scale: None,
+                padding: None,
+            });
+        }
+
+        if self.faces.len() == 1 {
+            return Some(self.crop_single_face(direction, target_width, target_height));
+        }
+
+        // multiple faces, more processing is needed
+        None
+    }
+
+    /// creates a range for a sliding window of target geometry to check for face intersections
+    fn sliding_window_range(
+        &self,
+        faces: &[Face],
+        direction: Direction,
+        target: u32,
+    ) -> impl Iterator<Item = (u32, u32)> {
+        // cannot exceed the image dimensions
+        let img_max = match direction {
+            Direction::X | Direction::Both => self.width - target,
+            Direction::Y => self.height - target,
+        };
+
+        // the min can only be first face - half of target width
+        let (first_min, first_max) = faces[0].dir_bounds(direction);
+        let start = {
+            // prevent subtract overflow
+            let tmp = first_min + first_max;
+            if tmp < target {
+                0
+            } else {
+                (tmp - target) / 2
+            }
+        };
+        let start = std::cmp::min(start, img_max);
+
+        // the max can only be last face + half of target width
+        let (last_min, last_max) = faces[faces.len() - 1].dir_bounds(direction);
+        let end = std::cmp::min((last_min + last_max + target) / 2, img_max);
+
+        (start..=end).map(move |rect_start| (rect_start, rect_start + target))
+    }
+
+    /// default crop, covering as many faces as possible
+    pub fn crop(&self, aspect_ratio: &AspectRatio) -> Geometry {
+        let (target_width, target_height, direction) = self.crop_rect(aspect_ratio);
+        let (target, img_len) = match direction {
+            Direction::X | Direction::Both => (target_width, self.width),
+            Direction::Y => (target_height, self.height),
+        };
+
+        if let Some(cropped_geom) = self.crop_trivial(direction, target_width, target_height) {
+            return cropped_geom;
+        }
+
+        // handle multiple faces
+        let faces = sort_faces_by_direction(self.faces.clone(), direction);
+        let primary = self.primary_face();
+
+        let mut max_faces = 0.0;
+        let mut face_areas: Vec<FaceArea> = vec![];
+
+        // slides a window of target geometry across the image, counting faces and intersections
+        for (rect_start, rect_end) in self.sliding_window_range(&faces, direction, target) {
+            let mut num_faces: f32 = 0.0;
+            let mut faces_area = 0;
+
+            for face in &faces {
+                // check number of faces in decimal within enclosed within larger rectangle
+                let (min_, max_) = face.dir_bounds(direction);
+                let weight = self.face_weight(face, primary) as f32;
+
+                // no intersection, we overshot the final box
+                if min_ > rect_end {
+                    break;
+                }
+                // no intersection
+                else if max_ < rect_start {
+                    continue;
+                }
+                // full intersection
+                else if min_ >= rect_start && max_ <= rect_end {
+                    num_faces += weight;
+                    faces_area += face.area();
+                    continue;
+                }
+
+                // partial intersection
+                if min_ <= rect_end && max_ > rect_end {
+                    num_faces += weight * (rect_end - min_) as f32 / (max_ - min_) as f32;
+                    faces_area += (rect_end - min_)
+                        * match direction {
+                            Direction::X | Direction::Both => face.ymax - face.ymin,
+                            Direction::Y => face.xmax - face.xmin,
+                        };
+                    continue;
+                }
+            }
+
+            // update max_boxes
+            if num_faces > 0.0 {
+                if num_faces > max_faces {
+                    max_faces = num_faces;
+                    face_areas = vec![FaceArea {
+                        area: faces_area,
+                        start: rect_start,
+                    }];
+                } else if (num_faces - max_faces).abs() < f32::EPSILON {
+                    face_areas.push(FaceArea {
+                        area: faces_area,
+                        start: rect_start,
+                    });
+                }
+            }
+        }
+
+        face_areas.sort_by_key(|face_info| (face_info.area, face_info.start));
+        // use the match with the maximum area of face coverage
+        let Some(max_face_area) = face_areas.last().map(|face_info| face_info.area) else {
+            // no window covers a face, e.g. the faces are outside of the image, so use the middle
+            return self.clamp(
+                f64::from(img_len.saturating_sub(target) / 2),
+                direction,
+                target_width,
+                target_height,
+            );
+        };
+        face_areas.retain(|face_info| face_info.area == max_face_area);
+
+        self.clamp(
+            f64::from(face_areas[face_areas.len() / 2].start),
+            direction,
+            target_width,
+            target_height,
+        )
+    }
+
+    /// shows cropping candidate rectangles for multiple faces
+    pub fn crop_candidates(&self, aspect_ratio: &AspectRatio) -> Vec<Geometry> {
+        self.scored_candidates(aspect_ratio)
+            .into_iter()
+            .map(|(_, geom)| geom)
+            .collect()
+    }
+
+    /// cropping candidates, where candidates within tolerance pixels of each other are merged
+    /// into the one covering the most face area
+    pub fn crop_candidates_within(
+        &self,
+        aspect_ratio: &AspectRatio,
+        tolerance: u32,
+    ) -> Vec<Geometry> {
+        let mut kept: Vec<(usize, Geometry)> = Vec::new();
+
+        // pick the representatives by descending face area, ties go to the earlier candidate
+        for (idx, (_, geom)) in self
+            .scored_candidates(aspect_ratio)
+            .into_iter()
+            .enumerate()
+            .sorted_by_key(|(idx, (area, _))| (std::cmp::Reverse(*area), *idx))
+        {
+            let is_near = kept.iter().any(|(_, other)| {
+                other.x.abs_diff(geom.x) <= tolerance && other.y.abs_diff(geom.y) <= tolerance
+            });
+            if !is_near {
+                kept.push((idx, geom));
+            }
+        }
+
+        // restore the ordering by position
+        kept.sort_by_key(|(idx, _)| *idx);
+        kept.into_iter().map(|(_, geom)| geom).unique().collect()
+    }
+
+    /// candidates framing runs of adjacent faces, e.g. a couple or a trio in a group shot
+    ///
+    /// faces are adjacent if the gap between them is at most the average face size, runs
+    /// that do not fit within the crop are skipped
+    fn cluster_candidates(
+        &self,
+        faces: &[Face],
+        direction: Direction,
+        target_width: u32,
+        target_height: u32,
+    ) -> Vec<(u32, Geometry)> {
+        let target = match direction {
+            Direction::X | Direction::Both => target_width,
+            Direction::Y => target_height,
+        };
+        let bounds: Vec<_> = faces
+            .iter()
+            .map(|face| face.dir_bounds(direction))
+            .collect();
+        let max_gap =
+            bounds.iter().map(|(min_, max_)| max_ - min_).sum::<u32>() / bounds.len().max(1) as u32;
+
+        let mut candidates = Vec::new();
+        for (start, (first, &(cluster_min, first_max))) in faces.iter().zip(&bounds).enumerate() {
+            let mut cluster_max = first_max;
+            let mut area = first.area();
+
+            for (face, &(min_, max_)) in faces.iter().zip(&bounds).skip(start + 1) {
+                if min_.saturating_sub(cluster_max) > max_gap {
+                    break;
+                }
+
+                cluster_max = cluster_max.max(max_);
+                area += face.area();
+                if cluster_max - cluster_min > target {
+                    break;
+                }
+
+                let mid = (f64::from(cluster_min + cluster_max) - f64::from(target)) / 2.0;
+                candidates.push((
+                    area,
+                    self.clamp(mid.max(0.0), direction, target_width, target_height),
+                ));
+            }
+        }
+        candidates
+    }
+
+    /// cropping candidates with the face area they cover, ordered by position
+    fn scored_candidates(&self, aspect_ratio: &AspectRatio) -> Vec<(u32, Geometry)> {
+        let (target_width, target_height, direction) = self.crop_rect(aspect_ratio);
+        let target = match direction {
+            Direction::X | Direction::Both => target_width,
+            Direction::Y => target_height,
+        };
+
+        if let Some(cropped_geom) = self.crop_trivial(direction, target_width, target_height) {
+            return vec![(0, cropped_geom)];
+        }
+
+        // handle multiple faces
+        let faces = sort_faces_by_direction(self.faces.clone(), direction);
+        let primary = self.primary_face();
+        let mut face_areas: Vec<FaceArea> = vec![];
+
+        // slides a window of target geometry across the image, counting faces and intersections
+        for (rect_start, rect_end) in self.sliding_window_range(&faces, direction, target) {
+            // check number of faces in decimal within enclosed within larger rectangle
+            for face in &faces {
+                let (min_, max_) = face.dir_bounds(direction);
+
+                // no intersection, we overshot the final box
+                if min_ > rect_end {
+                    break;
+                }
+                // no intersection
+                else if max_ < rect_start {
+                    continue;
+                }
+                // full intersection
+                else if min_ >= rect_start && max_ <= rect_end {
+                    face_areas.push(FaceArea {
+                        area: face.area() * self.face_weight(face, primary),
+                        start: rect_start,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        face_areas.sort_by_key(|face_info| (face_info.area, face_info.start));
+
+        // group faces by area
+        let faces_by_area: HashMap<_, Vec<_>> =
+            face_areas
+                .iter()
+                .fold(HashMap::new(), |mut acc, face_info| {
+                    acc.entry(face_info.area).or_default().push(face_info.start);
+                    acc
+                });
+
+        let clusters = self.cluster_candidates(&faces, direction, target_width, target_height);
+
+        // narrow portrait crops often only fit a single subject, so each face is also framed on
+        // its own
+        let portraits: Vec<_> = if aspect_ratio.is_portrait() {
+            faces
+                .iter()
+                .map(|face| {
+                    (
+                        face.area() * self.face_weight(face, primary),
+                        self.frame_face(face, direction, target_width, target_height, true),
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        faces_by_area
+            .iter()
+            .map(|(area, faces)| {
+                let mid = faces[faces.len() / 2];
+                (
+                    *area,
+                    self.clamp(f64::from(mid), direction, target_width, target_height),
+                )
+            })
+            .chain(clusters)
+            .chain(portraits)
+            .sorted_by_key(|(_, geom)| match direction {
+                Direction::X | Direction::Both => geom.x,
+                Direction::Y => geom.y,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallpapers::WallInfo;
+
+    const fn face(xmin: u32, xmax: u32, ymin: u32, ymax: u32) -> Face {
+        Face {
+            xmin,
+            xmax,
+            ymin,
+            ymax,
+            eye_y: None,
+        }
+    }
+
+    const fn geometry(w: u32, h: u32, x: u32, y: u32) -> Geometry {
+        Geometry {
+            w,
+            h,
+            x,
+            y,
+            scale: None,
+            padding: None,
+        }
+    }
+
+    #[test]
+    fn crop_same_ratio_keeps_image() {
+        let cropper = Cropper::new(&[face(100, 300, 100, 300)], 3840, 2160);
+        assert_eq!(
+            cropper.crop(&AspectRatio::new(16, 9)),
+            geometry(3840, 2160, 0, 0)
+        );
+    }
+
+    #[test]
+    fn crop_without_faces_is_centered() {
+        let cropper = Cropper::new(&[], 3840, 2160);
+        assert_eq!(
+            cropper.crop(&AspectRatio::new(1, 1)),
+            geometry(2160, 2160, 840, 0)
+        );
+    }
+
+    #[test]
+    fn crop_single_face_is_centered_on_face() {
+        let cropper = Cropper::new(&[face(1500, 1700, 500, 700)], 3840, 2160);
+        assert_eq!(
+            cropper.crop(&AspectRatio::new(1, 1)),
+            geometry(2160, 2160, 520, 0)
+        );
+    }
+
+    #[test]
+    fn crop_single_face_stays_within_image() {
+        let cropper = Cropper::new(&[face(3500, 3800, 500, 800)], 3840, 2160);
+        assert_eq!(
+            cropper.crop(&AspectRatio::new(1, 1)),
+            geometry(2160, 2160, 1680, 0)
+        );
+    }
+
+    #[test]
+    fn crop_multiple_faces_covers_most_faces() {
+        let faces = [
+            face(3400, 3600, 500, 700),
+            face(200, 400, 500, 700),
+            face(600, 800, 500, 700),
+        ];
+        let cropper = Cropper::new(&faces, 3840, 2160);
+        assert_eq!(
+            cropper.crop(&AspectRatio::new(1, 1)),
+            geometry(2160, 2160, 100, 0)
+        );
+    }
+
+    #[test]
+    fn crop_portrait_without_headroom() {
+        let cropper = Cropper::new(&[face(400, 600, 1000, 1200)], 1000, 3000);
+        let ratio = AspectRatio::new(9, 16);
+
+        assert_eq!(cropper.crop_rect(&ratio), (1000, 1777, Direction::Y));
+        assert_eq!(cropper.crop(&ratio), geometry(1000, 1777, 0, 211));
+        assert_eq!(cropper.headroom(&ratio), Some(geometry(1000, 1777, 0, 507)));
+    }
+
+    #[test]
+    fn crop_portrait_with_eye_line() {
+        let faces = [Face {
+            eye_y: Some(1050),
+            ..face(400, 600, 1000, 1200)
+        }];
+        let ratio = AspectRatio::new(9, 16);
+
+        // the eye line is only used when enabled
+        let cropper = Cropper::new(&faces, 1000, 3000);
+        assert_eq!(cropper.crop(&ratio), geometry(1000, 1777, 0, 211));
+
+        let cropper = cropper.with_options(CropOptions {
+            eye_line: true,
+            ..CropOptions::default()
+        });
+        assert_eq!(cropper.crop(&ratio), geometry(1000, 1777, 0, 457));
+    }
+
+    #[test]
+    fn headroom_only_for_portrait_ratios() {
+        let cropper = Cropper::new(&[face(1500, 1700, 500, 700)], 3840, 2160);
+        assert_eq!(cropper.headroom(&AspectRatio::new(16, 10)), None);
+        assert_eq!(
+            Cropper::new(&[], 1000, 3000).headroom(&AspectRatio::new(9, 16)),
+            None
+        );
+    }
+
+    #[test]
+    fn zoomed_crop_moves_in_both_axes() {
+        let info = WallInfo {
+            filename: "wall.png".to_string(),
+            width: 3840,
+            height: 2160,
+            faces: vec![face(1500, 1700, 500, 700)],
+            ..WallInfo::default()
+        };
+        let crop = info.cropper().crop(&AspectRatio::new(1, 1));
+        assert_eq!(info.direction(&crop), Direction::X);
+
+        let zoomed = crop.zoomed(2.0);
+        assert_eq!(
+            (zoomed.w, zoomed.h, zoomed.x, zoomed.y),
+            (1080, 1080, 1060, 540)
+        );
+        assert_eq!(info.direction(&zoomed), Direction::Both);
+        assert_eq!(
+            info.cropper()
+                .clamp(3000.0, Direction::Both, zoomed.w, zoomed.h),
+            geometry(1080, 1080, 2760, 0)
+        );
+    }
+}
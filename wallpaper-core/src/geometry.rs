@@ -0,0 +1,412 @@
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::aspect_ratio::AspectRatio;
+
+#[derive(Error, Debug)]
+pub enum GeometryError {
+    #[error("Invalid geometry coordinates")]
+    InvalidCoordinate,
+    #[error("Invalid geometry format")]
+    InvalidFormat,
+    #[error("Invalid geometry scale")]
+    InvalidScale,
+    #[error("Invalid geometry padding")]
+    InvalidPadding,
+}
+
+/// placement of a padded crop within its canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PadAlign {
+    Start,
+    Center,
+    End,
+}
+
+impl std::fmt::Display for PadAlign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Center => write!(f, "center"),
+            Self::End => write!(f, "end"),
+        }
+    }
+}
+
+impl std::str::FromStr for PadAlign {
+    type Err = GeometryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "center" => Ok(Self::Center),
+            "end" => Ok(Self::End),
+            _ => Err(GeometryError::InvalidPadding),
+        }
+    }
+}
+
+/// fill for crops that are padded to the target aspect ratio instead of being cut
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Padding {
+    /// hex color, e.g. #1e1e2e
+    pub color: String,
+    pub align: PadAlign,
+}
+
+impl Padding {
+    /// rgb components of the fill color
+    pub fn rgb(&self) -> Option<[u8; 3]> {
+        parse_hex_color(&self.color)
+    }
+}
+
+/// parses a hex color in the format #RRGGBB
+pub fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// crop of an image, in pixels of the source image
+// hash used for deduping
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Geometry {
+    pub w: u32,
+    pub h: u32,
+    pub x: u32,
+    pub y: u32,
+    /// factor the crop is upscaled by when exported, for crops smaller than the target resolution
+    pub scale: Option<OrderedFloat<f64>>,
+    /// crops that are letterboxed to the target aspect ratio
+    pub padding: Option<Padding>,
+}
+
+impl std::fmt::Display for Geometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.w, self.h, self.x, self.y)?;
+        if let Some(scale) = self.scale {
+            write!(f, "@{scale}")?;
+        }
+        if let Some(padding) = &self.padding {
+            write!(f, "{}:{}", padding.color, padding.align)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Geometry {
+    type Error = GeometryError;
+
+    /// parses WxH+X+Y, with an optional @SCALE and #RRGGBB:ALIGN padding suffix
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let (s, padding) = match s.split_once('#') {
+            Some((geom, padding)) => {
+                let (color, align) = padding
+                    .split_once(':')
+                    .ok_or(GeometryError::InvalidPadding)?;
+                let padding = Padding {
+                    color: format!("#{color}"),
+                    align: align.parse()?,
+                };
+                if padding.rgb().is_none() {
+                    return Err(GeometryError::InvalidPadding);
+                }
+                (geom, Some(padding))
+            }
+            None => (s.as_str(), None),
+        };
+
+        let (s, scale) = match s.split_once('@') {
+            Some((geom, scale)) => {
+                let scale: f64 = scale.parse().map_err(|_| GeometryError::InvalidScale)?;
+                if scale <= 0.0 || !scale.is_finite() {
+                    return Err(GeometryError::InvalidScale);
+                }
+                (geom, Some(OrderedFloat(scale)))
+            }
+            None => (s, None),
+        };
+
+        let parts: Vec<&str> = s.split(|c| c == 'x' || c == '+').collect();
+        if parts.len() != 4 {
+            return Err(GeometryError::InvalidFormat);
+        }
+
+        Ok(Self {
+            w: parts[0]
+                .parse()
+                .map_err(|_| GeometryError::InvalidCoordinate)?,
+            h: parts[1]
+                .parse()
+                .map_err(|_| GeometryError::InvalidCoordinate)?,
+            x: parts[2]
+                .parse()
+                .map_err(|_| GeometryError::InvalidCoordinate)?,
+            y: parts[3]
+                .parse()
+                .map_err(|_| GeometryError::InvalidCoordinate)?,
+            scale,
+            padding,
+        })
+    }
+}
+
+impl Serialize for Geometry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Some(format!("{}+{}", self.x, self.y)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Geometry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::try_from(s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Geometry {
+    /// size of the crop after it is upscaled on export
+    pub fn output_size(&self) -> (u32, u32) {
+        let scale = self.scale.map_or(1.0, OrderedFloat::into_inner);
+        (
+            (f64::from(self.w) * scale).round() as u32,
+            (f64::from(self.h) * scale).round() as u32,
+        )
+    }
+
+    /// shrinks the crop around its center by the scale, so it is upscaled back to the
+    /// original size on export
+    #[must_use]
+    pub fn zoomed(&self, scale: f64) -> Self {
+        if scale <= 1.0 {
+            return Self {
+                scale: None,
+                ..self.clone()
+            };
+        }
+
+        let w = (f64::from(self.w) / scale).round() as u32;
+        let h = (f64::from(self.h) / scale).round() as u32;
+        Self {
+            x: self.x + (self.w - w) / 2,
+            y: self.y + (self.h - h) / 2,
+            w,
+            h,
+            scale: Some(OrderedFloat(scale)),
+            padding: self.padding.clone(),
+        }
+    }
+
+    /// canvas of the target ratio for padded crops, after scaling
+    ///
+    /// returns the canvas width and height, and the offset of the crop within it
+    pub fn padded_canvas(&self, ratio: &AspectRatio) -> Option<(u32, u32, u32, u32)> {
+        let padding = self.padding.as_ref()?;
+        let (w, h) = self.output_size();
+
+        // widen or heighten the canvas, whichever is needed to reach the ratio
+        let (canvas_w, canvas_h) =
+            if u64::from(w) * u64::from(ratio.h) < u64::from(h) * u64::from(ratio.w) {
+                (
+                    (f64::from(h) * f64::from(ratio.w) / f64::from(ratio.h)).round() as u32,
+                    h,
+                )
+            } else {
+                (
+                    w,
+                    (f64::from(w) * f64::from(ratio.h) / f64::from(ratio.w)).round() as u32,
+                )
+            };
+
+        let offset = |space: u32| match padding.align {
+            PadAlign::Start => 0,
+            PadAlign::Center => space / 2,
+            PadAlign::End => space,
+        };
+
+        Some((
+            canvas_w,
+            canvas_h,
+            offset(canvas_w.saturating_sub(w)),
+            offset(canvas_h.saturating_sub(h)),
+        ))
+    }
+
+    /// sets the placement of padded crops
+    #[must_use]
+    fn with_pad_align(&self, align: PadAlign) -> Self {
+        Self {
+            padding: self.padding.as_ref().map(|padding| Padding {
+                align,
+                ..padding.clone()
+            }),
+            ..self.clone()
+        }
+    }
+
+    #[must_use]
+    pub fn align_start(&self, _img_width: u32, _img_height: u32) -> Self {
+        if self.padding.is_some() {
+            return self.with_pad_align(PadAlign::Start);
+        }
+
+        Self {
+            x: 0,
+            y: 0,
+            ..self.clone()
+        }
+    }
+
+    #[must_use]
+    pub fn align_center(&self, img_width: u32, img_height: u32) -> Self {
+        if self.padding.is_some() {
+            return self.with_pad_align(PadAlign::Center);
+        }
+
+        // the constrained axis has no room to move, so both axes can be aligned
+        Self {
+            x: (img_width - self.w) / 2,
+            y: (img_height - self.h) / 2,
+            ..self.clone()
+        }
+    }
+
+    #[must_use]
+    pub fn align_end(&self, img_width: u32, img_height: u32) -> Self {
+        if self.padding.is_some() {
+            return self.with_pad_align(PadAlign::End);
+        }
+
+        // the constrained axis has no room to move, so both axes can be aligned
+        Self {
+            x: img_width - self.w,
+            y: img_height - self.h,
+            ..self.clone()
+        }
+    }
+
+    /// position along each axis as a percentage of the room the crop has to move, 0 is aligned
+    /// to the start and 100 to the end, None for axes without any room
+    pub fn position(&self, img_width: u32, img_height: u32) -> (Option<f64>, Option<f64>) {
+        if self.padding.is_some() {
+            return (None, None);
+        }
+
+        let percent =
+            |pos: u32, room: u32| (room > 0).then(|| f64::from(pos) / f64::from(room) * 100.0);
+        (
+            percent(self.x, img_width.saturating_sub(self.w)),
+            percent(self.y, img_height.saturating_sub(self.h)),
+        )
+    }
+
+    /// moves the crop to the percentage of the room it has along each axis, padded crops are
+    /// unchanged
+    #[must_use]
+    pub fn at_position(&self, img_width: u32, img_height: u32, percent: f64) -> Self {
+        if self.padding.is_some() {
+            return self.clone();
+        }
+
+        let offset =
+            |room: u32| (f64::from(room) * percent.clamp(0.0, 100.0) / 100.0).round() as u32;
+        // the constrained axis has no room to move, so both axes can be moved
+        Self {
+            x: offset(img_width.saturating_sub(self.w)),
+            y: offset(img_height.saturating_sub(self.h)),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Geometry, GeometryError> {
+        Geometry::try_from(s.to_string())
+    }
+
+    #[test]
+    fn geometry_roundtrip() {
+        let geom = parse("1920x1080+10+20").expect("valid geometry");
+        assert_eq!(
+            geom,
+            Geometry {
+                w: 1920,
+                h: 1080,
+                x: 10,
+                y: 20,
+                scale: None,
+                padding: None,
+            }
+        );
+        assert_eq!(geom.to_string(), "1920x1080+10+20");
+    }
+
+    #[test]
+    fn geometry_roundtrip_with_scale() {
+        let geom = parse("1080x1080+100+0@1.5").expect("valid geometry");
+        assert_eq!(geom.scale, Some(OrderedFloat(1.5)));
+        assert_eq!(geom.output_size(), (1620, 1620));
+        assert_eq!(geom.to_string(), "1080x1080+100+0@1.5");
+    }
+
+    #[test]
+    fn geometry_roundtrip_with_padding() {
+        let geom = parse("1920x1080+0+0#1e1e2e:center").expect("valid geometry");
+        assert_eq!(
+            geom.padding,
+            Some(Padding {
+                color: "#1e1e2e".to_string(),
+                align: PadAlign::Center,
+            })
+        );
+        assert_eq!(geom.to_string(), "1920x1080+0+0#1e1e2e:center");
+
+        let geom = parse("960x540+0+0@2#FFFFFF:end").expect("valid geometry");
+        assert_eq!(geom.scale, Some(OrderedFloat(2.0)));
+        assert_eq!(geom.padding.as_ref().and_then(Padding::rgb), Some([255; 3]));
+        assert_eq!(geom.to_string(), "960x540+0+0@2#FFFFFF:end");
+    }
+
+    #[test]
+    fn geometry_parse_errors() {
+        assert!(matches!(
+            parse("1920x1080+0"),
+            Err(GeometryError::InvalidFormat)
+        ));
+        assert!(matches!(
+            parse("1920x1080+a+0"),
+            Err(GeometryError::InvalidCoordinate)
+        ));
+        assert!(matches!(
+            parse("1920x1080+0+0@0"),
+            Err(GeometryError::InvalidScale)
+        ));
+        assert!(matches!(
+            parse("1920x1080+0+0#1e1e2e"),
+            Err(GeometryError::InvalidPadding)
+        ));
+        assert!(matches!(
+            parse("1920x1080+0+0#1e1e:center"),
+            Err(GeometryError::InvalidPadding)
+        ));
+        assert!(matches!(
+            parse("1920x1080+0+0#1e1e2e:middle"),
+            Err(GeometryError::InvalidPadding)
+        ));
+    }
+}
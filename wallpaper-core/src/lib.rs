@@ -0,0 +1,46 @@
+//! face aware wallpaper cropping without the ui
+//!
+//! the config, crop geometry, cropper and wallpapers.csv used by wallpaper-ui, without any of
+//! its dioxus, terminal or server dependencies, e.g.
+//! `Cropper::new(&faces, width, height).crop(&AspectRatio::new(16, 9))`
+//!
+//! errors are returned instead of exiting, so the crate can be used by other programs. the
+//! pipeline of `wallpapers add` stays in wallpaper-ui, its stages run the external tools through
+//! the tokio job scheduler and the optional onnx face detection
+use std::path::{Path, PathBuf};
+
+pub mod aspect_ratio;
+pub mod config;
+pub mod cropper;
+pub mod geometry;
+pub mod paths;
+pub mod schedule;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod tools;
+pub mod wallpapers;
+
+pub use aspect_ratio::AspectRatio;
+pub use cropper::{CropOptions, Cropper, Direction};
+pub use geometry::{Geometry, Padding};
+pub use wallpapers::{Face, WallInfo};
+
+/// expands ~/ to the home directory, the path is kept as is without one
+pub fn full_path(p: &str) -> PathBuf {
+    p.strip_prefix("~/")
+        .zip(dirs::home_dir())
+        .map_or_else(|| PathBuf::from(p), |(p, home)| home.join(p))
+}
+
+/// writes to a temporary file next to the path and renames it, so readers never see a partial file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
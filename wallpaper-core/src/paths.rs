@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+const APP_NAME: &str = "wallpaper-ui";
+
+/// reads a directory from an environment variable, ignoring empty values
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// directory of the app in the xdg directory, relative to the current directory if it can't be
+/// found, e.g. without $HOME
+fn app_dir(xdg_dir: Option<PathBuf>) -> PathBuf {
+    xdg_dir.unwrap_or_default().join(APP_NAME)
+}
+
+/// directory containing config.ini, $WALLPAPER_UI_CONFIG or $XDG_CONFIG_HOME/wallpaper-ui
+pub fn config_dir() -> PathBuf {
+    env_dir("WALLPAPER_UI_CONFIG").unwrap_or_else(|| app_dir(dirs::config_dir()))
+}
+
+/// directory containing wallpapers.csv, $WALLPAPER_UI_DATA or $XDG_DATA_HOME/wallpaper-ui
+pub fn data_dir() -> PathBuf {
+    env_dir("WALLPAPER_UI_DATA").unwrap_or_else(|| app_dir(dirs::data_dir()))
+}
+
+/// directory for intermediate files, $XDG_CACHE_HOME/wallpaper-ui
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_NAME)
+}
+
+/// control socket of the daemon, $XDG_RUNTIME_DIR/wallpaper-ui.sock
+pub fn socket_file() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("{APP_NAME}.sock"))
+}
+
+/// path to config.ini
+pub fn config_file() -> PathBuf {
+    config_dir().join("config.ini")
+}
+
+/// default path to wallpapers.csv
+///
+/// falls back to the config directory where older versions stored it, unless the data
+/// directory has been overridden
+pub fn csv_file() -> PathBuf {
+    let csv = data_dir().join("wallpapers.csv");
+    if csv.exists() || env_dir("WALLPAPER_UI_DATA").is_some() {
+        return csv;
+    }
+
+    let legacy_csv = config_dir().join("wallpapers.csv");
+    if legacy_csv.exists() {
+        legacy_csv
+    } else {
+        csv
+    }
+}
@@ -0,0 +1,134 @@
+//! when wallpapers are shown, e.g. dark wallpapers at night or festive ones only in december
+//!
+//! a schedule is a list of entries of three kinds: times of day, weekdays and months or seasons.
+//! entries of the same kind match if any of them matches, entries of different kinds must all
+//! match and wallpapers without any entries are always shown
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// times of day with the hour each starts at, the night wraps around midnight
+pub const TIMES_OF_DAY: &[(&str, u32)] = &[
+    ("morning", 5),
+    ("afternoon", 12),
+    ("evening", 17),
+    ("night", 21),
+];
+
+/// weekdays, starting on monday
+pub const WEEKDAYS: &[&str] = &["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+pub const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// meteorological seasons of the northern hemisphere, winter starts in december
+pub const SEASONS: &[&str] = &["winter", "spring", "summer", "autumn"];
+
+/// the local time a schedule is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Moment {
+    pub hour: u32,
+    /// 0 is monday
+    pub weekday: u32,
+    /// 1 is january
+    pub month: u32,
+}
+
+impl Moment {
+    /// the current local time, read with date as std has no time zones, in utc if that fails
+    pub fn now() -> Self {
+        Command::new("date")
+            .arg("+%H %u %m")
+            .output()
+            .ok()
+            .and_then(|output| {
+                let output = String::from_utf8(output.stdout).ok()?;
+                let mut fields = output.split_whitespace().map(str::parse::<u32>);
+                Some(Self {
+                    hour: fields.next()?.ok()?,
+                    weekday: fields.next()?.ok()?.checked_sub(1)?,
+                    month: fields.next()?.ok()?,
+                })
+            })
+            .unwrap_or_else(|| {
+                let secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                Self::from_unix(secs)
+            })
+    }
+
+    /// the time in utc from the seconds since the unix epoch
+    pub fn from_unix(secs: u64) -> Self {
+        let days = secs / 86400;
+
+        // month from days, http://howardhinnant.github.io/date_algorithms.html
+        let doe = (days + 719_468) % 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+        Self {
+            hour: u32::try_from(secs % 86400 / 3600).unwrap_or_default(),
+            // the epoch was on a thursday
+            weekday: u32::try_from((days + 3) % 7).unwrap_or_default(),
+            month: u32::try_from(month).unwrap_or(1),
+        }
+    }
+
+    pub fn time_of_day(&self) -> &'static str {
+        TIMES_OF_DAY
+            .iter()
+            .rev()
+            .find(|(_, start)| self.hour >= *start)
+            // before the morning is still the night
+            .map_or("night", |(name, _)| name)
+    }
+
+    pub const fn weekday_name(&self) -> &'static str {
+        WEEKDAYS[self.weekday as usize % WEEKDAYS.len()]
+    }
+
+    pub const fn month_name(&self) -> &'static str {
+        MONTHS[(self.month as usize + MONTHS.len() - 1) % MONTHS.len()]
+    }
+
+    pub const fn season(&self) -> &'static str {
+        SEASONS[(self.month as usize % 12) / 3]
+    }
+}
+
+/// kind of the schedule entry: 0 for times of day, 1 for weekdays, 2 for months and seasons
+fn kind(entry: &str) -> Option<usize> {
+    if TIMES_OF_DAY.iter().any(|(name, _)| *name == entry) {
+        Some(0)
+    } else if WEEKDAYS.contains(&entry) {
+        Some(1)
+    } else if MONTHS.contains(&entry) || SEASONS.contains(&entry) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+pub fn is_valid(entry: &str) -> bool {
+    kind(entry).is_some()
+}
+
+/// checks the schedule against the time, unknown entries are ignored
+pub fn matches(schedule: &[String], now: &Moment) -> bool {
+    let current = [
+        vec![now.time_of_day()],
+        vec![now.weekday_name()],
+        vec![now.month_name(), now.season()],
+    ];
+
+    current.iter().enumerate().all(|(k, names)| {
+        let mut entries = schedule
+            .iter()
+            .filter(|entry| kind(entry) == Some(k))
+            .peekable();
+        entries.peek().is_none() || entries.any(|entry| names.contains(&entry.as_str()))
+    })
+}
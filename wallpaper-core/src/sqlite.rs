@@ -0,0 +1,332 @@
+//! wallpapers stored in an sqlite database instead of wallpapers.csv, with indexes on the number
+//! of faces and on whether each crop was modified, used with store = sqlite in the config
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    config::WallpaperConfig,
+    cropper::CropOptions,
+    store::Store,
+    wallpapers::{FaceCount, WallInfo, WallpapersError},
+};
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS wallpapers (
+        filename TEXT PRIMARY KEY NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        faces TEXT NOT NULL,
+        face_count INTEGER NOT NULL,
+        wallust TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        depth TEXT NOT NULL,
+        detector TEXT NOT NULL,
+        state TEXT NOT NULL,
+        schedule TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS wallpapers_face_count ON wallpapers (face_count);
+    CREATE TABLE IF NOT EXISTS crops (
+        filename TEXT NOT NULL REFERENCES wallpapers (filename) ON DELETE CASCADE,
+        ratio TEXT NOT NULL,
+        geometry TEXT NOT NULL,
+        modified INTEGER NOT NULL,
+        PRIMARY KEY (filename, ratio)
+    );
+    CREATE INDEX IF NOT EXISTS crops_modified ON crops (ratio, modified);
+";
+
+const COLUMNS: &str =
+    "filename, width, height, faces, wallust, tags, depth, detector, state, schedule";
+
+/// the columns of wallpapers.csv that are stored in the wallpapers table, in the order of COLUMNS
+const CSV_COLUMNS: &[&str] = &[
+    "filename", "width", "height", "faces", "wallust", "tags", "depth", "detector", "state",
+    "schedule",
+];
+
+/// maps the errors of the database at the path
+fn db_error(path: &Path) -> impl Fn(rusqlite::Error) -> WallpapersError + '_ {
+    move |source| WallpapersError::Database {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+    path: PathBuf,
+    /// options of the default crops, a crop is modified if it differs from the default when saved
+    crop_options: CropOptions,
+}
+
+impl SqliteStore {
+    /// opens the database of the config, it is created if it doesn't exist
+    pub fn open(config: &WallpaperConfig) -> Result<Self, WallpapersError> {
+        Self::open_path(&config.db_path, config.crop_options())
+    }
+
+    pub fn open_path(path: &Path, crop_options: CropOptions) -> Result<Self, WallpapersError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|source| WallpapersError::Write {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        }
+
+        let conn = Connection::open(path).map_err(db_error(path))?;
+        conn.execute_batch(SCHEMA).map_err(db_error(path))?;
+
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+            crop_options,
+        })
+    }
+
+    /// the columns of a row of the wallpapers table as a row of wallpapers.csv
+    fn row_values(row: &Row) -> rusqlite::Result<Vec<String>> {
+        Ok(vec![
+            row.get(0)?,
+            row.get::<_, u32>(1)?.to_string(),
+            row.get::<_, u32>(2)?.to_string(),
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+            row.get(6)?,
+            row.get(7)?,
+            row.get(8)?,
+            row.get(9)?,
+        ])
+    }
+
+    /// parses the wallpaper with the csv parser, so both stores read the same values
+    fn parse(
+        &self,
+        values: Vec<String>,
+        crops: Vec<(String, String)>,
+    ) -> Result<WallInfo, WallpapersError> {
+        let mut header: Vec<String> = CSV_COLUMNS.iter().map(ToString::to_string).collect();
+        let mut record = values;
+        for (ratio, geometry) in crops {
+            header.push(ratio);
+            record.push(geometry);
+        }
+
+        WallInfo::from_row(&header, &record).map_err(|source| WallpapersError::Parse {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// all the wallpapers, sorted by filename
+    pub fn load_all(&self) -> Result<Vec<WallInfo>, WallpapersError> {
+        let mut crops: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT filename, ratio, geometry FROM crops")
+            .map_err(db_error(&self.path))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(db_error(&self.path))?;
+        for row in rows {
+            let (fname, ratio, geometry): (String, String, String) =
+                row.map_err(db_error(&self.path))?;
+            crops.entry(fname).or_default().push((ratio, geometry));
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {COLUMNS} FROM wallpapers ORDER BY filename"
+            ))
+            .map_err(db_error(&self.path))?;
+        let rows = stmt
+            .query_map([], Self::row_values)
+            .map_err(db_error(&self.path))?;
+
+        let mut wallpapers = Vec::new();
+        for values in rows {
+            let values = values.map_err(db_error(&self.path))?;
+            let wall_crops = crops.remove(&values[0]).unwrap_or_default();
+            wallpapers.push(self.parse(values, wall_crops)?);
+        }
+        Ok(wallpapers)
+    }
+
+    /// filenames from a query with a single filename column
+    fn query_filenames<P>(&self, sql: &str, params: P) -> Result<Vec<String>, WallpapersError>
+    where
+        P: rusqlite::Params,
+    {
+        let mut stmt = self.conn.prepare(sql).map_err(db_error(&self.path))?;
+        let rows = stmt
+            .query_map(params, |row| row.get(0))
+            .map_err(db_error(&self.path))?;
+        rows.collect::<Result<_, _>>().map_err(db_error(&self.path))
+    }
+
+    fn insert(
+        tx: &Transaction,
+        info: &WallInfo,
+        ratios: &[AspectRatio],
+        crop_options: CropOptions,
+    ) -> rusqlite::Result<()> {
+        let faces = serde_json::to_string(&info.faces)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        tx.prepare_cached(&format!(
+            "INSERT OR REPLACE INTO wallpapers ({COLUMNS}, face_count)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
+        ))?
+        .execute(params![
+            info.filename,
+            info.width,
+            info.height,
+            faces,
+            info.wallust,
+            info.tags.iter().sorted().dedup().join(","),
+            info.depth,
+            info.detector,
+            info.state,
+            info.schedule.iter().sorted().dedup().join(","),
+            info.faces.len(),
+        ])?;
+
+        tx.prepare_cached("DELETE FROM crops WHERE filename = ?1")?
+            .execute([&info.filename])?;
+        let cropper = info.cropper().with_options(crop_options);
+        let mut insert_crop = tx.prepare_cached(
+            "INSERT INTO crops (filename, ratio, geometry, modified) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for ratio in ratios {
+            let geom = info.get_geometry(ratio);
+            insert_crop.execute(params![
+                info.filename,
+                ratio.to_string(),
+                geom.to_string(),
+                geom != cropper.crop(ratio),
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// writes the changed wallpapers and deletes the removed ones in a single transaction, the
+    /// other wallpapers of the database are left as is
+    pub fn update<'a, I>(
+        &mut self,
+        changed: I,
+        removed: &[String],
+        ratios: &[AspectRatio],
+    ) -> Result<(), WallpapersError>
+    where
+        I: IntoIterator<Item = &'a WallInfo>,
+    {
+        let tx = self.conn.transaction().map_err(db_error(&self.path))?;
+        for filename in removed {
+            tx.prepare_cached("DELETE FROM wallpapers WHERE filename = ?1")
+                .and_then(|mut stmt| stmt.execute([filename]))
+                .map_err(db_error(&self.path))?;
+        }
+        for info in changed {
+            Self::insert(&tx, info, ratios, self.crop_options).map_err(db_error(&self.path))?;
+        }
+        tx.commit().map_err(db_error(&self.path))
+    }
+
+    /// replaces all the wallpapers of the database in a single transaction, wallpapers that
+    /// aren't given are deleted, used by `wallpapers migrate`
+    pub fn replace_all<'a, I>(
+        &mut self,
+        wallpapers: I,
+        ratios: &[AspectRatio],
+    ) -> Result<(), WallpapersError>
+    where
+        I: IntoIterator<Item = &'a WallInfo>,
+    {
+        let tx = self.conn.transaction().map_err(db_error(&self.path))?;
+        tx.execute("DELETE FROM wallpapers", [])
+            .map_err(db_error(&self.path))?;
+        for info in wallpapers {
+            Self::insert(&tx, info, ratios, self.crop_options).map_err(db_error(&self.path))?;
+        }
+        tx.commit().map_err(db_error(&self.path))
+    }
+}
+
+impl Store for SqliteStore {
+    fn get(&self, filename: &str) -> Result<Option<WallInfo>, WallpapersError> {
+        let values = self
+            .conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM wallpapers WHERE filename = ?1"),
+                [filename],
+                Self::row_values,
+            )
+            .optional()
+            .map_err(db_error(&self.path))?;
+        let Some(values) = values else {
+            return Ok(None);
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT ratio, geometry FROM crops WHERE filename = ?1")
+            .map_err(db_error(&self.path))?;
+        let crops = stmt
+            .query_map([filename], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error(&self.path))?
+            .collect::<Result<_, _>>()
+            .map_err(db_error(&self.path))?;
+
+        self.parse(values, crops).map(Some)
+    }
+
+    fn wallpapers(&self) -> Result<Vec<WallInfo>, WallpapersError> {
+        self.load_all()
+    }
+
+    fn filenames(&self) -> Result<Vec<String>, WallpapersError> {
+        self.query_filenames("SELECT filename FROM wallpapers ORDER BY filename", [])
+    }
+
+    fn filenames_with_faces(&self, faces: FaceCount) -> Result<Vec<String>, WallpapersError> {
+        let condition = match faces {
+            FaceCount::All => "1",
+            FaceCount::Zero => "face_count = 0",
+            FaceCount::One => "face_count = 1",
+            FaceCount::Many => "face_count > 1",
+        };
+        self.query_filenames(
+            &format!("SELECT filename FROM wallpapers WHERE {condition} ORDER BY filename"),
+            [],
+        )
+    }
+
+    fn modified_filenames(&self, ratio: &AspectRatio) -> Result<Vec<String>, WallpapersError> {
+        self.query_filenames(
+            "SELECT filename FROM crops WHERE ratio = ?1 AND modified ORDER BY filename",
+            [ratio.to_string()],
+        )
+    }
+
+    fn upsert(&mut self, info: &WallInfo, ratios: &[AspectRatio]) -> Result<(), WallpapersError> {
+        let tx = self.conn.transaction().map_err(db_error(&self.path))?;
+        Self::insert(&tx, info, ratios, self.crop_options)
+            .and_then(|()| tx.commit())
+            .map_err(db_error(&self.path))
+    }
+
+    fn remove(&mut self, filename: &str) -> Result<(), WallpapersError> {
+        self.conn
+            .execute("DELETE FROM wallpapers WHERE filename = ?1", [filename])
+            .map(|_| ())
+            .map_err(db_error(&self.path))
+    }
+}
@@ -0,0 +1,154 @@
+//! storage of the wallpapers, either wallpapers.csv or an sqlite database with store = sqlite
+//!
+//! the commands read all the wallpapers with WallpapersCsv::open, which loads them from the
+//! configured store, the queries of the store are for looking up a few of them and the ui and the
+//! api read and write single wallpapers through it
+use crate::{
+    aspect_ratio::AspectRatio,
+    config::WallpaperConfig,
+    wallpapers::{FaceCount, WallInfo, WallpapersCsv, WallpapersError},
+};
+
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteStore;
+
+pub trait Store {
+    /// the wallpaper, None if it isn't stored
+    fn get(&self, filename: &str) -> Result<Option<WallInfo>, WallpapersError>;
+
+    /// all the wallpapers, in the order of wallpapers.csv or sorted by filename in the database
+    fn wallpapers(&self) -> Result<Vec<WallInfo>, WallpapersError>;
+
+    fn filenames(&self) -> Result<Vec<String>, WallpapersError>;
+
+    /// filenames of the wallpapers with the number of faces of the --faces argument
+    fn filenames_with_faces(&self, faces: FaceCount) -> Result<Vec<String>, WallpapersError>;
+
+    /// filenames of the wallpapers whose crop for the ratio isn't the default crop
+    fn modified_filenames(&self, ratio: &AspectRatio) -> Result<Vec<String>, WallpapersError>;
+
+    /// adds or replaces the wallpaper and writes it with the crops of the ratios
+    fn upsert(&mut self, info: &WallInfo, ratios: &[AspectRatio]) -> Result<(), WallpapersError>;
+
+    fn remove(&mut self, filename: &str) -> Result<(), WallpapersError>;
+}
+
+impl Store for WallpapersCsv {
+    fn get(&self, filename: &str) -> Result<Option<WallInfo>, WallpapersError> {
+        Ok(self.get(filename).cloned())
+    }
+
+    fn wallpapers(&self) -> Result<Vec<WallInfo>, WallpapersError> {
+        Ok(self.iter().map(|(_, info)| info.clone()).collect())
+    }
+
+    fn filenames(&self) -> Result<Vec<String>, WallpapersError> {
+        Ok(self.iter().map(|(fname, _)| fname.clone()).collect())
+    }
+
+    fn filenames_with_faces(&self, faces: FaceCount) -> Result<Vec<String>, WallpapersError> {
+        Ok(self
+            .iter()
+            .filter(|(_, info)| info.matches_faces(faces))
+            .map(|(fname, _)| fname.clone())
+            .collect())
+    }
+
+    fn modified_filenames(&self, ratio: &AspectRatio) -> Result<Vec<String>, WallpapersError> {
+        let crop_options = self.config().crop_options();
+        Ok(self
+            .iter()
+            .filter(|(_, info)| {
+                info.get_geometry(ratio) != info.cropper().with_options(crop_options).crop(ratio)
+            })
+            .map(|(fname, _)| fname.clone())
+            .collect())
+    }
+
+    fn upsert(&mut self, info: &WallInfo, ratios: &[AspectRatio]) -> Result<(), WallpapersError> {
+        self.insert(info.filename.clone(), info.clone());
+        self.save(ratios)
+    }
+
+    fn remove(&mut self, filename: &str) -> Result<(), WallpapersError> {
+        self.remove(filename);
+        let ratios = self.config().sorted_resolutions();
+        self.save(&ratios)
+    }
+}
+
+/// the store of the config, wallpapers.csv is read into memory
+pub fn open(config: &WallpaperConfig) -> Result<Box<dyn Store>, WallpapersError> {
+    if config.store == "sqlite" {
+        #[cfg(feature = "sqlite")]
+        return Ok(Box::new(SqliteStore::open(config)?));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(WallpapersError::NoSqlite);
+    }
+
+    WallpapersCsv::open_path(&config.csv_path, config.clone())
+        .map(|csv| Box::new(csv) as Box<dyn Store>)
+}
+
+/// all the wallpapers of the sqlite database, sorted by filename
+pub fn load_db(config: &WallpaperConfig) -> Result<Vec<WallInfo>, WallpapersError> {
+    #[cfg(feature = "sqlite")]
+    {
+        SqliteStore::open(config)?.load_all()
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = config;
+        Err(WallpapersError::NoSqlite)
+    }
+}
+
+/// writes the changed wallpapers to the sqlite database and deletes the removed ones, the other
+/// wallpapers aren't rewritten
+pub fn update_db<'a, I>(
+    config: &WallpaperConfig,
+    changed: I,
+    removed: &[String],
+    ratios: &[AspectRatio],
+) -> Result<(), WallpapersError>
+where
+    I: IntoIterator<Item = &'a WallInfo>,
+{
+    #[cfg(feature = "sqlite")]
+    {
+        SqliteStore::open(config)?.update(changed, removed, ratios)
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = (config, changed, removed, ratios);
+        Err(WallpapersError::NoSqlite)
+    }
+}
+
+/// replaces the wallpapers of the sqlite database when migrating to it, like saving
+/// wallpapers.csv the wallpapers that were deleted are removed
+pub fn save_db<'a, I>(
+    config: &WallpaperConfig,
+    wallpapers: I,
+    ratios: &[AspectRatio],
+) -> Result<(), WallpapersError>
+where
+    I: IntoIterator<Item = &'a WallInfo>,
+{
+    #[cfg(feature = "sqlite")]
+    {
+        let existing = wallpapers.into_iter().filter(|wall| {
+            let exists = config.wallpapers_path.join(&wall.filename).exists();
+            if !exists {
+                tracing::info!("Removed wallpaper: {}", wall.filename);
+            }
+            exists
+        });
+        SqliteStore::open(config)?.replace_all(existing, ratios)
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = (config, wallpapers, ratios);
+        Err(WallpapersError::NoSqlite)
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, path::PathBuf, process::Command};
+
+use crate::full_path;
+
+/// external tools that can be overridden in the [tools] section of the config
+pub const TOOL_NAMES: &[&str] = &[
+    "realcugan-ncnn-vulkan",
+    "cwebp",
+    "jpegoptim",
+    "oxipng",
+    "anime-face-detector",
+    "wallust",
+    "swww",
+    "rsync",
+    "feh",
+    "xwallpaper",
+    "heif-convert",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalTool {
+    pub path: PathBuf,
+    /// extra arguments passed before the usual arguments
+    pub args: Vec<String>,
+}
+
+impl ExternalTool {
+    pub fn new(name: &str) -> Self {
+        Self {
+            path: PathBuf::from(name),
+            args: Vec::new(),
+        }
+    }
+
+    /// command for the tool, with the extra arguments already added
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(&self.args);
+        cmd
+    }
+
+    /// the executable of the tool, paths set in the config are used as is and names are looked
+    /// up in PATH
+    pub fn find(&self) -> Option<PathBuf> {
+        if self.path.components().count() > 1 {
+            return self.path.is_file().then(|| self.path.clone());
+        }
+
+        std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(&self.path))
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExternalTools(HashMap<String, ExternalTool>);
+
+impl ExternalTools {
+    /// reads the overrides from the [tools] section, e.g.
+    /// cwebp = /nix/store/...-libwebp/bin/cwebp
+    /// cwebp_args = -q 90
+    pub fn from_section<'a, I>(section: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let section: HashMap<_, _> = section.into_iter().collect();
+
+        Self(
+            TOOL_NAMES
+                .iter()
+                .filter_map(|name| {
+                    let path = section.get(name).map(|path| full_path(path));
+                    let args = section.get(format!("{name}_args").as_str()).map(|args| {
+                        args.split_whitespace()
+                            .map(String::from)
+                            .collect::<Vec<_>>()
+                    });
+
+                    if path.is_none() && args.is_none() {
+                        return None;
+                    }
+
+                    Some((
+                        (*name).to_string(),
+                        ExternalTool {
+                            path: path.unwrap_or_else(|| PathBuf::from(name)),
+                            args: args.unwrap_or_default(),
+                        },
+                    ))
+                })
+                .collect(),
+        )
+    }
+
+    /// the tool with its overrides, if any
+    pub fn get(&self, name: &str) -> ExternalTool {
+        self.0
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| ExternalTool::new(name))
+    }
+
+    /// overridden tools, sorted by name
+    pub fn overrides(&self) -> Vec<(&String, &ExternalTool)> {
+        let mut overrides: Vec<_> = self.0.iter().collect();
+        overrides.sort_by_key(|(name, _)| *name);
+        overrides
+    }
+}
@@ -0,0 +1,1070 @@
+use indexmap::IndexMap;
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{
+    de::{self},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    aspect_ratio::AspectRatio,
+    config::{ConfigErrors, WallpaperConfig},
+    cropper::{CropOptions, Cropper, Direction},
+    geometry::Geometry,
+    schedule::{self, Moment},
+    store, write_atomic,
+};
+
+#[derive(Error, Debug)]
+pub enum WallpapersError {
+    #[error("{0:?} not found, have you run \"wallpapers-add\" to create it?")]
+    NotFound(PathBuf),
+    #[error("could not read {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("could not parse {path:?}: {source}")]
+    Parse { path: PathBuf, source: csv::Error },
+    #[error("could not write {path:?}: {source}")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("could not get the dimensions of {path:?}: {source}")]
+    Dimensions {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("no wallpaper info for {0}")]
+    MissingInfo(String),
+    #[error("duplicate faces: {}", .0.join("; "))]
+    Duplicates(Vec<String>),
+    #[cfg(feature = "sqlite")]
+    #[error("could not access the database {path:?}: {source}")]
+    Database {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    #[error("store = sqlite needs wallpaper-ui to be built with the sqlite feature")]
+    NoSqlite,
+    #[error("invalid config at {:?}:\n{0}", crate::paths::config_file())]
+    Config(#[from] ConfigErrors),
+    #[error("invalid faces: {0}, expected all, zero, none, one, single, many or multiple")]
+    InvalidFaces(String),
+    #[error(
+        "invalid resolution: {0}, expected a name from the config, WxH, W:H or a decimal ratio"
+    )]
+    InvalidResolution(String),
+}
+
+/// review states of a wallpaper in the order they are advanced: added by the pipeline, flagged
+/// for editing its crops, crops saved or accepted in the ui and published with `wallpapers state`
+pub const REVIEW_STATES: &[&str] = &["new", "needs-crop", "reviewed", "published"];
+
+/// number of faces of the --faces argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaceCount {
+    All,
+    Zero,
+    One,
+    Many,
+}
+
+impl TryFrom<&str> for FaceCount {
+    type Error = WallpapersError;
+
+    fn try_from(faces: &str) -> Result<Self, Self::Error> {
+        match faces {
+            "all" => Ok(Self::All),
+            "zero" | "none" => Ok(Self::Zero),
+            "one" | "single" => Ok(Self::One),
+            "many" | "multiple" => Ok(Self::Many),
+            _ => Err(WallpapersError::InvalidFaces(faces.to_string())),
+        }
+    }
+}
+
+/// bounding box of a detected face
+#[derive(Debug, Default, Clone, Deserialize, PartialEq, Eq)]
+pub struct Face {
+    pub xmin: u32,
+    pub xmax: u32,
+    pub ymin: u32,
+    pub ymax: u32,
+    /// y coordinate of the eye line, if the face detector provides landmarks
+    #[serde(default)]
+    pub eye_y: Option<u32>,
+}
+
+impl Face {
+    pub const fn dir_bounds(&self, direction: Direction) -> (u32, u32) {
+        match direction {
+            Direction::X | Direction::Both => (self.xmin, self.xmax),
+            Direction::Y => (self.ymin, self.ymax),
+        }
+    }
+
+    #[inline]
+    pub const fn area(&self) -> u32 {
+        (self.xmax - self.xmin) * (self.ymax - self.ymin)
+    }
+
+    pub const fn geometry(&self) -> Geometry {
+        Geometry {
+            w: self.xmax - self.xmin,
+            h: self.ymax - self.ymin,
+            x: self.xmin,
+            y: self.ymin,
+            scale: None,
+            padding: None,
+        }
+    }
+
+    pub fn geometry_str(&self) -> String {
+        format!(
+            "{}x{}+{}+{}",
+            self.xmax - self.xmin,
+            self.ymax - self.ymin,
+            self.xmin,
+            self.ymin
+        )
+    }
+}
+
+impl Serialize for Face {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // required for setting order
+        let mut values = vec![self.xmin, self.xmax, self.ymin, self.ymax];
+        values.extend(self.eye_y);
+        Some(values).serialize(serializer)
+    }
+}
+
+/// a row of wallpapers.csv: the image, its faces and its crops for each aspect ratio
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WallInfo {
+    pub filename: String,
+    pub width: u32,
+    pub height: u32,
+    pub faces: Vec<Face>,
+    pub geometries: HashMap<AspectRatio, Geometry>,
+    /// wallust arguments for the palette, e.g. --backend full, the palette itself is only in the
+    /// cache of wallust
+    pub wallust: String,
+    pub tags: Vec<String>,
+    /// path of the depth map for parallax wallpapers, empty if none was generated
+    pub depth: String,
+    /// backend the faces were detected with, empty for wallpapers added before it was recorded
+    pub detector: String,
+    /// one of REVIEW_STATES
+    pub state: String,
+    /// times of day, weekdays, months and seasons the wallpaper is shown in, always if empty
+    pub schedule: Vec<String>,
+}
+
+impl<'de> Deserialize<'de> for WallInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Filename,
+            Faces,
+            Geometries,
+            Wallust,
+            Tags,
+            Depth,
+            Detector,
+            State,
+            Schedule,
+        }
+
+        struct WallInfoVisitor;
+
+        impl<'de> de::Visitor<'de> for WallInfoVisitor {
+            type Value = WallInfo;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("struct WallInfo2")
+            }
+
+            fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mut filename = None;
+                let mut width = None;
+                let mut height = None;
+                let mut faces = None;
+                let mut geometries: HashMap<AspectRatio, Geometry> = HashMap::new();
+                let mut wallust = None;
+                let mut tags = Vec::new();
+                let mut depth = String::new();
+                let mut detector = String::new();
+                // wallpapers added before the states were recorded already went through the ui
+                let mut state = "reviewed".to_string();
+                let mut schedule = Vec::new();
+
+                while let Some((key, value)) = map.next_entry::<&str, String>()? {
+                    match key {
+                        "filename" => {
+                            filename = Some(value);
+                        }
+                        "width" => {
+                            width = Some(value.parse::<u32>().map_err(de::Error::custom)?);
+                        }
+                        "height" => {
+                            height = Some(value.parse::<u32>().map_err(de::Error::custom)?);
+                        }
+                        "faces" => {
+                            faces =
+                                Some(serde_json::from_str::<Vec<Face>>(&value).map_err(|_| {
+                                    de::Error::custom(format!("could not parse faces: {value:?}"))
+                                })?);
+                        }
+                        "wallust" => {
+                            wallust = Some(value);
+                        }
+                        "tags" => {
+                            tags = value
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|tag| !tag.is_empty())
+                                .map(String::from)
+                                .collect();
+                        }
+                        "depth" => {
+                            depth = value;
+                        }
+                        "detector" => {
+                            detector = value;
+                        }
+                        "state" => {
+                            if !value.is_empty() {
+                                state = value;
+                            }
+                        }
+                        "schedule" => {
+                            schedule = value
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|entry| !entry.is_empty())
+                                .map(String::from)
+                                .collect();
+                        }
+                        _ => {
+                            geometries.insert(
+                                key.try_into().map_err(|()| {
+                                    de::Error::custom(format!("invalid aspect ratio: {key}"))
+                                })?,
+                                value.try_into().map_err(de::Error::custom)?,
+                            );
+                        }
+                    }
+                }
+
+                Ok(WallInfo {
+                    filename: filename.ok_or_else(|| de::Error::missing_field("filename"))?,
+                    width: width.ok_or_else(|| de::Error::missing_field("width"))?,
+                    height: height.ok_or_else(|| de::Error::missing_field("height"))?,
+                    faces: faces.ok_or_else(|| de::Error::missing_field("faces"))?,
+                    wallust: wallust.ok_or_else(|| de::Error::missing_field("wallust"))?,
+                    // tags, depth, detector, state and schedule are optional for csvs created
+                    // before they were added
+                    tags,
+                    depth,
+                    detector,
+                    state,
+                    schedule,
+                    geometries,
+                })
+            }
+        }
+
+        const FIELDS: &[&str] = &[
+            "filename",
+            "width",
+            "height",
+            "faces",
+            "geometries",
+            "wallust",
+            "tags",
+            "depth",
+            "detector",
+            "state",
+            "schedule",
+        ];
+        deserializer.deserialize_struct("WallInfo", FIELDS, WallInfoVisitor)
+    }
+}
+
+impl WallInfo {
+    pub const fn direction(&self, g: &Geometry) -> Direction {
+        if self.height == g.h {
+            Direction::X
+        } else if self.width == g.w {
+            Direction::Y
+        } else {
+            Direction::Both
+        }
+    }
+
+    pub fn cropper(&self) -> Cropper {
+        Cropper::new(&self.faces, self.width, self.height)
+    }
+
+    pub fn get_geometry(&self, ratio: &AspectRatio) -> Geometry {
+        self.geometries
+            .get(ratio)
+            .map_or_else(|| self.cropper().crop(ratio), std::clone::Clone::clone)
+    }
+
+    pub fn set_geometry(&mut self, ratio: &AspectRatio, new_geom: &Geometry) {
+        self.geometries.insert(ratio.clone(), new_geom.clone());
+    }
+
+    /// the header and record of the wallpaper as it is written to wallpapers.csv, with the crops
+    /// that have been saved for it
+    pub fn to_row(&self) -> Result<(Vec<String>, Vec<String>), serde_json::Error> {
+        let ratios: Vec<_> = self.geometries.keys().cloned().collect();
+        let record = wall_record(self, self.width, self.height, &ratios)?;
+        Ok((header_record(&ratios), record))
+    }
+
+    /// parses a record of wallpapers.csv with its header
+    pub fn from_row(header: &[String], record: &[String]) -> Result<Self, csv::Error> {
+        csv::StringRecord::from(record.to_vec())
+            .deserialize(Some(&csv::StringRecord::from(header.to_vec())))
+    }
+
+    pub fn is_default_crops(&self, resolutions: &[AspectRatio], options: CropOptions) -> bool {
+        let cropper = self.cropper().with_options(options);
+
+        resolutions
+            .iter()
+            .all(|ratio| self.get_geometry(ratio) == cropper.crop(ratio))
+    }
+
+    /// checks the number of faces against the --faces argument
+    pub const fn matches_faces(&self, faces: FaceCount) -> bool {
+        match faces {
+            FaceCount::All => true,
+            FaceCount::Zero => self.faces.is_empty(),
+            FaceCount::One => self.faces.len() == 1,
+            FaceCount::Many => self.faces.len() > 1,
+        }
+    }
+
+    /// adds the comma separated tags, tags it already has in any case are skipped, the csv joins
+    /// the tags with commas so they can't be part of a tag
+    pub fn add_tags(&mut self, tags: &str) {
+        for tag in tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                self.tags.push(tag.to_string());
+            }
+        }
+    }
+
+    /// checks the wallpaper against the tag filters (case-insensitive)
+    ///
+    /// each filter is a comma separated list of tags, of which any can match (OR);
+    /// every include filter has to match (AND) and no exclude filter can match
+    pub fn matches_tags(&self, include: &[String], exclude: &[String]) -> bool {
+        let tags: Vec<_> = self.tags.iter().map(|tag| tag.to_lowercase()).collect();
+        let matches_any = |filter: &String| {
+            filter
+                .split(',')
+                .map(|tag| tag.trim().to_lowercase())
+                .any(|tag| tags.contains(&tag))
+        };
+
+        include.iter().all(matches_any) && !exclude.iter().any(matches_any)
+    }
+
+    /// checks the review state against the --state argument
+    pub fn matches_state(&self, state: &str) -> bool {
+        state == "all" || self.state == state
+    }
+
+    /// checks the schedule of the wallpaper against the time
+    pub fn matches_schedule(&self, now: &Moment) -> bool {
+        schedule::matches(&self.schedule, now)
+    }
+
+    /// start and end ratios of the crop along its direction, crops free in both axes
+    /// use the x axis
+    pub fn overlay_transforms(&self, g: &Geometry) -> (Direction, f64, f64) {
+        let img_w = f64::from(self.width);
+        let img_h = f64::from(self.height);
+
+        match self.direction(g) {
+            Direction::Y => (
+                Direction::Y,
+                f64::from(g.y) / img_h,
+                (1.0 - f64::from(g.y + g.h) / img_h),
+            ),
+            direction => (
+                direction,
+                f64::from(g.x) / img_w,
+                (1.0 - f64::from(g.x + g.w) / img_w),
+            ),
+        }
+    }
+
+    /// left, top, width and height of the crop as percentages of the image
+    pub fn crop_box(&self, g: &Geometry) -> (f64, f64, f64, f64) {
+        let img_w = f64::from(self.width);
+        let img_h = f64::from(self.height);
+
+        (
+            f64::from(g.x) / img_w * 100.0,
+            f64::from(g.y) / img_h * 100.0,
+            f64::from(g.w) / img_w * 100.0,
+            f64::from(g.h) / img_h * 100.0,
+        )
+    }
+}
+
+/// rows of wallpapers.csv read by each chunk of rewrite
+const REWRITE_CHUNK_SIZE: usize = 1024;
+
+/// the ratio columns are written from the narrowest to the widest ratio, so the order of the
+/// resolutions in the config doesn't change the csv
+fn normalized_ratios(ratios: &[AspectRatio]) -> Vec<AspectRatio> {
+    let mut ratios = ratios.to_vec();
+    ratios.sort();
+    ratios.dedup();
+    ratios
+}
+
+/// csv writer with unix line endings, so the csv diffs cleanly in git
+fn csv_writer<W: std::io::Write>(writer: W) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .has_headers(false)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(writer)
+}
+
+fn header_record(ratios: &[AspectRatio]) -> Vec<String> {
+    let mut header: Vec<String> = vec![
+        "filename".into(),
+        "width".into(),
+        "height".into(),
+        "faces".into(),
+    ];
+    header.extend(ratios.iter().map(std::string::ToString::to_string));
+    header.push("wallust".into());
+    header.push("tags".into());
+    header.push("depth".into());
+    header.push("detector".into());
+    header.push("state".into());
+    header.push("schedule".into());
+    header
+}
+
+fn wall_record(
+    wall: &WallInfo,
+    width: u32,
+    height: u32,
+    ratios: &[AspectRatio],
+) -> Result<Vec<String>, serde_json::Error> {
+    let mut record: Vec<String> = vec![
+        wall.filename.to_string(),
+        width.to_string(),
+        height.to_string(),
+        serde_json::to_string(&wall.faces)?,
+    ];
+    for resolution in ratios {
+        record.push(wall.get_geometry(resolution).to_string());
+    }
+    record.push(wall.wallust.to_string());
+    record.push(wall.tags.iter().sorted().dedup().join(","));
+    record.push(wall.depth.to_string());
+    record.push(wall.detector.to_string());
+    record.push(wall.state.to_string());
+    record.push(wall.schedule.iter().sorted().dedup().join(","));
+    Ok(record)
+}
+
+/// result of WallpapersCsv::merge
+#[derive(Debug, Default)]
+pub struct Merge {
+    /// number of wallpapers that were only in the other csv
+    pub added: usize,
+    /// wallpapers in both csvs whose rows differ in more than the tags
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct WallpapersCsv {
+    wallpapers: IndexMap<String, WallInfo>,
+    config: WallpaperConfig,
+    /// wallpapers inserted or modified since they were read, only these are written to the
+    /// database with store = sqlite
+    changed: HashSet<String>,
+    /// wallpapers removed since they were read, deleted from the database with store = sqlite
+    removed: HashSet<String>,
+}
+
+impl WallpapersCsv {
+    /// reads the wallpapers from the store of the config
+    pub fn open() -> Result<Self, WallpapersError> {
+        let config = WallpaperConfig::load()?;
+        if config.store == "sqlite" {
+            let wallpapers = store::load_db(&config)?;
+            return Ok(Self::from_wallpapers(wallpapers, config));
+        }
+
+        let csv_path = config.csv_path.clone();
+        Self::open_path(&csv_path, config)
+    }
+
+    pub fn from_wallpapers(wallpapers: Vec<WallInfo>, config: WallpaperConfig) -> Self {
+        Self {
+            wallpapers: wallpapers
+                .into_iter()
+                .map(|wall_info| (wall_info.filename.to_string(), wall_info))
+                .collect(),
+            config,
+            changed: HashSet::new(),
+            removed: HashSet::new(),
+        }
+    }
+
+    pub const fn config(&self) -> &WallpaperConfig {
+        &self.config
+    }
+
+    fn reader(csv_path: &Path) -> Result<csv::Reader<BufReader<File>>, WallpapersError> {
+        let csv_file = File::open(csv_path).map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                WallpapersError::NotFound(csv_path.to_path_buf())
+            } else {
+                WallpapersError::Read {
+                    path: csv_path.to_path_buf(),
+                    source,
+                }
+            }
+        })?;
+        Ok(csv::Reader::from_reader(BufReader::new(csv_file)))
+    }
+
+    /// reads a wallpapers.csv other than the one in the config, e.g. from another machine
+    pub fn open_path(csv_path: &Path, config: WallpaperConfig) -> Result<Self, WallpapersError> {
+        // don't silently drop rows, they would be removed on the next save
+        let wallpapers = Self::stream(csv_path)?
+            .map_ok(|wall_info| (wall_info.filename.to_string(), wall_info))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            wallpapers,
+            config,
+            changed: HashSet::new(),
+            removed: HashSet::new(),
+        })
+    }
+
+    /// reads the rows of wallpapers.csv one at a time, so bulk commands that only need each
+    /// wallpaper once don't keep all of them in memory
+    pub fn stream(csv_path: &Path) -> Result<WallpapersReader, WallpapersError> {
+        let path = csv_path.to_path_buf();
+        let rows = Self::reader(csv_path)?
+            .into_deserialize::<WallInfo>()
+            .map(move |row| {
+                row.map_err(|source| WallpapersError::Parse {
+                    path: path.clone(),
+                    source,
+                })
+            });
+        Ok(WallpapersReader {
+            rows: Box::new(rows),
+        })
+    }
+
+    /// streams the wallpapers of the store of the config, the sqlite database is read at once
+    pub fn open_stream(config: &WallpaperConfig) -> Result<WallpapersReader, WallpapersError> {
+        if config.store == "sqlite" {
+            return store::load_db(config).map(|wallpapers| WallpapersReader {
+                rows: Box::new(wallpapers.into_iter().map(Ok)),
+            });
+        }
+        Self::stream(&config.csv_path)
+    }
+
+    /// rewrites wallpapers.csv without reading all of it into memory, the rows are passed to
+    /// transform in chunks so it can process them in parallel
+    ///
+    /// unlike save, the dimensions are taken from the csv instead of the images, the csv is
+    /// replaced atomically so errors don't leave it half written
+    pub fn rewrite<F>(
+        config: &WallpaperConfig,
+        ratios: &[AspectRatio],
+        mut transform: F,
+    ) -> Result<(), WallpapersError>
+    where
+        F: FnMut(Vec<WallInfo>) -> Vec<WallInfo>,
+    {
+        if config.store == "sqlite" {
+            let rows = store::load_db(config)?;
+            // wallpapers that transform doesn't return are removed like in wallpapers.csv
+            let mut removed: HashSet<String> =
+                rows.iter().map(|wall| wall.filename.clone()).collect();
+            let mut rows = rows.into_iter().peekable();
+            let mut wallpapers = Vec::new();
+            while rows.peek().is_some() {
+                for wall in transform(rows.by_ref().take(REWRITE_CHUNK_SIZE).collect()) {
+                    if config.wallpapers_path.join(&wall.filename).exists() {
+                        removed.remove(&wall.filename);
+                        wallpapers.push(wall);
+                    } else {
+                        info!("Removed wallpaper: {}", wall.filename);
+                    }
+                }
+            }
+            let removed: Vec<_> = removed.into_iter().collect();
+            return store::update_db(config, &wallpapers, &removed, ratios);
+        }
+
+        let csv_path = &config.csv_path;
+        let write_error = |source| WallpapersError::Write {
+            path: csv_path.clone(),
+            source,
+        };
+
+        let mut tmp = csv_path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let ratios = &normalized_ratios(ratios);
+        let mut rows = Self::stream(csv_path)?.peekable();
+        let mut wtr = csv_writer(BufWriter::new(File::create(&tmp).map_err(write_error)?));
+        wtr.write_record(header_record(ratios))
+            .map_err(|e| write_error(e.into()))?;
+
+        while rows.peek().is_some() {
+            let chunk = rows
+                .by_ref()
+                .take(REWRITE_CHUNK_SIZE)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for wall in transform(chunk) {
+                if !config.wallpapers_path.join(&wall.filename).exists() {
+                    info!("Removed wallpaper: {}", wall.filename);
+                    continue;
+                }
+
+                let record = wall_record(&wall, wall.width, wall.height, ratios)
+                    .map_err(|e| write_error(e.into()))?;
+                wtr.write_record(record)
+                    .map_err(|e| write_error(e.into()))?;
+            }
+        }
+
+        wtr.into_inner()
+            .map_err(|e| write_error(std::io::Error::other(e.to_string())))?
+            .into_inner()
+            .map_err(|e| write_error(e.into_error()))?
+            .sync_all()
+            .map_err(write_error)?;
+        std::fs::rename(&tmp, csv_path).map_err(write_error)
+    }
+
+    /// reads wallpapers.csv, a missing csv is empty so it can be created
+    pub fn open_or_default() -> Result<Self, WallpapersError> {
+        match Self::open() {
+            Err(WallpapersError::NotFound(_)) => {
+                Ok(Self::from_wallpapers(Vec::new(), WallpaperConfig::load()?))
+            }
+            res => res,
+        }
+    }
+
+    /// info of the wallpaper, as an error if it isn't in the csv
+    pub fn info(&self, filename: &str) -> Result<&WallInfo, WallpapersError> {
+        self.get(filename)
+            .ok_or_else(|| WallpapersError::MissingInfo(filename.to_string()))
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&WallInfo> {
+        self.wallpapers.get(filename)
+    }
+
+    /// filenames of the wallpapers with the same dimensions and faces, in the order of the csv
+    pub fn duplicate_groups(&self) -> Vec<Vec<String>> {
+        let mut groups: IndexMap<_, Vec<_>> = IndexMap::new();
+
+        // check for duplicates using the faces array
+        for wall_info in self.wallpapers.values().filter(|w| !w.faces.is_empty()) {
+            groups
+                .entry(format!(
+                    "{}|{}|{:?}",
+                    &wall_info.width, &wall_info.height, &wall_info.faces
+                ))
+                .or_default()
+                .push(wall_info.filename.clone());
+        }
+
+        groups
+            .into_values()
+            .filter(|filenames| filenames.len() > 1)
+            .collect()
+    }
+
+    pub fn find_duplicates(&self) -> Result<(), WallpapersError> {
+        let duplicates = self.duplicate_groups();
+
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(WallpapersError::Duplicates(
+                duplicates
+                    .iter()
+                    .map(|filenames| filenames.join(", "))
+                    .collect(),
+            ))
+        }
+    }
+
+    pub fn iter(&self) -> WallpapersIter {
+        WallpapersIter {
+            iter: self.wallpapers.iter(),
+        }
+    }
+
+    pub fn remove(&mut self, filename: &str) -> Option<WallInfo> {
+        self.changed.remove(filename);
+        self.removed.insert(filename.to_string());
+        self.wallpapers.shift_remove(filename)
+    }
+
+    pub fn get_mut(&mut self, filename: &str) -> Option<&mut WallInfo> {
+        let info = self.wallpapers.get_mut(filename)?;
+        self.changed.insert(filename.to_string());
+        Some(info)
+    }
+
+    pub fn insert(&mut self, filename: String, wall_info: WallInfo) {
+        self.removed.remove(&filename);
+        self.changed.insert(filename.clone());
+        self.wallpapers.insert(filename, wall_info);
+    }
+
+    /// adds the wallpapers of the other csv
+    ///
+    /// wallpapers in both get the tags of both, if anything else differs, e.g. the crops or the
+    /// palette, the row of this csv is kept and the wallpaper is a conflict
+    pub fn merge(&mut self, other: Self) -> Merge {
+        let mut merge = Merge::default();
+
+        for (fname, mut other_info) in other.wallpapers {
+            if let Some(info) = self.get_mut(&fname) {
+                let other_tags = std::mem::replace(&mut other_info.tags, info.tags.clone());
+                if other_info != *info {
+                    merge.conflicts.push(fname);
+                }
+                for tag in other_tags {
+                    if !info.tags.contains(&tag) {
+                        info.tags.push(tag);
+                    }
+                }
+            } else {
+                self.insert(fname, other_info);
+                merge.added += 1;
+            }
+        }
+
+        merge
+    }
+
+    pub fn header(&self, ratios: &[AspectRatio]) -> Vec<String> {
+        header_record(ratios)
+    }
+
+    /// writes wallpapers.csv, the csv is replaced atomically so errors don't leave it half written
+    ///
+    /// the rows are sorted by filename and the columns and tags are in a fixed order, so saving
+    /// the same wallpapers always writes the same csv
+    ///
+    /// with store = sqlite only the wallpapers changed since the last save are written, and only
+    /// their files are checked, wallpapers that are deleted have to be removed
+    pub fn save(&mut self, ratios: &[AspectRatio]) -> Result<(), WallpapersError> {
+        // the database is indexed, so it doesn't need a stable order
+        if self.config.store == "sqlite" {
+            let mut removed: Vec<String> = self.removed.iter().cloned().collect();
+            let mut changed = Vec::new();
+            for wall in self
+                .changed
+                .iter()
+                .filter_map(|fname| self.wallpapers.get(fname))
+            {
+                if self.config.wallpapers_path.join(&wall.filename).exists() {
+                    changed.push(wall);
+                } else {
+                    info!("Removed wallpaper: {}", wall.filename);
+                    removed.push(wall.filename.clone());
+                }
+            }
+            store::update_db(&self.config, changed, &removed, ratios)?;
+
+            self.changed.clear();
+            self.removed.clear();
+            return Ok(());
+        }
+
+        let csv_path = &self.config.csv_path;
+        let write_error = |source| WallpapersError::Write {
+            path: csv_path.clone(),
+            source,
+        };
+        let ratios = &normalized_ratios(ratios);
+
+        let mut wtr = csv_writer(Vec::new());
+
+        // manually write the header
+        wtr.write_record(self.header(ratios))
+            .map_err(|e| write_error(e.into()))?;
+
+        // reading the dimensions dominates for large collections, so the records are built in
+        // parallel and sorted afterwards
+        let mut records: Vec<Vec<String>> = self
+            .wallpapers
+            .par_values()
+            .filter_map(|wall| {
+                let wall_path = self.config.wallpapers_path.join(&wall.filename);
+                if !wall_path.exists() {
+                    info!("Removed wallpaper: {}", wall.filename);
+                    return None;
+                }
+
+                let record = || -> Result<Vec<String>, WallpapersError> {
+                    let (width, height) =
+                        image::image_dimensions(&wall_path).map_err(|source| {
+                            WallpapersError::Dimensions {
+                                path: wall_path.clone(),
+                                source,
+                            }
+                        })?;
+                    wall_record(wall, width, height, ratios).map_err(|e| write_error(e.into()))
+                };
+                Some(record())
+            })
+            .collect::<Result<_, _>>()?;
+        // the filename is the first column and unique
+        records.sort_unstable();
+
+        for record in records {
+            wtr.write_record(record)
+                .map_err(|e| write_error(e.into()))?;
+        }
+
+        let contents = wtr
+            .into_inner()
+            .map_err(|e| write_error(std::io::Error::other(e.to_string())))?;
+        write_atomic(csv_path, &contents).map_err(write_error)
+    }
+}
+
+pub struct WallpapersIter<'a> {
+    iter: indexmap::map::Iter<'a, String, WallInfo>,
+}
+
+impl<'a> Iterator for WallpapersIter<'a> {
+    type Item = (&'a String, &'a WallInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+impl<'a> IntoIterator for &'a WallpapersCsv {
+    type Item = (&'a String, &'a WallInfo);
+    type IntoIter = WallpapersIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        WallpapersIter {
+            iter: self.wallpapers.iter(),
+        }
+    }
+}
+
+/// rows of wallpapers.csv, from WallpapersCsv::stream
+pub struct WallpapersReader {
+    rows: Box<dyn Iterator<Item = Result<WallInfo, WallpapersError>> + Send>,
+}
+
+impl Iterator for WallpapersReader {
+    type Item = Result<WallInfo, WallpapersError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{PadAlign, Padding};
+    use ordered_float::OrderedFloat;
+
+    fn wall_info() -> WallInfo {
+        WallInfo {
+            filename: "wall.png".to_string(),
+            width: 64,
+            height: 36,
+            faces: vec![
+                Face {
+                    xmin: 4,
+                    xmax: 12,
+                    ymin: 2,
+                    ymax: 10,
+                    eye_y: Some(5),
+                },
+                Face {
+                    xmin: 40,
+                    xmax: 48,
+                    ymin: 20,
+                    ymax: 28,
+                    eye_y: None,
+                },
+            ],
+            geometries: [
+                (
+                    AspectRatio::new(16, 9),
+                    Geometry {
+                        w: 32,
+                        h: 18,
+                        x: 8,
+                        y: 4,
+                        scale: Some(OrderedFloat(2.0)),
+                        padding: None,
+                    },
+                ),
+                (
+                    AspectRatio::new(9, 16),
+                    Geometry {
+                        w: 20,
+                        h: 36,
+                        x: 30,
+                        y: 0,
+                        scale: None,
+                        padding: None,
+                    },
+                ),
+                (
+                    AspectRatio::new(21, 9),
+                    Geometry {
+                        w: 64,
+                        h: 36,
+                        x: 0,
+                        y: 0,
+                        scale: None,
+                        padding: Some(Padding {
+                            color: "#1e1e2e".to_string(),
+                            align: PadAlign::End,
+                        }),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            wallust: "--backend full".to_string(),
+            tags: vec!["anime".to_string(), "sky".to_string()],
+            depth: "depth/wall.png".to_string(),
+            detector: "onnx".to_string(),
+            state: "needs-crop".to_string(),
+            schedule: vec!["night".to_string(), "winter".to_string()],
+        }
+    }
+
+    #[test]
+    fn row_roundtrip() {
+        let info = wall_info();
+        let (header, record) = info.to_row().expect("serializable faces");
+        assert_eq!(
+            WallInfo::from_row(&header, &record).expect("valid row"),
+            info
+        );
+    }
+
+    #[test]
+    fn row_without_optional_columns() {
+        let header: Vec<String> = ["filename", "width", "height", "faces", "16x9", "wallust"]
+            .map(String::from)
+            .to_vec();
+        let record: Vec<String> = ["wall.png", "64", "36", "[[4,12,2,10]]", "32x18+8+4", ""]
+            .map(String::from)
+            .to_vec();
+
+        let info = WallInfo::from_row(&header, &record).expect("valid row");
+        assert_eq!(
+            info.faces,
+            vec![Face {
+                xmin: 4,
+                xmax: 12,
+                ymin: 2,
+                ymax: 10,
+                eye_y: None,
+            }]
+        );
+        assert!(info.tags.is_empty());
+        assert!(info.depth.is_empty());
+        assert!(info.detector.is_empty());
+        assert_eq!(info.state, "reviewed");
+        assert!(info.schedule.is_empty());
+    }
+
+    #[test]
+    fn row_with_invalid_geometry() {
+        let header: Vec<String> = ["filename", "width", "height", "faces", "16x9", "wallust"]
+            .map(String::from)
+            .to_vec();
+        let record: Vec<String> = ["wall.png", "64", "36", "[]", "32x18+8", ""]
+            .map(String::from)
+            .to_vec();
+        assert!(WallInfo::from_row(&header, &record).is_err());
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("wallpaper-core-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("could not create test dir");
+        image::RgbImage::new(64, 36)
+            .save(dir.join("wall.png"))
+            .expect("could not write test image");
+
+        let config = WallpaperConfig {
+            wallpapers_path: dir.clone(),
+            csv_path: dir.join("wallpapers.csv"),
+            ..WallpaperConfig::default()
+        };
+        let info = wall_info();
+        let ratios: Vec<_> = info.geometries.keys().cloned().collect();
+        let mut missing = info.clone();
+        missing.filename = "missing.png".to_string();
+
+        WallpapersCsv::from_wallpapers(vec![info.clone(), missing], config.clone())
+            .save(&ratios)
+            .expect("could not write csv");
+        let wallpapers_csv =
+            WallpapersCsv::open_path(&config.csv_path, config.clone()).expect("could not read csv");
+        std::fs::remove_dir_all(&dir).ok();
+
+        // wallpapers without an image are dropped on save
+        assert_eq!(wallpapers_csv.iter().count(), 1);
+        assert_eq!(wallpapers_csv.get("wall.png"), Some(&info));
+    }
+}